@@ -138,6 +138,121 @@ macro_rules! map_iter_bench {
     )
 }
 
+macro_rules! frozen_find_rand_bench {
+    ($name: ident, $n: expr) => (
+        #[bench]
+        pub fn $name(b: &mut Bencher) {
+            let mut map = Map::new();
+            let n: usize = $n;
+
+            // setup
+            let mut rng = weak_rng();
+            let mut keys: Vec<_> = (0..n).map(|_| rng.gen() % n).collect();
+
+            for &k in &keys {
+                map.insert(k, k);
+            }
+
+            let map = map.freeze();
+            rng.shuffle(&mut keys);
+
+            // measure
+            let mut i = 0;
+            b.iter(|| {
+                let t = map.get(&keys[i]);
+                i = (i + 1) % n;
+                black_box(t);
+            })
+        }
+    )
+}
+
+macro_rules! frozen_find_seq_bench {
+    ($name: ident, $n: expr) => (
+        #[bench]
+        pub fn $name(b: &mut Bencher) {
+            let mut map = Map::new();
+            let n: usize = $n;
+
+            // setup
+            for i in 0..n {
+                map.insert(i, i);
+            }
+
+            let map = map.freeze();
+
+            // measure
+            let mut i = 0;
+            b.iter(|| {
+                let x = map.get(&i);
+                i = (i + 1) % n;
+                black_box(x);
+            })
+        }
+    )
+}
+
+macro_rules! frozen_succ_rand_bench {
+    ($name: ident, $n: expr, $freeze: expr) => (
+        #[bench]
+        pub fn $name(b: &mut Bencher) {
+            let mut map = Map::new();
+            let n: usize = $n;
+
+            // setup
+            let mut rng = weak_rng();
+
+            for _ in 0..n {
+                let k = rng.gen() % (n * 2);
+                map.insert(k, k);
+            }
+
+            let keys: Vec<usize> = (0..n).map(|_| rng.gen() % (n * 2)).collect();
+
+            // measure
+            let mut i = 0;
+
+            if $freeze {
+                let map = map.freeze();
+                b.iter(|| {
+                    let t = map.succ(&keys[i], false);
+                    i = (i + 1) % n;
+                    black_box(t);
+                })
+            } else {
+                b.iter(|| {
+                    let t = map.succ(&keys[i], false);
+                    i = (i + 1) % n;
+                    black_box(t);
+                })
+            }
+        }
+    )
+}
+
+macro_rules! frozen_iter_bench {
+    ($name: ident, $n: expr) => (
+        #[bench]
+        pub fn $name(b: &mut Bencher) {
+            let mut map = Map::<u32, u32>::new();
+            let n: usize = $n;
+            let mut rng = weak_rng();
+
+            for _ in 0..n {
+                map.insert(rng.gen(), rng.gen());
+            }
+
+            let map = map.freeze();
+
+            b.iter(|| {
+                for entry in map.iter() {
+                    black_box(entry);
+                }
+            });
+        }
+    )
+}
+
 map_insert_rand_bench!{insert_rand_100,    100}
 map_insert_rand_bench!{insert_rand_10_000, 10_000}
 
@@ -153,3 +268,19 @@ map_find_seq_bench!{find_seq_10_000, 10_000}
 map_iter_bench!{iter_100,     100}
 map_iter_bench!{iter_1000,    1000}
 map_iter_bench!{iter_100_000, 100_000}
+
+map_find_rand_bench!{find_rand_1_000_000, 1_000_000}
+
+frozen_find_rand_bench!{frozen_find_rand_100,       100}
+frozen_find_rand_bench!{frozen_find_rand_10_000,    10_000}
+frozen_find_rand_bench!{frozen_find_rand_1_000_000, 1_000_000}
+
+frozen_find_seq_bench!{frozen_find_seq_100,    100}
+frozen_find_seq_bench!{frozen_find_seq_10_000, 10_000}
+
+frozen_succ_rand_bench!{succ_rand_10_000,        10_000, false}
+frozen_succ_rand_bench!{frozen_succ_rand_10_000, 10_000, true}
+
+frozen_iter_bench!{frozen_iter_100,     100}
+frozen_iter_bench!{frozen_iter_1000,    1000}
+frozen_iter_bench!{frozen_iter_100_000, 100_000}
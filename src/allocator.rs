@@ -0,0 +1,23 @@
+//! Allocator support for the crate's collections.
+//!
+//! With the `allocator_api` feature enabled, this module re-exports the `alloc` crate's
+//! `Allocator` trait and `Global` allocator, and every node of a `Map` or `Set` is allocated in
+//! the collection's allocator. Without the feature, `Global` is the only allocator and nodes are
+//! allocated with `Box::new`.
+
+#[cfg(feature = "allocator_api")] pub use alloc::alloc::{Allocator, Global};
+
+/// A memory allocator.
+///
+/// This is a stand-in for `alloc::alloc::Allocator`, which is only available with the
+/// `allocator_api` feature. `Global` is its only implementation.
+#[cfg(not(feature = "allocator_api"))]
+pub trait Allocator {}
+
+/// The global memory allocator.
+#[cfg(not(feature = "allocator_api"))]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Global;
+
+#[cfg(not(feature = "allocator_api"))]
+impl Allocator for Global {}
@@ -0,0 +1,342 @@
+//! An ordered bijective map.
+//!
+//! A `BiMap` relates left values to right values one-to-one. It keeps a `Map` in each direction,
+//! each ordered by its own comparator, and updates both together so that they cannot drift apart.
+//!
+//! # Examples
+//!
+//! ```
+//! use tree::bimap::{BiMap, Overwritten};
+//!
+//! let mut users = BiMap::new();
+//! users.insert(1, "alice");
+//! users.insert(2, "bob");
+//!
+//! assert_eq!(users.get_by_left(&1), Some(&"alice"));
+//! assert_eq!(users.get_by_right(&"bob"), Some(&2));
+//!
+//! // Renaming user 1 to "bob" evicts user 2.
+//! assert_eq!(users.insert(1, "bob"), Overwritten::Both((1, "alice"), (2, "bob")));
+//! assert_eq!(users.len(), 1);
+//! ```
+
+use compare::{Compare, Natural};
+use core::fmt::{self, Debug};
+use core::iter;
+#[cfg(feature = "range")] use core::ops::Bound;
+use map::{self, Map};
+
+/// The pairs removed from a `BiMap` by an insertion.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Overwritten<L, R> {
+    /// No pair was removed.
+    Neither,
+    /// The pair with the same left value, but a different right value, was removed.
+    Left(L, R),
+    /// The pair with the same right value, but a different left value, was removed.
+    Right(L, R),
+    /// A pair equal to the inserted pair was removed.
+    Pair(L, R),
+    /// The pair with the same left value and the pair with the same right value were removed,
+    /// in that order.
+    Both((L, R), (L, R)),
+}
+
+impl<L, R> Overwritten<L, R> {
+    /// Checks if the insertion removed any pair.
+    pub fn did_overwrite(&self) -> bool {
+        match *self {
+            Overwritten::Neither => false,
+            _ => true,
+        }
+    }
+}
+
+/// An ordered bijective map.
+///
+/// See the [module documentation](index.html) for details.
+pub struct BiMap<L, R, CL = Natural<L>, CR = Natural<R>> where CL: Compare<L>, CR: Compare<R> {
+    by_left: Map<L, R, CL>,
+    by_right: Map<R, L, CR>,
+}
+
+impl<L, R> BiMap<L, R> where L: Ord, R: Ord {
+    /// Creates an empty map ordered according to the natural order of its left and right values.
+    pub fn new() -> Self { BiMap::with_cmps(Natural::default(), Natural::default()) }
+}
+
+impl<L, R, CL, CR> BiMap<L, R, CL, CR> where CL: Compare<L>, CR: Compare<R> {
+    /// Creates an empty map whose left and right values are ordered according to the given
+    /// comparators.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate compare;
+    /// # extern crate tree;
+    /// use compare::{Compare, natural};
+    /// use tree::bimap::BiMap;
+    ///
+    /// # fn main() {
+    /// let mut map = BiMap::with_cmps(natural(), natural().rev());
+    /// map.insert(1, 'a');
+    /// map.insert(2, 'b');
+    ///
+    /// assert_eq!(map.iter_by_left().collect::<Vec<_>>(), [(&1, &'a'), (&2, &'b')]);
+    /// assert_eq!(map.iter_by_right().collect::<Vec<_>>(), [(&'b', &2), (&'a', &1)]);
+    /// # }
+    /// ```
+    pub fn with_cmps(left_cmp: CL, right_cmp: CR) -> Self {
+        BiMap { by_left: Map::with_cmp(left_cmp), by_right: Map::with_cmp(right_cmp) }
+    }
+
+    /// Checks if the map is empty.
+    pub fn is_empty(&self) -> bool { self.by_left.is_empty() }
+
+    /// Returns the number of pairs in the map.
+    pub fn len(&self) -> usize { self.by_left.len() }
+
+    /// Returns a reference to the comparator of the map's left values.
+    pub fn left_cmp(&self) -> &CL { self.by_left.cmp() }
+
+    /// Returns a reference to the comparator of the map's right values.
+    pub fn right_cmp(&self) -> &CR { self.by_right.cmp() }
+
+    /// Removes all pairs from the map.
+    pub fn clear(&mut self) {
+        self.by_left.clear();
+        self.by_right.clear();
+    }
+
+    /// Inserts a pair into the map, first removing any pairs whose left value is equal to the
+    /// given left value or whose right value is equal to the given right value. Returns the
+    /// removed pairs.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tree::bimap::{BiMap, Overwritten};
+    ///
+    /// let mut map = BiMap::new();
+    ///
+    /// assert_eq!(map.insert(1, 'a'), Overwritten::Neither);
+    /// assert_eq!(map.insert(1, 'a'), Overwritten::Pair(1, 'a'));
+    /// assert_eq!(map.insert(1, 'b'), Overwritten::Left(1, 'a'));
+    /// assert_eq!(map.insert(2, 'b'), Overwritten::Right(1, 'b'));
+    /// assert_eq!(map.len(), 1);
+    /// ```
+    pub fn insert(&mut self, left: L, right: R) -> Overwritten<L, R> where L: Clone, R: Clone {
+        let by_left = self.remove_by_left(&left);
+        let by_right = self.remove_by_right(&right);
+
+        let overwritten = match (by_left, by_right) {
+            (None, None) => Overwritten::Neither,
+            (Some(pair), None) => if self.by_right.cmp().compares_eq(&pair.1, &right) {
+                Overwritten::Pair(pair.0, pair.1)
+            } else {
+                Overwritten::Left(pair.0, pair.1)
+            },
+            (None, Some(pair)) => Overwritten::Right(pair.0, pair.1),
+            (Some(left_pair), Some(right_pair)) => Overwritten::Both(left_pair, right_pair),
+        };
+
+        self.insert_unchecked(left, right);
+        overwritten
+    }
+
+    /// Inserts a pair into the map unless the map contains its left value or its right value,
+    /// in which case the map is left unchanged and the pair is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tree::bimap::BiMap;
+    ///
+    /// let mut map = BiMap::new();
+    ///
+    /// assert_eq!(map.insert_no_overwrite(1, 'a'), Ok(()));
+    /// assert_eq!(map.insert_no_overwrite(1, 'b'), Err((1, 'b')));
+    /// assert_eq!(map.insert_no_overwrite(2, 'a'), Err((2, 'a')));
+    /// assert_eq!(map.get_by_left(&1), Some(&'a'));
+    /// ```
+    pub fn insert_no_overwrite(&mut self, left: L, right: R) -> Result<(), (L, R)>
+        where L: Clone, R: Clone {
+
+        if self.by_left.contains_key(&left) || self.by_right.contains_key(&right) {
+            return Err((left, right));
+        }
+
+        self.insert_unchecked(left, right);
+        Ok(())
+    }
+
+    fn insert_unchecked(&mut self, left: L, right: R) where L: Clone, R: Clone {
+        self.by_left.insert(left.clone(), right.clone());
+        self.by_right.insert(right, left);
+    }
+
+    /// Removes and returns the pair whose left value is equal to the given value, returning
+    /// `None` if the map contains no such pair.
+    pub fn remove_by_left<Q: ?Sized>(&mut self, left: &Q) -> Option<(L, R)>
+        where CL: Compare<Q, L> {
+
+        self.by_left.remove(left).map(|(left, right)| {
+            self.by_right.remove(&right).expect("map's directions are inconsistent");
+            (left, right)
+        })
+    }
+
+    /// Removes and returns the pair whose right value is equal to the given value, returning
+    /// `None` if the map contains no such pair.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tree::bimap::BiMap;
+    ///
+    /// let mut map = BiMap::new();
+    /// map.insert(1, 'a');
+    ///
+    /// assert_eq!(map.remove_by_right(&'a'), Some((1, 'a')));
+    /// assert_eq!(map.get_by_left(&1), None);
+    /// ```
+    pub fn remove_by_right<Q: ?Sized>(&mut self, right: &Q) -> Option<(L, R)>
+        where CR: Compare<Q, R> {
+
+        self.by_right.remove(right).map(|(right, left)| {
+            self.by_left.remove(&left).expect("map's directions are inconsistent");
+            (left, right)
+        })
+    }
+
+    /// Returns a reference to the right value related to the given left value, or `None` if the
+    /// map contains no such pair.
+    pub fn get_by_left<Q: ?Sized>(&self, left: &Q) -> Option<&R> where CL: Compare<Q, L> {
+        self.by_left.get(left)
+    }
+
+    /// Returns a reference to the left value related to the given right value, or `None` if the
+    /// map contains no such pair.
+    pub fn get_by_right<Q: ?Sized>(&self, right: &Q) -> Option<&L> where CR: Compare<Q, R> {
+        self.by_right.get(right)
+    }
+
+    /// Checks if the map contains a pair with the given left value.
+    pub fn contains_left<Q: ?Sized>(&self, left: &Q) -> bool where CL: Compare<Q, L> {
+        self.by_left.contains_key(left)
+    }
+
+    /// Checks if the map contains a pair with the given right value.
+    pub fn contains_right<Q: ?Sized>(&self, right: &Q) -> bool where CR: Compare<Q, R> {
+        self.by_right.contains_key(right)
+    }
+
+    /// Returns an iterator over the map's pairs as `(left, right)`.
+    ///
+    /// The iterator yields the pairs in ascending order of their left values.
+    pub fn iter_by_left(&self) -> map::Iter<'_, L, R> { self.by_left.iter() }
+
+    /// Returns an iterator over the map's pairs as `(right, left)`.
+    ///
+    /// The iterator yields the pairs in ascending order of their right values.
+    pub fn iter_by_right(&self) -> map::Iter<'_, R, L> { self.by_right.iter() }
+}
+
+#[cfg(feature = "range")]
+impl<L, R, CL, CR> BiMap<L, R, CL, CR> where CL: Compare<L>, CR: Compare<R> {
+    /// Returns an iterator over the map's pairs, as `(left, right)`, whose left values lie in the
+    /// given range.
+    ///
+    /// The iterator yields the pairs in ascending order of their left values.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::ops::Bound::{Included, Unbounded};
+    /// use tree::bimap::BiMap;
+    ///
+    /// let map: BiMap<_, _> = vec![(1, 'c'), (2, 'b'), (3, 'a')].into_iter().collect();
+    ///
+    /// assert_eq!(map.range_by_left(Included(&2), Unbounded).collect::<Vec<_>>(),
+    ///            [(&2, &'b'), (&3, &'a')]);
+    /// assert_eq!(map.range_by_right(Included(&'b'), Unbounded).collect::<Vec<_>>(),
+    ///            [(&'b', &2), (&'c', &1)]);
+    /// ```
+    pub fn range_by_left<Min: ?Sized, Max: ?Sized>(&self, min: Bound<&Min>, max: Bound<&Max>)
+        -> map::Range<'_, L, R> where CL: Compare<Min, L> + Compare<Max, L> {
+
+        self.by_left.range(min, max)
+    }
+
+    /// Returns an iterator over the map's pairs, as `(right, left)`, whose right values lie in
+    /// the given range.
+    ///
+    /// The iterator yields the pairs in ascending order of their right values.
+    pub fn range_by_right<Min: ?Sized, Max: ?Sized>(&self, min: Bound<&Min>, max: Bound<&Max>)
+        -> map::Range<'_, R, L> where CR: Compare<Min, R> + Compare<Max, R> {
+
+        self.by_right.range(min, max)
+    }
+}
+
+impl<L, R, CL, CR> Clone for BiMap<L, R, CL, CR>
+    where L: Clone, R: Clone, CL: Compare<L> + Clone, CR: Compare<R> + Clone {
+
+    fn clone(&self) -> Self {
+        BiMap { by_left: self.by_left.clone(), by_right: self.by_right.clone() }
+    }
+}
+
+impl<L, R, CL, CR> Debug for BiMap<L, R, CL, CR>
+    where L: Debug, R: Debug, CL: Compare<L>, CR: Compare<R> {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.iter_by_left()).finish()
+    }
+}
+
+impl<L, R, CL, CR> Default for BiMap<L, R, CL, CR>
+    where CL: Compare<L> + Default, CR: Compare<R> + Default {
+
+    fn default() -> Self { BiMap::with_cmps(CL::default(), CR::default()) }
+}
+
+impl<L, R, CL, CR> Extend<(L, R)> for BiMap<L, R, CL, CR>
+    where L: Clone, R: Clone, CL: Compare<L>, CR: Compare<R> {
+
+    fn extend<I: IntoIterator<Item=(L, R)>>(&mut self, it: I) {
+        for (left, right) in it { self.insert(left, right); }
+    }
+}
+
+impl<L, R, CL, CR> iter::FromIterator<(L, R)> for BiMap<L, R, CL, CR>
+    where L: Clone, R: Clone, CL: Compare<L> + Default, CR: Compare<R> + Default {
+
+    fn from_iter<I: IntoIterator<Item=(L, R)>>(it: I) -> Self {
+        let mut map = BiMap::default();
+        map.extend(it);
+        map
+    }
+}
+
+impl<'a, L, R, CL, CR> IntoIterator for &'a BiMap<L, R, CL, CR>
+    where CL: Compare<L>, CR: Compare<R> {
+
+    type Item = (&'a L, &'a R);
+    type IntoIter = map::Iter<'a, L, R>;
+    fn into_iter(self) -> map::Iter<'a, L, R> { self.iter_by_left() }
+}
+
+impl<L, R, CL, CR> IntoIterator for BiMap<L, R, CL, CR> where CL: Compare<L>, CR: Compare<R> {
+    type Item = (L, R);
+    type IntoIter = map::IntoIter<L, R>;
+    fn into_iter(self) -> map::IntoIter<L, R> { self.by_left.into_iter() }
+}
+
+impl<L, R, CL, CR> PartialEq for BiMap<L, R, CL, CR>
+    where R: PartialEq, CL: Compare<L>, CR: Compare<R> {
+
+    fn eq(&self, other: &Self) -> bool { self.by_left == other.by_left }
+}
+
+impl<L, R, CL, CR> Eq for BiMap<L, R, CL, CR> where R: Eq, CL: Compare<L>, CR: Compare<R> {}
@@ -0,0 +1,346 @@
+//! An ordered map that detects inconsistent comparators.
+//!
+//! A `Map` assumes that its comparator defines a total order on its keys that does not change
+//! while the keys are in the map. A comparator that breaks this assumption, for example by not
+//! being antisymmetric or transitive, or by consulting state that changes over time, leaves the
+//! map in an unspecified state: lookups may miss keys that are present, and insertions may place
+//! keys where later lookups will not find them.
+//!
+//! A `CheckedMap` wraps a `Map` and, before each lookup or modification, checks the comparator's
+//! results along the path that the operation searches. Each key on the path must lie strictly
+//! between the keys above it that bound its subtree, and comparing the searched key with a key on
+//! the path must give the reverse result when the keys are swapped. Its iterator likewise checks
+//! that each key is strictly greater than the previous one. Violations are reported as an `Error`,
+//! and the operation that detected one leaves the map unchanged.
+//!
+//! The checks are best-effort: they examine only the keys that an operation visits, so a
+//! violation elsewhere in the map goes unnoticed until an operation visits it. Use
+//! [`CheckedMap::validate`](struct.CheckedMap.html#method.validate) to check the whole map. The
+//! check makes up to six comparisons for each level of the path, in addition to those of the
+//! operation itself.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::cell::Cell;
+//! use std::cmp::Ordering;
+//! use tree::checked::{CheckedMap, Error};
+//!
+//! // A comparator whose order is reversed partway through.
+//! let reversed = Cell::new(false);
+//! let cmp = |l: &i32, r: &i32| if reversed.get() { r.cmp(l) } else { l.cmp(r) };
+//!
+//! let mut map = CheckedMap::with_cmp(&cmp);
+//!
+//! for i in 0..8 { assert_eq!(map.insert(i, ()), Ok(None)); }
+//! assert_eq!(map.validate(), Ok(()));
+//!
+//! reversed.set(true);
+//! assert_eq!(map.insert(8, ()), Err(Error::OutOfOrder));
+//! assert_eq!(map.validate(), Err(Error::OutOfOrder));
+//! assert_eq!(map.len(), 8);
+//! ```
+
+use compare::{Compare, Natural};
+use core::fmt;
+use map::{self, Entry, Map};
+use node;
+
+/// An inconsistency in a comparator detected by a `CheckedMap`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Error {
+    /// Comparing two keys did not give the reverse result of comparing them in the opposite
+    /// order.
+    Asymmetric,
+    /// Two keys in the map are not in the order that the comparator gives them, either because
+    /// the comparator's order changed while they were in the map, or because it is not
+    /// transitive.
+    OutOfOrder,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Error::Asymmetric => "comparator is not antisymmetric",
+            Error::OutOfOrder => "keys are not in the comparator's order",
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+impl ::std::error::Error for Error {}
+
+impl From<node::Inconsistency> for Error {
+    fn from(e: node::Inconsistency) -> Self {
+        match e {
+            node::Inconsistency::Asymmetric => Error::Asymmetric,
+            node::Inconsistency::OutOfOrder => Error::OutOfOrder,
+        }
+    }
+}
+
+/// An ordered map that detects inconsistent comparators.
+///
+/// See the [module documentation](index.html) for details.
+#[derive(Clone, Debug, Default)]
+pub struct CheckedMap<K, V, C = Natural<K>> where C: Compare<K> {
+    map: Map<K, V, C>,
+}
+
+impl<K, V> CheckedMap<K, V> where K: Ord {
+    /// Creates an empty map ordered according to the natural order of its keys.
+    pub fn new() -> Self { CheckedMap::with_cmp(Natural::default()) }
+}
+
+impl<K, V, C> CheckedMap<K, V, C> where C: Compare<K> {
+    /// Creates an empty map ordered according to the given comparator.
+    pub fn with_cmp(cmp: C) -> Self { CheckedMap { map: Map::with_cmp(cmp) } }
+
+    /// Wraps an existing map after checking it with
+    /// [`validate`](#method.validate), returning the error if the check fails.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tree::checked::CheckedMap;
+    ///
+    /// let map: tree::Map<_, _> = (0..100).map(|i| (i, i)).collect();
+    /// let map = CheckedMap::from_map(map).unwrap();
+    /// assert_eq!(map.get(&10), Ok(Some(&10)));
+    /// ```
+    pub fn from_map(map: Map<K, V, C>) -> Result<Self, Error> {
+        let map = CheckedMap { map: map };
+        map.validate()?;
+        Ok(map)
+    }
+
+    /// Unwraps the map.
+    pub fn into_inner(self) -> Map<K, V, C> { self.map }
+
+    /// Returns a reference to the underlying map, for operations that `CheckedMap` does not
+    /// check.
+    ///
+    /// Lookups through the returned map are not checked, so they may silently miss keys if the
+    /// comparator is inconsistent.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut map = tree::checked::CheckedMap::new();
+    /// map.insert(1, "a").unwrap();
+    /// map.insert(2, "b").unwrap();
+    ///
+    /// assert_eq!(map.as_unchecked().iter().collect::<Vec<_>>(), [(&1, &"a"), (&2, &"b")]);
+    /// ```
+    pub fn as_unchecked(&self) -> &Map<K, V, C> { &self.map }
+
+    /// Checks if the map is empty.
+    pub fn is_empty(&self) -> bool { self.map.is_empty() }
+
+    /// Returns the number of entries in the map.
+    pub fn len(&self) -> usize { self.map.len() }
+
+    /// Removes all entries from the map.
+    pub fn clear(&mut self) { self.map.clear() }
+
+    /// Checks the whole map, returning the first violation found in ascending order.
+    ///
+    /// This takes O(n) time.
+    pub fn validate(&self) -> Result<(), Error> {
+        for e in self.iter() { e?; }
+
+        Ok(())
+    }
+
+    fn check_path(&self, key: &K) -> Result<(), Error> {
+        node::check_path(self.map.root(), self.map.cmp(), key).map_err(Error::from)
+    }
+
+    /// Inserts an entry into the map, returning the previous value, if any, associated with the
+    /// key.
+    ///
+    /// Returns an error without inserting the entry if the search for the key detects a
+    /// violation.
+    pub fn insert(&mut self, key: K, value: V) -> Result<Option<V>, Error> {
+        self.check_path(&key)?;
+        Ok(self.map.insert(key, value))
+    }
+
+    /// Removes and returns the entry whose key is equal to the given key, returning `None` if
+    /// the map does not contain the key.
+    ///
+    /// Returns an error without removing an entry if the search for the key detects a violation.
+    pub fn remove(&mut self, key: &K) -> Result<Option<(K, V)>, Error> {
+        self.check_path(key)?;
+        Ok(self.map.remove(key))
+    }
+
+    /// Returns the map's entry corresponding to the given key.
+    ///
+    /// Returns an error if the search for the key detects a violation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut counts = tree::checked::CheckedMap::new();
+    ///
+    /// for s in vec!["a", "b", "a"] {
+    ///     *counts.entry(s).unwrap().or_insert(0) += 1;
+    /// }
+    ///
+    /// assert_eq!(counts.get(&"a"), Ok(Some(&2)));
+    /// ```
+    pub fn entry(&mut self, key: K) -> Result<Entry<'_, K, V>, Error> {
+        self.check_path(&key)?;
+        Ok(self.map.entry(key))
+    }
+
+    /// Checks if the map contains the given key.
+    ///
+    /// Returns an error if the search for the key detects a violation.
+    pub fn contains_key(&self, key: &K) -> Result<bool, Error> {
+        self.get(key).map(|v| v.is_some())
+    }
+
+    /// Returns a reference to the value associated with the given key, or `None` if the map does
+    /// not contain the key.
+    ///
+    /// Returns an error if the search for the key detects a violation.
+    pub fn get(&self, key: &K) -> Result<Option<&V>, Error> {
+        self.check_path(key).map(|()| self.map.get(key))
+    }
+
+    /// Returns a mutable reference to the value associated with the given key, or `None` if the
+    /// map does not contain the key.
+    ///
+    /// Returns an error if the search for the key detects a violation.
+    pub fn get_mut(&mut self, key: &K) -> Result<Option<&mut V>, Error> {
+        self.check_path(key)?;
+        Ok(self.map.get_mut(key))
+    }
+
+    /// Returns a reference to the map's maximum key and a reference to its associated value, or
+    /// `None` if the map is empty.
+    ///
+    /// Returns an error if the search for the maximum key detects a violation.
+    pub fn max(&self) -> Result<Option<(&K, &V)>, Error> {
+        self.checked(self.map.max())
+    }
+
+    /// Returns a reference to the map's minimum key and a reference to its associated value, or
+    /// `None` if the map is empty.
+    ///
+    /// Returns an error if the search for the minimum key detects a violation.
+    pub fn min(&self) -> Result<Option<(&K, &V)>, Error> {
+        self.checked(self.map.min())
+    }
+
+    /// Returns a reference to the predecessor of the given key and a reference to its associated
+    /// value, or `None` if no entry's key is less than the given key.
+    ///
+    /// If `inclusive` is `true`, the given key itself is returned if the map contains it.
+    ///
+    /// Returns an error if the search for the key detects a violation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut map = tree::checked::CheckedMap::new();
+    /// map.insert(1, "a").unwrap();
+    /// map.insert(3, "c").unwrap();
+    ///
+    /// assert_eq!(map.pred(&3, false), Ok(Some((&1, &"a"))));
+    /// assert_eq!(map.pred(&3, true), Ok(Some((&3, &"c"))));
+    /// assert_eq!(map.succ(&1, false), Ok(Some((&3, &"c"))));
+    /// assert_eq!(map.max(), Ok(Some((&3, &"c"))));
+    /// ```
+    pub fn pred(&self, key: &K, inclusive: bool) -> Result<Option<(&K, &V)>, Error> {
+        self.check_path(key)?;
+        Ok(self.map.pred(key, inclusive))
+    }
+
+    /// Returns a reference to the successor of the given key and a reference to its associated
+    /// value, or `None` if no entry's key is greater than the given key.
+    ///
+    /// If `inclusive` is `true`, the given key itself is returned if the map contains it.
+    ///
+    /// Returns an error if the search for the key detects a violation.
+    pub fn succ(&self, key: &K, inclusive: bool) -> Result<Option<(&K, &V)>, Error> {
+        self.check_path(key)?;
+        Ok(self.map.succ(key, inclusive))
+    }
+
+    // Checks the path to the given entry's key, which searching for the key retraces.
+    fn checked<'a>(&self, entry: Option<(&'a K, &'a V)>)
+        -> Result<Option<(&'a K, &'a V)>, Error> {
+
+        if let Some((key, _)) = entry { self.check_path(key)?; }
+        Ok(entry)
+    }
+
+    /// Returns an iterator over the map's entries with immutable references to the values.
+    ///
+    /// The iterator yields the entries in ascending order according to the map's comparator,
+    /// checking each key against the previous one. After yielding an error, it yields nothing
+    /// more.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut map = tree::checked::CheckedMap::new();
+    /// map.insert(2, "b").unwrap();
+    /// map.insert(1, "a").unwrap();
+    ///
+    /// assert_eq!(map.iter().collect::<Vec<_>>(), [Ok((&1, &"a")), Ok((&2, &"b"))]);
+    /// ```
+    pub fn iter(&self) -> Iter<'_, K, V, C> {
+        Iter { it: self.map.iter(), cmp: self.map.cmp(), prev: None, done: false }
+    }
+}
+
+impl<'a, K, V, C> IntoIterator for &'a CheckedMap<K, V, C> where C: Compare<K> {
+    type Item = Result<(&'a K, &'a V), Error>;
+    type IntoIter = Iter<'a, K, V, C>;
+    fn into_iter(self) -> Iter<'a, K, V, C> { self.iter() }
+}
+
+/// An iterator over a checked map's entries with immutable references to the values, which checks
+/// that the keys are in ascending order.
+///
+/// Acquire through [`CheckedMap::iter`](struct.CheckedMap.html#method.iter) or the
+/// `IntoIterator` implementation for `&CheckedMap`.
+pub struct Iter<'a, K: 'a, V: 'a, C: 'a> {
+    it: map::Iter<'a, K, V>,
+    cmp: &'a C,
+    prev: Option<&'a K>,
+    done: bool,
+}
+
+impl<'a, K, V, C> Clone for Iter<'a, K, V, C> {
+    fn clone(&self) -> Self {
+        Iter { it: self.it.clone(), cmp: self.cmp, prev: self.prev, done: self.done }
+    }
+}
+
+impl<'a, K, V, C> Iterator for Iter<'a, K, V, C> where C: Compare<K> {
+    type Item = Result<(&'a K, &'a V), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done { return None; }
+
+        let (key, value) = self.it.next()?;
+
+        if let Some(prev) = self.prev.replace(key) {
+            if let Err(e) = node::check_order(self.cmp, prev, key) {
+                self.done = true;
+                return Some(Err(e.into()));
+            }
+        }
+
+        Some(Ok((key, value)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.done { (0, Some(0)) } else { self.it.size_hint() }
+    }
+}
@@ -0,0 +1,372 @@
+//! An ordered map that can be shared between threads.
+//!
+//! `ConcurrentMap` partitions its keys into shards by key range, where each shard is a `Map`
+//! guarded by its own lock. Operations on a single key lock only the shard containing the key, so
+//! operations on keys in different shards proceed in parallel.
+//!
+//! Operations spanning several shards, such as `len`, `snapshot` and `range`, acquire the locks of
+//! all affected shards in ascending order and hold them until the operation completes, so they
+//! observe a consistent state of the map: for any two single-key operations, they observe either
+//! both, neither, or only the one that completed first.
+//!
+//! Every single-key operation holds at most one lock, so the map cannot deadlock, provided that
+//! the functions passed to `with_mut` and `scan` do not access the map.
+//!
+//! A panic while a shard is locked, for example in a function passed to `with_mut` or in the
+//! comparator, does not poison the map. The shard remains a valid `Map` and later operations use
+//! it as the panic left it.
+
+use alloc::vec::Vec;
+use allocator::Global;
+use compare::{Compare, Natural};
+#[cfg(feature = "range")] use core::ops::Bound;
+use std::sync::{PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use super::Map;
+
+/// An ordered map that can be shared between threads.
+///
+/// See the [module documentation](index.html) for details.
+///
+/// The map's shards are determined by the bounds given when it is created. A map with `n` bounds
+/// has `n + 1` shards: the first contains the keys less than the first bound, the last contains the
+/// keys greater than or equal to the last bound, and the others contain the keys greater than or
+/// equal to one bound and less than the next. A map created without bounds has a single shard,
+/// which makes it equivalent to a `Map` inside a single lock.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::Arc;
+/// use std::thread;
+///
+/// let map = Arc::new(tree::concurrent::ConcurrentMap::with_bounds(vec![250, 500, 750]));
+///
+/// let threads: Vec<_> = (0..4).map(|i| {
+///     let map = map.clone();
+///     thread::spawn(move || for key in i * 250..(i + 1) * 250 { map.insert(key, i); })
+/// }).collect();
+///
+/// for thread in threads { thread.join().unwrap(); }
+///
+/// assert_eq!(map.len(), 1000);
+/// assert_eq!(map.get(&600), Some(2));
+/// ```
+pub struct ConcurrentMap<K, V, C = Natural<K>> where C: Compare<K> {
+    bounds: Vec<K>,
+    shards: Vec<RwLock<Map<K, V, C>>>,
+    cmp: C,
+}
+
+impl<K, V> ConcurrentMap<K, V> where K: Ord {
+    /// Creates an empty map with a single shard, ordered according to the natural order of its
+    /// keys.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let map = tree::concurrent::ConcurrentMap::new();
+    /// map.insert(1, "a");
+    /// assert_eq!(map.get(&1), Some("a"));
+    /// ```
+    pub fn new() -> Self { ConcurrentMap::with_cmp_and_bounds(Natural::default(), Vec::new()) }
+
+    /// Creates an empty map with shards split at the given bounds, ordered according to the
+    /// natural order of its keys.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the bounds are not in strictly ascending order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let map: tree::concurrent::ConcurrentMap<_, ()> =
+    ///     tree::concurrent::ConcurrentMap::with_bounds(vec![10, 20]);
+    /// assert_eq!(map.shards(), 3);
+    /// ```
+    pub fn with_bounds(bounds: Vec<K>) -> Self {
+        ConcurrentMap::with_cmp_and_bounds(Natural::default(), bounds)
+    }
+}
+
+impl<K, V, C> ConcurrentMap<K, V, C> where C: Compare<K> + Clone {
+    /// Creates an empty map with a single shard, ordered according to the given comparator.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate compare;
+    /// # extern crate tree;
+    /// # fn main() {
+    /// use compare::{Compare, natural};
+    ///
+    /// let map = tree::concurrent::ConcurrentMap::with_cmp(natural().rev());
+    /// map.insert(1, "a");
+    /// map.insert(2, "b");
+    /// assert_eq!(map.snapshot().iter().next(), Some((&2, &"b")));
+    /// # }
+    /// ```
+    pub fn with_cmp(cmp: C) -> Self { ConcurrentMap::with_cmp_and_bounds(cmp, Vec::new()) }
+
+    /// Creates an empty map with shards split at the given bounds, ordered according to the given
+    /// comparator.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the bounds are not in strictly ascending order according to the comparator.
+    pub fn with_cmp_and_bounds(cmp: C, bounds: Vec<K>) -> Self {
+        assert!(bounds.windows(2).all(|w| cmp.compares_lt(&w[0], &w[1])),
+                "shard bounds must be in strictly ascending order");
+
+        let shards = (0..bounds.len() + 1).map(|_| RwLock::new(Map::with_cmp(cmp.clone())))
+                                          .collect();
+        ConcurrentMap { bounds: bounds, shards: shards, cmp: cmp }
+    }
+}
+
+impl<K, V, C> ConcurrentMap<K, V, C> where C: Compare<K> {
+    /// Returns a reference to the map's comparator.
+    pub fn cmp(&self) -> &C { &self.cmp }
+
+    /// Returns the number of shards in the map.
+    pub fn shards(&self) -> usize { self.shards.len() }
+
+    /// Checks if the map is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let map = tree::concurrent::ConcurrentMap::with_bounds(vec![10]);
+    /// assert!(map.is_empty());
+    ///
+    /// map.insert(20, "a");
+    /// assert!(!map.is_empty());
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        let shards: Vec<_> = self.read_all(0, self.shards.len()).collect();
+        shards.iter().all(|s| s.is_empty())
+    }
+
+    /// Returns the number of entries in the map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let map = tree::concurrent::ConcurrentMap::with_bounds(vec![10]);
+    /// assert_eq!(map.len(), 0);
+    ///
+    /// map.insert(5, "a");
+    /// map.insert(15, "b");
+    /// assert_eq!(map.len(), 2);
+    /// ```
+    pub fn len(&self) -> usize {
+        let shards: Vec<_> = self.read_all(0, self.shards.len()).collect();
+        shards.iter().map(|s| s.len()).sum()
+    }
+
+    /// Removes all entries from the map.
+    pub fn clear(&self) {
+        let mut shards: Vec<_> = (0..self.shards.len()).map(|i| self.write(i)).collect();
+        for shard in &mut shards { shard.clear(); }
+    }
+
+    /// Inserts an entry into the map, returning the previous value, if any, associated
+    /// with the key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let map = tree::concurrent::ConcurrentMap::new();
+    /// assert_eq!(map.insert(1, "a"), None);
+    /// assert_eq!(map.insert(1, "b"), Some("a"));
+    /// ```
+    pub fn insert(&self, key: K, value: V) -> Option<V> {
+        let i = self.shard_of(&key);
+        self.write(i).insert(key, value)
+    }
+
+    /// Removes and returns the entry whose key is equal to the given key, returning
+    /// `None` if the map does not contain the key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let map = tree::concurrent::ConcurrentMap::new();
+    /// map.insert(1, "a");
+    /// assert_eq!(map.remove(&1), Some((1, "a")));
+    /// assert_eq!(map.remove(&1), None);
+    /// ```
+    pub fn remove<Q: ?Sized>(&self, key: &Q) -> Option<(K, V)> where C: Compare<Q, K> {
+        self.write(self.shard_of(key)).remove(key)
+    }
+
+    /// Checks if the map contains the given key.
+    pub fn contains_key<Q: ?Sized>(&self, key: &Q) -> bool where C: Compare<Q, K> {
+        self.read(self.shard_of(key)).contains_key(key)
+    }
+
+    /// Returns a copy of the value associated with the given key, or `None` if the map does not
+    /// contain the key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let map = tree::concurrent::ConcurrentMap::new();
+    /// assert_eq!(map.get(&1), None);
+    /// map.insert(1, "a");
+    /// assert_eq!(map.get(&1), Some("a"));
+    /// ```
+    pub fn get<Q: ?Sized>(&self, key: &Q) -> Option<V> where C: Compare<Q, K>, V: Clone {
+        self.read(self.shard_of(key)).get(key).cloned()
+    }
+
+    /// Calls the given function with a mutable reference to the value associated with the given
+    /// key, or with `None` if the map does not contain the key, and returns its result.
+    ///
+    /// The key's shard is write-locked while the function runs, so the function must not access
+    /// the map. Calling a method that locks the same shard, such as `get` with a key in that
+    /// shard, deadlocks or panics. Calling one that locks another shard can deadlock with a
+    /// concurrent operation that locks the two shards in the opposite order, such as a `with_mut`
+    /// on the other shard's key that accesses this one, or a `len` or `snapshot`, which lock all
+    /// shards in ascending order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let map = tree::concurrent::ConcurrentMap::new();
+    /// map.insert("a", 1);
+    ///
+    /// map.with_mut(&"a", |value| *value.unwrap() += 1);
+    /// assert_eq!(map.get(&"a"), Some(2));
+    /// ```
+    pub fn with_mut<Q: ?Sized, F, R>(&self, key: &Q, f: F) -> R
+        where C: Compare<Q, K>, F: FnOnce(Option<&mut V>) -> R {
+
+        f(self.write(self.shard_of(key)).get_mut(key))
+    }
+
+    /// Returns a copy of the map's entries as a `Map`.
+    ///
+    /// The copy reflects a consistent state of the map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let map = tree::concurrent::ConcurrentMap::with_bounds(vec![2]);
+    /// map.insert(3, "c");
+    /// map.insert(1, "a");
+    ///
+    /// let snapshot = map.snapshot();
+    /// assert_eq!(snapshot.iter().collect::<Vec<_>>(), [(&1, &"a"), (&3, &"c")]);
+    /// ```
+    pub fn snapshot(&self) -> Map<K, V, C> where K: Clone, V: Clone, C: Clone {
+        let shards: Vec<_> = self.read_all(0, self.shards.len()).collect();
+
+        // the shards hold ascending, disjoint ranges of keys, so their entries are already sorted
+        let entries: Vec<_> = shards.iter().flat_map(|s| s.iter())
+            .map(|(k, v)| (k.clone(), v.clone())).collect();
+
+        Map::from_sorted(entries, self.cmp.clone(), Global)
+    }
+
+    // Returns the index of the shard that may contain the given key.
+    fn shard_of<Q: ?Sized>(&self, key: &Q) -> usize where C: Compare<Q, K> {
+        let (mut lo, mut hi) = (0, self.bounds.len());
+
+        // Find the number of bounds that are less than or equal to the key.
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            if self.cmp.compares_lt(key, &self.bounds[mid]) { hi = mid; } else { lo = mid + 1; }
+        }
+
+        lo
+    }
+
+    fn read(&self, i: usize) -> RwLockReadGuard<'_, Map<K, V, C>> {
+        self.shards[i].read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(&self, i: usize) -> RwLockWriteGuard<'_, Map<K, V, C>> {
+        self.shards[i].write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // Locks the shards in `start..end` in ascending order.
+    //
+    // The locks are acquired lazily, so the result must be collected before it is used in order
+    // to observe a consistent state.
+    fn read_all(&self, start: usize, end: usize)
+        -> impl Iterator<Item=RwLockReadGuard<'_, Map<K, V, C>>> {
+
+        self.shards[start..end].iter().map(|s| s.read().unwrap_or_else(PoisonError::into_inner))
+    }
+}
+
+#[cfg(feature = "range")]
+impl<K, V, C> ConcurrentMap<K, V, C> where C: Compare<K> {
+    /// Calls the given function on each of the map's entries whose keys lie in the given range,
+    /// in ascending order according to the map's comparator.
+    ///
+    /// The shards overlapping the range are locked while the function runs, so the entries
+    /// reflect a consistent state of the map. The function must not access the map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::ops::Bound::{Included, Unbounded};
+    ///
+    /// let map = tree::concurrent::ConcurrentMap::with_bounds(vec![2, 4]);
+    /// for i in 0..6 { map.insert(i, i * 10); }
+    ///
+    /// let mut sum = 0;
+    /// map.scan(Included(&1), Unbounded, |_, value| sum += *value);
+    /// assert_eq!(sum, 150);
+    /// ```
+    pub fn scan<Min: ?Sized, Max: ?Sized, F>(&self, min: Bound<&Min>, max: Bound<&Max>, mut f: F)
+        where C: Compare<Min, K> + Compare<Max, K>, F: FnMut(&K, &V) {
+
+        let start = match min {
+            Bound::Included(key) | Bound::Excluded(key) => self.shard_of(key),
+            Bound::Unbounded => 0,
+        };
+
+        let end = match max {
+            Bound::Included(key) | Bound::Excluded(key) => self.shard_of(key) + 1,
+            Bound::Unbounded => self.shards.len(),
+        };
+
+        if start >= end { return; }
+
+        let shards: Vec<_> = self.read_all(start, end).collect();
+
+        for shard in &shards {
+            for (key, value) in shard.range(min, max) { f(key, value); }
+        }
+    }
+
+    /// Returns a copy of the map's entries whose keys lie in the given range, in ascending order
+    /// according to the map's comparator.
+    ///
+    /// The entries reflect a consistent state of the map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::ops::Bound::{Excluded, Included};
+    ///
+    /// let map = tree::concurrent::ConcurrentMap::with_bounds(vec![2, 4]);
+    /// for i in 0..6 { map.insert(i, i * 10); }
+    ///
+    /// assert_eq!(map.range(Included(&1), Excluded(&4)), [(1, 10), (2, 20), (3, 30)]);
+    /// ```
+    pub fn range<Min: ?Sized, Max: ?Sized>(&self, min: Bound<&Min>, max: Bound<&Max>)
+        -> Vec<(K, V)> where C: Compare<Min, K> + Compare<Max, K>, K: Clone, V: Clone {
+
+        let mut entries = Vec::new();
+        self.scan(min, max, |key, value| entries.push((key.clone(), value.clone())));
+        entries
+    }
+}
+
+impl<K, V, C> Default for ConcurrentMap<K, V, C> where C: Compare<K> + Clone + Default {
+    fn default() -> Self { ConcurrentMap::with_cmp(C::default()) }
+}
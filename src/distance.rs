@@ -0,0 +1,77 @@
+//! Keys with a distance between them.
+//!
+//! The `Distance` trait supports `Map::nearest` and `Map::k_nearest`. It is implemented for the
+//! primitive numeric types and `Duration`.
+
+use core::time::Duration;
+
+/// A key with a distance to other keys.
+///
+/// The distance must be consistent with the map's order: for keys `a <= b <= c`, the distance
+/// from `a` to `b` must not exceed the distance from `a` to `c`, and likewise from `c`.
+pub trait Distance<Q: ?Sized = Self> {
+    /// The type of the distance.
+    type Output: PartialOrd;
+
+    /// Returns the distance between the two keys.
+    fn distance(&self, other: &Q) -> Self::Output;
+}
+
+macro_rules! unsigned_distance {
+    ($($T:ty)*) => {$(
+        impl Distance for $T {
+            type Output = $T;
+            fn distance(&self, other: &$T) -> $T {
+                if self >= other { self - other } else { other - self }
+            }
+        }
+    )*}
+}
+
+unsigned_distance!(u8 u16 u32 u64 u128 usize);
+
+macro_rules! signed_distance {
+    ($($T:ty, $U:ty;)*) => {$(
+        impl Distance for $T {
+            type Output = $U;
+            fn distance(&self, other: &$T) -> $U {
+                if self >= other { (*self as $U).wrapping_sub(*other as $U) }
+                else { (*other as $U).wrapping_sub(*self as $U) }
+            }
+        }
+    )*}
+}
+
+signed_distance! {
+    i8, u8;
+    i16, u16;
+    i32, u32;
+    i64, u64;
+    i128, u128;
+    isize, usize;
+}
+
+macro_rules! float_distance {
+    ($($T:ty)*) => {$(
+        impl Distance for $T {
+            type Output = $T;
+            fn distance(&self, other: &$T) -> $T {
+                if self >= other { self - other } else { other - self }
+            }
+        }
+    )*}
+}
+
+float_distance!(f32 f64);
+
+impl Distance for Duration {
+    type Output = Duration;
+    fn distance(&self, other: &Duration) -> Duration {
+        if self >= other { *self - *other } else { *other - *self }
+    }
+}
+
+impl<'a, T: ?Sized> Distance for &'a T where T: Distance {
+    type Output = T::Output;
+    fn distance(&self, other: &&'a T) -> T::Output { (**self).distance(*other) }
+}
@@ -0,0 +1,373 @@
+//! An ordered map persisted to disk through a write-ahead log.
+//!
+//! A `DurableMap` keeps its entries in a `Map` in memory and stores them in a directory holding
+//! two files:
+//!
+//! - `snapshot`, a [packed map](../packed/index.html) of the entries as of the last compaction,
+//!   absent if the map has never been compacted.
+//! - `log`, a write-ahead log of the changes made since then.
+//!
+//! Every change is appended to the log, as a single write, before it is applied in memory.
+//! `DurableMap::open` loads the snapshot and replays the log. A record that is incomplete or whose
+//! checksum does not match, as left by a crash in the middle of a write, ends the log: it and
+//! everything after it are discarded and truncated away, so that the map recovers the state
+//! after the last complete record.
+//!
+//! Appending does not flush the operating system's buffers. Call `DurableMap::sync` to make the
+//! preceding changes survive a power failure as well as a crash of the process.
+//!
+//! `DurableMap::compact` writes a new snapshot and empties the log. It replaces the snapshot
+//! atomically, by renaming, and can be interrupted at any point without losing changes. It can
+//! also be triggered automatically once the log reaches a given length, in which case its errors
+//! are reported separately from those of the change that triggered it.
+//!
+//! Keys are ordered by their natural order, which must agree with the order of their encodings,
+//! as [`Encode`](../packed/trait.Encode.html) requires.
+//!
+//! # Log format
+//!
+//! The log begins with the eight bytes `TREEWAL\0` and a four-byte version. Each record consists
+//! of a one-byte tag, the record's fields and the 64-bit FNV-1a checksum of the preceding bytes of
+//! the record. The tags are `1` for an insertion, whose fields are a key and a value, `2` for a
+//! removal, whose field is a key, and `3` for clearing the map, which has no fields. Each field is
+//! preceded by its length as a four-byte integer. All integers are little-endian.
+//!
+//! # Examples
+//!
+//! ```
+//! use tree::durable::DurableMap;
+//!
+//! let dir = std::env::temp_dir().join(format!("tree-durable-doc-{}", std::process::id()));
+//! # let _ = std::fs::remove_dir_all(&dir);
+//!
+//! {
+//!     let mut map = DurableMap::<String, u64>::open(&dir).unwrap();
+//!     map.insert("a".to_string(), 1).unwrap();
+//!     map.insert("b".to_string(), 2).unwrap();
+//!     map.compact().unwrap();
+//!     map.remove(&"a".to_string()).unwrap();
+//! }
+//!
+//! let map = DurableMap::<String, u64>::open(&dir).unwrap();
+//! assert_eq!(map.iter().collect::<Vec<_>>(), [(&"b".to_string(), &2)]);
+//! # std::fs::remove_dir_all(&dir).unwrap();
+//! ```
+
+use allocator::Global;
+use compare::{Compare, Natural};
+use map::Map;
+use packed::{self, Decode, Encode, PackedMap, read_u32, read_u64, FNV_OFFSET};
+use std::fmt::{self, Debug};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::vec::Vec;
+
+const MAGIC: [u8; 8] = *b"TREEWAL\0";
+const VERSION: u32 = 1;
+const HEADER_LEN: u64 = 12;
+
+const INSERT: u8 = 1;
+const REMOVE: u8 = 2;
+const CLEAR: u8 = 3;
+
+/// An ordered map persisted to disk through a write-ahead log.
+///
+/// See the [module documentation](index.html) for details.
+pub struct DurableMap<K, V> where K: Ord {
+    map: Map<K, V>,
+    dir: PathBuf,
+    log: File,
+    log_len: u64,
+    threshold: Option<u64>,
+    compaction_error: Option<io::Error>,
+    record: Vec<u8>,
+}
+
+impl<K, V> DurableMap<K, V>
+    where K: Ord + Encode + for<'a> Decode<'a>, V: Encode + for<'a> Decode<'a> {
+
+    /// Opens the map stored in the given directory, creating the directory and an empty map if
+    /// they do not exist.
+    ///
+    /// This loads the snapshot, if any, and replays the log, truncating it after its last
+    /// complete record. It returns an error of kind `InvalidData` if the snapshot is corrupt or
+    /// either file was written for different key or value types.
+    pub fn open<P: AsRef<Path>>(dir: P) -> io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+
+        let mut map = match fs::read(dir.join("snapshot")) {
+            Ok(bytes) => {
+                let packed = PackedMap::<K, V>::new(&bytes).map_err(invalid_data)?;
+                Map::from_sorted(packed.iter(), Natural::default(), Global)
+            }
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Map::new(),
+            Err(e) => return Err(e),
+        };
+
+        let mut log = OpenOptions::new().read(true).write(true).create(true).truncate(false)
+                                        .open(dir.join("log"))?;
+        let mut bytes = Vec::new();
+        log.read_to_end(&mut bytes)?;
+
+        let log_len = if (bytes.len() as u64) < HEADER_LEN {
+            // The log is new, or a crash interrupted the writing of its header.
+            log.set_len(0)?;
+            log.seek(SeekFrom::Start(0))?;
+            log.write_all(&header())?;
+            HEADER_LEN
+        } else {
+            if bytes[..HEADER_LEN as usize] != header()[..] {
+                return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                          "log has the wrong magic number or version"));
+            }
+
+            let len = replay(&mut map, &bytes)?;
+            log.set_len(len)?;
+            log.seek(SeekFrom::Start(len))?;
+            len
+        };
+
+        Ok(DurableMap {
+            map: map,
+            dir: dir,
+            log: log,
+            log_len: log_len,
+            threshold: None,
+            compaction_error: None,
+            record: Vec::new(),
+        })
+    }
+
+    /// Inserts an entry into the map, returning the previous value, if any, associated with the
+    /// key.
+    ///
+    /// The map is unchanged if the insertion cannot be logged.
+    pub fn insert(&mut self, key: K, value: V) -> io::Result<Option<V>> {
+        self.record.clear();
+        self.record.push(INSERT);
+        push_field(&mut self.record, &key);
+        push_field(&mut self.record, &value);
+        self.append()?;
+        let old_value = self.map.insert(key, value);
+        self.compact_if_needed();
+        Ok(old_value)
+    }
+
+    /// Removes the entry whose key is equal to the given key, returning its value, or `None` if
+    /// the map does not contain the key.
+    ///
+    /// The map is unchanged if the removal cannot be logged. Nothing is logged if the map does
+    /// not contain the key.
+    pub fn remove<Q: ?Sized>(&mut self, key: &Q) -> io::Result<Option<V>>
+        where Q: Encode, Natural<K>: Compare<Q, K> {
+
+        if !self.map.contains_key(key) { return Ok(None); }
+
+        self.record.clear();
+        self.record.push(REMOVE);
+        push_field(&mut self.record, key);
+        self.append()?;
+        let removed = self.map.remove(key).map(|e| e.1);
+        self.compact_if_needed();
+        Ok(removed)
+    }
+
+    /// Removes all entries from the map.
+    ///
+    /// The map is unchanged if the clearing cannot be logged.
+    pub fn clear(&mut self) -> io::Result<()> {
+        self.record.clear();
+        self.record.push(CLEAR);
+        self.append()?;
+        self.map.clear();
+        self.compact_if_needed();
+        Ok(())
+    }
+
+    /// Writes a snapshot of the map and empties the log.
+    pub fn compact(&mut self) -> io::Result<()> {
+        let tmp = self.dir.join("snapshot.tmp");
+
+        {
+            let mut file = io::BufWriter::new(File::create(&tmp)?);
+            packed::write(&self.map, &mut file)?;
+            file.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        }
+
+        fs::rename(&tmp, self.dir.join("snapshot"))?;
+        sync_dir(&self.dir)?;
+
+        self.log.set_len(HEADER_LEN)?;
+        self.log.seek(SeekFrom::Start(HEADER_LEN))?;
+        self.log.sync_all()?;
+        self.log_len = HEADER_LEN;
+        Ok(())
+    }
+
+    /// Flushes the log to disk, so that the preceding changes survive a power failure.
+    pub fn sync(&mut self) -> io::Result<()> { self.log.sync_data() }
+
+    /// Returns the length in bytes of the log.
+    pub fn log_len(&self) -> u64 { self.log_len }
+
+    /// Returns the log length at which the map is automatically compacted, if any.
+    pub fn compaction_threshold(&self) -> Option<u64> { self.threshold }
+
+    /// Sets the log length at which the map is automatically compacted after a change, or
+    /// disables automatic compaction if `None`.
+    ///
+    /// A failed automatic compaction does not fail the change that triggered it, which has
+    /// already been logged and applied. Instead, the error is kept for
+    /// [`take_compaction_error`](#method.take_compaction_error), and compaction is attempted again
+    /// after the next change.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tree::durable::DurableMap;
+    ///
+    /// let dir = std::env::temp_dir()
+    ///                      .join(format!("tree-durable-threshold-{}", std::process::id()));
+    /// # let _ = std::fs::remove_dir_all(&dir);
+    /// let mut map = DurableMap::<u32, u32>::open(&dir).unwrap();
+    /// map.set_compaction_threshold(Some(1024));
+    ///
+    /// for i in 0..1000 {
+    ///     map.insert(i % 10, i).unwrap();
+    ///     assert!(map.log_len() < 1024);
+    /// }
+    /// # std::fs::remove_dir_all(&dir).unwrap();
+    /// ```
+    pub fn set_compaction_threshold(&mut self, threshold: Option<u64>) {
+        self.threshold = threshold;
+    }
+
+    /// Returns the error of the last failed automatic compaction, if any, and clears it.
+    pub fn take_compaction_error(&mut self) -> Option<io::Error> { self.compaction_error.take() }
+
+    /// Returns the directory in which the map is stored.
+    pub fn dir(&self) -> &Path { &self.dir }
+
+    /// Returns the map held in memory, closing the log.
+    pub fn into_inner(self) -> Map<K, V> { self.map }
+
+    // Appends the record in `self.record` to the log, followed by its checksum. On failure, the
+    // log is truncated to its previous length, if possible, so that later records are not lost
+    // behind a partial one.
+    fn append(&mut self) -> io::Result<()> {
+        let checksum = packed::fnv(FNV_OFFSET, &self.record);
+        self.record.extend_from_slice(&checksum.to_le_bytes());
+
+        if let Err(e) = self.log.write_all(&self.record) {
+            let _ = self.log.set_len(self.log_len);
+            let _ = self.log.seek(SeekFrom::Start(self.log_len));
+            return Err(e);
+        }
+
+        self.log_len += self.record.len() as u64;
+        Ok(())
+    }
+
+    fn compact_if_needed(&mut self) {
+        match self.threshold {
+            Some(threshold) if self.log_len >= threshold => {
+                if let Err(e) = self.compact() { self.compaction_error = Some(e); }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl<K, V> Debug for DurableMap<K, V> where K: Ord + Debug, V: Debug {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { self.map.fmt(f) }
+}
+
+impl<K, V> Deref for DurableMap<K, V> where K: Ord {
+    type Target = Map<K, V>;
+    fn deref(&self) -> &Map<K, V> { &self.map }
+}
+
+fn header() -> [u8; HEADER_LEN as usize] {
+    let mut header = [0; HEADER_LEN as usize];
+    header[..8].copy_from_slice(&MAGIC);
+    header[8..].copy_from_slice(&VERSION.to_le_bytes());
+    header
+}
+
+fn push_field<T: ?Sized + Encode>(record: &mut Vec<u8>, value: &T) {
+    let start = record.len();
+    record.extend_from_slice(&[0; 4]);
+    value.encode(record);
+    let len = record.len() - start - 4;
+    assert!(len <= u32::MAX as usize, "encoding is too long");
+    record[start..start + 4].copy_from_slice(&(len as u32).to_le_bytes());
+}
+
+// Applies the log's complete records to the map, returning the length of the log up to the end of
+// the last one.
+fn replay<K, V>(map: &mut Map<K, V>, bytes: &[u8]) -> io::Result<u64>
+    where K: Ord + for<'a> Decode<'a>, V: for<'a> Decode<'a> {
+
+    let mut at = HEADER_LEN as usize;
+
+    loop {
+        let mut reader = Reader { bytes: bytes, at: at + 1 };
+
+        let tag = match bytes.get(at) { Some(&tag) => tag, None => return Ok(at as u64) };
+        let fields = match tag { INSERT => 2, REMOVE => 1, CLEAR => 0, _ => return Ok(at as u64) };
+        let mut field = [&[][..]; 2];
+
+        for f in &mut field[..fields] {
+            *f = match reader.field() { Some(f) => f, None => return Ok(at as u64) };
+        }
+
+        let end = reader.at;
+        let checksum = packed::fnv(FNV_OFFSET, &bytes[at..end]);
+        if bytes.len() < end + 8 || read_u64(bytes, end) != checksum { return Ok(at as u64); }
+
+        match tag {
+            INSERT => {
+                let key = decode(field[0])?;
+                map.insert(key, decode(field[1])?);
+            }
+            REMOVE => { map.remove(&decode::<K>(field[0])?); }
+            _ => map.clear(),
+        }
+
+        at = end + 8;
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    at: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn field(&mut self) -> Option<&'a [u8]> {
+        if self.bytes.len() < self.at + 4 { return None; }
+        let start = self.at + 4;
+        let end = start + read_u32(self.bytes, self.at) as usize;
+        if self.bytes.len() < end { return None; }
+        self.at = end;
+        Some(&self.bytes[start..end])
+    }
+}
+
+fn decode<T: for<'a> Decode<'a>>(bytes: &[u8]) -> io::Result<T> {
+    T::decode(bytes)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "log record cannot be decoded"))
+}
+
+fn invalid_data<E>(e: E) -> io::Error where E: ::std::error::Error + Send + Sync + 'static {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> io::Result<()> { File::open(dir)?.sync_all() }
+
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) -> io::Result<()> { Ok(()) }
@@ -0,0 +1,99 @@
+//! A comparator chosen at runtime.
+//!
+//! A map's comparator is part of its type, so a map whose order is only known at runtime, such as
+//! one sorted by a user-selected collation, needs a comparator type that can hold any ordering.
+//! `DynCompare` is such a type: it wraps any comparator behind a shared pointer, so that
+//! `Map<K, V, DynCompare<K>>` can be ordered by any of them. Use
+//! [`Map::resort_with`](../map/struct.Map.html#method.resort_with) to switch a map to a different
+//! ordering.
+//!
+//! # Inconsistent comparators
+//!
+//! Like every comparator given to a map, a `DynCompare` must define a total order on the map's
+//! keys, and that order must not change while the keys are in the map. A comparator that breaks
+//! this (for example, one that is not transitive, or that consults state that changes over
+//! time) does not cause undefined behavior, but the map's behavior is then unspecified: lookups
+//! may miss keys that are present, insertions may add keys that are equal to existing ones, and
+//! iteration yields the keys in the order in which they were placed in the tree rather than in
+//! any order the comparator agrees with. Re-sorting with such a comparator may panic.
+//!
+//! # Examples
+//!
+//! ```
+//! use tree::Map;
+//! use tree::dyn_compare::DynCompare;
+//!
+//! let case_sensitive = true;
+//!
+//! let cmp = if case_sensitive {
+//!     DynCompare::natural()
+//! } else {
+//!     DynCompare::new(|l: &String, r: &String| l.to_lowercase().cmp(&r.to_lowercase()))
+//! };
+//!
+//! let mut map = Map::with_cmp(cmp);
+//! map.insert("b".to_string(), 2);
+//! map.insert("A".to_string(), 1);
+//!
+//! assert_eq!(map.iter().next(), Some((&"A".to_string(), &1)));
+//! ```
+
+use alloc::sync::Arc;
+use compare::{Compare, Natural};
+use core::cmp::Ordering;
+use core::fmt::{self, Debug};
+
+/// A comparator that wraps any comparator of `K`s chosen at runtime.
+///
+/// Cloning a `DynCompare` shares the wrapped comparator. See the
+/// [module documentation](index.html) for details.
+pub struct DynCompare<K: ?Sized>(Arc<dyn Compare<K> + Send + Sync>);
+
+impl<K: ?Sized> DynCompare<K> {
+    /// Wraps the given comparator.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate compare;
+    /// # extern crate tree;
+    /// # fn main() {
+    /// use compare::Compare;
+    /// use std::cmp::Ordering::Less;
+    /// use tree::dyn_compare::DynCompare;
+    ///
+    /// let cmp = DynCompare::new(|l: &i32, r: &i32| r.cmp(l));
+    /// assert_eq!(cmp.compare(&2, &1), Less);
+    /// # }
+    /// ```
+    pub fn new<C>(cmp: C) -> Self where C: Compare<K> + Send + Sync + 'static {
+        DynCompare(Arc::new(cmp))
+    }
+
+    /// Returns a comparator that orders `K`s according to their natural order.
+    pub fn natural() -> Self where K: Ord + 'static {
+        DynCompare::new(Natural::default())
+    }
+}
+
+impl<K: ?Sized> Clone for DynCompare<K> {
+    fn clone(&self) -> Self { DynCompare(self.0.clone()) }
+}
+
+impl<K: ?Sized> Debug for DynCompare<K> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { f.write_str("DynCompare") }
+}
+
+impl<K: ?Sized> Default for DynCompare<K> where K: Ord + 'static {
+    fn default() -> Self { DynCompare::natural() }
+}
+
+impl<K: ?Sized> Compare<K> for DynCompare<K> {
+    fn compare(&self, l: &K, r: &K) -> Ordering { self.0.compare(l, r) }
+    fn compares_lt(&self, l: &K, r: &K) -> bool { self.0.compares_lt(l, r) }
+    fn compares_le(&self, l: &K, r: &K) -> bool { self.0.compares_le(l, r) }
+    fn compares_ge(&self, l: &K, r: &K) -> bool { self.0.compares_ge(l, r) }
+    fn compares_gt(&self, l: &K, r: &K) -> bool { self.0.compares_gt(l, r) }
+    fn compares_eq(&self, l: &K, r: &K) -> bool { self.0.compares_eq(l, r) }
+    fn compares_ne(&self, l: &K, r: &K) -> bool { self.0.compares_ne(l, r) }
+}
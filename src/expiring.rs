@@ -0,0 +1,421 @@
+//! An ordered map whose entries may expire.
+//!
+//! An `ExpiringMap` pairs a `Map` holding the entries with a secondary index ordering the
+//! entries' deadlines. Expired entries are hidden from lookups and iteration immediately, and
+//! reclaimed by `evict_expired`, which repeatedly removes the earliest deadline from the index and
+//! so takes time proportional to the number of evicted entries.
+//!
+//! The current time is read from a `Clock`, which defaults to the system's monotonic clock. Tests
+//! can inject a `ManualClock` to control time deterministically.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::time::Duration;
+//! use tree::expiring::{Clock, ExpiringMap, ManualClock};
+//!
+//! let clock = ManualClock::new();
+//! let mut cache = ExpiringMap::with_clock(&clock);
+//!
+//! cache.insert_with_ttl("a", 1, Duration::from_secs(10));
+//! cache.insert_with_ttl("b", 2, Duration::from_secs(20));
+//! cache.insert("c", 3);
+//!
+//! clock.advance(Duration::from_secs(15));
+//! assert_eq!(cache.get(&"a"), None);
+//! assert_eq!(cache.get(&"b"), Some(&2));
+//!
+//! assert_eq!(cache.evict_expired(&clock.now()), 1);
+//! assert_eq!(cache.len(), 2);
+//! ```
+
+use compare::{Compare, Natural};
+use map::{self, Map};
+use std::cell::Cell;
+use std::fmt::{self, Debug};
+#[cfg(feature = "range")] use std::ops::Bound;
+use std::ops::Add;
+use std::time::{Duration, Instant};
+
+/// A source of the current time.
+pub trait Clock {
+    /// A point in time.
+    type Instant: Ord + Clone + Add<Duration, Output=Self::Instant>;
+
+    /// Returns the current time.
+    fn now(&self) -> Self::Instant;
+}
+
+impl<'a, T: ?Sized> Clock for &'a T where T: Clock {
+    type Instant = T::Instant;
+    fn now(&self) -> T::Instant { (**self).now() }
+}
+
+/// The system's monotonic clock.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    type Instant = Instant;
+    fn now(&self) -> Instant { Instant::now() }
+}
+
+/// A clock that advances only when told to.
+///
+/// Its instants are the durations elapsed since the clock's creation.
+#[derive(Clone, Debug, Default)]
+pub struct ManualClock(Cell<Duration>);
+
+impl ManualClock {
+    /// Creates a clock reading zero.
+    pub fn new() -> Self { ManualClock::default() }
+
+    /// Advances the clock by the given duration.
+    pub fn advance(&self, duration: Duration) { self.0.set(self.0.get() + duration); }
+
+    /// Sets the clock to the given instant.
+    pub fn set(&self, now: Duration) { self.0.set(now); }
+}
+
+impl Clock for ManualClock {
+    type Instant = Duration;
+    fn now(&self) -> Duration { self.0.get() }
+}
+
+// A value and its deadline, if any, which is unique thanks to a sequence number.
+#[derive(Clone)]
+struct Slot<V, I> {
+    value: V,
+    deadline: Option<(I, u64)>,
+}
+
+impl<V, I> Slot<V, I> where I: Ord {
+    fn is_live(&self, now: &I) -> bool {
+        self.deadline.as_ref().map_or(true, |deadline| deadline.0 > *now)
+    }
+}
+
+/// An ordered map whose entries may expire.
+///
+/// See the [module documentation](index.html) for details.
+///
+/// An entry expires once the clock reaches its deadline. Operations other than `len` and
+/// `is_empty` treat expired entries as absent, whether or not they have been evicted.
+pub struct ExpiringMap<K, V, C = Natural<K>, T = SystemClock> where C: Compare<K>, T: Clock {
+    entries: Map<K, Slot<V, T::Instant>, C>,
+    deadlines: Map<(T::Instant, u64), K>,
+    next_id: u64,
+    clock: T,
+}
+
+impl<K, V> ExpiringMap<K, V> where K: Ord {
+    /// Creates an empty map ordered according to the natural order of its keys and using the
+    /// system's clock.
+    pub fn new() -> Self { ExpiringMap::with_cmp_and_clock(Natural::default(), SystemClock) }
+}
+
+impl<K, V, T> ExpiringMap<K, V, Natural<K>, T> where K: Ord, T: Clock {
+    /// Creates an empty map ordered according to the natural order of its keys and using the
+    /// given clock.
+    pub fn with_clock(clock: T) -> Self {
+        ExpiringMap::with_cmp_and_clock(Natural::default(), clock)
+    }
+}
+
+impl<K, V, C, T> ExpiringMap<K, V, C, T> where C: Compare<K>, T: Clock {
+    /// Creates an empty map ordered according to the given comparator and using the given clock.
+    pub fn with_cmp_and_clock(cmp: C, clock: T) -> Self {
+        ExpiringMap { entries: Map::with_cmp(cmp), deadlines: Map::new(), next_id: 0, clock: clock }
+    }
+
+    /// Checks if the map contains no entries, including expired entries that have not been
+    /// evicted.
+    pub fn is_empty(&self) -> bool { self.entries.is_empty() }
+
+    /// Returns the number of entries in the map, including expired entries that have not been
+    /// evicted.
+    pub fn len(&self) -> usize { self.entries.len() }
+
+    /// Returns a reference to the map's comparator.
+    pub fn cmp(&self) -> &C { self.entries.cmp() }
+
+    /// Returns a reference to the map's clock.
+    pub fn clock(&self) -> &T { &self.clock }
+
+    /// Removes all entries from the map.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.deadlines.clear();
+    }
+
+    /// Inserts an entry that never expires into the map, returning the previous value, if any,
+    /// associated with the key.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> where K: Clone {
+        self.insert_slot(key, value, None)
+    }
+
+    /// Inserts an entry that expires after the given duration into the map, returning the
+    /// previous value, if any, associated with the key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use tree::expiring::{ExpiringMap, ManualClock};
+    ///
+    /// let clock = ManualClock::new();
+    /// let mut map = ExpiringMap::with_clock(&clock);
+    ///
+    /// assert_eq!(map.insert_with_ttl("a", 1, Duration::from_secs(5)), None);
+    /// clock.advance(Duration::from_secs(5));
+    /// assert_eq!(map.insert_with_ttl("a", 2, Duration::from_secs(5)), None);
+    /// assert_eq!(map.get(&"a"), Some(&2));
+    /// ```
+    pub fn insert_with_ttl(&mut self, key: K, value: V, ttl: Duration) -> Option<V>
+        where K: Clone {
+
+        let deadline = self.clock.now() + ttl;
+        self.insert_with_deadline(key, value, deadline)
+    }
+
+    /// Inserts an entry that expires at the given instant into the map, returning the previous
+    /// value, if any, associated with the key.
+    pub fn insert_with_deadline(&mut self, key: K, value: V, deadline: T::Instant) -> Option<V>
+        where K: Clone {
+
+        let id = self.next_id;
+        self.next_id += 1;
+        self.deadlines.insert((deadline.clone(), id), key.clone());
+        self.insert_slot(key, value, Some((deadline, id)))
+    }
+
+    fn insert_slot(&mut self, key: K, value: V, deadline: Option<(T::Instant, u64)>)
+        -> Option<V> {
+
+        let now = self.clock.now();
+        let slot = Slot { value: value, deadline: deadline };
+
+        self.entries.insert(key, slot).and_then(|old| {
+            if let Some(ref deadline) = old.deadline { self.deadlines.remove(deadline); }
+            if old.is_live(&now) { Some(old.value) } else { None }
+        })
+    }
+
+    /// Removes and returns the entry whose key is equal to the given key, returning `None` if
+    /// the map does not contain the key or its entry has expired.
+    pub fn remove<Q: ?Sized>(&mut self, key: &Q) -> Option<(K, V)> where C: Compare<Q, K> {
+        let now = self.clock.now();
+
+        self.entries.remove(key).and_then(|(key, old)| {
+            if let Some(ref deadline) = old.deadline { self.deadlines.remove(deadline); }
+            if old.is_live(&now) { Some((key, old.value)) } else { None }
+        })
+    }
+
+    /// Returns a reference to the value associated with the given key, or `None` if the map
+    /// does not contain the key or its entry has expired.
+    pub fn get<Q: ?Sized>(&self, key: &Q) -> Option<&V> where C: Compare<Q, K> {
+        let now = self.clock.now();
+        self.entries.get(key).and_then(|slot| if slot.is_live(&now) { Some(&slot.value) }
+                                              else { None })
+    }
+
+    /// Returns a mutable reference to the value associated with the given key, or `None` if the
+    /// map does not contain the key or its entry has expired.
+    pub fn get_mut<Q: ?Sized>(&mut self, key: &Q) -> Option<&mut V> where C: Compare<Q, K> {
+        let now = self.clock.now();
+        self.entries.get_mut(key).and_then(|slot| if slot.is_live(&now) { Some(&mut slot.value) }
+                                                  else { None })
+    }
+
+    /// Checks if the map contains the given key and its entry has not expired.
+    pub fn contains_key<Q: ?Sized>(&self, key: &Q) -> bool where C: Compare<Q, K> {
+        self.get(key).is_some()
+    }
+
+    /// Returns the deadline of the entry whose key is equal to the given key, or `None` if the
+    /// map does not contain the key, its entry has expired or its entry never expires.
+    pub fn deadline<Q: ?Sized>(&self, key: &Q) -> Option<&T::Instant> where C: Compare<Q, K> {
+        let now = self.clock.now();
+
+        match self.entries.get(key) {
+            Some(slot) if slot.is_live(&now) => slot.deadline.as_ref().map(|deadline| &deadline.0),
+            _ => None,
+        }
+    }
+
+    /// Removes every entry that has expired at the given instant, returning the number of
+    /// removed entries.
+    ///
+    /// This takes time proportional to the number of removed entries times the logarithm of the
+    /// map's size.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use tree::expiring::{Clock, ExpiringMap, ManualClock};
+    ///
+    /// let clock = ManualClock::new();
+    /// let mut map = ExpiringMap::with_clock(&clock);
+    ///
+    /// for i in 0..10 { map.insert_with_ttl(i, i, Duration::from_secs(i)); }
+    ///
+    /// assert_eq!(map.evict_expired(&Duration::from_secs(4)), 5);
+    /// assert_eq!(map.len(), 5);
+    /// assert_eq!(map.evict_expired(&clock.now()), 0);
+    /// ```
+    pub fn evict_expired(&mut self, now: &T::Instant) -> usize {
+        let mut evicted = 0;
+
+        while self.deadlines.min().map_or(false, |(deadline, _)| deadline.0 <= *now) {
+            let (deadline, key) = self.deadlines.remove_min().unwrap();
+            let (_, slot) = self.entries.remove(&key).expect("map's indices are inconsistent");
+            debug_assert!(slot.deadline.as_ref().map_or(false, |d| d.1 == deadline.1));
+            evicted += 1;
+        }
+
+        evicted
+    }
+
+    /// Returns an iterator over the map's entries that have not expired.
+    ///
+    /// The iterator yields the entries in ascending order according to the map's comparator.
+    /// Entries are checked against the time at which the iterator was created.
+    pub fn iter(&self) -> Iter<'_, K, V, T::Instant> {
+        Iter { entries: self.entries.iter(), now: self.clock.now() }
+    }
+}
+
+#[cfg(feature = "range")]
+impl<K, V, C, T> ExpiringMap<K, V, C, T> where C: Compare<K>, T: Clock {
+    /// Returns an iterator over the map's entries whose keys lie in the given range and that
+    /// have not expired.
+    ///
+    /// The iterator yields the entries in ascending order according to the map's comparator.
+    /// Entries are checked against the time at which the iterator was created.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::ops::Bound::{Included, Unbounded};
+    /// use std::time::Duration;
+    /// use tree::expiring::{ExpiringMap, ManualClock};
+    ///
+    /// let clock = ManualClock::new();
+    /// let mut map = ExpiringMap::with_clock(&clock);
+    ///
+    /// map.insert("a", 1);
+    /// map.insert_with_ttl("b", 2, Duration::from_secs(1));
+    /// map.insert("c", 3);
+    /// clock.advance(Duration::from_secs(1));
+    ///
+    /// assert_eq!(map.range(Included(&"b"), Unbounded).collect::<Vec<_>>(), [(&"c", &3)]);
+    /// ```
+    pub fn range<Min: ?Sized, Max: ?Sized>(&self, min: Bound<&Min>, max: Bound<&Max>)
+        -> Range<'_, K, V, T::Instant> where C: Compare<Min, K> + Compare<Max, K> {
+
+        Range { entries: self.entries.range(min, max), now: self.clock.now() }
+    }
+}
+
+impl<K, V, C, T> Clone for ExpiringMap<K, V, C, T>
+    where K: Clone, V: Clone, C: Compare<K> + Clone, T: Clock + Clone {
+
+    fn clone(&self) -> Self {
+        ExpiringMap {
+            entries: self.entries.clone(),
+            deadlines: self.deadlines.clone(),
+            next_id: self.next_id,
+            clock: self.clock.clone(),
+        }
+    }
+}
+
+impl<K, V, C, T> Debug for ExpiringMap<K, V, C, T>
+    where K: Debug, V: Debug, C: Compare<K>, T: Clock {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K, V, C, T> Default for ExpiringMap<K, V, C, T>
+    where C: Compare<K> + Default, T: Clock + Default {
+
+    fn default() -> Self { ExpiringMap::with_cmp_and_clock(C::default(), T::default()) }
+}
+
+impl<'a, K, V, C, T> IntoIterator for &'a ExpiringMap<K, V, C, T> where C: Compare<K>, T: Clock {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V, T::Instant>;
+    fn into_iter(self) -> Self::IntoIter { self.iter() }
+}
+
+/// An iterator over the map's entries that have not expired.
+///
+/// Acquire through [`ExpiringMap::iter`](struct.ExpiringMap.html#method.iter).
+pub struct Iter<'a, K: 'a, V: 'a, I: 'a> {
+    entries: map::Iter<'a, K, Slot<V, I>>,
+    now: I,
+}
+
+impl<'a, K, V, I> Clone for Iter<'a, K, V, I> where I: Clone {
+    fn clone(&self) -> Self { Iter { entries: self.entries.clone(), now: self.now.clone() } }
+}
+
+impl<'a, K, V, I> Iterator for Iter<'a, K, V, I> where I: Ord {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let now = &self.now;
+        self.entries.by_ref().filter(|e| e.1.is_live(now)).map(|(k, slot)| (k, &slot.value))
+            .next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) { (0, self.entries.size_hint().1) }
+}
+
+impl<'a, K, V, I> DoubleEndedIterator for Iter<'a, K, V, I> where I: Ord {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let now = &self.now;
+        self.entries.by_ref().rev().filter(|e| e.1.is_live(now)).map(|(k, slot)| (k, &slot.value))
+            .next()
+    }
+}
+
+/// An iterator over the map's entries whose keys lie in a range and that have not expired.
+///
+/// Acquire through [`ExpiringMap::range`](struct.ExpiringMap.html#method.range).
+#[cfg(feature = "range")]
+pub struct Range<'a, K: 'a, V: 'a, I: 'a> {
+    entries: map::Range<'a, K, Slot<V, I>>,
+    now: I,
+}
+
+#[cfg(feature = "range")]
+impl<'a, K, V, I> Clone for Range<'a, K, V, I> where I: Clone {
+    fn clone(&self) -> Self { Range { entries: self.entries.clone(), now: self.now.clone() } }
+}
+
+#[cfg(feature = "range")]
+impl<'a, K, V, I> Iterator for Range<'a, K, V, I> where I: Ord {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let now = &self.now;
+        self.entries.by_ref().filter(|e| e.1.is_live(now)).map(|(k, slot)| (k, &slot.value))
+            .next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) { (0, self.entries.size_hint().1) }
+}
+
+#[cfg(feature = "range")]
+impl<'a, K, V, I> DoubleEndedIterator for Range<'a, K, V, I> where I: Ord {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let now = &self.now;
+        self.entries.by_ref().rev().filter(|e| e.1.is_live(now)).map(|(k, slot)| (k, &slot.value))
+            .next()
+    }
+}
@@ -0,0 +1,462 @@
+//! An immutable ordered map stored in a contiguous array.
+//!
+//! A `FrozenMap` is created by `Map::freeze` and stores its keys in Eytzinger (breadth-first)
+//! order: the children of the key at index `i` are at indices `2 * i + 1` and `2 * i + 2`. A
+//! lookup therefore descends an implicit, perfectly balanced tree whose upper levels share a few
+//! cache lines, with no pointers to chase. Values are stored in a separate array in the same
+//! order, so that lookups touch only keys.
+//!
+//! A frozen map cannot be modified, but `FrozenMap::thaw` converts it back into a `Map` in linear
+//! time.
+//!
+//! # Examples
+//!
+//! ```
+//! let map: tree::Map<_, _> = (0..10).map(|i| (i * 2, i)).collect();
+//! let frozen = map.freeze();
+//!
+//! assert_eq!(frozen.get(&6), Some(&3));
+//! assert_eq!(frozen.pred(&7, false), Some((&6, &3)));
+//! assert_eq!(frozen.succ(&7, false), Some((&8, &4)));
+//!
+//! let mut map = frozen.thaw();
+//! map.insert(7, 0);
+//! assert_eq!(map.len(), 11);
+//! ```
+
+use alloc::vec::Vec;
+use allocator::Global;
+use compare::{Compare, Natural};
+use core::cmp::Ordering::*;
+use core::fmt::{self, Debug};
+#[cfg(feature = "range")] use core::ops::Bound;
+use map::Map;
+
+/// An immutable ordered map stored in a contiguous array.
+///
+/// See the [module documentation](index.html) for details.
+#[derive(Clone)]
+pub struct FrozenMap<K, V, C = Natural<K>> where C: Compare<K> {
+    keys: Vec<K>,
+    values: Vec<V>,
+    cmp: C,
+}
+
+impl<K, V, C> FrozenMap<K, V, C> where C: Compare<K> {
+    // Builds a frozen map from entries that are in strictly ascending order according to `cmp`.
+    pub(crate) fn from_sorted<I>(it: I, cmp: C) -> Self where I: ExactSizeIterator<Item=(K, V)> {
+        let len = it.len();
+        let mut slots: Vec<Option<(K, V)>> = (0..len).map(|_| None).collect();
+        let mut i = first(len);
+
+        for entry in it {
+            let index = i.expect("iterator yielded too many entries");
+            slots[index] = Some(entry);
+            i = next(index, len);
+        }
+
+        let (keys, values) =
+            slots.into_iter().map(|slot| slot.expect("iterator yielded too few entries")).unzip();
+
+        FrozenMap { keys: keys, values: values, cmp: cmp }
+    }
+
+    /// Converts the frozen map back into a `Map`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let map: tree::Map<_, _> = vec![(2, "b"), (1, "a"), (3, "c")].into_iter().collect();
+    /// let mut map = map.freeze().thaw();
+    ///
+    /// map.insert(4, "d");
+    /// assert_eq!(map.iter().collect::<Vec<_>>(),
+    ///            [(&1, &"a"), (&2, &"b"), (&3, &"c"), (&4, &"d")]);
+    /// ```
+    pub fn thaw(self) -> Map<K, V, C> {
+        let len = self.keys.len();
+        let mut slots: Vec<_> = self.keys.into_iter().zip(self.values).map(Some).collect();
+        let mut sorted = Vec::with_capacity(len);
+        let mut i = first(len);
+
+        while let Some(index) = i {
+            sorted.push(slots[index].take().expect("entry visited twice"));
+            i = next(index, len);
+        }
+
+        Map::from_sorted(sorted, self.cmp, Global)
+    }
+
+    /// Checks if the map is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// assert!(tree::Map::<u32, u32>::new().freeze().is_empty());
+    /// ```
+    pub fn is_empty(&self) -> bool { self.keys.is_empty() }
+
+    /// Returns the number of entries in the map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let map: tree::Map<_, _> = (0..10).map(|i| (i, i)).collect();
+    /// assert_eq!(map.freeze().len(), 10);
+    /// ```
+    pub fn len(&self) -> usize { self.keys.len() }
+
+    /// Returns a reference to the map's comparator.
+    pub fn cmp(&self) -> &C { &self.cmp }
+
+    /// Returns a reference to the value associated with the given key, or `None` if the map does
+    /// not contain the key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let map: tree::Map<_, _> = vec![(1, "a"), (2, "b")].into_iter().collect();
+    /// let map = map.freeze();
+    ///
+    /// assert_eq!(map.get(&1), Some(&"a"));
+    /// assert_eq!(map.get(&3), None);
+    /// ```
+    pub fn get<Q: ?Sized>(&self, key: &Q) -> Option<&V> where C: Compare<Q, K> {
+        let mut i = 0;
+
+        while i < self.keys.len() {
+            match self.cmp.compare(key, &self.keys[i]) {
+                Less => i = 2 * i + 1,
+                Equal => return Some(&self.values[i]),
+                Greater => i = 2 * i + 2,
+            }
+        }
+
+        None
+    }
+
+    /// Checks if the map contains the given key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let map: tree::Map<_, _> = vec![(1, "a")].into_iter().collect();
+    /// let map = map.freeze();
+    ///
+    /// assert!(map.contains_key(&1));
+    /// assert!(!map.contains_key(&2));
+    /// ```
+    pub fn contains_key<Q: ?Sized>(&self, key: &Q) -> bool where C: Compare<Q, K> {
+        self.get(key).is_some()
+    }
+
+    /// Returns a reference to the maximum key in the map and its associated value, or `None` if
+    /// the map is empty.
+    pub fn max(&self) -> Option<(&K, &V)> { last(self.len()).map(|i| self.entry(i)) }
+
+    /// Returns a reference to the minimum key in the map and its associated value, or `None` if
+    /// the map is empty.
+    pub fn min(&self) -> Option<(&K, &V)> { first(self.len()).map(|i| self.entry(i)) }
+
+    /// Returns a reference to the predecessor of the given key and its associated value, as
+    /// `Map::pred` does.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let map: tree::Map<_, _> = vec![(1, "a"), (2, "b"), (3, "c")].into_iter().collect();
+    /// let map = map.freeze();
+    ///
+    /// assert_eq!(map.pred(&1, false), None);
+    /// assert_eq!(map.pred(&2, false), Some((&1, &"a")));
+    /// assert_eq!(map.pred(&2, true), Some((&2, &"b")));
+    /// assert_eq!(map.pred(&4, true), Some((&3, &"c")));
+    /// ```
+    pub fn pred<Q: ?Sized>(&self, key: &Q, inclusive: bool) -> Option<(&K, &V)>
+        where C: Compare<Q, K> {
+
+        self.pred_index(key, inclusive).map(|i| self.entry(i))
+    }
+
+    /// Returns a reference to the successor of the given key and its associated value, as
+    /// `Map::succ` does.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let map: tree::Map<_, _> = vec![(1, "a"), (2, "b"), (3, "c")].into_iter().collect();
+    /// let map = map.freeze();
+    ///
+    /// assert_eq!(map.succ(&0, false), Some((&1, &"a")));
+    /// assert_eq!(map.succ(&2, false), Some((&3, &"c")));
+    /// assert_eq!(map.succ(&2, true), Some((&2, &"b")));
+    /// assert_eq!(map.succ(&3, false), None);
+    /// ```
+    pub fn succ<Q: ?Sized>(&self, key: &Q, inclusive: bool) -> Option<(&K, &V)>
+        where C: Compare<Q, K> {
+
+        self.succ_index(key, inclusive).map(|i| self.entry(i))
+    }
+
+    /// Returns an iterator over the map's entries with immutable references to the values.
+    ///
+    /// The iterator yields the entries in ascending order according to the map's comparator.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let map: tree::Map<_, _> = vec![(2, "b"), (1, "a"), (3, "c")].into_iter().collect();
+    /// let map = map.freeze();
+    ///
+    /// assert_eq!(map.iter().collect::<Vec<_>>(), [(&1, &"a"), (&2, &"b"), (&3, &"c")]);
+    /// assert_eq!(map.iter().rev().next(), Some((&3, &"c")));
+    /// ```
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        let len = self.len();
+        Iter { entries: Entries::new(&self.keys, &self.values, first(len), last(len)), len: len }
+    }
+
+    /// Returns an iterator over the map's entries whose keys lie in the given range with immutable
+    /// references to the values, as `Map::range` does.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::ops::Bound::{Excluded, Included, Unbounded};
+    ///
+    /// let map: tree::Map<_, _> = (0..10).map(|i| (i, i * 10)).collect();
+    /// let map = map.freeze();
+    ///
+    /// assert_eq!(map.range(Excluded(&2), Included(&4)).collect::<Vec<_>>(),
+    ///            [(&3, &30), (&4, &40)]);
+    /// assert_eq!(map.range(Included(&8), Unbounded).rev().collect::<Vec<_>>(),
+    ///            [(&9, &90), (&8, &80)]);
+    /// assert_eq!(map.range(Included(&5), Excluded(&5)).next(), None);
+    /// ```
+    #[cfg(feature = "range")]
+    pub fn range<Min: ?Sized, Max: ?Sized>(&self, min: Bound<&Min>, max: Bound<&Max>)
+        -> Range<'_, K, V> where C: Compare<Min, K> + Compare<Max, K> {
+
+        let len = self.len();
+
+        let front = match min {
+            Bound::Included(key) => self.succ_index(key, true),
+            Bound::Excluded(key) => self.succ_index(key, false),
+            Bound::Unbounded => first(len),
+        };
+
+        let back = match max {
+            Bound::Included(key) => self.pred_index(key, true),
+            Bound::Excluded(key) => self.pred_index(key, false),
+            Bound::Unbounded => last(len),
+        };
+
+        let (front, back) = match (front, back) {
+            (Some(front), Some(back))
+                if self.cmp.compares_le(&self.keys[front], &self.keys[back]) =>
+                    (Some(front), Some(back)),
+            _ => (None, None),
+        };
+
+        Range(Entries::new(&self.keys, &self.values, front, back))
+    }
+
+    fn entry(&self, i: usize) -> (&K, &V) { (&self.keys[i], &self.values[i]) }
+
+    fn pred_index<Q: ?Sized>(&self, key: &Q, inclusive: bool) -> Option<usize>
+        where C: Compare<Q, K> {
+
+        let mut i = 0;
+        let mut found = None;
+
+        while i < self.keys.len() {
+            let k = &self.keys[i];
+
+            if inclusive && self.cmp.compares_ge(key, k) || self.cmp.compares_gt(key, k) {
+                found = Some(i);
+                i = 2 * i + 2;
+            } else {
+                i = 2 * i + 1;
+            }
+        }
+
+        found
+    }
+
+    fn succ_index<Q: ?Sized>(&self, key: &Q, inclusive: bool) -> Option<usize>
+        where C: Compare<Q, K> {
+
+        let mut i = 0;
+        let mut found = None;
+
+        while i < self.keys.len() {
+            let k = &self.keys[i];
+
+            if inclusive && self.cmp.compares_le(key, k) || self.cmp.compares_lt(key, k) {
+                found = Some(i);
+                i = 2 * i + 1;
+            } else {
+                i = 2 * i + 2;
+            }
+        }
+
+        found
+    }
+}
+
+impl<K, V, C> Debug for FrozenMap<K, V, C> where K: Debug, V: Debug, C: Compare<K> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<'a, K, V, C> IntoIterator for &'a FrozenMap<K, V, C> where C: Compare<K> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+    fn into_iter(self) -> Iter<'a, K, V> { self.iter() }
+}
+
+// Returns the index of the minimum entry in an Eytzinger array of the given length.
+fn first(len: usize) -> Option<usize> {
+    if len == 0 { return None; }
+    let mut i = 0;
+    while 2 * i + 1 < len { i = 2 * i + 1; }
+    Some(i)
+}
+
+// Returns the index of the maximum entry in an Eytzinger array of the given length.
+fn last(len: usize) -> Option<usize> {
+    if len == 0 { return None; }
+    let mut i = 0;
+    while 2 * i + 2 < len { i = 2 * i + 2; }
+    Some(i)
+}
+
+// Returns the index of the entry following the one at index `i`.
+fn next(mut i: usize, len: usize) -> Option<usize> {
+    if 2 * i + 2 < len {
+        i = 2 * i + 2;
+        while 2 * i + 1 < len { i = 2 * i + 1; }
+        return Some(i);
+    }
+
+    // Climb while `i` is a right child.
+    while i > 0 && i % 2 == 0 { i = (i - 1) / 2; }
+    if i == 0 { None } else { Some((i - 1) / 2) }
+}
+
+// Returns the index of the entry preceding the one at index `i`.
+fn prev(mut i: usize, len: usize) -> Option<usize> {
+    if 2 * i + 1 < len {
+        i = 2 * i + 1;
+        while 2 * i + 2 < len { i = 2 * i + 2; }
+        return Some(i);
+    }
+
+    // Climb while `i` is a left child.
+    while i % 2 == 1 { i = (i - 1) / 2; }
+    if i == 0 { None } else { Some((i - 1) / 2) }
+}
+
+// The entries between two indices, inclusive, in ascending order.
+#[derive(Clone)]
+struct Entries<'a, K: 'a, V: 'a> {
+    keys: &'a [K],
+    values: &'a [V],
+    front: usize,
+    back: usize,
+    done: bool,
+}
+
+impl<'a, K, V> Entries<'a, K, V> {
+    fn new(keys: &'a [K], values: &'a [V], front: Option<usize>, back: Option<usize>) -> Self {
+        match (front, back) {
+            (Some(front), Some(back)) =>
+                Entries { keys: keys, values: values, front: front, back: back, done: false },
+            _ => Entries { keys: keys, values: values, front: 0, back: 0, done: true },
+        }
+    }
+
+    fn entry(&self, i: usize) -> (&'a K, &'a V) { (&self.keys[i], &self.values[i]) }
+
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        if self.done { return None; }
+        let i = self.front;
+
+        if i == self.back {
+            self.done = true;
+        } else {
+            self.front = next(i, self.keys.len()).expect("range ended before its back");
+        }
+
+        Some(self.entry(i))
+    }
+
+    fn next_back(&mut self) -> Option<(&'a K, &'a V)> {
+        if self.done { return None; }
+        let i = self.back;
+
+        if i == self.front {
+            self.done = true;
+        } else {
+            self.back = prev(i, self.keys.len()).expect("range ended before its front");
+        }
+
+        Some(self.entry(i))
+    }
+}
+
+/// An iterator over a frozen map's entries with immutable references to the values.
+///
+/// The iterator yields the entries in ascending order according to the map's comparator.
+///
+/// Acquire through [`FrozenMap::iter`](struct.FrozenMap.html#method.iter) or the
+/// `IntoIterator` implementation for `&FrozenMap`.
+#[derive(Clone)]
+pub struct Iter<'a, K: 'a, V: 'a> {
+    entries: Entries<'a, K, V>,
+    len: usize,
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.entries.next();
+        if entry.is_some() { self.len -= 1; }
+        entry
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) { (self.len, Some(self.len)) }
+}
+
+impl<'a, K, V> DoubleEndedIterator for Iter<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let entry = self.entries.next_back();
+        if entry.is_some() { self.len -= 1; }
+        entry
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for Iter<'a, K, V> {}
+
+/// An iterator over a frozen map's entries whose keys lie in a given range with immutable
+/// references to the values.
+///
+/// The iterator yields the entries in ascending order according to the map's comparator.
+///
+/// Acquire through [`FrozenMap::range`](struct.FrozenMap.html#method.range).
+#[cfg(feature = "range")]
+#[derive(Clone)]
+pub struct Range<'a, K: 'a, V: 'a>(Entries<'a, K, V>);
+
+#[cfg(feature = "range")]
+impl<'a, K, V> Iterator for Range<'a, K, V> {
+    type Item = (&'a K, &'a V);
+    fn next(&mut self) -> Option<Self::Item> { self.0.next() }
+}
+
+#[cfg(feature = "range")]
+impl<'a, K, V> DoubleEndedIterator for Range<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> { self.0.next_back() }
+}
@@ -0,0 +1,616 @@
+//! An ordered map supporting updates and aggregate queries over key ranges.
+//!
+//! Every node of a `LazyMap` caches an aggregate of the values in its subtree, such as their sum
+//! or maximum, so the aggregate of any key range can be computed in logarithmic time. An update
+//! applied to a key range, such as adding a delta to each value, is applied eagerly only to the
+//! logarithmic number of nodes covering the range; the rest of the work is recorded in those nodes
+//! as a pending update and pushed down to their children only when they are visited by a later
+//! operation.
+//!
+//! The aggregates and updates are defined by an implementation of `Ops`. The module provides
+//! `AddSum`, `AddMax` and `AddMin` for the primitive numeric types.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::ops::Bound::{Excluded, Included, Unbounded};
+//! use tree::lazy::{AddSum, LazyMap};
+//!
+//! let mut prices: LazyMap<u32, i64, AddSum> = (0..10).map(|i| (i, 100)).collect();
+//!
+//! prices.update_range(Included(&2), Excluded(&5), 10);
+//! assert_eq!(prices.get(&3), Some(&110));
+//! assert_eq!(prices.aggregate(Unbounded, Unbounded), Some(1030));
+//! assert_eq!(prices.aggregate(Included(&4), Included(&5)), Some(210));
+//! ```
+
+use allocator::Global;
+use compare::{Compare, Natural};
+use core::fmt::{self, Debug};
+use core::iter;
+use core::ops::Bound;
+use super::node::{self, Augment, Counters, MarkedNode, NodeBox};
+use super::node::build::{Get, GetMut, PathBuilder};
+
+/// The aggregates and updates of a `LazyMap`.
+///
+/// Implementations must satisfy the following laws, where `agg(x..y)` denotes the aggregate of a
+/// sequence of values:
+///
+/// - `combine` is associative.
+/// - Updating an aggregate is equivalent to updating each of its values:
+///   `update_agg(u, agg(x..y), n)` is equivalent to the aggregate of the `n` values `x..y`, each
+///   updated with `u`.
+/// - `compose(first, second)` is equivalent to applying `first` and then `second`.
+pub trait Ops<V> {
+    /// An aggregate of a sequence of values, such as their sum.
+    type Agg: Clone;
+
+    /// An update that can be applied to every value in a key range, such as adding a delta.
+    type Update: Clone;
+
+    /// Returns the aggregate of a single value.
+    fn agg(value: &V) -> Self::Agg;
+
+    /// Returns the aggregate of two adjacent sequences of values, given their aggregates.
+    fn combine(left: &Self::Agg, right: &Self::Agg) -> Self::Agg;
+
+    /// Applies the update to a value.
+    fn update(update: &Self::Update, value: &mut V);
+
+    /// Applies the update to the aggregate of `len` values.
+    fn update_agg(update: &Self::Update, agg: &mut Self::Agg, len: usize);
+
+    /// Returns an update equivalent to applying `first` and then `second`.
+    fn compose(first: &Self::Update, second: &Self::Update) -> Self::Update;
+}
+
+/// Adds a delta to values and aggregates them by their sum.
+///
+/// For the integer types, values, deltas and sums are computed with wrapping arithmetic, so a
+/// value or aggregate equals its exact result whenever that result fits in the type, even if an
+/// intermediate result does not.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AddSum;
+
+/// Adds a delta to values and aggregates them by their maximum.
+///
+/// For the integer types, values and deltas are computed with wrapping arithmetic, so an update
+/// never panics. The aggregate is the maximum of the values as long as no value wraps.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AddMax;
+
+/// Adds a delta to values and aggregates them by their minimum.
+///
+/// For the integer types, values and deltas are computed with wrapping arithmetic, so an update
+/// never panics. The aggregate is the minimum of the values as long as no value wraps.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AddMin;
+
+macro_rules! add_ops {
+    ($($T:ty)*) => {$(
+        impl Ops<$T> for AddMax {
+            type Agg = $T;
+            type Update = $T;
+            fn agg(value: &$T) -> $T { *value }
+            fn combine(left: &$T, right: &$T) -> $T { if left >= right { *left } else { *right } }
+            fn update(update: &$T, value: &mut $T) { *value = value.wrapping_add(*update); }
+
+            fn update_agg(update: &$T, agg: &mut $T, _len: usize) {
+                *agg = agg.wrapping_add(*update);
+            }
+
+            fn compose(first: &$T, second: &$T) -> $T { first.wrapping_add(*second) }
+        }
+
+        impl Ops<$T> for AddMin {
+            type Agg = $T;
+            type Update = $T;
+            fn agg(value: &$T) -> $T { *value }
+            fn combine(left: &$T, right: &$T) -> $T { if left <= right { *left } else { *right } }
+            fn update(update: &$T, value: &mut $T) { *value = value.wrapping_add(*update); }
+
+            fn update_agg(update: &$T, agg: &mut $T, _len: usize) {
+                *agg = agg.wrapping_add(*update);
+            }
+
+            fn compose(first: &$T, second: &$T) -> $T { first.wrapping_add(*second) }
+        }
+    )*}
+}
+
+macro_rules! add_float_ops {
+    ($($T:ty)*) => {$(
+        impl Ops<$T> for AddMax {
+            type Agg = $T;
+            type Update = $T;
+            fn agg(value: &$T) -> $T { *value }
+            fn combine(left: &$T, right: &$T) -> $T { if left >= right { *left } else { *right } }
+            fn update(update: &$T, value: &mut $T) { *value += *update; }
+            fn update_agg(update: &$T, agg: &mut $T, _len: usize) { *agg += *update; }
+            fn compose(first: &$T, second: &$T) -> $T { *first + *second }
+        }
+
+        impl Ops<$T> for AddMin {
+            type Agg = $T;
+            type Update = $T;
+            fn agg(value: &$T) -> $T { *value }
+            fn combine(left: &$T, right: &$T) -> $T { if left <= right { *left } else { *right } }
+            fn update(update: &$T, value: &mut $T) { *value += *update; }
+            fn update_agg(update: &$T, agg: &mut $T, _len: usize) { *agg += *update; }
+            fn compose(first: &$T, second: &$T) -> $T { *first + *second }
+        }
+    )*}
+}
+
+macro_rules! add_sum_ops {
+    ($($T:ty)*) => {$(
+        impl Ops<$T> for AddSum {
+            type Agg = $T;
+            type Update = $T;
+            fn agg(value: &$T) -> $T { *value }
+            fn combine(left: &$T, right: &$T) -> $T { left.wrapping_add(*right) }
+            fn update(update: &$T, value: &mut $T) { *value = value.wrapping_add(*update); }
+            fn update_agg(update: &$T, agg: &mut $T, len: usize) {
+                // `len as $T` may truncate, but only by a multiple of the type's modulus, which
+                // wrapping arithmetic ignores.
+                *agg = agg.wrapping_add(update.wrapping_mul(len as $T));
+            }
+
+            fn compose(first: &$T, second: &$T) -> $T { first.wrapping_add(*second) }
+        }
+    )*}
+}
+
+macro_rules! add_sum_float_ops {
+    ($($T:ty)*) => {$(
+        impl Ops<$T> for AddSum {
+            type Agg = $T;
+            type Update = $T;
+            fn agg(value: &$T) -> $T { *value }
+            fn combine(left: &$T, right: &$T) -> $T { *left + *right }
+            fn update(update: &$T, value: &mut $T) { *value += *update; }
+            fn update_agg(update: &$T, agg: &mut $T, len: usize) { *agg += *update * len as $T; }
+            fn compose(first: &$T, second: &$T) -> $T { *first + *second }
+        }
+    )*}
+}
+
+add_ops!(i8 i16 i32 i64 i128 isize u8 u16 u32 u64 u128 usize);
+add_float_ops!(f32 f64);
+add_sum_ops!(i8 i16 i32 i64 i128 isize u8 u16 u32 u64 u128 usize);
+add_sum_float_ops!(f32 f64);
+
+// The augmentation of a `LazyMap`'s tree: the aggregate of the node's subtree, the subtree's
+// length, and the update pending for the node's children.
+//
+// A node's value and aggregate reflect every update applied to it, except for those pending in
+// its ancestors.
+struct Tags<V, O> where O: Ops<V> {
+    agg: O::Agg,
+    len: usize,
+    pending: Option<O::Update>,
+}
+
+impl<V, O> Clone for Tags<V, O> where O: Ops<V> {
+    fn clone(&self) -> Self {
+        Tags { agg: self.agg.clone(), len: self.len, pending: self.pending.clone() }
+    }
+}
+
+impl<K, V, O> Augment<K, V> for Tags<V, O> where O: Ops<V> {
+    fn new(_key: &K, value: &V) -> Self { Tags { agg: O::agg(value), len: 1, pending: None } }
+
+    fn update(&mut self, _key: &K, value: &V, left: Option<&Self>, right: Option<&Self>) {
+        let mut agg = O::agg(value);
+        let mut len = 1;
+
+        if let Some(left) = left {
+            agg = O::combine(&left.agg, &agg);
+            len += left.len;
+        }
+
+        if let Some(right) = right {
+            agg = O::combine(&agg, &right.agg);
+            len += right.len;
+        }
+
+        self.agg = agg;
+        self.len = len;
+    }
+
+    fn push(&mut self, left: Option<(&mut V, &mut Self)>, right: Option<(&mut V, &mut Self)>) {
+        if let Some(update) = self.pending.take() {
+            if let Some((value, tags)) = left { apply(value, tags, &update); }
+            if let Some((value, tags)) = right { apply(value, tags, &update); }
+        }
+    }
+}
+
+type Node<K, V, O> = node::Node<K, V, Global, Tags<V, O>>;
+
+// Applies the update to every value in a node's subtree, given the node's value and tags, and
+// defers the work for its children.
+fn apply<V, O>(value: &mut V, tags: &mut Tags<V, O>, update: &O::Update) where O: Ops<V> {
+    O::update(update, value);
+    O::update_agg(update, &mut tags.agg, tags.len);
+
+    tags.pending = Some(match tags.pending.take() {
+        None => update.clone(),
+        Some(pending) => O::compose(&pending, update),
+    });
+}
+
+// Pushes every pending update in the subtree down to the leaves.
+fn push_all<K, V, O>(node: &mut Node<K, V, O>) where O: Ops<V> {
+    node.push();
+    if let Some(left) = node.left_child_mut() { push_all(left); }
+    if let Some(right) = node.right_child_mut() { push_all(right); }
+}
+
+// Adds the entries of the subtree to the formatter in order. `outer` is the update pending for
+// the node in its ancestors, which is applied to a copy of each value as it is formatted.
+fn fmt_entries<K, V, O>(node: Option<&Node<K, V, O>>, outer: Option<&O::Update>,
+                        map: &mut fmt::DebugMap) where K: Debug, V: Debug + Clone, O: Ops<V> {
+    let node = match node {
+        None => return,
+        Some(node) => node,
+    };
+
+    let pending = match (node.aug().pending.as_ref(), outer) {
+        (Some(inner), Some(outer)) => Some(O::compose(inner, outer)),
+        (inner, outer) => inner.or(outer).cloned(),
+    };
+
+    fmt_entries(node.left_child(), pending.as_ref(), map);
+
+    match outer {
+        None => { map.entry(node.key(), node.value()); }
+        Some(update) => {
+            let mut value = node.value().clone();
+            O::update(update, &mut value);
+            map.entry(node.key(), &value);
+        }
+    }
+
+    fmt_entries(node.right_child(), pending.as_ref(), map);
+}
+
+fn is_unbounded<T>(bound: &Bound<T>) -> bool {
+    match *bound { Bound::Unbounded => true, _ => false }
+}
+
+// The part of a range that a subtree may intersect. `min_covered` and `max_covered` indicate
+// whether every key in the subtree is known to satisfy the lower and upper bounds, respectively.
+struct Span<'a, C: 'a + ?Sized, Min: 'a + ?Sized, Max: 'a + ?Sized> {
+    cmp: &'a C,
+    min: Bound<&'a Min>,
+    max: Bound<&'a Max>,
+}
+
+impl<'a, C: ?Sized, Min: ?Sized, Max: ?Sized> Span<'a, C, Min, Max> {
+    fn update<K, V, O>(&self, node: Option<&mut Node<K, V, O>>, min_covered: bool,
+                       max_covered: bool, update: &O::Update)
+        where C: Compare<Min, K> + Compare<Max, K>, O: Ops<V> {
+
+        let node = match node {
+            None => return,
+            Some(node) => node,
+        };
+
+        if min_covered && max_covered {
+            let (value, tags) = node.parts_mut();
+            return apply(value, tags, update);
+        }
+
+        node.push();
+
+        if !min_covered && node::below(self.cmp, node.key(), self.min) {
+            self.update(node.right_child_mut(), false, max_covered, update);
+        } else if !max_covered && node::above(self.cmp, node.key(), self.max) {
+            self.update(node.left_child_mut(), min_covered, false, update);
+        } else {
+            O::update(update, node.value_mut());
+            self.update(node.left_child_mut(), min_covered, true, update);
+            self.update(node.right_child_mut(), true, max_covered, update);
+        }
+
+        node.update();
+    }
+
+    fn aggregate<K, V, O>(&self, node: Option<&mut Node<K, V, O>>, min_covered: bool,
+                          max_covered: bool) -> Option<O::Agg>
+        where C: Compare<Min, K> + Compare<Max, K>, O: Ops<V> {
+
+        let node = match node {
+            None => return None,
+            Some(node) => node,
+        };
+
+        if min_covered && max_covered { return Some(node.aug().agg.clone()); }
+
+        node.push();
+
+        if !min_covered && node::below(self.cmp, node.key(), self.min) {
+            self.aggregate(node.right_child_mut(), false, max_covered)
+        } else if !max_covered && node::above(self.cmp, node.key(), self.max) {
+            self.aggregate(node.left_child_mut(), min_covered, false)
+        } else {
+            let agg = O::agg(node.value());
+
+            let agg = match self.aggregate(node.left_child_mut(), min_covered, true) {
+                None => agg,
+                Some(left) => O::combine(&left, &agg),
+            };
+
+            match self.aggregate(node.right_child_mut(), true, max_covered) {
+                None => Some(agg),
+                Some(right) => Some(O::combine(&agg, &right)),
+            }
+        }
+    }
+}
+
+/// An ordered map supporting updates and aggregate queries over key ranges.
+///
+/// See the [module documentation](index.html) for details.
+///
+/// Operations that read values push pending updates down the paths they visit, so they require
+/// mutable access to the map. The map provides no mutable access to individual values, since
+/// changing a value would invalidate the aggregates of its ancestors. Use `insert` or
+/// `update_range` instead.
+pub struct LazyMap<K, V, O, C = Natural<K>> where C: Compare<K>, O: Ops<V> {
+    root: node::Link<K, V, Global, Tags<V, O>>,
+    cmp: C,
+}
+
+impl<K, V, O> LazyMap<K, V, O> where K: Ord, O: Ops<V> {
+    /// Creates an empty map ordered according to the natural order of its keys.
+    pub fn new() -> Self { LazyMap::with_cmp(Natural::default()) }
+}
+
+impl<K, V, O, C> LazyMap<K, V, O, C> where C: Compare<K>, O: Ops<V> {
+    /// Creates an empty map ordered according to the given comparator.
+    pub fn with_cmp(cmp: C) -> Self { LazyMap { root: None, cmp: cmp } }
+
+    /// Checks if the map is empty.
+    pub fn is_empty(&self) -> bool { self.root.is_none() }
+
+    /// Returns the number of entries in the map.
+    pub fn len(&self) -> usize { self.root.as_ref().map_or(0, |node| node.aug().len) }
+
+    /// Returns a reference to the map's comparator.
+    pub fn cmp(&self) -> &C { &self.cmp }
+
+    /// Removes all entries from the map.
+    pub fn clear(&mut self) { self.root = None; }
+
+    /// Inserts an entry into the map, returning the previous value, if any, associated
+    /// with the key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tree::lazy::{AddSum, LazyMap};
+    ///
+    /// let mut map: LazyMap<_, _, AddSum> = LazyMap::new();
+    /// assert_eq!(map.insert(1, 10), None);
+    /// assert_eq!(map.insert(1, 20), Some(10));
+    /// assert_eq!(map.aggregate_all(), Some(&20));
+    /// ```
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        node::insert(&mut self.root, &self.cmp, key, value, &Global, &Counters::default())
+    }
+
+    /// Removes and returns the entry whose key is equal to the given key, returning
+    /// `None` if the map does not contain the key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::ops::Bound::Unbounded;
+    /// use tree::lazy::{AddSum, LazyMap};
+    ///
+    /// let mut map: LazyMap<_, _, AddSum> = vec![(1, 10), (2, 20)].into_iter().collect();
+    /// map.update_range(Unbounded, Unbounded, 1);
+    ///
+    /// assert_eq!(map.remove(&1), Some((1, 11)));
+    /// assert_eq!(map.remove(&1), None);
+    /// ```
+    pub fn remove<Q: ?Sized>(&mut self, key: &Q) -> Option<(K, V)> where C: Compare<Q, K> {
+        node::find(&mut self.root, PathBuilder::default(), &self.cmp, key)
+            .take(&Counters::default())
+    }
+
+    /// Returns a reference to the value associated with the given key, or `None` if the
+    /// map does not contain the key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::ops::Bound::Included;
+    /// use tree::lazy::{AddSum, LazyMap};
+    ///
+    /// let mut map: LazyMap<_, _, AddSum> = (0..10).map(|i| (i, 0)).collect();
+    /// map.update_range(Included(&3), Included(&6), 5);
+    ///
+    /// assert_eq!(map.get(&2), Some(&0));
+    /// assert_eq!(map.get(&3), Some(&5));
+    /// assert_eq!(map.get(&10), None);
+    /// ```
+    pub fn get<Q: ?Sized>(&mut self, key: &Q) -> Option<&V> where C: Compare<Q, K> {
+        node::find(&mut self.root, GetMut::default(), &self.cmp, key).map(|e| &*e.1)
+    }
+
+    /// Checks if the map contains the given key.
+    pub fn contains_key<Q: ?Sized>(&self, key: &Q) -> bool where C: Compare<Q, K> {
+        node::find(&self.root, Get::default(), &self.cmp, key).is_some()
+    }
+
+    /// Applies the update to every value whose key lies in the given range.
+    ///
+    /// This takes logarithmic time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::ops::Bound::{Excluded, Included, Unbounded};
+    /// use tree::lazy::{AddMax, LazyMap};
+    ///
+    /// let mut map: LazyMap<_, _, AddMax> = (0..10).map(|i| (i, i)).collect();
+    /// map.update_range(Unbounded, Excluded(&5), 10);
+    ///
+    /// assert_eq!(map.aggregate(Unbounded, Unbounded), Some(14));
+    /// assert_eq!(map.aggregate(Included(&5), Unbounded), Some(9));
+    /// ```
+    pub fn update_range<Min: ?Sized, Max: ?Sized>(&mut self, min: Bound<&Min>, max: Bound<&Max>,
+                                                  update: O::Update)
+        where C: Compare<Min, K> + Compare<Max, K> {
+
+        let span = Span { cmp: &self.cmp, min: min, max: max };
+        span.update(self.root.as_mut().map(|node| &mut **node), is_unbounded(&min),
+                    is_unbounded(&max), &update);
+    }
+
+    /// Returns the aggregate of the values whose keys lie in the given range, or `None` if the
+    /// range contains no keys.
+    ///
+    /// This takes logarithmic time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::ops::Bound::{Excluded, Included};
+    /// use tree::lazy::{AddMin, LazyMap};
+    ///
+    /// let mut map: LazyMap<_, _, AddMin> = (0..10).map(|i| (i, 10 - i)).collect();
+    ///
+    /// assert_eq!(map.aggregate(Included(&2), Excluded(&5)), Some(6));
+    /// assert_eq!(map.aggregate(Included(&20), Excluded(&30)), None);
+    /// ```
+    pub fn aggregate<Min: ?Sized, Max: ?Sized>(&mut self, min: Bound<&Min>, max: Bound<&Max>)
+        -> Option<O::Agg> where C: Compare<Min, K> + Compare<Max, K> {
+
+        let span = Span { cmp: &self.cmp, min: min, max: max };
+        span.aggregate(self.root.as_mut().map(|node| &mut **node), is_unbounded(&min),
+                       is_unbounded(&max))
+    }
+
+    /// Returns the aggregate of all values in the map, or `None` if the map is empty.
+    ///
+    /// This takes constant time.
+    pub fn aggregate_all(&self) -> Option<&O::Agg> {
+        self.root.as_ref().map(|node| &node.aug().agg)
+    }
+
+    /// Returns an iterator over the map's entries.
+    ///
+    /// The iterator yields the entries in ascending order according to the map's comparator.
+    /// Creating it pushes all pending updates down to the leaves, which takes linear time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::ops::Bound::{Included, Unbounded};
+    /// use tree::lazy::{AddSum, LazyMap};
+    ///
+    /// let mut map: LazyMap<_, _, AddSum> = (0..4).map(|i| (i, i)).collect();
+    /// map.update_range(Included(&2), Unbounded, 10);
+    ///
+    /// assert_eq!(map.iter().map(|e| *e.1).collect::<Vec<_>>(), [0, 1, 12, 13]);
+    /// ```
+    pub fn iter(&mut self) -> Iter<'_, K, V, O> {
+        if let Some(ref mut root) = self.root { push_all(root); }
+        Iter(node::Iter::new(self.root.as_ref().map(MarkedNode::new), self.len()))
+    }
+}
+
+impl<K, V, O, C> Clone for LazyMap<K, V, O, C>
+    where K: Clone, V: Clone, C: Compare<K> + Clone, O: Ops<V> {
+
+    fn clone(&self) -> Self { LazyMap { root: self.root.clone(), cmp: self.cmp.clone() } }
+}
+
+impl<K, V, O, C> Debug for LazyMap<K, V, O, C>
+    where K: Debug, V: Debug + Clone, C: Compare<K>, O: Ops<V> {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut map = f.debug_map();
+        fmt_entries(self.root.as_ref().map(|node| &**node), None, &mut map);
+        map.finish()
+    }
+}
+
+impl<K, V, O, C> Default for LazyMap<K, V, O, C> where C: Compare<K> + Default, O: Ops<V> {
+    fn default() -> Self { LazyMap::with_cmp(C::default()) }
+}
+
+impl<K, V, O, C> Extend<(K, V)> for LazyMap<K, V, O, C> where C: Compare<K>, O: Ops<V> {
+    fn extend<I: IntoIterator<Item=(K, V)>>(&mut self, it: I) {
+        for (k, v) in it { self.insert(k, v); }
+    }
+}
+
+impl<K, V, O, C> iter::FromIterator<(K, V)> for LazyMap<K, V, O, C>
+    where C: Compare<K> + Default, O: Ops<V> {
+
+    fn from_iter<I: IntoIterator<Item=(K, V)>>(it: I) -> Self {
+        let mut map = LazyMap::default();
+        map.extend(it);
+        map
+    }
+}
+
+impl<K, V, O, C> IntoIterator for LazyMap<K, V, O, C> where C: Compare<K>, O: Ops<V> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V, O>;
+
+    fn into_iter(mut self) -> IntoIter<K, V, O> {
+        if let Some(ref mut root) = self.root { push_all(root); }
+        let len = self.len();
+        IntoIter(node::Iter::new(self.root.take(), len))
+    }
+}
+
+/// An iterator that consumes the map.
+///
+/// Acquire through the `IntoIterator` trait.
+pub struct IntoIter<K, V, O>(node::Iter<NodeBox<K, V, Global, Tags<V, O>>>) where O: Ops<V>;
+
+impl<K, V, O> Iterator for IntoIter<K, V, O> where O: Ops<V> {
+    type Item = (K, V);
+    fn next(&mut self) -> Option<Self::Item> { self.0.next() }
+    fn size_hint(&self) -> (usize, Option<usize>) { self.0.size_hint() }
+}
+
+impl<K, V, O> DoubleEndedIterator for IntoIter<K, V, O> where O: Ops<V> {
+    fn next_back(&mut self) -> Option<Self::Item> { self.0.next_back() }
+}
+
+impl<K, V, O> ExactSizeIterator for IntoIter<K, V, O> where O: Ops<V> {
+    fn len(&self) -> usize { self.0.len() }
+}
+
+/// An iterator over the map's entries.
+///
+/// Acquire through [`LazyMap::iter`](struct.LazyMap.html#method.iter).
+pub struct Iter<'a, K: 'a, V: 'a, O: 'a>(node::Iter<MarkedNode<'a, K, V, Global, Tags<V, O>>>)
+    where O: Ops<V>;
+
+impl<'a, K, V, O> Clone for Iter<'a, K, V, O> where O: Ops<V> {
+    fn clone(&self) -> Self { Iter(self.0.clone()) }
+}
+
+impl<'a, K, V, O> Iterator for Iter<'a, K, V, O> where O: Ops<V> {
+    type Item = (&'a K, &'a V);
+    fn next(&mut self) -> Option<Self::Item> { self.0.next() }
+    fn size_hint(&self) -> (usize, Option<usize>) { self.0.size_hint() }
+}
+
+impl<'a, K, V, O> DoubleEndedIterator for Iter<'a, K, V, O> where O: Ops<V> {
+    fn next_back(&mut self) -> Option<Self::Item> { self.0.next_back() }
+}
+
+impl<'a, K, V, O> ExactSizeIterator for Iter<'a, K, V, O> where O: Ops<V> {
+    fn len(&self) -> usize { self.0.len() }
+}
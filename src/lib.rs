@@ -1,21 +1,76 @@
 //! An ordered map and set based on a binary search tree.
 
-#![cfg_attr(feature = "range", feature(collections_bound))]
+#![no_std]
+#![cfg_attr(feature = "allocator_api", feature(allocator_api))]
 
+extern crate alloc;
 extern crate compare;
+#[cfg(any(test, feature = "std"))] extern crate std;
 
 pub use map::Map;
 pub use set::Set;
 
+#[forbid(missing_docs)]
+pub mod allocator;
+#[forbid(missing_docs)]
+pub mod bimap;
+#[forbid(missing_docs)]
+pub mod checked;
+#[forbid(missing_docs)]
+pub mod distance;
+#[forbid(missing_docs)]
+pub mod dyn_compare;
+#[forbid(missing_docs)]
+pub mod frozen;
+#[forbid(missing_docs)]
+pub mod lazy;
 #[forbid(missing_docs)]
 pub mod map;
 #[forbid(missing_docs)]
+pub mod observed;
+#[forbid(missing_docs)]
+pub mod packed;
+#[forbid(missing_docs)]
+pub mod prefix;
+#[forbid(missing_docs)]
+pub mod queue;
+#[forbid(missing_docs)]
+pub mod seq;
+#[forbid(missing_docs)]
 pub mod set;
+#[forbid(missing_docs)]
+pub mod small;
+#[forbid(missing_docs)]
+pub mod versioned;
+
+#[cfg(feature = "stats")]
+#[forbid(missing_docs)]
+pub mod stats;
 
 mod node;
 
-#[cfg(feature = "ordered_iter")]
+#[cfg(all(feature = "std", feature = "ordered_iter"))]
 mod ordered_iter;
 
-#[cfg(feature = "quickcheck")]
+#[cfg(all(feature = "std", feature = "quickcheck"))]
 mod quickcheck;
+
+#[cfg(all(feature = "std", feature = "rayon"))]
+#[forbid(missing_docs)]
+pub mod rayon;
+
+#[cfg(feature = "std")]
+#[forbid(missing_docs)]
+pub mod concurrent;
+
+#[cfg(feature = "std")]
+#[forbid(missing_docs)]
+pub mod durable;
+
+#[cfg(feature = "std")]
+#[forbid(missing_docs)]
+pub mod expiring;
+
+#[cfg(feature = "std")]
+#[forbid(missing_docs)]
+pub mod merkle;
@@ -1,28 +1,47 @@
 //! An ordered map based on a binary search tree.
 
-use compare::{Compare, Natural};
-use std::cmp::Ordering;
-use std::cmp::Ordering::*;
-#[cfg(feature = "range")] use std::collections::Bound;
-use std::fmt::{self, Debug};
-use std::hash::{self, Hash};
-use std::iter;
-use std::ops;
-use super::node::{self, Extreme, Max, Min, MarkedNode, MutMarkedNode, Node};
+use alloc::borrow::Cow;
+use alloc::vec::Vec;
+use allocator::{Allocator, Global};
+use compare::{Borrowing, Compare, Natural};
+use core::borrow::Borrow;
+use core::cmp::Ordering;
+use core::cmp::Ordering::*;
+use core::fmt::{self, Debug};
+use core::hash::{self, Hash};
+use core::iter;
+use core::ops::Bound;
+use core::ops;
+use distance::Distance;
+use frozen::FrozenMap;
+use prefix::Prefix;
+#[cfg(feature = "stats")] use stats::{Shape, Stats};
+use super::node::{self, Extreme, Max, Min, MarkedNode, MutMarkedNode, NodeBox};
 use super::node::build::{Get, GetMut, PathBuilder};
 
-pub use super::node::{OccupiedEntry, VacantEntry};
+pub use super::node::{Augment, OccupiedEntry, VacantEntry};
 
 /// An ordered map based on a binary search tree.
 ///
 /// The behavior of this map is undefined if a key's ordering relative to any other key changes
 /// while the key is in the map. This is normally only possible through `Cell`, `RefCell`, or
-/// unsafe code.
+/// unsafe code. See the [`checked`](../checked/index.html) module for a map that detects such
+/// changes.
+///
+/// # Augmentation
+///
+/// The map's nodes can cache data about the entries in their subtrees, such as a digest of the
+/// entries, through the `G` parameter, which implements [`Augment`](trait.Augment.html). The
+/// default, `()`, caches nothing. An augmented map provides no mutable access to its values,
+/// since changing a value would invalidate the data cached by the value's ancestors. Use `insert`
+/// to replace a value instead. [`augment`](#method.augment) converts a map into an augmented one.
 #[derive(Clone)]
-pub struct Map<K, V, C = Natural<K>> where C: Compare<K> {
-    root: node::Link<K, V>,
+pub struct Map<K, V, C = Natural<K>, A = Global, G = ()> where C: Compare<K>, A: Allocator {
+    root: node::Link<K, V, A, G>,
     len: usize,
     cmp: C,
+    alloc: A,
+    counters: node::Counters,
 }
 
 impl<K, V> Map<K, V> where K: Ord {
@@ -71,8 +90,187 @@ impl<K, V, C> Map<K, V, C> where C: Compare<K> {
     /// # }
     /// ```
     pub fn with_cmp(cmp: C) -> Self {
-        Map { root: None, len: 0, cmp: cmp }
+        Map::with_cmp_in(cmp, Global)
+    }
+}
+
+impl<K, V, A> Map<K, V, Natural<K>, A> where K: Ord, A: Allocator + Clone {
+    /// Creates an empty map ordered according to the natural order of its keys, whose nodes are
+    /// allocated in the given allocator.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #![cfg_attr(feature = "allocator_api", feature(allocator_api))]
+    /// let mut map = tree::Map::new_in(tree::allocator::Global);
+    ///
+    /// map.insert(2, "b");
+    /// map.insert(1, "a");
+    /// map.insert(3, "c");
+    ///
+    /// let mut it = map.iter();
+    /// assert_eq!(it.next(), Some((&1, &"a")));
+    /// assert_eq!(it.next(), Some((&2, &"b")));
+    /// assert_eq!(it.next(), Some((&3, &"c")));
+    /// assert_eq!(it.next(), None);
+    /// ```
+    pub fn new_in(alloc: A) -> Self { Map::with_cmp_in(Natural::default(), alloc) }
+
+    /// Returns a mutable reference to the value associated with the given key, which may be any
+    /// borrowed form of the map's key type, or `None` if the map does not contain the key.
+    ///
+    /// See [`get_borrowed`](#method.get_borrowed) for the requirements on the borrowed form.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut map = tree::Map::new();
+    /// map.insert("a".to_string(), 1);
+    ///
+    /// *map.get_mut_borrowed("a").unwrap() += 1;
+    /// assert_eq!(map.get_borrowed("a"), Some(&2));
+    /// ```
+    pub fn get_mut_borrowed<Q: ?Sized>(&mut self, key: &Q) -> Option<&mut V>
+        where K: Borrow<Q>, Q: Ord {
+
+        let cmp = borrowing();
+        let cmp = self.counters.counting(&cmp);
+        node::find(&mut self.root, GetMut::default(), &cmp, key).map(|e| e.1)
+    }
+}
+
+impl<K, V, A, G> Map<K, V, Natural<K>, A, G>
+    where K: Ord, A: Allocator + Clone, G: Augment<K, V> {
+
+    /// Checks if the map contains the given key, which may be any borrowed form of the map's key
+    /// type.
+    ///
+    /// See [`get_borrowed`](#method.get_borrowed) for the requirements on the borrowed form.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut map = tree::Map::new();
+    /// map.insert("a".to_string(), 1);
+    ///
+    /// assert!(map.contains_key_borrowed("a"));
+    /// assert!(!map.contains_key_borrowed("b"));
+    /// ```
+    pub fn contains_key_borrowed<Q: ?Sized>(&self, key: &Q) -> bool where K: Borrow<Q>, Q: Ord {
+        self.get_borrowed(key).is_some()
+    }
+
+    /// Returns a reference to the value associated with the given key, which may be any borrowed
+    /// form of the map's key type, or `None` if the map does not contain the key.
+    ///
+    /// As with `std::collections::BTreeMap`, the borrowed form's ordering must agree with the
+    /// ordering of the key type.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut map = tree::Map::new();
+    /// map.insert("a".to_string(), 1);
+    ///
+    /// assert_eq!(map.get_borrowed("a"), Some(&1));
+    /// assert_eq!(map.get_borrowed("b"), None);
+    /// ```
+    pub fn get_borrowed<Q: ?Sized>(&self, key: &Q) -> Option<&V> where K: Borrow<Q>, Q: Ord {
+        let cmp = borrowing();
+        let cmp = self.counters.counting(&cmp);
+        node::find(&self.root, Get::default(), &cmp, key).map(|e| e.1)
+    }
+
+    /// Removes and returns the entry whose key is equal to the given key, which may be any
+    /// borrowed form of the map's key type, returning `None` if the map does not contain the key.
+    ///
+    /// See [`get_borrowed`](#method.get_borrowed) for the requirements on the borrowed form.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut map = tree::Map::new();
+    /// map.insert("a".to_string(), 1);
+    ///
+    /// assert_eq!(map.remove_borrowed("a"), Some(("a".to_string(), 1)));
+    /// assert_eq!(map.remove_borrowed("a"), None);
+    /// ```
+    pub fn remove_borrowed<Q: ?Sized>(&mut self, key: &Q) -> Option<(K, V)>
+        where K: Borrow<Q>, Q: Ord {
+
+        let cmp = borrowing();
+        let cmp = self.counters.counting(&cmp);
+        node::find(&mut self.root, PathBuilder::default(), &cmp, key)
+            .remove(&mut self.len, &self.counters)
+    }
+
+    /// Returns an iterator over the map's entries whose keys lie in the given range with immutable
+    /// references to the values, where the bounds may be any borrowed form of the map's key type.
+    ///
+    /// See [`get_borrowed`](#method.get_borrowed) for the requirements on the borrowed form.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::ops::Bound::{Excluded, Unbounded};
+    ///
+    /// let map: tree::Map<_, _> = vec![("a".to_string(), 1), ("b".to_string(), 2)]
+    ///     .into_iter().collect();
+    ///
+    /// assert_eq!(map.range_borrowed(Excluded("a"), Unbounded).collect::<Vec<_>>(),
+    ///     [(&"b".to_string(), &2)]);
+    /// ```
+    #[cfg(feature = "range")]
+    pub fn range_borrowed<Q: ?Sized>(&self, min: Bound<&Q>, max: Bound<&Q>)
+        -> Range<'_, K, V, A, G> where K: Borrow<Q>, Q: Ord {
+
+        let cmp = borrowing();
+        let cmp = self.counters.counting(&cmp);
+        Range(node::Range::new(self.root.as_ref().map(MarkedNode::new), self.len, &cmp, min,
+            max))
+    }
+}
+
+// Returns a comparator that compares a borrowed form of a key with keys according to the
+// borrowed form's natural order.
+fn borrowing<Q: ?Sized>() -> Borrowing<Natural<Q>, Q> where Q: Ord {
+    Natural::default().borrowing()
+}
+
+impl<K, V, C, A> Map<K, V, C, A> where C: Compare<K>, A: Allocator + Clone {
+    /// Creates an empty map ordered according to the given comparator, whose nodes are allocated
+    /// in the given allocator.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #![cfg_attr(feature = "allocator_api", feature(allocator_api))]
+    /// # extern crate compare;
+    /// # extern crate tree;
+    /// # fn main() {
+    /// use compare::{Compare, natural};
+    /// use tree::allocator::Global;
+    ///
+    /// let mut map = tree::Map::with_cmp_in(natural().rev(), Global);
+    ///
+    /// map.insert(2, "b");
+    /// map.insert(1, "a");
+    /// map.insert(3, "c");
+    ///
+    /// let mut it = map.iter();
+    /// assert_eq!(it.next(), Some((&3, &"c")));
+    /// assert_eq!(it.next(), Some((&2, &"b")));
+    /// assert_eq!(it.next(), Some((&1, &"a")));
+    /// assert_eq!(it.next(), None);
+    /// # }
+    /// ```
+    pub fn with_cmp_in(cmp: C, alloc: A) -> Self {
+        Map { root: None, len: 0, cmp: cmp, alloc: alloc, counters: Default::default() }
     }
+}
+
+impl<K, V, C, A, G> Map<K, V, C, A, G>
+    where C: Compare<K>, A: Allocator + Clone, G: Augment<K, V> {
 
     /// Checks if the map is empty.
     ///
@@ -119,6 +317,9 @@ impl<K, V, C> Map<K, V, C> where C: Compare<K> {
     /// ```
     pub fn cmp(&self) -> &C { &self.cmp }
 
+    /// Returns a reference to the map's allocator.
+    pub fn allocator(&self) -> &A { &self.alloc }
+
     /// Removes all entries from the map.
     ///
     /// # Examples
@@ -156,7 +357,8 @@ impl<K, V, C> Map<K, V, C> where C: Compare<K> {
     /// assert_eq!(map.get(&1), Some(&"b"));
     /// ```
     pub fn insert(&mut self, key: K, value: V) -> Option<V> {
-        let old_value = node::insert(&mut self.root, &self.cmp, key, value);
+        let cmp = self.counters.counting(&self.cmp);
+        let old_value = node::insert(&mut self.root, &cmp, key, value, &self.alloc, &self.counters);
         if old_value.is_none() { self.len += 1; }
         old_value
     }
@@ -184,27 +386,9 @@ impl<K, V, C> Map<K, V, C> where C: Compare<K> {
     pub fn remove<Q: ?Sized>(&mut self, key: &Q) -> Option<(K, V)>
         where C: Compare<Q, K> {
 
-        node::find(&mut self.root, PathBuilder::default(), &self.cmp, key).remove(&mut self.len)
-    }
-
-    /// Returns the map's entry corresponding to the given key.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// let mut counts = tree::Map::new();
-    ///
-    /// for s in vec!["a", "b", "a", "c", "a", "b"] {
-    ///     *counts.entry(s).or_insert(0) += 1;
-    /// }
-    ///
-    /// assert_eq!(counts[&"a"], 3);
-    /// assert_eq!(counts[&"b"], 2);
-    /// assert_eq!(counts[&"c"], 1);
-    /// ```
-    pub fn entry(&mut self, key: K) -> Entry<K, V> {
-        node::find(&mut self.root, PathBuilder::default(), &self.cmp, &key)
-            .into_entry(&mut self.len, key)
+        let cmp = self.counters.counting(&self.cmp);
+        node::find(&mut self.root, PathBuilder::default(), &cmp, key)
+            .remove(&mut self.len, &self.counters)
     }
 
     /// Checks if the map contains the given key.
@@ -233,31 +417,8 @@ impl<K, V, C> Map<K, V, C> where C: Compare<K> {
     /// assert_eq!(map.get(&1), Some(&"a"));
     /// ```
     pub fn get<Q: ?Sized>(&self, key: &Q) -> Option<&V> where C: Compare<Q, K> {
-        node::find(&self.root, Get::default(), &self.cmp, key).map(|e| e.1)
-    }
-
-    /// Returns a mutable reference to the value associated with the given key, or `None`
-    /// if the map does not contain the key.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// let mut map = tree::Map::new();
-    /// assert_eq!(map.get(&1), None);
-    /// map.insert(1, "a");
-    ///
-    /// {
-    ///     let value = map.get_mut(&1).unwrap();
-    ///     assert_eq!(*value, "a");
-    ///     *value = "b";
-    /// }
-    ///
-    /// assert_eq!(map.get(&1), Some(&"b"));
-    /// ```
-    pub fn get_mut<Q: ?Sized>(&mut self, key: &Q) -> Option<&mut V>
-        where C: Compare<Q, K> {
-
-        node::find(&mut self.root, GetMut::default(), &self.cmp, key).map(|e| e.1)
+        let cmp = self.counters.counting(&self.cmp);
+        node::find(&self.root, Get::default(), &cmp, key).map(|e| e.1)
     }
 
     /// Returns a reference to the map's maximum key and a reference to its associated
@@ -279,31 +440,6 @@ impl<K, V, C> Map<K, V, C> where C: Compare<K> {
         Max::extreme(&self.root, Get::default())
     }
 
-    /// Returns a reference to the map's maximum key and a mutable reference to its
-    /// associated value, or `None` if the map is empty.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// let mut map = tree::Map::new();
-    /// assert_eq!(map.max(), None);
-    ///
-    /// map.insert(2, "b");
-    /// map.insert(1, "a");
-    /// map.insert(3, "c");
-    ///
-    /// {
-    ///     let max = map.max_mut().unwrap();
-    ///     assert_eq!(max, (&3, &mut "c"));
-    ///     *max.1 = "cc";
-    /// }
-    ///
-    /// assert_eq!(map.max(), Some((&3, &"cc")));
-    /// ```
-    pub fn max_mut(&mut self) -> Option<(&K, &mut V)> {
-        Max::extreme(&mut self.root, GetMut::default())
-    }
-
     /// Removes the map's maximum key and returns it and its associated value, or `None` if the map
     /// is empty.
     ///
@@ -320,31 +456,7 @@ impl<K, V, C> Map<K, V, C> where C: Compare<K> {
     /// assert_eq!(map.remove_max(), Some((3, "c")));
     /// ```
     pub fn remove_max(&mut self) -> Option<(K, V)> {
-        Max::extreme(&mut self.root, PathBuilder::default()).remove(&mut self.len)
-    }
-
-    /// Returns the map's entry corresponding to its maximum key.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// let mut map = tree::Map::new();
-    /// assert!(map.max_entry().is_none());
-    ///
-    /// map.insert(2, "b");
-    /// map.insert(1, "a");
-    /// map.insert(3, "c");
-    ///
-    /// {
-    ///     let mut e = map.max_entry().unwrap();
-    ///     assert_eq!(*e.key(), 3);
-    ///     assert_eq!(e.insert("cc"), "c");
-    /// }
-    ///
-    /// assert_eq!(map[&3], "cc");
-    /// ```
-    pub fn max_entry(&mut self) -> Option<OccupiedEntry<K, V>> {
-        Max::extreme(&mut self.root, PathBuilder::default()).into_occupied_entry(&mut self.len)
+        Max::extreme(&mut self.root, PathBuilder::default()).remove(&mut self.len, &self.counters)
     }
 
     /// Returns a reference to the map's minimum key and a reference to its associated
@@ -366,31 +478,6 @@ impl<K, V, C> Map<K, V, C> where C: Compare<K> {
         Min::extreme(&self.root, Get::default())
     }
 
-    /// Returns a reference to the map's minimum key and a mutable reference to its
-    /// associated value, or `None` if the map is empty.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// let mut map = tree::Map::new();
-    /// assert_eq!(map.min(), None);
-    ///
-    /// map.insert(2, "b");
-    /// map.insert(1, "a");
-    /// map.insert(3, "c");
-    ///
-    /// {
-    ///     let min = map.min_mut().unwrap();
-    ///     assert_eq!(min, (&1, &mut "a"));
-    ///     *min.1 = "aa";
-    /// }
-    ///
-    /// assert_eq!(map.min(), Some((&1, &"aa")));
-    /// ```
-    pub fn min_mut(&mut self) -> Option<(&K, &mut V)> {
-        Min::extreme(&mut self.root, GetMut::default())
-    }
-
     /// Removes the map's minimum key and returns it and its associated value, or `None` if the map
     /// is empty.
     ///
@@ -407,31 +494,7 @@ impl<K, V, C> Map<K, V, C> where C: Compare<K> {
     /// assert_eq!(map.remove_min(), Some((1, "a")));
     /// ```
     pub fn remove_min(&mut self) -> Option<(K, V)> {
-        Min::extreme(&mut self.root, PathBuilder::default()).remove(&mut self.len)
-    }
-
-    /// Returns the map's entry corresponding to its minimum key.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// let mut map = tree::Map::new();
-    /// assert!(map.min_entry().is_none());
-    ///
-    /// map.insert(2, "b");
-    /// map.insert(1, "a");
-    /// map.insert(3, "c");
-    ///
-    /// {
-    ///     let mut e = map.min_entry().unwrap();
-    ///     assert_eq!(*e.key(), 1);
-    ///     assert_eq!(e.insert("aa"), "a");
-    /// }
-    ///
-    /// assert_eq!(map[&1], "aa");
-    /// ```
-    pub fn min_entry(&mut self) -> Option<OccupiedEntry<K, V>> {
-        Min::extreme(&mut self.root, PathBuilder::default()).into_occupied_entry(&mut self.len)
+        Min::extreme(&mut self.root, PathBuilder::default()).remove(&mut self.len, &self.counters)
     }
 
     /// Returns a reference to the predecessor of the given key and a
@@ -467,14 +530,15 @@ impl<K, V, C> Map<K, V, C> where C: Compare<K> {
     pub fn pred<Q: ?Sized>(&self, key: &Q, inclusive: bool) -> Option<(&K, &V)>
         where C: Compare<Q, K> {
 
-        Min::closest(&self.root, Get::default(), &self.cmp, key, inclusive)
+        let cmp = self.counters.counting(&self.cmp);
+        Min::closest(&self.root, Get::default(), &cmp, key, inclusive)
     }
 
-    /// Returns a reference to the predecessor of the given key and a
-    /// mutable reference to its associated value, or `None` if no such key is present in the map.
+    /// Removes the predecessor of the given key from the map and returns it and its associated
+    /// value, or `None` if no such key is present in the map.
     ///
-    /// If `inclusive` is `false`, this method finds the greatest key that is strictly less than
-    /// the given key. If `inclusive` is `true`, this method finds the greatest key that is less
+    /// If `inclusive` is `false`, this method removes the greatest key that is strictly less than
+    /// the given key. If `inclusive` is `true`, this method removes the greatest key that is less
     /// than or equal to the given key.
     ///
     /// The given key need not itself be present in the map.
@@ -488,41 +552,66 @@ impl<K, V, C> Map<K, V, C> where C: Compare<K> {
     /// map.insert(1, "a");
     /// map.insert(3, "c");
     ///
-    /// {
-    ///     let pred = map.pred_mut(&2, false).unwrap();
-    ///     assert_eq!(pred, (&1, &mut "a"));
-    ///     *pred.1 = "aa";
-    /// }
-    ///
-    /// assert_eq!(map.pred(&2, false), Some((&1, &"aa")));
+    /// assert_eq!(map.remove_pred(&1, false), None);
+    /// assert!(map.contains_key(&1));
     ///
-    /// {
-    ///     let pred_or_eq = map.pred_mut(&1, true).unwrap();
-    ///     assert_eq!(pred_or_eq, (&1, &mut "aa"));
-    ///     *pred_or_eq.1 = "aaa";
-    /// }
+    /// assert_eq!(map.remove_pred(&2, false), Some((1, "a")));
+    /// assert!(!map.contains_key(&1));
     ///
-    /// {
-    ///     let pred_or_eq = map.pred_mut(&4, true).unwrap();
-    ///     assert_eq!(pred_or_eq, (&3, &mut "c"));
-    ///     *pred_or_eq.1 = "cc";
-    /// }
+    /// assert_eq!(map.remove_pred(&2, true), Some((2, "b")));
+    /// assert!(!map.contains_key(&2));
+    /// ```
+    pub fn remove_pred<Q: ?Sized>(&mut self, key: &Q, inclusive: bool) -> Option<(K, V)>
+        where C: Compare<Q, K> {
+
+        let cmp = self.counters.counting(&self.cmp);
+        Min::closest(&mut self.root, PathBuilder::default(), &cmp, key, inclusive)
+            .remove(&mut self.len, &self.counters)
+    }
+
+    /// Returns a reference to the successor of the given key and a
+    /// reference to its associated value, or `None` if no such key is present in the map.
+    ///
+    /// If `inclusive` is `false`, this method finds the smallest key that is strictly greater than
+    /// the given key. If `inclusive` is `true`, this method finds the smallest key that is greater
+    /// than or equal to the given key.
+    ///
+    /// The given key need not itself be present in the map.
+    ///
+    /// # Examples
     ///
-    /// assert_eq!(map.pred(&1, true), Some((&1, &"aaa")));
-    /// assert_eq!(map.pred(&4, true), Some((&3, &"cc")));
     /// ```
-    pub fn pred_mut<Q: ?Sized>(&mut self, key: &Q, inclusive: bool) -> Option<(&K, &mut V)>
+    /// let mut map = tree::Map::new();
+    ///
+    /// map.insert(2, "b");
+    /// map.insert(1, "a");
+    /// map.insert(3, "c");
+    ///
+    /// assert_eq!(map.succ(&0, false), Some((&1, &"a")));
+    /// assert_eq!(map.succ(&1, false), Some((&2, &"b")));
+    /// assert_eq!(map.succ(&2, false), Some((&3, &"c")));
+    /// assert_eq!(map.succ(&3, false), None);
+    /// assert_eq!(map.succ(&4, false), None);
+    ///
+    /// assert_eq!(map.succ(&0, true), Some((&1, &"a")));
+    /// assert_eq!(map.succ(&1, true), Some((&1, &"a")));
+    /// assert_eq!(map.succ(&2, true), Some((&2, &"b")));
+    /// assert_eq!(map.succ(&3, true), Some((&3, &"c")));
+    /// assert_eq!(map.succ(&4, true), None);
+    /// ```
+    pub fn succ<Q: ?Sized>(&self, key: &Q, inclusive: bool) -> Option<(&K, &V)>
         where C: Compare<Q, K> {
 
-        Min::closest(&mut self.root, GetMut::default(), &self.cmp, key, inclusive)
+        let cmp = self.counters.counting(&self.cmp);
+        Max::closest(&self.root, Get::default(), &cmp, key, inclusive)
     }
 
-    /// Removes the predecessor of the given key from the map and returns it and its associated
+    /// Removes the successor of the given key from the map and returns it and its associated
     /// value, or `None` if no such key is present in the map.
     ///
-    /// If `inclusive` is `false`, this method removes the greatest key that is strictly less than
-    /// the given key. If `inclusive` is `true`, this method removes the greatest key that is less
-    /// than or equal to the given key.
+    /// If `inclusive` is `false`, this method removes the smallest key that is strictly greater
+    /// than the given key. If `inclusive` is `true`, this method removes the smallest key that is
+    /// greater than or equal to the given key.
     ///
     /// The given key need not itself be present in the map.
     ///
@@ -535,29 +624,140 @@ impl<K, V, C> Map<K, V, C> where C: Compare<K> {
     /// map.insert(1, "a");
     /// map.insert(3, "c");
     ///
-    /// assert_eq!(map.remove_pred(&1, false), None);
-    /// assert!(map.contains_key(&1));
+    /// assert_eq!(map.remove_succ(&3, false), None);
+    /// assert!(map.contains_key(&3));
     ///
-    /// assert_eq!(map.remove_pred(&2, false), Some((1, "a")));
-    /// assert!(!map.contains_key(&1));
+    /// assert_eq!(map.remove_succ(&2, false), Some((3, "c")));
+    /// assert!(!map.contains_key(&3));
     ///
-    /// assert_eq!(map.remove_pred(&2, true), Some((2, "b")));
+    /// assert_eq!(map.remove_succ(&2, true), Some((2, "b")));
     /// assert!(!map.contains_key(&2));
     /// ```
-    pub fn remove_pred<Q: ?Sized>(&mut self, key: &Q, inclusive: bool) -> Option<(K, V)>
+    pub fn remove_succ<Q: ?Sized>(&mut self, key: &Q, inclusive: bool) -> Option<(K, V)>
         where C: Compare<Q, K> {
 
-        Min::closest(&mut self.root, PathBuilder::default(), &self.cmp, key, inclusive)
-            .remove(&mut self.len)
+        let cmp = self.counters.counting(&self.cmp);
+        Max::closest(&mut self.root, PathBuilder::default(), &cmp, key, inclusive)
+            .remove(&mut self.len, &self.counters)
     }
 
-    /// Returns the entry corresponding to the predecessor of the given key.
+    /// Returns the entry whose key is closest to the given key, or `None` if the map is empty.
     ///
-    /// If `inclusive` is `false`, this method returns the entry corresponding to the greatest key
-    /// that is strictly less than the given key. If `inclusive` is `true`, this method returns
-    /// the entry corresponding to the greatest key that is less than or equal to the given key.
+    /// If two keys are equally close, the lesser one is returned. The given key need not itself
+    /// be present in the map.
     ///
-    /// The given key need not itself be present in the map.
+    /// # Examples
+    ///
+    /// ```
+    /// let mut map = tree::Map::new();
+    ///
+    /// map.insert(10, "a");
+    /// map.insert(20, "b");
+    /// map.insert(30, "c");
+    ///
+    /// assert_eq!(map.nearest(&0), Some((&10, &"a")));
+    /// assert_eq!(map.nearest(&16), Some((&20, &"b")));
+    /// assert_eq!(map.nearest(&25), Some((&20, &"b")));
+    /// assert_eq!(map.nearest(&30), Some((&30, &"c")));
+    /// ```
+    pub fn nearest<Q: ?Sized>(&self, key: &Q) -> Option<(&K, &V)>
+        where K: Distance<Q>, C: Compare<Q, K> {
+
+        match (self.pred(key, true), self.succ(key, false)) {
+            (Some(lo), Some(hi)) => Some(if hi.0.distance(key) < lo.0.distance(key) { hi }
+                                         else { lo }),
+            (lo, hi) => lo.or(hi),
+        }
+    }
+
+    /// Returns the `k` entries whose keys are closest to the given key, in order of increasing
+    /// distance, or all of the map's entries if it contains fewer than `k`.
+    ///
+    /// If two keys are equally close, the lesser one comes first. The search descends the tree once
+    /// on each side of the given key's position and then walks outward in both directions, so it
+    /// takes `O(log n + k)` time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut map = tree::Map::new();
+    ///
+    /// for i in 0..10 { map.insert(i * 10, i); }
+    ///
+    /// assert_eq!(map.k_nearest(&42, 3), [(&40, &4), (&50, &5), (&30, &3)]);
+    /// assert_eq!(map.k_nearest(&-5, 2), [(&0, &0), (&10, &1)]);
+    /// assert_eq!(map.k_nearest(&42, 20).len(), 10);
+    /// ```
+    pub fn k_nearest<Q: ?Sized>(&self, key: &Q, k: usize) -> Vec<(&K, &V)>
+        where K: Distance<Q>, C: Compare<Q, K> {
+
+        let cmp = self.counters.counting(&self.cmp);
+        let root = || self.root.as_ref().map(MarkedNode::new);
+
+        let mut lo = node::Range::new::<_, Q, Q>(root(), self.len, &cmp, Bound::Unbounded,
+                                                 Bound::Included(key)).rev().peekable();
+        let mut hi = node::Range::new::<_, Q, Q>(root(), self.len, &cmp, Bound::Excluded(key),
+                                                 Bound::Unbounded).peekable();
+
+        let mut nearest = Vec::with_capacity(k.min(self.len));
+
+        while nearest.len() < k {
+            let take_lo = match (lo.peek(), hi.peek()) {
+                (None, None) => break,
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (Some(l), Some(h)) => !(h.0.distance(key) < l.0.distance(key)),
+            };
+
+            nearest.extend(if take_lo { lo.next() } else { hi.next() });
+        }
+
+        nearest
+    }
+
+    /// Returns the entry whose key is the longest prefix of the given key, or `None` if no key in
+    /// the map is a prefix of it.
+    ///
+    /// The map's comparator must order its keys lexicographically, as described in
+    /// [`Prefix`](../prefix/trait.Prefix.html). The search starts with the predecessor of the
+    /// given key and, while that is not a prefix of the given key, continues with the predecessor
+    /// of their longest common prefix, so it performs at most one lookup per element of the key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut routes = tree::Map::new();
+    ///
+    /// routes.insert("/", 1);
+    /// routes.insert("/static/", 2);
+    /// routes.insert("/static/css/", 3);
+    /// routes.insert("/static/js/", 4);
+    ///
+    /// assert_eq!(routes.longest_prefix_of(&"/static/img/logo.png"), Some((&"/static/", &2)));
+    /// assert_eq!(routes.longest_prefix_of(&"/static/js/app.js"), Some((&"/static/js/", &4)));
+    /// assert_eq!(routes.longest_prefix_of(&"/index.html"), Some((&"/", &1)));
+    /// assert_eq!(routes.longest_prefix_of(&"index.html"), None);
+    /// ```
+    pub fn longest_prefix_of<Q: ?Sized>(&self, key: &Q) -> Option<(&K, &V)>
+        where Q: Prefix, K: Borrow<Q>, C: Compare<Q, K> {
+
+        let mut query = Cow::Borrowed(key);
+
+        loop {
+            let (k, v) = match self.pred(&*query, true) {
+                None => return None,
+                Some(e) => e,
+            };
+
+            let len = k.borrow().common_prefix_len(&query);
+            if len == k.borrow().prefix_len() { return Some((k, v)); }
+            query = key.truncate(len);
+        }
+    }
+
+    /// Returns an iterator over the map's entries with immutable references to the values.
+    ///
+    /// The iterator yields the entries in ascending order according to the map's comparator.
     ///
     /// # Examples
     ///
@@ -568,71 +768,342 @@ impl<K, V, C> Map<K, V, C> where C: Compare<K> {
     /// map.insert(1, "a");
     /// map.insert(3, "c");
     ///
-    /// assert!(map.pred_entry(&1, false).is_none());
+    /// let mut it = map.iter();
+    /// assert_eq!(it.next(), Some((&1, &"a")));
+    /// assert_eq!(it.next(), Some((&2, &"b")));
+    /// assert_eq!(it.next(), Some((&3, &"c")));
+    /// assert_eq!(it.next(), None);
+    /// ```
+    pub fn iter(&self) -> Iter<'_, K, V, A, G> {
+        Iter(node::Iter::new(self.root.as_ref().map(MarkedNode::new), self.len))
+    }
+
+    /// Rebuilds the map so that it is ordered according to the given comparator.
     ///
-    /// {
-    ///     let mut e = map.pred_entry(&4, true).unwrap();
-    ///     assert_eq!(*e.key(), 3);
-    ///     assert_eq!(e.insert("cc"), "c");
+    /// This takes O(n) time if the map's entries are already in ascending order according to the
+    /// new comparator, and O(n log n) time otherwise. If the new comparator considers several of
+    /// the map's keys equal, only the entry whose key comes first in the map's current order is
+    /// kept.
+    ///
+    /// See the [`dyn_compare`](../dyn_compare/index.html) module for a comparator that can be
+    /// chosen at runtime, and for the map's behavior when the comparator is inconsistent.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the new comparator does not define a total order on the map's keys and this is
+    /// detected while sorting them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate compare;
+    /// # extern crate tree;
+    /// # fn main() {
+    /// use compare::{Compare, natural};
+    /// use tree::Map;
+    ///
+    /// let mut map = Map::new();
+    ///
+    /// map.insert("b", 2);
+    /// map.insert("A", 1);
+    /// map.insert("a", 3);
+    ///
+    /// let map = map.resort_with(|l: &&str, r: &&str| l.to_lowercase().cmp(&r.to_lowercase()));
+    /// assert_eq!(map.iter().collect::<Vec<_>>(), [(&"A", &1), (&"b", &2)]);
+    ///
+    /// let map = map.resort_with(natural().rev());
+    /// assert_eq!(map.iter().collect::<Vec<_>>(), [(&"b", &2), (&"A", &1)]);
+    /// # }
+    /// ```
+    pub fn resort_with<D>(self, cmp: D) -> Map<K, V, D, A, G> where D: Compare<K> {
+        let Map { root, len, alloc, .. } = self;
+        let mut entries: Vec<_> = IntoIter(node::Iter::new(root, len)).collect();
+
+        if !entries.windows(2).all(|w| cmp.compares_lt(&w[0].0, &w[1].0)) {
+            entries.sort_by(|l, r| cmp.compare(&l.0, &r.0));
+            entries.dedup_by(|r, l| cmp.compares_eq(&l.0, &r.0));
+        }
+
+        Map::from_sorted(entries, cmp, alloc)
+    }
+
+    // Removes the entries for which `f` returns `false`, rebuilding the tree in linear time.
+    pub(crate) fn retain<F>(&mut self, mut f: F) where F: FnMut(&K, &mut V) -> bool {
+        let len = self.len;
+        self.len = 0;
+
+        let entries: Vec<_> = IntoIter(node::Iter::new(self.root.take(), len))
+            .filter_map(|(key, mut value)| {
+                if f(&key, &mut value) { Some((key, value)) } else { None }
+            })
+            .collect();
+
+        self.len = entries.len();
+        self.root = node::from_sorted(&mut entries.into_iter(), self.len, &self.alloc);
+    }
+
+    // Builds a map from entries that are in strictly ascending order according to `cmp`.
+    pub(crate) fn from_sorted<I>(it: I, cmp: C, alloc: A) -> Self
+        where I: IntoIterator<Item=(K, V)>, I::IntoIter: ExactSizeIterator {
+
+        let mut it = it.into_iter();
+        let len = it.len();
+        let root = node::from_sorted(&mut it, len, &alloc);
+        Map { root: root, len: len, cmp: cmp, alloc: alloc, counters: Default::default() }
+    }
+
+    pub(crate) fn root(&self) -> &node::Link<K, V, A, G> { &self.root }
+}
+
+impl<K, V, C, A> Map<K, V, C, A> where C: Compare<K>, A: Allocator + Clone {
+    /// Returns the map's entry corresponding to the given key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut counts = tree::Map::new();
+    ///
+    /// for s in vec!["a", "b", "a", "c", "a", "b"] {
+    ///     *counts.entry(s).or_insert(0) += 1;
     /// }
     ///
-    /// assert_eq!(map[&3], "cc");
+    /// assert_eq!(counts[&"a"], 3);
+    /// assert_eq!(counts[&"b"], 2);
+    /// assert_eq!(counts[&"c"], 1);
+    /// ```
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, A> {
+        let cmp = self.counters.counting(&self.cmp);
+        node::find(&mut self.root, PathBuilder::default(), &cmp, &key)
+            .into_entry(&mut self.len, &self.alloc, &self.counters, key)
+    }
+
+    /// Inserts an entry into the map, combining its value with the existing value, if any,
+    /// associated with the key. Returns a mutable reference to the resulting value.
+    ///
+    /// The combiner is called with the existing value and the given value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut totals = tree::Map::new();
+    ///
+    /// for &(name, amount) in &[("a", 1), ("b", 2), ("a", 3)] {
+    ///     totals.insert_with(name, amount, |total, amount| *total += amount);
+    /// }
+    ///
+    /// assert_eq!(totals[&"a"], 4);
+    /// assert_eq!(totals[&"b"], 2);
+    /// ```
+    pub fn insert_with<F>(&mut self, key: K, value: V, f: F) -> &mut V
+        where F: FnOnce(&mut V, V) {
+
+        match self.entry(key) {
+            Entry::Occupied(e) => {
+                let old_value = e.into_mut();
+                f(old_value, value);
+                old_value
+            }
+            Entry::Vacant(e) => e.insert(value),
+        }
+    }
+
+    /// Updates the value associated with the given key.
+    ///
+    /// The function is called with the existing value, if any, and its result becomes the key's
+    /// new value. If it returns `None`, the key is removed from the map. The value is updated in
+    /// place, so the key is searched for only once.
+    ///
+    /// If the function panics, the key is removed from the map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut stock = tree::Map::new();
+    /// stock.insert("apples", 1);
+    ///
+    /// let take = |count: Option<u32>| count.and_then(|c| c.checked_sub(1)).filter(|&c| c > 0);
+    ///
+    /// stock.update("apples", take);
+    /// stock.update("pears", take);
+    /// assert!(stock.is_empty());
+    ///
+    /// stock.update("pears", |count| Some(count.unwrap_or(0) + 5));
+    /// assert_eq!(stock[&"pears"], 5);
+    /// ```
+    pub fn update<F>(&mut self, key: K, f: F) where F: FnOnce(Option<V>) -> Option<V> {
+        match self.entry(key) {
+            Entry::Occupied(e) => e.update(|value| f(Some(value))),
+            Entry::Vacant(e) => if let Some(value) = f(None) { e.insert(value); },
+        }
+    }
+
+    /// Inserts the entries of the given iterator into the map, combining the values of
+    /// duplicate keys with the given combiner as `insert_with` does.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut counts = tree::Map::new();
+    ///
+    /// counts.extend_with("abracadabra".chars().map(|c| (c, 1)), |count, one| *count += one);
+    ///
+    /// assert_eq!(counts.iter().collect::<Vec<_>>(),
+    ///     [(&'a', &5), (&'b', &2), (&'c', &1), (&'d', &1), (&'r', &2)]);
+    /// ```
+    pub fn extend_with<I, F>(&mut self, it: I, mut f: F)
+        where I: IntoIterator<Item=(K, V)>, F: FnMut(&mut V, V) {
+
+        for (key, value) in it { self.insert_with(key, value, &mut f); }
+    }
+
+    /// Moves the entries of the other map into this map, combining the values of keys present in
+    /// both maps with the given combiner as `insert_with` does.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut a: tree::Map<_, _> = vec![(1, 10), (2, 20)].into_iter().collect();
+    /// let b: tree::Map<_, _> = vec![(2, 2), (3, 3)].into_iter().collect();
+    ///
+    /// a.merge(b, |x, y| *x += y);
+    /// assert_eq!(a.into_iter().collect::<Vec<_>>(), [(1, 10), (2, 22), (3, 3)]);
+    /// ```
+    pub fn merge<B, F>(&mut self, other: Map<K, V, C, B>, f: F)
+        where B: Allocator + Clone, F: FnMut(&mut V, V) {
+
+        self.extend_with(other, f);
+    }
+
+    /// Returns a mutable reference to the value associated with the given key, or `None`
+    /// if the map does not contain the key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut map = tree::Map::new();
+    /// assert_eq!(map.get(&1), None);
+    /// map.insert(1, "a");
     ///
     /// {
-    ///     let e = map.pred_entry(&3, false).unwrap();
-    ///     assert_eq!(e.remove(), (2, "b"));
+    ///     let value = map.get_mut(&1).unwrap();
+    ///     assert_eq!(*value, "a");
+    ///     *value = "b";
     /// }
     ///
-    /// assert!(!map.contains_key(&2));
+    /// assert_eq!(map.get(&1), Some(&"b"));
     /// ```
-    pub fn pred_entry<Q: ?Sized>(&mut self, key: &Q, inclusive: bool)
-        -> Option<OccupiedEntry<K, V>> where C: Compare<Q, K> {
+    pub fn get_mut<Q: ?Sized>(&mut self, key: &Q) -> Option<&mut V>
+        where C: Compare<Q, K> {
 
-        Min::closest(&mut self.root, PathBuilder::default(), &self.cmp, key, inclusive)
-            .into_occupied_entry(&mut self.len)
+        let cmp = self.counters.counting(&self.cmp);
+        node::find(&mut self.root, GetMut::default(), &cmp, key).map(|e| e.1)
     }
 
-    /// Returns a reference to the successor of the given key and a
-    /// reference to its associated value, or `None` if no such key is present in the map.
+    /// Returns a reference to the map's maximum key and a mutable reference to its
+    /// associated value, or `None` if the map is empty.
     ///
-    /// If `inclusive` is `false`, this method finds the smallest key that is strictly greater than
-    /// the given key. If `inclusive` is `true`, this method finds the smallest key that is greater
-    /// than or equal to the given key.
+    /// # Examples
     ///
-    /// The given key need not itself be present in the map.
+    /// ```
+    /// let mut map = tree::Map::new();
+    /// assert_eq!(map.max(), None);
+    ///
+    /// map.insert(2, "b");
+    /// map.insert(1, "a");
+    /// map.insert(3, "c");
+    ///
+    /// {
+    ///     let max = map.max_mut().unwrap();
+    ///     assert_eq!(max, (&3, &mut "c"));
+    ///     *max.1 = "cc";
+    /// }
+    ///
+    /// assert_eq!(map.max(), Some((&3, &"cc")));
+    /// ```
+    pub fn max_mut(&mut self) -> Option<(&K, &mut V)> {
+        Max::extreme(&mut self.root, GetMut::default())
+    }
+
+    /// Returns the map's entry corresponding to its maximum key.
     ///
     /// # Examples
     ///
     /// ```
     /// let mut map = tree::Map::new();
+    /// assert!(map.max_entry().is_none());
     ///
     /// map.insert(2, "b");
     /// map.insert(1, "a");
     /// map.insert(3, "c");
     ///
-    /// assert_eq!(map.succ(&0, false), Some((&1, &"a")));
-    /// assert_eq!(map.succ(&1, false), Some((&2, &"b")));
-    /// assert_eq!(map.succ(&2, false), Some((&3, &"c")));
-    /// assert_eq!(map.succ(&3, false), None);
-    /// assert_eq!(map.succ(&4, false), None);
+    /// {
+    ///     let mut e = map.max_entry().unwrap();
+    ///     assert_eq!(*e.key(), 3);
+    ///     assert_eq!(e.insert("cc"), "c");
+    /// }
     ///
-    /// assert_eq!(map.succ(&0, true), Some((&1, &"a")));
-    /// assert_eq!(map.succ(&1, true), Some((&1, &"a")));
-    /// assert_eq!(map.succ(&2, true), Some((&2, &"b")));
-    /// assert_eq!(map.succ(&3, true), Some((&3, &"c")));
-    /// assert_eq!(map.succ(&4, true), None);
+    /// assert_eq!(map[&3], "cc");
     /// ```
-    pub fn succ<Q: ?Sized>(&self, key: &Q, inclusive: bool) -> Option<(&K, &V)>
-        where C: Compare<Q, K> {
+    pub fn max_entry(&mut self) -> Option<OccupiedEntry<'_, K, V, A>> {
+        Max::extreme(&mut self.root, PathBuilder::default())
+            .into_occupied_entry(&mut self.len, &self.counters)
+    }
 
-        Max::closest(&self.root, Get::default(), &self.cmp, key, inclusive)
+    /// Returns a reference to the map's minimum key and a mutable reference to its
+    /// associated value, or `None` if the map is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut map = tree::Map::new();
+    /// assert_eq!(map.min(), None);
+    ///
+    /// map.insert(2, "b");
+    /// map.insert(1, "a");
+    /// map.insert(3, "c");
+    ///
+    /// {
+    ///     let min = map.min_mut().unwrap();
+    ///     assert_eq!(min, (&1, &mut "a"));
+    ///     *min.1 = "aa";
+    /// }
+    ///
+    /// assert_eq!(map.min(), Some((&1, &"aa")));
+    /// ```
+    pub fn min_mut(&mut self) -> Option<(&K, &mut V)> {
+        Min::extreme(&mut self.root, GetMut::default())
     }
 
-    /// Returns a reference to the successor of the given key and a
+    /// Returns the map's entry corresponding to its minimum key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut map = tree::Map::new();
+    /// assert!(map.min_entry().is_none());
+    ///
+    /// map.insert(2, "b");
+    /// map.insert(1, "a");
+    /// map.insert(3, "c");
+    ///
+    /// {
+    ///     let mut e = map.min_entry().unwrap();
+    ///     assert_eq!(*e.key(), 1);
+    ///     assert_eq!(e.insert("aa"), "a");
+    /// }
+    ///
+    /// assert_eq!(map[&1], "aa");
+    /// ```
+    pub fn min_entry(&mut self) -> Option<OccupiedEntry<'_, K, V, A>> {
+        Min::extreme(&mut self.root, PathBuilder::default())
+            .into_occupied_entry(&mut self.len, &self.counters)
+    }
+
+    /// Returns a reference to the predecessor of the given key and a
     /// mutable reference to its associated value, or `None` if no such key is present in the map.
     ///
-    /// If `inclusive` is `false`, this method finds the smallest key that is strictly greater than
-    /// the given key. If `inclusive` is `true`, this method finds the smallest key that is greater
+    /// If `inclusive` is `false`, this method finds the greatest key that is strictly less than
+    /// the given key. If `inclusive` is `true`, this method finds the greatest key that is less
     /// than or equal to the given key.
     ///
     /// The given key need not itself be present in the map.
@@ -647,40 +1118,40 @@ impl<K, V, C> Map<K, V, C> where C: Compare<K> {
     /// map.insert(3, "c");
     ///
     /// {
-    ///     let succ = map.succ_mut(&2, false).unwrap();
-    ///     assert_eq!(succ, (&3, &mut "c"));
-    ///     *succ.1 = "cc";
+    ///     let pred = map.pred_mut(&2, false).unwrap();
+    ///     assert_eq!(pred, (&1, &mut "a"));
+    ///     *pred.1 = "aa";
     /// }
     ///
-    /// assert_eq!(map.succ(&2, false), Some((&3, &"cc")));
+    /// assert_eq!(map.pred(&2, false), Some((&1, &"aa")));
     ///
     /// {
-    ///     let succ_or_eq = map.succ_mut(&0, true).unwrap();
-    ///     assert_eq!(succ_or_eq, (&1, &mut "a"));
-    ///     *succ_or_eq.1 = "aa";
+    ///     let pred_or_eq = map.pred_mut(&1, true).unwrap();
+    ///     assert_eq!(pred_or_eq, (&1, &mut "aa"));
+    ///     *pred_or_eq.1 = "aaa";
     /// }
     ///
     /// {
-    ///     let succ_or_eq = map.succ_mut(&3, true).unwrap();
-    ///     assert_eq!(succ_or_eq, (&3, &mut "cc"));
-    ///     *succ_or_eq.1 = "ccc";
+    ///     let pred_or_eq = map.pred_mut(&4, true).unwrap();
+    ///     assert_eq!(pred_or_eq, (&3, &mut "c"));
+    ///     *pred_or_eq.1 = "cc";
     /// }
     ///
-    /// assert_eq!(map.succ(&0, true), Some((&1, &"aa")));
-    /// assert_eq!(map.succ(&3, true), Some((&3, &"ccc")));
+    /// assert_eq!(map.pred(&1, true), Some((&1, &"aaa")));
+    /// assert_eq!(map.pred(&4, true), Some((&3, &"cc")));
     /// ```
-    pub fn succ_mut<Q: ?Sized>(&mut self, key: &Q, inclusive: bool) -> Option<(&K, &mut V)>
+    pub fn pred_mut<Q: ?Sized>(&mut self, key: &Q, inclusive: bool) -> Option<(&K, &mut V)>
         where C: Compare<Q, K> {
 
-        Max::closest(&mut self.root, GetMut::default(), &self.cmp, key, inclusive)
+        let cmp = self.counters.counting(&self.cmp);
+        Min::closest(&mut self.root, GetMut::default(), &cmp, key, inclusive)
     }
 
-    /// Removes the successor of the given key from the map and returns it and its associated
-    /// value, or `None` if no such key is present in the map.
+    /// Returns the entry corresponding to the predecessor of the given key.
     ///
-    /// If `inclusive` is `false`, this method removes the smallest key that is strictly greater
-    /// than the given key. If `inclusive` is `true`, this method removes the smallest key that is
-    /// greater than or equal to the given key.
+    /// If `inclusive` is `false`, this method returns the entry corresponding to the greatest key
+    /// that is strictly less than the given key. If `inclusive` is `true`, this method returns
+    /// the entry corresponding to the greatest key that is less than or equal to the given key.
     ///
     /// The given key need not itself be present in the map.
     ///
@@ -693,20 +1164,77 @@ impl<K, V, C> Map<K, V, C> where C: Compare<K> {
     /// map.insert(1, "a");
     /// map.insert(3, "c");
     ///
-    /// assert_eq!(map.remove_succ(&3, false), None);
-    /// assert!(map.contains_key(&3));
+    /// assert!(map.pred_entry(&1, false).is_none());
     ///
-    /// assert_eq!(map.remove_succ(&2, false), Some((3, "c")));
-    /// assert!(!map.contains_key(&3));
+    /// {
+    ///     let mut e = map.pred_entry(&4, true).unwrap();
+    ///     assert_eq!(*e.key(), 3);
+    ///     assert_eq!(e.insert("cc"), "c");
+    /// }
+    ///
+    /// assert_eq!(map[&3], "cc");
+    ///
+    /// {
+    ///     let e = map.pred_entry(&3, false).unwrap();
+    ///     assert_eq!(e.remove(), (2, "b"));
+    /// }
     ///
-    /// assert_eq!(map.remove_succ(&2, true), Some((2, "b")));
     /// assert!(!map.contains_key(&2));
     /// ```
-    pub fn remove_succ<Q: ?Sized>(&mut self, key: &Q, inclusive: bool) -> Option<(K, V)>
+    pub fn pred_entry<Q: ?Sized>(&mut self, key: &Q, inclusive: bool)
+        -> Option<OccupiedEntry<'_, K, V, A>> where C: Compare<Q, K> {
+
+        let cmp = self.counters.counting(&self.cmp);
+        Min::closest(&mut self.root, PathBuilder::default(), &cmp, key, inclusive)
+            .into_occupied_entry(&mut self.len, &self.counters)
+    }
+
+    /// Returns a reference to the successor of the given key and a
+    /// mutable reference to its associated value, or `None` if no such key is present in the map.
+    ///
+    /// If `inclusive` is `false`, this method finds the smallest key that is strictly greater than
+    /// the given key. If `inclusive` is `true`, this method finds the smallest key that is greater
+    /// than or equal to the given key.
+    ///
+    /// The given key need not itself be present in the map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut map = tree::Map::new();
+    ///
+    /// map.insert(2, "b");
+    /// map.insert(1, "a");
+    /// map.insert(3, "c");
+    ///
+    /// {
+    ///     let succ = map.succ_mut(&2, false).unwrap();
+    ///     assert_eq!(succ, (&3, &mut "c"));
+    ///     *succ.1 = "cc";
+    /// }
+    ///
+    /// assert_eq!(map.succ(&2, false), Some((&3, &"cc")));
+    ///
+    /// {
+    ///     let succ_or_eq = map.succ_mut(&0, true).unwrap();
+    ///     assert_eq!(succ_or_eq, (&1, &mut "a"));
+    ///     *succ_or_eq.1 = "aa";
+    /// }
+    ///
+    /// {
+    ///     let succ_or_eq = map.succ_mut(&3, true).unwrap();
+    ///     assert_eq!(succ_or_eq, (&3, &mut "cc"));
+    ///     *succ_or_eq.1 = "ccc";
+    /// }
+    ///
+    /// assert_eq!(map.succ(&0, true), Some((&1, &"aa")));
+    /// assert_eq!(map.succ(&3, true), Some((&3, &"ccc")));
+    /// ```
+    pub fn succ_mut<Q: ?Sized>(&mut self, key: &Q, inclusive: bool) -> Option<(&K, &mut V)>
         where C: Compare<Q, K> {
 
-        Max::closest(&mut self.root, PathBuilder::default(), &self.cmp, key, inclusive)
-            .remove(&mut self.len)
+        let cmp = self.counters.counting(&self.cmp);
+        Max::closest(&mut self.root, GetMut::default(), &cmp, key, inclusive)
     }
 
     /// Returns the entry corresponding to the successor of the given key.
@@ -744,33 +1272,11 @@ impl<K, V, C> Map<K, V, C> where C: Compare<K> {
     /// assert!(!map.contains_key(&2));
     /// ```
     pub fn succ_entry<Q: ?Sized>(&mut self, key: &Q, inclusive: bool)
-        -> Option<OccupiedEntry<K, V>> where C: Compare<Q, K> {
-
-        Max::closest(&mut self.root, PathBuilder::default(), &self.cmp, key, inclusive)
-            .into_occupied_entry(&mut self.len)
-    }
+        -> Option<OccupiedEntry<'_, K, V, A>> where C: Compare<Q, K> {
 
-    /// Returns an iterator over the map's entries with immutable references to the values.
-    ///
-    /// The iterator yields the entries in ascending order according to the map's comparator.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// let mut map = tree::Map::new();
-    ///
-    /// map.insert(2, "b");
-    /// map.insert(1, "a");
-    /// map.insert(3, "c");
-    ///
-    /// let mut it = map.iter();
-    /// assert_eq!(it.next(), Some((&1, &"a")));
-    /// assert_eq!(it.next(), Some((&2, &"b")));
-    /// assert_eq!(it.next(), Some((&3, &"c")));
-    /// assert_eq!(it.next(), None);
-    /// ```
-    pub fn iter(&self) -> Iter<K, V> {
-        Iter(node::Iter::new(self.root.as_ref().map(MarkedNode::new), self.len))
+        let cmp = self.counters.counting(&self.cmp);
+        Max::closest(&mut self.root, PathBuilder::default(), &cmp, key, inclusive)
+            .into_occupied_entry(&mut self.len, &self.counters)
     }
 
     /// Returns an iterator over the map's entries with mutable references to the values.
@@ -798,16 +1304,209 @@ impl<K, V, C> Map<K, V, C> where C: Compare<K> {
     /// assert_eq!(map[&"b"], 4);
     /// assert_eq!(map[&"c"], 6);
     /// ```
-    pub fn iter_mut(&mut self) -> IterMut<K, V> {
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V, A> {
         IterMut(node::Iter::new(self.root.as_mut().map(MutMarkedNode::new), self.len))
     }
 
-    #[cfg(test)]
-    pub fn root(&self) -> &node::Link<K, V> { &self.root }
+    /// Returns an iterator over the differences between the map and the given map.
+    ///
+    /// The iterator walks both maps in a single pass, comparing their keys with this map's
+    /// comparator, and yields the differences in ascending order: `Added` for each entry of
+    /// `other` whose key is not in this map, `Removed` for each entry of this map whose key is not
+    /// in `other`, and `Changed` for each key whose values differ.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tree::map::DiffItem;
+    ///
+    /// let mut old = tree::Map::new();
+    /// old.insert("a", 1);
+    /// old.insert("b", 2);
+    ///
+    /// let mut new = old.clone();
+    /// new.remove(&"a");
+    /// new.insert("b", 3);
+    /// new.insert("c", 4);
+    ///
+    /// let diff: Vec<_> = old.diff(&new).collect();
+    /// assert_eq!(diff, [DiffItem::Removed(&"a", &1),
+    ///                   DiffItem::Changed(&"b", &2, &3),
+    ///                   DiffItem::Added(&"c", &4)]);
+    /// ```
+    pub fn diff<'a, B>(&'a self, other: &'a Map<K, V, C, B>) -> Diff<'a, K, V, C, A, B>
+        where V: PartialEq, B: Allocator + Clone {
+
+        Diff { old: self.iter().peekable(), new: other.iter().peekable(), cmp: &self.cmp }
+    }
+
+    /// Applies the given differences to the map.
+    ///
+    /// `Added` and `Changed` items insert their new value, replacing any existing value, and
+    /// `Removed` items remove their key, if present. Applying the differences returned by
+    /// [`Map::diff`](#method.diff) makes the map equal to the map it was compared with.
+    ///
+    /// The items must be in strictly ascending order of key, as `Map::diff` returns them. A patch
+    /// that is small compared to the map is applied by searching the tree for each item, and a
+    /// larger one is merged with the map's entries in a single pass that rebuilds the tree in
+    /// linear time.
+    ///
+    /// # Panics
+    ///
+    /// Panics, leaving the map unchanged, if the items are not in strictly ascending order of
+    /// key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut old = tree::Map::new();
+    /// old.insert("a", 1);
+    /// old.insert("b", 2);
+    ///
+    /// let mut new = old.clone();
+    /// new.remove(&"a");
+    /// new.insert("c", 3);
+    ///
+    /// let patch: Vec<_> = old.diff(&new).map(|item| item.cloned()).collect();
+    /// old.apply_patch(patch);
+    /// assert!(old == new);
+    /// ```
+    pub fn apply_patch<I>(&mut self, patch: I) where I: IntoIterator<Item=DiffItem<K, V>> {
+        let patch: Vec<_> = patch.into_iter().collect();
+
+        assert!(patch.windows(2).all(|w| self.cmp.compares_lt(w[0].key(), w[1].key())),
+                "patch is not in ascending order");
+
+        // searching for an item takes time proportional to the tree's height, which is at most
+        // about twice the number of bits in the map's length
+        let height = 2 * (0usize.leading_zeros() - self.len.leading_zeros()) as usize;
+
+        if patch.len() * height < self.len {
+            for item in patch {
+                match item {
+                    DiffItem::Added(key, value) | DiffItem::Changed(key, _, value) => {
+                        self.insert(key, value);
+                    }
+                    DiffItem::Removed(key, _) => { self.remove(&key); }
+                }
+            }
+
+            return;
+        }
+
+        let len = self.len;
+        self.len = 0;
+
+        let mut old = IntoIter(node::Iter::new(self.root.take(), len)).peekable();
+        let mut entries = Vec::with_capacity(len + patch.len());
+
+        for item in patch {
+            let (key, value) = match item {
+                DiffItem::Added(key, value) | DiffItem::Changed(key, _, value) =>
+                    (key, Some(value)),
+                DiffItem::Removed(key, _) => (key, None),
+            };
+
+            while old.peek().map_or(false, |e| self.cmp.compares_lt(&e.0, &key)) {
+                entries.extend(old.next());
+            }
+
+            if old.peek().map_or(false, |e| self.cmp.compares_eq(&e.0, &key)) { old.next(); }
+            if let Some(value) = value { entries.push((key, value)); }
+        }
+
+        entries.extend(old);
+        self.len = entries.len();
+        self.root = node::from_sorted(&mut entries.into_iter(), self.len, &self.alloc);
+    }
+
+    /// Runs the given function in a transaction on the map, undoing all of its changes if it
+    /// returns `Err` or panics.
+    ///
+    /// The transaction records the entries displaced by each of its insertions and removals in
+    /// an undo log, which it replays in reverse to restore the map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut balances = tree::Map::new();
+    /// balances.insert("alice", 10);
+    /// balances.insert("bob", 0);
+    ///
+    /// let transfer = |balances: &mut tree::Map<_, _>, amount| balances.transaction(|tx| {
+    ///     let bob = tx.get(&"bob").cloned().unwrap_or(0);
+    ///     tx.insert("bob", bob + amount);
+    ///
+    ///     let alice = tx.get(&"alice").cloned().unwrap_or(0);
+    ///     if alice < amount { return Err("insufficient funds"); }
+    ///     tx.insert("alice", alice - amount);
+    ///     Ok(())
+    /// });
+    ///
+    /// assert_eq!(transfer(&mut balances, 4), Ok(()));
+    /// assert_eq!(transfer(&mut balances, 7), Err("insufficient funds"));
+    /// assert_eq!(balances[&"alice"], 6);
+    /// assert_eq!(balances[&"bob"], 4);
+    /// ```
+    pub fn transaction<F, T, E>(&mut self, f: F) -> Result<T, E>
+        where F: FnOnce(&mut Transaction<K, V, C, A>) -> Result<T, E> {
+
+        let mut tx = Transaction { map: self, log: Vec::new() };
+        let result = f(&mut tx);
+        if result.is_ok() { tx.log.clear(); }
+        result
+    }
+
+    /// Converts the map into an immutable `FrozenMap`, which stores its entries in a contiguous
+    /// array for faster lookups.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut map = tree::Map::new();
+    ///
+    /// map.insert(2, "b");
+    /// map.insert(1, "a");
+    /// map.insert(3, "c");
+    ///
+    /// let map = map.freeze();
+    /// assert_eq!(map.get(&2), Some(&"b"));
+    /// assert_eq!(map.succ(&2, false), Some((&3, &"c")));
+    /// ```
+    pub fn freeze(self) -> FrozenMap<K, V, C> {
+        let Map { root, len, cmp, .. } = self;
+        FrozenMap::from_sorted(IntoIter(node::Iter::new(root, len)), cmp)
+    }
+
+    /// Converts the map into an augmented map, whose nodes cache data about their subtrees.
+    ///
+    /// This takes O(n) time. See [`Augment`](trait.Augment.html) for details.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tree::merkle::MerkleMap;
+    ///
+    /// let map: tree::Map<_, _> = (0..10).map(|i| (i, i)).collect();
+    /// let merkle: MerkleMap<_, _> = map.clone().augment();
+    /// let collected: MerkleMap<_, _> = map.clone().into_iter().collect();
+    ///
+    /// assert!(merkle.iter().eq(map.iter()));
+    /// assert_eq!(merkle.root_hash(), collected.root_hash());
+    /// ```
+    pub fn augment<G>(self) -> Map<K, V, C, A, G> where G: Augment<K, V> {
+        let Map { root, len, cmp, alloc, .. } = self;
+        Map::from_sorted(IntoIter(node::Iter::new(root, len)), cmp, alloc)
+    }
+
+    #[cfg(all(feature = "std", feature = "rayon"))]
+    pub(crate) fn root_mut(&mut self) -> &mut node::Link<K, V, A> { &mut self.root }
 }
 
 #[cfg(feature = "range")]
-impl<K, V, C> Map<K, V, C> where C: Compare<K> {
+impl<K, V, C, A, G> Map<K, V, C, A, G>
+    where C: Compare<K>, A: Allocator + Clone, G: Augment<K, V> {
+
     /// Returns an iterator that consumes the map, yielding only those entries whose keys lie in
     /// the given range.
     ///
@@ -816,10 +1515,7 @@ impl<K, V, C> Map<K, V, C> where C: Compare<K> {
     /// # Examples
     ///
     /// ```
-    /// # #![feature(collections_bound)]
-    /// # extern crate tree;
-    /// # fn main() {
-    /// use std::collections::Bound::{Excluded, Unbounded};
+    /// use std::ops::Bound::{Excluded, Unbounded};
     ///
     /// let mut map = tree::Map::new();
     ///
@@ -829,12 +1525,12 @@ impl<K, V, C> Map<K, V, C> where C: Compare<K> {
     ///
     /// assert_eq!(map.into_range(Excluded(&"a"), Unbounded).collect::<Vec<_>>(),
     ///     [("b", 2), ("c", 3)]);
-    /// # }
     /// ```
     pub fn into_range<Min: ?Sized, Max: ?Sized>(mut self, min: Bound<&Min>, max: Bound<&Max>)
-        -> IntoRange<K, V> where C: Compare<Min, K> + Compare<Max, K> {
+        -> IntoRange<K, V, A, G> where C: Compare<Min, K> + Compare<Max, K> {
 
-        IntoRange(node::Range::new(self.root.take(), self.len, &self.cmp, min, max))
+        let cmp = self.counters.counting(&self.cmp);
+        IntoRange(node::Range::new(self.root.take(), self.len, &cmp, min, max))
     }
 
     /// Returns an iterator over the map's entries whose keys lie in the given range with immutable
@@ -845,10 +1541,7 @@ impl<K, V, C> Map<K, V, C> where C: Compare<K> {
     /// # Examples
     ///
     /// ```
-    /// # #![feature(collections_bound)]
-    /// # extern crate tree;
-    /// # fn main() {
-    /// use std::collections::Bound::{Included, Excluded, Unbounded};
+    /// use std::ops::Bound::{Included, Excluded, Unbounded};
     ///
     /// let mut map = tree::Map::new();
     ///
@@ -862,15 +1555,47 @@ impl<K, V, C> Map<K, V, C> where C: Compare<K> {
     ///     [(&"b", &2), (&"c", &3)]);
     /// assert_eq!(map.range(Included(&"a"), Excluded(&"b")).collect::<Vec<_>>(),
     ///     [(&"a", &1)]);
-    /// # }
     /// ```
     pub fn range<Min: ?Sized, Max: ?Sized>(&self, min: Bound<&Min>, max: Bound<&Max>)
-        -> Range<K, V> where C: Compare<Min, K> + Compare<Max, K> {
+        -> Range<'_, K, V, A, G> where C: Compare<Min, K> + Compare<Max, K> {
 
-        Range(node::Range::new(self.root.as_ref().map(MarkedNode::new), self.len, &self.cmp, min,
+        let cmp = self.counters.counting(&self.cmp);
+        Range(node::Range::new(self.root.as_ref().map(MarkedNode::new), self.len, &cmp, min,
             max))
     }
 
+    /// Returns an iterator over the map's entries whose keys start with the given prefix with
+    /// immutable references to the values.
+    ///
+    /// The iterator yields the entries in ascending order according to the map's comparator,
+    /// which must order its keys lexicographically, as described in
+    /// [`Prefix`](../prefix/trait.Prefix.html).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut map = tree::Map::new();
+    ///
+    /// map.insert("car", 1);
+    /// map.insert("cart", 2);
+    /// map.insert("cat", 3);
+    /// map.insert("dog", 4);
+    ///
+    /// assert_eq!(map.prefix_range(&"ca").collect::<Vec<_>>(),
+    ///     [(&"car", &1), (&"cart", &2), (&"cat", &3)]);
+    /// assert_eq!(map.prefix_range(&"car").collect::<Vec<_>>(), [(&"car", &1), (&"cart", &2)]);
+    /// assert_eq!(map.prefix_range(&"d").count(), 1);
+    /// ```
+    pub fn prefix_range<'a, Q: ?Sized>(&'a self, prefix: &'a Q)
+        -> PrefixRange<'a, K, V, Q, A, G> where Q: Prefix, K: Borrow<Q>, C: Compare<Q, K> {
+
+        PrefixRange { range: self.range::<Q, Q>(Bound::Included(prefix), Bound::Unbounded),
+                      prefix: Some(prefix) }
+    }
+}
+
+#[cfg(feature = "range")]
+impl<K, V, C, A> Map<K, V, C, A> where C: Compare<K>, A: Allocator + Clone {
     /// Returns an iterator over the map's entries whose keys lie in the given range with mutable
     /// references to the values.
     ///
@@ -879,10 +1604,7 @@ impl<K, V, C> Map<K, V, C> where C: Compare<K> {
     /// # Examples
     ///
     /// ```
-    /// # #![feature(collections_bound)]
-    /// # extern crate tree;
-    /// # fn main() {
-    /// use std::collections::Bound;
+    /// use std::ops::Bound;
     ///
     /// let mut map = tree::Map::new();
     ///
@@ -901,34 +1623,100 @@ impl<K, V, C> Map<K, V, C> where C: Compare<K> {
     /// assert_eq!(map[&"a"], 2);
     /// assert_eq!(map[&"b"], 4);
     /// assert_eq!(map[&"c"], 3);
-    /// # }
     /// ```
     pub fn range_mut<Min: ?Sized, Max: ?Sized>(&mut self, min: Bound<&Min>, max: Bound<&Max>)
-        -> RangeMut<K, V> where C: Compare<Min, K> + Compare<Max, K> {
+        -> RangeMut<'_, K, V, A> where C: Compare<Min, K> + Compare<Max, K> {
 
-        RangeMut(node::Range::new(self.root.as_mut().map(MutMarkedNode::new), self.len, &self.cmp,
+        let cmp = self.counters.counting(&self.cmp);
+        RangeMut(node::Range::new(self.root.as_mut().map(MutMarkedNode::new), self.len, &cmp,
             min, max))
     }
 }
 
-impl<K, V, C> Debug for Map<K, V, C> where K: Debug, V: Debug, C: Compare<K> {
+#[cfg(feature = "stats")]
+impl<K, V, C, A, G> Map<K, V, C, A, G>
+    where C: Compare<K>, A: Allocator + Clone, G: Augment<K, V> {
+
+    /// Returns the counts of the work done by the map's operations since it was created or cloned,
+    /// or since the last call to `reset_stats`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut map = tree::Map::new();
+    ///
+    /// map.insert(1, "a");
+    /// map.insert(2, "b");
+    /// map.insert(3, "c");
+    ///
+    /// let stats = map.stats();
+    /// assert_eq!(stats.compares, 3);
+    /// assert_eq!(stats.skews, 0);
+    /// assert_eq!(stats.splits, 1);
+    /// ```
+    pub fn stats(&self) -> Stats { self.counters.stats() }
+
+    /// Resets the counts returned by `stats` to zero.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut map = tree::Map::new();
+    ///
+    /// map.insert(1, "a");
+    /// map.reset_stats();
+    /// assert_eq!(map.stats(), Default::default());
+    ///
+    /// map.get(&1);
+    /// assert_eq!(map.stats().compares, 1);
+    /// ```
+    pub fn reset_stats(&self) { self.counters.reset() }
+
+    /// Returns a report of the shape of the map's tree.
+    ///
+    /// This takes O(n) time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let map: tree::Map<_, _> = (0..7).map(|i| (i, ())).collect();
+    ///
+    /// let shape = map.shape();
+    /// assert_eq!(shape.nodes, 7);
+    /// assert_eq!(shape.height, 3);
+    /// assert_eq!(shape.levels, [1, 2, 4]);
+    /// ```
+    pub fn shape(&self) -> Shape {
+        let mut levels = Vec::new();
+        node::count_levels(&self.root, &mut levels, 0);
+        Shape { nodes: self.len, height: levels.len(), levels: levels }
+    }
+}
+
+impl<K, V, C, A, G> Debug for Map<K, V, C, A, G>
+    where K: Debug, V: Debug, C: Compare<K>, A: Allocator + Clone, G: Augment<K, V> {
+
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_map().entries(self).finish()
     }
 }
 
-impl<K, V, C> Default for Map<K, V, C> where C: Compare<K> + Default {
-    fn default() -> Self { Map::with_cmp(C::default()) }
+impl<K, V, C, G> Default for Map<K, V, C, Global, G> where C: Compare<K> + Default {
+    fn default() -> Self {
+        Map { root: None, len: 0, cmp: C::default(), alloc: Global, counters: Default::default() }
+    }
 }
 
-impl<K, V, C> Extend<(K, V)> for Map<K, V, C> where C: Compare<K> {
+impl<K, V, C, A, G> Extend<(K, V)> for Map<K, V, C, A, G>
+    where C: Compare<K>, A: Allocator + Clone, G: Augment<K, V> {
+
     fn extend<I: IntoIterator<Item=(K, V)>>(&mut self, it: I) {
         for (k, v) in it { self.insert(k, v); }
     }
 }
 
-impl<K, V, C> iter::FromIterator<(K, V)> for Map<K, V, C>
-    where C: Compare<K> + Default {
+impl<K, V, C, G> iter::FromIterator<(K, V)> for Map<K, V, C, Global, G>
+    where C: Compare<K> + Default, G: Augment<K, V> {
 
     fn from_iter<I: IntoIterator<Item=(K, V)>>(it: I) -> Self {
         let mut map = Map::default();
@@ -937,34 +1725,42 @@ impl<K, V, C> iter::FromIterator<(K, V)> for Map<K, V, C>
     }
 }
 
-impl<K, V, C> Hash for Map<K, V, C> where K: Hash, V: Hash, C: Compare<K> {
+impl<K, V, C, A, G> Hash for Map<K, V, C, A, G>
+    where K: Hash, V: Hash, C: Compare<K>, A: Allocator + Clone, G: Augment<K, V> {
+
     fn hash<H: hash::Hasher>(&self, h: &mut H) {
         for e in self.iter() { e.hash(h); }
     }
 }
 
-impl<'a, K, V, C, Q: ?Sized> ops::Index<&'a Q> for Map<K, V, C>
-    where C: Compare<K> + Compare<Q, K> {
+impl<'a, K, V, C, A, G, Q: ?Sized> ops::Index<&'a Q> for Map<K, V, C, A, G>
+    where C: Compare<K> + Compare<Q, K>, A: Allocator + Clone, G: Augment<K, V> {
 
     type Output = V;
     fn index(&self, key: &Q) -> &V { self.get(key).expect("key not found") }
 }
 
-impl<'a, K, V, C> IntoIterator for &'a Map<K, V, C> where C: Compare<K> {
+impl<'a, K, V, C, A, G> IntoIterator for &'a Map<K, V, C, A, G>
+    where C: Compare<K>, A: Allocator + Clone, G: Augment<K, V> {
+
     type Item = (&'a K, &'a V);
-    type IntoIter = Iter<'a, K, V>;
-    fn into_iter(self) -> Iter<'a, K, V> { self.iter() }
+    type IntoIter = Iter<'a, K, V, A, G>;
+    fn into_iter(self) -> Iter<'a, K, V, A, G> { self.iter() }
 }
 
-impl<'a, K, V, C> IntoIterator for &'a mut Map<K, V, C> where C: Compare<K> {
+impl<'a, K, V, C, A> IntoIterator for &'a mut Map<K, V, C, A>
+    where C: Compare<K>, A: Allocator + Clone {
+
     type Item = (&'a K, &'a mut V);
-    type IntoIter = IterMut<'a, K, V>;
-    fn into_iter(self) -> IterMut<'a, K, V> { self.iter_mut() }
+    type IntoIter = IterMut<'a, K, V, A>;
+    fn into_iter(self) -> IterMut<'a, K, V, A> { self.iter_mut() }
 }
 
-impl<K, V, C> IntoIterator for Map<K, V, C> where C: Compare<K> {
+impl<K, V, C, A, G> IntoIterator for Map<K, V, C, A, G>
+    where C: Compare<K>, A: Allocator + Clone, G: Augment<K, V> {
+
     type Item = (K, V);
-    type IntoIter = IntoIter<K, V>;
+    type IntoIter = IntoIter<K, V, A, G>;
 
     /// Returns an iterator that consumes the map.
     ///
@@ -985,10 +1781,14 @@ impl<K, V, C> IntoIterator for Map<K, V, C> where C: Compare<K> {
     /// assert_eq!(it.next(), Some((3, "c")));
     /// assert_eq!(it.next(), None);
     /// ```
-    fn into_iter(self) -> IntoIter<K, V> { IntoIter(node::Iter::new(self.root, self.len)) }
+    fn into_iter(self) -> IntoIter<K, V, A, G> {
+        IntoIter(node::Iter::new(self.root, self.len))
+    }
 }
 
-impl<K, V, C> PartialEq for Map<K, V, C> where V: PartialEq, C: Compare<K> {
+impl<K, V, C, A, G> PartialEq for Map<K, V, C, A, G>
+    where V: PartialEq, C: Compare<K>, A: Allocator + Clone, G: Augment<K, V> {
+
     fn eq(&self, other: &Self) -> bool {
         self.len() == other.len() && self.iter().zip(other.iter()).all(|(l, r)| {
             self.cmp.compares_eq(&l.0, &r.0) && l.1 == r.1
@@ -996,9 +1796,12 @@ impl<K, V, C> PartialEq for Map<K, V, C> where V: PartialEq, C: Compare<K> {
     }
 }
 
-impl<K, V, C> Eq for Map<K, V, C> where V: Eq, C: Compare<K> {}
+impl<K, V, C, A, G> Eq for Map<K, V, C, A, G>
+    where V: Eq, C: Compare<K>, A: Allocator + Clone, G: Augment<K, V> {}
+
+impl<K, V, C, A, G> PartialOrd for Map<K, V, C, A, G>
+    where V: PartialOrd, C: Compare<K>, A: Allocator + Clone, G: Augment<K, V> {
 
-impl<K, V, C> PartialOrd for Map<K, V, C> where V: PartialOrd, C: Compare<K> {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         let mut l = self.iter();
         let mut r = other.iter();
@@ -1020,7 +1823,9 @@ impl<K, V, C> PartialOrd for Map<K, V, C> where V: PartialOrd, C: Compare<K> {
     }
 }
 
-impl<K, V, C> Ord for Map<K, V, C> where V: Ord, C: Compare<K> {
+impl<K, V, C, A, G> Ord for Map<K, V, C, A, G>
+    where V: Ord, C: Compare<K>, A: Allocator + Clone, G: Augment<K, V> {
+
     fn cmp(&self, other: &Self) -> Ordering {
         let mut l = self.iter();
         let mut r = other.iter();
@@ -1062,9 +1867,10 @@ impl<K, V, C> Ord for Map<K, V, C> where V: Ord, C: Compare<K> {
 /// }
 /// ```
 #[derive(Clone)]
-pub struct IntoIter<K, V>(node::Iter<Box<Node<K, V>>>);
+pub struct IntoIter<K, V, A = Global, G = ()>(node::Iter<NodeBox<K, V, A, G>>)
+    where A: Allocator;
 
-impl<K, V> Iterator for IntoIter<K, V> {
+impl<K, V, A, G> Iterator for IntoIter<K, V, A, G> where A: Allocator {
     type Item = (K, V);
     fn next(&mut self) -> Option<Self::Item> { self.0.next() }
     fn size_hint(&self) -> (usize, Option<usize>) { self.0.size_hint() }
@@ -1073,11 +1879,11 @@ impl<K, V> Iterator for IntoIter<K, V> {
     fn last(mut self) -> Option<Self::Item> { self.next_back() }
 }
 
-impl<K, V> DoubleEndedIterator for IntoIter<K, V> {
+impl<K, V, A, G> DoubleEndedIterator for IntoIter<K, V, A, G> where A: Allocator {
     fn next_back(&mut self) -> Option<Self::Item> { self.0.next_back() }
 }
 
-impl<K, V> ExactSizeIterator for IntoIter<K, V> {
+impl<K, V, A, G> ExactSizeIterator for IntoIter<K, V, A, G> where A: Allocator {
     fn len(&self) -> usize { self.0.len() }
 }
 
@@ -1100,13 +1906,14 @@ impl<K, V> ExactSizeIterator for IntoIter<K, V> {
 ///     println!("{:?}: {:?}", key, value);
 /// }
 /// ```
-pub struct Iter<'a, K: 'a, V: 'a>(node::Iter<MarkedNode<'a, K, V>>);
+pub struct Iter<'a, K: 'a, V: 'a, A: 'a + Allocator = Global, G: 'a = ()>(
+    node::Iter<MarkedNode<'a, K, V, A, G>>);
 
-impl<'a, K, V> Clone for Iter<'a, K, V> {
+impl<'a, K, V, A, G> Clone for Iter<'a, K, V, A, G> where A: Allocator {
     fn clone(&self) -> Self { Iter(self.0.clone()) }
 }
 
-impl<'a, K, V> Iterator for Iter<'a, K, V> {
+impl<'a, K, V, A, G> Iterator for Iter<'a, K, V, A, G> where A: Allocator {
     type Item = (&'a K, &'a V);
     fn next(&mut self) -> Option<Self::Item> { self.0.next() }
     fn size_hint(&self) -> (usize, Option<usize>) { self.0.size_hint() }
@@ -1115,11 +1922,11 @@ impl<'a, K, V> Iterator for Iter<'a, K, V> {
     fn last(mut self) -> Option<Self::Item> { self.next_back() }
 }
 
-impl<'a, K, V> DoubleEndedIterator for Iter<'a, K, V> {
+impl<'a, K, V, A, G> DoubleEndedIterator for Iter<'a, K, V, A, G> where A: Allocator {
     fn next_back(&mut self) -> Option<Self::Item> { self.0.next_back() }
 }
 
-impl<'a, K, V> ExactSizeIterator for Iter<'a, K, V> {
+impl<'a, K, V, A, G> ExactSizeIterator for Iter<'a, K, V, A, G> where A: Allocator {
     fn len(&self) -> usize { self.0.len() }
 }
 
@@ -1143,9 +1950,10 @@ impl<'a, K, V> ExactSizeIterator for Iter<'a, K, V> {
 ///     println!("{:?}: {:?}", key, value);
 /// }
 /// ```
-pub struct IterMut<'a, K: 'a, V: 'a>(node::Iter<MutMarkedNode<'a, K, V>>);
+pub struct IterMut<'a, K: 'a, V: 'a, A: 'a + Allocator = Global>(
+    node::Iter<MutMarkedNode<'a, K, V, A>>);
 
-impl<'a, K, V> Iterator for IterMut<'a, K, V> {
+impl<'a, K, V, A> Iterator for IterMut<'a, K, V, A> where A: Allocator {
     type Item = (&'a K, &'a mut V);
     fn next(&mut self) -> Option<Self::Item> { self.0.next() }
     fn size_hint(&self) -> (usize, Option<usize>) { self.0.size_hint() }
@@ -1154,11 +1962,11 @@ impl<'a, K, V> Iterator for IterMut<'a, K, V> {
     fn last(mut self) -> Option<Self::Item> { self.next_back() }
 }
 
-impl<'a, K, V> DoubleEndedIterator for IterMut<'a, K, V> {
+impl<'a, K, V, A> DoubleEndedIterator for IterMut<'a, K, V, A> where A: Allocator {
     fn next_back(&mut self) -> Option<Self::Item> { self.0.next_back() }
 }
 
-impl<'a, K, V> ExactSizeIterator for IterMut<'a, K, V> {
+impl<'a, K, V, A> ExactSizeIterator for IterMut<'a, K, V, A> where A: Allocator {
     fn len(&self) -> usize { self.0.len() }
 }
 
@@ -1169,10 +1977,11 @@ impl<'a, K, V> ExactSizeIterator for IterMut<'a, K, V> {
 /// Acquire through [`Map::into_range`](struct.Map.html#method.into_range).
 #[cfg(feature = "range")]
 #[derive(Clone)]
-pub struct IntoRange<K, V>(node::Range<Box<Node<K, V>>>);
+pub struct IntoRange<K, V, A = Global, G = ()>(node::Range<NodeBox<K, V, A, G>>)
+    where A: Allocator;
 
 #[cfg(feature = "range")]
-impl<K, V> Iterator for IntoRange<K, V> {
+impl<K, V, A, G> Iterator for IntoRange<K, V, A, G> where A: Allocator {
     type Item = (K, V);
     fn next(&mut self) -> Option<Self::Item> { self.0.next() }
     fn size_hint(&self) -> (usize, Option<usize>) { self.0.size_hint() }
@@ -1181,7 +1990,7 @@ impl<K, V> Iterator for IntoRange<K, V> {
 }
 
 #[cfg(feature = "range")]
-impl<K, V> DoubleEndedIterator for IntoRange<K, V> {
+impl<K, V, A, G> DoubleEndedIterator for IntoRange<K, V, A, G> where A: Allocator {
     fn next_back(&mut self) -> Option<Self::Item> { self.0.next_back() }
 }
 
@@ -1192,15 +2001,16 @@ impl<K, V> DoubleEndedIterator for IntoRange<K, V> {
 ///
 /// Acquire through [`Map::range`](struct.Map.html#method.range).
 #[cfg(feature = "range")]
-pub struct Range<'a, K: 'a, V: 'a>(node::Range<MarkedNode<'a, K, V>>);
+pub struct Range<'a, K: 'a, V: 'a, A: 'a + Allocator = Global, G: 'a = ()>(
+    node::Range<MarkedNode<'a, K, V, A, G>>);
 
 #[cfg(feature = "range")]
-impl<'a, K, V> Clone for Range<'a, K, V> {
+impl<'a, K, V, A, G> Clone for Range<'a, K, V, A, G> where A: Allocator {
     fn clone(&self) -> Self { Range(self.0.clone()) }
 }
 
 #[cfg(feature = "range")]
-impl<'a, K, V> Iterator for Range<'a, K, V> {
+impl<'a, K, V, A, G> Iterator for Range<'a, K, V, A, G> where A: Allocator {
     type Item = (&'a K, &'a V);
     fn next(&mut self) -> Option<Self::Item> { self.0.next() }
     fn size_hint(&self) -> (usize, Option<usize>) { self.0.size_hint() }
@@ -1209,7 +2019,7 @@ impl<'a, K, V> Iterator for Range<'a, K, V> {
 }
 
 #[cfg(feature = "range")]
-impl<'a, K, V> DoubleEndedIterator for Range<'a, K, V> {
+impl<'a, K, V, A, G> DoubleEndedIterator for Range<'a, K, V, A, G> where A: Allocator {
     fn next_back(&mut self) -> Option<Self::Item> { self.0.next_back() }
 }
 
@@ -1220,10 +2030,11 @@ impl<'a, K, V> DoubleEndedIterator for Range<'a, K, V> {
 ///
 /// Acquire through [`Map::range_mut`](struct.Map.html#method.range_mut).
 #[cfg(feature = "range")]
-pub struct RangeMut<'a, K: 'a, V: 'a>(node::Range<MutMarkedNode<'a, K, V>>);
+pub struct RangeMut<'a, K: 'a, V: 'a, A: 'a + Allocator = Global>(
+    node::Range<MutMarkedNode<'a, K, V, A>>);
 
 #[cfg(feature = "range")]
-impl<'a, K, V> Iterator for RangeMut<'a, K, V> {
+impl<'a, K, V, A> Iterator for RangeMut<'a, K, V, A> where A: Allocator {
     type Item = (&'a K, &'a mut V);
     fn next(&mut self) -> Option<Self::Item> { self.0.next() }
     fn size_hint(&self) -> (usize, Option<usize>) { self.0.size_hint() }
@@ -1232,21 +2043,232 @@ impl<'a, K, V> Iterator for RangeMut<'a, K, V> {
 }
 
 #[cfg(feature = "range")]
-impl<'a, K, V> DoubleEndedIterator for RangeMut<'a, K, V> {
+impl<'a, K, V, A> DoubleEndedIterator for RangeMut<'a, K, V, A> where A: Allocator {
     fn next_back(&mut self) -> Option<Self::Item> { self.0.next_back() }
 }
 
+/// An iterator over the map's entries whose keys start with a given prefix with immutable
+/// references to the values.
+///
+/// The iterator yields the entries in ascending order according to the map's comparator.
+///
+/// Acquire through [`Map::prefix_range`](struct.Map.html#method.prefix_range).
+#[cfg(feature = "range")]
+pub struct PrefixRange<'a, K: 'a, V: 'a, Q: 'a + ?Sized, A: 'a + Allocator = Global, G: 'a = ()> {
+    range: Range<'a, K, V, A, G>,
+    prefix: Option<&'a Q>,
+}
+
+#[cfg(feature = "range")]
+impl<'a, K, V, Q: ?Sized, A, G> Clone for PrefixRange<'a, K, V, Q, A, G> where A: Allocator {
+    fn clone(&self) -> Self { PrefixRange { range: self.range.clone(), prefix: self.prefix } }
+}
+
+#[cfg(feature = "range")]
+impl<'a, K, V, Q: ?Sized, A, G> Iterator for PrefixRange<'a, K, V, Q, A, G>
+    where Q: Prefix, K: Borrow<Q>, A: Allocator {
+
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let prefix = match self.prefix {
+            None => return None,
+            Some(prefix) => prefix,
+        };
+
+        match self.range.next() {
+            Some(e) if prefix.is_prefix_of(e.0.borrow()) => Some(e),
+            _ => { self.prefix = None; None }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.prefix.is_none() { (0, Some(0)) } else { (0, self.range.size_hint().1) }
+    }
+}
+
+// A change to undo when a transaction is rolled back.
+enum Undo<K, V> {
+    // Remove an inserted key.
+    Remove(K),
+    // Restore a removed or overwritten entry.
+    Insert(K, V),
+}
+
+/// A transaction on a map.
+///
+/// Changes made through the transaction are applied to the map immediately and undone when the
+/// transaction is dropped, unless it succeeded.
+///
+/// Acquire through [`Map::transaction`](struct.Map.html#method.transaction).
+pub struct Transaction<'a, K: 'a, V: 'a, C: 'a, A: 'a = Global>
+    where C: Compare<K>, A: Allocator + Clone {
+
+    map: &'a mut Map<K, V, C, A>,
+    log: Vec<Undo<K, V>>,
+}
+
+impl<'a, K, V, C, A> Transaction<'a, K, V, C, A> where C: Compare<K>, A: Allocator + Clone {
+    /// Returns a reference to the map, as modified by the transaction so far.
+    pub fn map(&self) -> &Map<K, V, C, A> { self.map }
+
+    /// Checks if the map is empty.
+    pub fn is_empty(&self) -> bool { self.map.is_empty() }
+
+    /// Returns the number of entries in the map.
+    pub fn len(&self) -> usize { self.map.len() }
+
+    /// Inserts an entry into the map, returning a reference to the previous value, if any,
+    /// associated with the key.
+    pub fn insert(&mut self, key: K, value: V) -> Option<&V> where K: Clone {
+        match self.map.insert(key.clone(), value) {
+            None => {
+                self.log.push(Undo::Remove(key));
+                None
+            }
+            Some(old_value) => {
+                self.log.push(Undo::Insert(key, old_value));
+                self.last_undone().map(|e| e.1)
+            }
+        }
+    }
+
+    /// Removes the entry whose key is equal to the given key, returning references to it, or
+    /// `None` if the map does not contain the key.
+    pub fn remove<Q: ?Sized>(&mut self, key: &Q) -> Option<(&K, &V)> where C: Compare<Q, K> {
+        match self.map.remove(key) {
+            None => None,
+            Some((key, value)) => {
+                self.log.push(Undo::Insert(key, value));
+                self.last_undone()
+            }
+        }
+    }
+
+    /// Returns a reference to the value associated with the given key, or `None` if the map does
+    /// not contain the key.
+    pub fn get<Q: ?Sized>(&self, key: &Q) -> Option<&V> where C: Compare<Q, K> {
+        self.map.get(key)
+    }
+
+    /// Checks if the map contains the given key.
+    pub fn contains_key<Q: ?Sized>(&self, key: &Q) -> bool where C: Compare<Q, K> {
+        self.map.contains_key(key)
+    }
+
+    fn last_undone(&self) -> Option<(&K, &V)> {
+        match self.log.last() {
+            Some(&Undo::Insert(ref key, ref value)) => Some((key, value)),
+            _ => None,
+        }
+    }
+}
+
+impl<'a, K, V, C, A> Drop for Transaction<'a, K, V, C, A>
+    where C: Compare<K>, A: Allocator + Clone {
+
+    fn drop(&mut self) {
+        while let Some(undo) = self.log.pop() {
+            match undo {
+                Undo::Remove(key) => { self.map.remove(&key); }
+                Undo::Insert(key, value) => { self.map.insert(key, value); }
+            }
+        }
+    }
+}
+
+/// A difference between two maps.
+///
+/// See [`Map::diff`](struct.Map.html#method.diff) for an example.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DiffItem<K, V> {
+    /// An entry that is only in the new map.
+    Added(K, V),
+    /// An entry that is only in the old map.
+    Removed(K, V),
+    /// A key whose value differs between the maps, with its old and new values.
+    Changed(K, V, V),
+}
+
+impl<K, V> DiffItem<K, V> {
+    fn key(&self) -> &K {
+        match *self {
+            DiffItem::Added(ref k, _) | DiffItem::Removed(ref k, _) => k,
+            DiffItem::Changed(ref k, _, _) => k,
+        }
+    }
+}
+
+impl<'a, K, V> DiffItem<&'a K, &'a V> where K: Clone, V: Clone {
+    /// Returns a copy of the item that owns its key and values.
+    pub fn cloned(self) -> DiffItem<K, V> {
+        match self {
+            DiffItem::Added(k, v) => DiffItem::Added(k.clone(), v.clone()),
+            DiffItem::Removed(k, v) => DiffItem::Removed(k.clone(), v.clone()),
+            DiffItem::Changed(k, old, new) =>
+                DiffItem::Changed(k.clone(), old.clone(), new.clone()),
+        }
+    }
+}
+
+/// An iterator over the differences between two maps.
+///
+/// Acquire through [`Map::diff`](struct.Map.html#method.diff).
+pub struct Diff<'a, K: 'a, V: 'a, C: 'a, A: 'a + Allocator = Global, B: 'a + Allocator = Global> {
+    old: iter::Peekable<Iter<'a, K, V, A>>,
+    new: iter::Peekable<Iter<'a, K, V, B>>,
+    cmp: &'a C,
+}
+
+impl<'a, K, V, C, A, B> Clone for Diff<'a, K, V, C, A, B> where A: Allocator, B: Allocator {
+    fn clone(&self) -> Self {
+        Diff { old: self.old.clone(), new: self.new.clone(), cmp: self.cmp }
+    }
+}
+
+impl<'a, K, V, C, A, B> Iterator for Diff<'a, K, V, C, A, B>
+    where V: PartialEq, C: Compare<K>, A: Allocator, B: Allocator {
+
+    type Item = DiffItem<&'a K, &'a V>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let order = match (self.old.peek(), self.new.peek()) {
+                (None, None) => return None,
+                (Some(_), None) => Less,
+                (None, Some(_)) => Greater,
+                (Some(old), Some(new)) => self.cmp.compare(old.0, new.0),
+            };
+
+            match order {
+                Less => return self.old.next().map(|(k, v)| DiffItem::Removed(k, v)),
+                Greater => return self.new.next().map(|(k, v)| DiffItem::Added(k, v)),
+                Equal => {
+                    let (k, old) = self.old.next().unwrap();
+                    let (_, new) = self.new.next().unwrap();
+                    if old != new { return Some(DiffItem::Changed(k, old, new)); }
+                }
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (old, new) = (self.old.len(), self.new.len());
+        (if old > new { old - new } else { new - old }, Some(old + new))
+    }
+}
+
 /// An entry in the map.
 ///
 /// See [`Map::entry`](struct.Map.html#method.entry) for an example.
-pub enum Entry<'a, K: 'a, V: 'a> {
+pub enum Entry<'a, K: 'a, V: 'a, A: 'a + Allocator = Global> {
     /// An occupied entry.
-    Occupied(OccupiedEntry<'a, K, V>),
+    Occupied(OccupiedEntry<'a, K, V, A>),
     /// A vacant entry.
-    Vacant(VacantEntry<'a, K, V>),
+    Vacant(VacantEntry<'a, K, V, A>),
 }
 
-impl<'a, K, V> Entry<'a, K, V> {
+impl<'a, K, V, A> Entry<'a, K, V, A> where A: Allocator + Clone {
     /// Returns the entry's value, inserting the given default if the entry is vacant.
     pub fn or_insert(self, default: V) -> &'a mut V {
         match self {
@@ -0,0 +1,330 @@
+//! Ordered maps that maintain a hash of their contents.
+//!
+//! Every node of a [`MerkleMap`](type.MerkleMap.html) caches a digest of its subtree, so the
+//! digest of the whole map is available in constant time, and the digest of any key range in
+//! logarithmic time. Two maps can therefore be compared cheaply, and the differences between a map
+//! and a remote replica can be found by comparing only the digests of subtrees that differ.
+//!
+//! A `MerkleMap` is a [`Map`](../map/struct.Map.html) augmented with [`Hashes`](struct.Hashes.html)
+//! and supports the operations of an augmented map, along with
+//! [`root_hash`](../map/struct.Map.html#method.root_hash),
+//! [`digest`](../map/struct.Map.html#method.digest) and
+//! [`diff_against`](../map/struct.Map.html#method.diff_against).
+//!
+//! A digest depends only on the sequence of entries it covers, not on the shape of the tree, so
+//! maps with equal entries have equal digests regardless of the order in which the entries were
+//! inserted. Digests are computed from a hash of each entry, using a hasher of type `H` created by
+//! `H::default()`, which must therefore produce the same hashes for all replicas being compared.
+//! The default, `DefaultHasher`, does so for replicas built with the same version of Rust.
+//!
+//! # Examples
+//!
+//! ```
+//! use tree::merkle::{Difference, MerkleMap};
+//!
+//! let local: MerkleMap<_, _> = (0..1000).map(|i| (i, i)).collect();
+//! let mut remote = local.clone();
+//! assert_eq!(local.root_hash(), remote.root_hash());
+//!
+//! remote.insert(500, 0);
+//! remote.insert(2000, 0);
+//! assert!(local.root_hash() != remote.root_hash());
+//!
+//! let diff = local.diff_against(&remote);
+//! assert_eq!(diff.len(), 2);
+//! assert_eq!(diff[0], Difference::Entry(&500, &500));
+//! assert!(match diff[1] { Difference::Missing(..) => true, _ => false });
+//! ```
+
+use alloc::vec::Vec;
+use allocator::{Allocator, Global};
+use compare::{Compare, Natural};
+use core::hash::{Hash, Hasher};
+use core::marker::PhantomData;
+use core::ops::Bound;
+use std::collections::hash_map::DefaultHasher;
+use super::map::{Augment, Map};
+use super::node::{self, Node};
+
+// The modulus of the polynomial hash, the Mersenne prime 2^61 - 1.
+const MODULUS: u64 = (1 << 61) - 1;
+
+// The base of the polynomial hash.
+const BASE: u64 = 0x1d8e_4e27_c47d_124f % MODULUS;
+
+fn mul(l: u64, r: u64) -> u64 { ((l as u128 * r as u128) % MODULUS as u128) as u64 }
+
+/// An ordered map that maintains a hash of its contents.
+///
+/// See the [module documentation](index.html) for details.
+pub type MerkleMap<K, V, C = Natural<K>, H = DefaultHasher> = Map<K, V, C, Global, Hashes<H>>;
+
+/// A digest of a sequence of map entries.
+///
+/// The digest combines a polynomial hash of the entries' hashes with the number of entries.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Digest {
+    hash: u64,
+    len: usize,
+}
+
+impl Digest {
+    /// Returns the hash of the entries.
+    pub fn hash(&self) -> u64 { self.hash }
+
+    /// Returns the number of entries.
+    pub fn len(&self) -> usize { self.len }
+
+    /// Checks if the digest covers no entries.
+    pub fn is_empty(&self) -> bool { self.len == 0 }
+}
+
+// A digest together with `BASE` raised to the number of entries it covers, which is needed to
+// concatenate it with another.
+#[derive(Clone, Copy)]
+struct Poly {
+    digest: Digest,
+    pow: u64,
+}
+
+impl Poly {
+    fn empty() -> Self { Poly { digest: Digest { hash: 0, len: 0 }, pow: 1 } }
+
+    fn entry(hash: u64) -> Self { Poly { digest: Digest { hash: hash, len: 1 }, pow: BASE } }
+
+    fn concat(self, other: Poly) -> Self {
+        Poly {
+            digest: Digest {
+                hash: (mul(self.digest.hash, other.pow) + other.digest.hash) % MODULUS,
+                len: self.digest.len + other.digest.len,
+            },
+            pow: mul(self.pow, other.pow),
+        }
+    }
+}
+
+/// The data cached by each node of a `MerkleMap`: the hash of the node's entry, computed with a
+/// hasher of type `H`, and the digest of the node's subtree.
+pub struct Hashes<H = DefaultHasher> {
+    entry: u64,
+    subtree: Poly,
+    _hasher: PhantomData<fn() -> H>,
+}
+
+impl<H> Clone for Hashes<H> {
+    fn clone(&self) -> Self {
+        Hashes { entry: self.entry, subtree: self.subtree, _hasher: PhantomData }
+    }
+}
+
+impl<K, V, H> Augment<K, V> for Hashes<H> where K: Hash, V: Hash, H: Hasher + Default {
+    fn new(key: &K, value: &V) -> Self {
+        let mut hasher = H::default();
+        key.hash(&mut hasher);
+        value.hash(&mut hasher);
+        let entry = hasher.finish() % MODULUS;
+        Hashes { entry: entry, subtree: Poly::entry(entry), _hasher: PhantomData }
+    }
+
+    fn update(&mut self, _key: &K, _value: &V, left: Option<&Self>, right: Option<&Self>) {
+        self.subtree = subtree(left).concat(Poly::entry(self.entry)).concat(subtree(right));
+    }
+}
+
+fn subtree<H>(hashes: Option<&Hashes<H>>) -> Poly {
+    hashes.map_or(Poly::empty(), |hashes| hashes.subtree)
+}
+
+fn entry<K, V, A, H>(node: &Node<K, V, A, Hashes<H>>) -> Poly where A: Allocator {
+    Poly::entry(node.aug().entry)
+}
+
+fn left<K, V, A, H>(node: &Node<K, V, A, Hashes<H>>) -> Poly where A: Allocator {
+    subtree(node.left_child().map(Node::aug))
+}
+
+fn right<K, V, A, H>(node: &Node<K, V, A, Hashes<H>>) -> Poly where A: Allocator {
+    subtree(node.right_child().map(Node::aug))
+}
+
+// Returns the digest of the subtree's entries whose keys are not less than `min`.
+fn suffix<K, V, A, H, C: ?Sized, Q: ?Sized>(node: Option<&Node<K, V, A, Hashes<H>>>, cmp: &C,
+                                            min: Bound<&Q>) -> Poly
+    where A: Allocator, C: Compare<Q, K> {
+
+    match node {
+        None => Poly::empty(),
+        Some(node) =>
+            if node::below(cmp, node.key(), min) {
+                suffix(node.right_child(), cmp, min)
+            } else {
+                suffix(node.left_child(), cmp, min).concat(entry(node)).concat(right(node))
+            },
+    }
+}
+
+// Returns the digest of the subtree's entries whose keys are not greater than `max`.
+fn prefix<K, V, A, H, C: ?Sized, Q: ?Sized>(node: Option<&Node<K, V, A, Hashes<H>>>, cmp: &C,
+                                            max: Bound<&Q>) -> Poly
+    where A: Allocator, C: Compare<Q, K> {
+
+    match node {
+        None => Poly::empty(),
+        Some(node) =>
+            if node::above(cmp, node.key(), max) {
+                prefix(node.left_child(), cmp, max)
+            } else {
+                left(node).concat(entry(node)).concat(prefix(node.right_child(), cmp, max))
+            },
+    }
+}
+
+fn range<K, V, A, H, C: ?Sized, Min: ?Sized, Max: ?Sized>(node: Option<&Node<K, V, A, Hashes<H>>>,
+                                                          cmp: &C, min: Bound<&Min>,
+                                                          max: Bound<&Max>) -> Poly
+    where A: Allocator, C: Compare<Min, K> + Compare<Max, K> {
+
+    match node {
+        None => Poly::empty(),
+        Some(node) =>
+            if node::below(cmp, node.key(), min) {
+                range(node.right_child(), cmp, min, max)
+            } else if node::above(cmp, node.key(), max) {
+                range(node.left_child(), cmp, min, max)
+            } else {
+                suffix(node.left_child(), cmp, min)
+                    .concat(entry(node))
+                    .concat(prefix(node.right_child(), cmp, max))
+            },
+    }
+}
+
+/// A source of digests of a map's key ranges, usually a remote replica.
+pub trait DigestProvider<K> {
+    /// Returns the digest of the entries whose keys lie in the given range.
+    fn digest(&mut self, min: Bound<&K>, max: Bound<&K>) -> Digest;
+}
+
+impl<'a, K, V, C, A, H> DigestProvider<K> for &'a Map<K, V, C, A, Hashes<H>>
+    where K: Hash, V: Hash, C: Compare<K>, A: Allocator + Clone, H: Hasher + Default {
+
+    fn digest(&mut self, min: Bound<&K>, max: Bound<&K>) -> Digest {
+        Map::digest(*self, min, max)
+    }
+}
+
+impl<'a, K, P: ?Sized> DigestProvider<K> for &'a mut P where P: DigestProvider<K> {
+    fn digest(&mut self, min: Bound<&K>, max: Bound<&K>) -> Digest { (**self).digest(min, max) }
+}
+
+/// A difference between a map and a remote replica, as returned by
+/// [`Map::diff_against`](../map/struct.Map.html#method.diff_against).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Difference<'a, K: 'a, V: 'a> {
+    /// An entry that the remote replica does not contain, either because it does not contain
+    /// the key or because it associates the key with a different value.
+    Entry(&'a K, &'a V),
+    /// A key range containing no keys of the map in which the remote replica contains at least
+    /// one entry.
+    Missing(Bound<&'a K>, Bound<&'a K>),
+}
+
+impl<K, V, C, A, H> Map<K, V, C, A, Hashes<H>>
+    where K: Hash, V: Hash, C: Compare<K>, A: Allocator + Clone, H: Hasher + Default {
+
+    /// Returns the hash of the map's entries.
+    ///
+    /// This takes constant time. Maps with equal entries have equal hashes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tree::merkle::MerkleMap;
+    ///
+    /// let a: MerkleMap<_, _> = (0..10).map(|i| (i, i)).collect();
+    /// let mut b: MerkleMap<_, _> = (0..10).rev().map(|i| (i, i)).collect();
+    /// assert_eq!(a.root_hash(), b.root_hash());
+    ///
+    /// b.insert(5, 0);
+    /// assert!(a.root_hash() != b.root_hash());
+    /// ```
+    pub fn root_hash(&self) -> u64 {
+        subtree(self.root().as_ref().map(|node| node.aug())).digest.hash
+    }
+
+    /// Returns the digest of the entries whose keys lie in the given range.
+    ///
+    /// This takes logarithmic time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::ops::Bound::{Excluded, Included, Unbounded};
+    /// use tree::merkle::MerkleMap;
+    ///
+    /// let a: MerkleMap<_, _> = (0..10).map(|i| (i, i)).collect();
+    /// let b: MerkleMap<_, _> = (5..8).map(|i| (i, i)).collect();
+    ///
+    /// assert_eq!(a.digest(Included(&5), Excluded(&8)), b.digest(Unbounded, Unbounded));
+    /// assert_eq!(a.digest(Included(&5), Excluded(&8)).len(), 3);
+    /// ```
+    pub fn digest<Min: ?Sized, Max: ?Sized>(&self, min: Bound<&Min>, max: Bound<&Max>) -> Digest
+        where C: Compare<Min, K> + Compare<Max, K> {
+
+        range(self.root().as_ref().map(|node| &**node), self.cmp(), min, max).digest
+    }
+
+    /// Compares the map with a remote replica and returns their differences in ascending order
+    /// according to the map's comparator.
+    ///
+    /// The remote replica is consulted only through the digests of key ranges, and only the
+    /// subtrees whose digests differ from the remote ones are visited, so the number of requests
+    /// is proportional to the number of differences times the height of the tree.
+    ///
+    /// The differences report the map's entries that the remote replica lacks and the key ranges
+    /// in which the remote replica has entries that the map lacks. The latter can be retrieved
+    /// from the remote replica in order to synchronize the map with it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::ops::Bound::{Excluded, Unbounded};
+    /// use tree::merkle::{Difference, MerkleMap};
+    ///
+    /// let local: MerkleMap<_, _> = vec![(1, "a"), (2, "b")].into_iter().collect();
+    /// let remote: MerkleMap<_, _> = vec![(1, "a"), (2, "c"), (3, "d")].into_iter().collect();
+    ///
+    /// assert_eq!(local.diff_against(&remote),
+    ///            [Difference::Entry(&2, &"b"), Difference::Missing(Excluded(&2), Unbounded)]);
+    /// ```
+    pub fn diff_against<P>(&self, mut remote: P) -> Vec<Difference<'_, K, V>>
+        where P: DigestProvider<K> {
+
+        fn diff<'a, K, V, A, H, P>(node: Option<&'a Node<K, V, A, Hashes<H>>>,
+                                   min: Bound<&'a K>, max: Bound<&'a K>, remote: &mut P,
+                                   diffs: &mut Vec<Difference<'a, K, V>>)
+            where A: Allocator, P: DigestProvider<K> {
+
+            if subtree(node.map(Node::aug)).digest == remote.digest(min, max) { return; }
+
+            match node {
+                None => diffs.push(Difference::Missing(min, max)),
+                Some(node) => {
+                    let key = Bound::Included(node.key());
+                    diff(node.left_child(), min, Bound::Excluded(node.key()), remote, diffs);
+
+                    if entry(node).digest != remote.digest(key, key) {
+                        diffs.push(Difference::Entry(node.key(), node.value()));
+                    }
+
+                    diff(node.right_child(), Bound::Excluded(node.key()), max, remote, diffs);
+                }
+            }
+        }
+
+        let mut diffs = Vec::new();
+        diff(self.root().as_ref().map(|node| &**node), Bound::Unbounded, Bound::Unbounded,
+             &mut remote, &mut diffs);
+        diffs
+    }
+}
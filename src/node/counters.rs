@@ -0,0 +1,109 @@
+use compare::Compare;
+use core::cmp::Ordering;
+#[cfg(feature = "stats")] use core::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+#[cfg(feature = "stats")] use stats::Stats;
+
+// A map's operation counters, which are only kept with the `stats` feature. Without it, this is
+// empty and counting does nothing.
+//
+// The counters are atomic so that maps remain `Sync` and can count during shared lookups.
+#[derive(Default)]
+pub struct Counters {
+    #[cfg(feature = "stats")] compares: AtomicUsize,
+    #[cfg(feature = "stats")] skews: AtomicUsize,
+    #[cfg(feature = "stats")] splits: AtomicUsize,
+    #[cfg(feature = "stats")] rebalances: AtomicUsize,
+}
+
+impl Counters {
+    #[inline]
+    pub fn compare(&self) {
+        #[cfg(feature = "stats")] self.compares.fetch_add(1, Relaxed);
+    }
+
+    #[inline]
+    pub fn skew(&self) {
+        #[cfg(feature = "stats")] self.skews.fetch_add(1, Relaxed);
+    }
+
+    #[inline]
+    pub fn split(&self) {
+        #[cfg(feature = "stats")] self.splits.fetch_add(1, Relaxed);
+    }
+
+    #[inline]
+    pub fn rebalance(&self) {
+        #[cfg(feature = "stats")] self.rebalances.fetch_add(1, Relaxed);
+    }
+
+    // Returns a comparator that counts its comparisons in these counters.
+    pub fn counting<'a, C: ?Sized>(&'a self, cmp: &'a C) -> Counting<'a, C> {
+        Counting { cmp: cmp, counters: self }
+    }
+
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> Stats {
+        Stats {
+            compares: self.compares.load(Relaxed),
+            skews: self.skews.load(Relaxed),
+            splits: self.splits.load(Relaxed),
+            rebalances: self.rebalances.load(Relaxed),
+        }
+    }
+
+    #[cfg(feature = "stats")]
+    pub fn reset(&self) {
+        for counter in &[&self.compares, &self.skews, &self.splits, &self.rebalances] {
+            counter.store(0, Relaxed);
+        }
+    }
+}
+
+// A clone of a map starts counting afresh.
+impl Clone for Counters {
+    fn clone(&self) -> Self { Counters::default() }
+}
+
+pub struct Counting<'a, C: 'a + ?Sized> {
+    cmp: &'a C,
+    counters: &'a Counters,
+}
+
+impl<'a, C: ?Sized, L: ?Sized, R: ?Sized> Compare<L, R> for Counting<'a, C>
+    where C: Compare<L, R> {
+
+    fn compare(&self, l: &L, r: &R) -> Ordering {
+        self.counters.compare();
+        self.cmp.compare(l, r)
+    }
+
+    fn compares_lt(&self, l: &L, r: &R) -> bool {
+        self.counters.compare();
+        self.cmp.compares_lt(l, r)
+    }
+
+    fn compares_le(&self, l: &L, r: &R) -> bool {
+        self.counters.compare();
+        self.cmp.compares_le(l, r)
+    }
+
+    fn compares_ge(&self, l: &L, r: &R) -> bool {
+        self.counters.compare();
+        self.cmp.compares_ge(l, r)
+    }
+
+    fn compares_gt(&self, l: &L, r: &R) -> bool {
+        self.counters.compare();
+        self.cmp.compares_gt(l, r)
+    }
+
+    fn compares_eq(&self, l: &L, r: &R) -> bool {
+        self.counters.compare();
+        self.cmp.compares_eq(l, r)
+    }
+
+    fn compares_ne(&self, l: &L, r: &R) -> bool {
+        self.counters.compare();
+        self.cmp.compares_ne(l, r)
+    }
+}
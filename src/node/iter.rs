@@ -1,9 +1,10 @@
-#[cfg(feature = "range")] use compare::Compare;
-#[cfg(feature = "range")] use std::cmp::Ordering::*;
-#[cfg(feature = "range")] use std::collections::Bound;
-use std::collections::VecDeque;
-use std::marker::PhantomData;
-use super::Node;
+use alloc::collections::VecDeque;
+use allocator::Allocator;
+use compare::Compare;
+use core::cmp::Ordering::*;
+use core::marker::PhantomData;
+use core::ops::Bound;
+use super::{Node, NodeBox};
 
 pub trait NodeRef: Sized {
     type Key;
@@ -14,25 +15,25 @@ pub trait NodeRef: Sized {
     fn right(&mut self) -> Option<Self>;
 }
 
-pub struct MarkedNode<'a, K: 'a, V: 'a> {
-    node: &'a Node<K, V>,
+pub struct MarkedNode<'a, K: 'a, V: 'a, A: 'a + Allocator, G: 'a = ()> {
+    node: &'a Node<K, V, A, G>,
     seen_l: bool,
     seen_r: bool,
 }
 
-impl<'a, K, V> Clone for MarkedNode<'a, K, V> {
+impl<'a, K, V, A, G> Clone for MarkedNode<'a, K, V, A, G> where A: Allocator {
     fn clone(&self) -> Self { *self }
 }
 
-impl<'a, K, V> Copy for MarkedNode<'a, K, V> {}
+impl<'a, K, V, A, G> Copy for MarkedNode<'a, K, V, A, G> where A: Allocator {}
 
-impl<'a, K, V> MarkedNode<'a, K, V> {
-    pub fn new(node: &'a Box<Node<K, V>>) -> Self {
+impl<'a, K, V, A, G> MarkedNode<'a, K, V, A, G> where A: Allocator {
+    pub fn new(node: &'a NodeBox<K, V, A, G>) -> Self {
         MarkedNode { node: node, seen_l: false, seen_r: false }
     }
 }
 
-impl<'a, K, V> NodeRef for MarkedNode<'a, K, V> {
+impl<'a, K, V, A, G> NodeRef for MarkedNode<'a, K, V, A, G> where A: Allocator {
     type Key = K;
     type Item = (&'a K, &'a V);
     fn key(&self) -> &Self::Key { &self.node.key }
@@ -57,23 +58,25 @@ impl<'a, K, V> NodeRef for MarkedNode<'a, K, V> {
     }
 }
 
-pub struct MutMarkedNode<'a, K: 'a, V: 'a> {
-    node: *mut Node<K, V>,
+pub struct MutMarkedNode<'a, K: 'a, V: 'a, A: 'a + Allocator, G: 'a = ()> {
+    node: *mut Node<K, V, A, G>,
     seen_l: bool,
     seen_r: bool,
-    _marker: PhantomData<&'a mut Node<K, V>>,
+    _marker: PhantomData<&'a mut Node<K, V, A, G>>,
 }
 
-impl<'a, K, V> MutMarkedNode<'a, K, V> {
-    pub fn new(node: &'a mut Box<Node<K, V>>) -> Self {
+impl<'a, K, V, A, G> MutMarkedNode<'a, K, V, A, G> where A: Allocator {
+    pub fn new(node: &'a mut NodeBox<K, V, A, G>) -> Self {
         MutMarkedNode { node: &mut **node, seen_l: false, seen_r: false, _marker: PhantomData }
     }
 }
 
-unsafe impl<'a, K, V> Send for MutMarkedNode<'a, K, V> where K: Send, V: Send {}
-unsafe impl<'a, K, V> Sync for MutMarkedNode<'a, K, V> where K: Sync, V: Sync {}
+unsafe impl<'a, K, V, A, G> Send for MutMarkedNode<'a, K, V, A, G>
+    where K: Send, V: Send, A: Allocator + Send, G: Send {}
+unsafe impl<'a, K, V, A, G> Sync for MutMarkedNode<'a, K, V, A, G>
+    where K: Sync, V: Sync, A: Allocator + Sync, G: Sync {}
 
-impl<'a, K, V> NodeRef for MutMarkedNode<'a, K, V> {
+impl<'a, K, V, A, G> NodeRef for MutMarkedNode<'a, K, V, A, G> where A: Allocator {
     type Key = K;
     type Item = (&'a K, &'a mut V);
 
@@ -103,7 +106,7 @@ impl<'a, K, V> NodeRef for MutMarkedNode<'a, K, V> {
     }
 }
 
-impl<K, V> NodeRef for Box<Node<K, V>> {
+impl<K, V, A, G> NodeRef for NodeBox<K, V, A, G> where A: Allocator {
     type Key = K;
     type Item = (K, V);
     fn key(&self) -> &Self::Key { &self.key }
@@ -212,11 +215,9 @@ impl<N> ExactSizeIterator for Iter<N> where N: NodeRef {
     fn len(&self) -> usize { self.size }
 }
 
-#[cfg(feature = "range")]
 #[derive(Clone)]
 pub struct Range<N>(Iter<N>) where N: NodeRef;
 
-#[cfg(feature = "range")]
 impl<N> Range<N> where N: NodeRef {
     pub fn new<C, Min: ?Sized, Max: ?Sized>(root: Option<N>, size: usize, cmp: &C,
                                             min: Bound<&Min>, max: Bound<&Max>) -> Self
@@ -244,14 +245,12 @@ impl<N> Range<N> where N: NodeRef {
     }
 }
 
-#[cfg(feature = "range")]
 impl<N> Iterator for Range<N> where N: NodeRef {
     type Item = N::Item;
     fn next(&mut self) -> Option<N::Item> { self.0.next() }
     fn size_hint(&self) -> (usize, Option<usize>) { (self.0.nodes.len(), Some(self.0.size)) }
 }
 
-#[cfg(feature = "range")]
 impl<N> DoubleEndedIterator for Range<N> where N: NodeRef {
     fn next_back(&mut self) -> Option<N::Item> { self.0.next_back() }
 }
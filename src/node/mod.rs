@@ -1,39 +1,134 @@
+mod counters;
 mod iter;
 
 #[cfg(test)]
 mod test;
 
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use allocator::{Allocator, Global};
 use compare::Compare;
+use core::cmp::Ordering::{self, Equal, Greater, Less};
+use core::marker::PhantomData;
+use core::mem::{forget, replace, swap};
+use core::ops::Bound;
+use core::ptr;
 use self::build::{Build, PathBuilder};
-use std::cmp::Ordering::*;
-use std::mem::{self, replace, swap};
 use super::map::Entry;
 
-pub use self::iter::{Iter, MarkedNode, MutMarkedNode};
-#[cfg(feature = "range")] pub use self::iter::Range;
+pub use self::counters::Counters;
+pub use self::iter::{Iter, MarkedNode, MutMarkedNode, NodeRef};
+pub use self::iter::Range;
 
-pub type Link<K, V> = Option<Box<Node<K, V>>>;
+/// Data that each node of a map caches about the entries in its subtree.
+///
+/// The data of a node is recomputed, bottom-up, whenever its entry or its subtree changes, so the
+/// data of the whole map, or of any subtree, is available without visiting the subtree's entries.
+/// The default, `()`, caches nothing. See the [`merkle`](../merkle/index.html) module for an
+/// augmentation that caches a digest of each subtree.
+pub trait Augment<K, V>: Sized {
+    /// Returns the data of a node that holds the given entry and has no children.
+    fn new(key: &K, value: &V) -> Self;
+
+    /// Recomputes the data of a node that holds the given entry, given the data of its children.
+    fn update(&mut self, key: &K, value: &V, left: Option<&Self>, right: Option<&Self>);
+
+    /// Applies any work that the node has deferred to its children, given their values and data.
+    ///
+    /// A `Map` calls this on a node before an operation that modifies the map, such as `insert`,
+    /// `remove` or `entry`, searches or moves the node's children. Operations that take the map
+    /// immutably, such as `get`, `iter`, `range`, `pred` and `succ`, never call it, so they see
+    /// the children without the work deferred to them. The default does nothing.
+    fn push(&mut self, _left: Option<(&mut V, &mut Self)>, _right: Option<(&mut V, &mut Self)>) {}
+}
+
+impl<K, V> Augment<K, V> for () {
+    fn new(_key: &K, _value: &V) -> Self {}
+    fn update(&mut self, _key: &K, _value: &V, _left: Option<&()>, _right: Option<&()>) {}
+}
+
+#[cfg(feature = "allocator_api")]
+pub type NodeBox<K, V, A = Global, G = ()> = Box<Node<K, V, A, G>, A>;
+#[cfg(not(feature = "allocator_api"))]
+pub type NodeBox<K, V, A = Global, G = ()> = Box<Node<K, V, A, G>>;
+
+pub type Link<K, V, A = Global, G = ()> = Option<NodeBox<K, V, A, G>>;
 
 #[derive(Clone)]
-pub struct Node<K, V> {
-    left: Link<K, V>,
-    right: Link<K, V>,
+pub struct Node<K, V, A = Global, G = ()> where A: Allocator {
+    left: Link<K, V, A, G>,
+    right: Link<K, V, A, G>,
     level: usize,
     key: K,
     value: V,
+    aug: G,
+    _alloc: PhantomData<A>,
+}
+
+impl<K, V, A, G> Node<K, V, A, G> where A: Allocator + Clone, G: Augment<K, V> {
+    #[cfg(feature = "allocator_api")]
+    fn boxed(key: K, value: V, alloc: &A) -> NodeBox<K, V, A, G> {
+        Box::new_in(Node::new(key, value), alloc.clone())
+    }
+
+    #[cfg(not(feature = "allocator_api"))]
+    fn boxed(key: K, value: V, _alloc: &A) -> NodeBox<K, V, A, G> {
+        Box::new(Node::new(key, value))
+    }
 }
 
-impl<K, V> Node<K, V> {
+impl<K, V, A, G> Node<K, V, A, G> where A: Allocator {
+    pub fn key(&self) -> &K { &self.key }
+
+    pub fn value(&self) -> &V { &self.value }
+
+    pub fn value_mut(&mut self) -> &mut V { &mut self.value }
+
+    pub fn aug(&self) -> &G { &self.aug }
+
+    pub fn parts_mut(&mut self) -> (&mut V, &mut G) { (&mut self.value, &mut self.aug) }
+
+    pub fn left_child(&self) -> Option<&Self> { self.left.as_ref().map(|node| &**node) }
+
+    pub fn right_child(&self) -> Option<&Self> { self.right.as_ref().map(|node| &**node) }
+
+    pub fn left_child_mut(&mut self) -> Option<&mut Self> {
+        self.left.as_mut().map(|node| &mut **node)
+    }
+
+    pub fn right_child_mut(&mut self) -> Option<&mut Self> {
+        self.right.as_mut().map(|node| &mut **node)
+    }
+}
+
+impl<K, V, A, G> Node<K, V, A, G> where A: Allocator, G: Augment<K, V> {
     fn new(key: K, value: V) -> Self {
-        Node { left: None, right: None, level: 1, key: key, value: value }
+        let aug = G::new(&key, &value);
+        Node {
+            left: None, right: None, level: 1, key: key, value: value, aug: aug,
+            _alloc: PhantomData,
+        }
+    }
+
+    // Applies the node's deferred work to its children.
+    pub fn push(&mut self) {
+        self.aug.push(self.left.as_mut().map(|node| node.parts_mut()),
+                      self.right.as_mut().map(|node| node.parts_mut()));
+    }
+
+    // Recomputes the node's data from its entry and its children's data.
+    pub fn update(&mut self) {
+        self.aug.update(&self.key, &self.value, self.left.as_ref().map(|node| &node.aug),
+                        self.right.as_ref().map(|node| &node.aug));
     }
 
-    fn rebalance(node: &mut Box<Self>) {
+    fn rebalance(node: &mut NodeBox<K, V, A, G>, counters: &Counters) {
         let left_level = node.left.as_ref().map_or(0, |node| node.level);
         let right_level = node.right.as_ref().map_or(0, |node| node.level);
 
         // re-balance, if necessary
         if left_level < node.level - 1 || right_level < node.level - 1 {
+            counters.rebalance();
             node.level -= 1;
 
             if right_level > node.level {
@@ -41,27 +136,36 @@ impl<K, V> Node<K, V> {
                 if let Some(ref mut x) = node.right { x.level = node_level; }
             }
 
-            Node::skew(node);
+            Node::skew(node, counters);
 
             if let Some(ref mut right) = node.right {
-                Node::skew(right);
-                if let Some(ref mut x) = right.right { Node::skew(x); };
+                right.push();
+                Node::skew(right, counters);
+                if let Some(ref mut x) = right.right { Node::skew(x, counters); };
+                right.update();
             }
 
-            Node::split(node);
-            if let Some(ref mut x) = node.right { Node::split(x); }
+            Node::split(node, counters);
+            if let Some(ref mut x) = node.right { Node::split(x, counters); }
         }
+
+        node.update();
     }
 
     // Remove left horizontal link by rotating right
     //
     // From https://github.com/Gankro/collect-rs/tree/map.rs
-    fn skew(node: &mut Box<Self>) {
+    fn skew(node: &mut NodeBox<K, V, A, G>, counters: &Counters) {
         if node.left.as_ref().map_or(false, |x| x.level == node.level) {
+            counters.skew();
+            node.push();
             let mut save = node.left.take().unwrap();
+            save.push();
             swap(&mut node.left, &mut save.right); // save.right now None
             swap(node, &mut save);
+            save.update();
             node.right = Some(save);
+            node.update();
         }
     }
 
@@ -69,57 +173,187 @@ impl<K, V> Node<K, V> {
     // the parent
     //
     // From https://github.com/Gankro/collect-rs/tree/map.rs
-    fn split(node: &mut Box<Self>) {
+    fn split(node: &mut NodeBox<K, V, A, G>, counters: &Counters) {
         if node.right.as_ref().map_or(false,
           |x| x.right.as_ref().map_or(false, |y| y.level == node.level)) {
+            counters.split();
+            node.push();
             let mut save = node.right.take().unwrap();
+            save.push();
             swap(&mut node.right, &mut save.left); // save.left now None
             save.level += 1;
             swap(node, &mut save);
+            save.update();
             node.left = Some(save);
+            node.update();
         }
     }
 }
 
-pub fn insert<K, V, C>(link: &mut Link<K, V>, cmp: &C, key: K, value: V) -> Option<V>
-    where C: Compare<K> {
+pub fn insert<K, V, A, G, C>(link: &mut Link<K, V, A, G>, cmp: &C, key: K, value: V, alloc: &A,
+                             counters: &Counters) -> Option<V>
+    where A: Allocator + Clone, G: Augment<K, V>, C: Compare<K> {
+
+    insert_by(link, &mut |key, node| cmp.compare(key, &node.key), key, value, alloc, counters)
+}
+
+// Inserts an entry at the position given by `dir`, which is called with the entry's key and each
+// node on the path and returns the entry's order relative to the node's entry. If `dir` returns
+// `Equal`, the node's value is replaced and the old value is returned.
+pub fn insert_by<K, V, A, G, F>(link: &mut Link<K, V, A, G>, dir: &mut F, key: K, value: V,
+                                alloc: &A, counters: &Counters) -> Option<V>
+    where A: Allocator + Clone, G: Augment<K, V>, F: FnMut(&K, &Node<K, V, A, G>) -> Ordering {
 
     match *link {
         None => {
-            *link = Some(Box::new(Node::new(key, value)));
+            *link = Some(Node::boxed(key, value, alloc));
             None
         }
         Some(ref mut node) => {
-            let old_value = match cmp.compare(&key, &node.key) {
-                Equal => return Some(mem::replace(&mut node.value, value)),
-                Less => insert(&mut node.left, cmp, key, value),
-                Greater => insert(&mut node.right, cmp, key, value),
+            node.push();
+
+            let old_value = match dir(&key, node) {
+                Equal => {
+                    let old_value = replace(&mut node.value, value);
+                    node.aug = G::new(&node.key, &node.value);
+                    node.update();
+                    return Some(old_value);
+                }
+                Less => insert_by(&mut node.left, dir, key, value, alloc, counters),
+                Greater => insert_by(&mut node.right, dir, key, value, alloc, counters),
             };
 
-            Node::skew(node);
-            Node::split(node);
+            node.update();
+            Node::skew(node, counters);
+            Node::split(node, counters);
             old_value
         },
     }
 }
 
-pub mod build {
-    use std::marker::PhantomData;
-    use super::{Link, Node, Path};
+// Builds a tree from the next `len` entries of `it`, which must be in strictly ascending order.
+//
+// The tree is built bottom-up in linear time. Each right subtree has at most one more entry than
+// its sibling, so a node's level is one more than its left child's.
+pub fn from_sorted<K, V, A, G, I>(it: &mut I, len: usize, alloc: &A) -> Link<K, V, A, G>
+    where A: Allocator + Clone, G: Augment<K, V>, I: Iterator<Item=(K, V)> {
+
+    if len == 0 { return None; }
+
+    let left_len = (len - 1) / 2;
+    let left = from_sorted(it, left_len, alloc);
+    let (key, value) = it.next().expect("iterator yielded too few entries");
+    let right = from_sorted(it, len - 1 - left_len, alloc);
+
+    let mut node = Node::boxed(key, value, alloc);
+    node.level = left.as_ref().map_or(0, |node| node.level) + 1;
+    node.left = left;
+    node.right = right;
+    node.update();
+    Some(node)
+}
+
+fn level<K, V, A, G>(link: &Link<K, V, A, G>) -> usize where A: Allocator {
+    link.as_ref().map_or(0, |node| node.level)
+}
 
-    pub struct Closed<'a, K: 'a, V: 'a> {
-        link: *const Link<K, V>,
-        _marker: PhantomData<&'a Link<K, V>>,
+// Joins two trees and a node whose entry lies between theirs into one tree.
+//
+// This descends the taller tree's inner spine to the first node whose level equals the other
+// tree's, and so takes time proportional to the difference of the trees' levels.
+pub fn join<K, V, A, G>(left: Link<K, V, A, G>, mut mid: NodeBox<K, V, A, G>,
+                        right: Link<K, V, A, G>, counters: &Counters) -> NodeBox<K, V, A, G>
+    where A: Allocator, G: Augment<K, V> {
+
+    let (left_level, right_level) = (level(&left), level(&right));
+
+    if left_level > right_level {
+        let mut node = left.unwrap();
+        node.push();
+        let inner = node.right.take();
+        node.right = Some(join(inner, mid, right, counters));
+        node.update();
+        Node::skew(&mut node, counters);
+        Node::split(&mut node, counters);
+        node
+    } else if right_level > left_level {
+        let mut node = right.unwrap();
+        node.push();
+        let inner = node.left.take();
+        node.left = Some(join(left, mid, inner, counters));
+        node.update();
+        Node::skew(&mut node, counters);
+        Node::split(&mut node, counters);
+        node
+    } else {
+        mid.left = left;
+        mid.right = right;
+        mid.level = left_level + 1;
+        mid.update();
+        mid
+    }
+}
+
+// Splits a tree into the nodes that precede a position and the nodes that follow it. `dir` is
+// called on some of the nodes, and returns whether the node follows the position.
+pub fn split<K, V, A, G, F>(link: Link<K, V, A, G>, dir: &mut F, counters: &Counters)
+    -> (Link<K, V, A, G>, Link<K, V, A, G>)
+    where A: Allocator, G: Augment<K, V>, F: FnMut(&Node<K, V, A, G>) -> bool {
+
+    match link {
+        None => (None, None),
+        Some(mut node) => {
+            node.push();
+            let follows = dir(&node);
+            let (left, right) = (node.left.take(), node.right.take());
+
+            if follows {
+                let (left, mid) = split(left, dir, counters);
+                (left, Some(join(mid, node, right, counters)))
+            } else {
+                let (mid, right) = split(right, dir, counters);
+                (Some(join(left, node, mid, counters)), right)
+            }
+        }
+    }
+}
+
+// Concatenates two trees, where every entry of `left` precedes every entry of `right`.
+//
+// This removes the minimum entry of `right` and joins the trees around it, and so takes
+// logarithmic time.
+pub fn concat<K, V, A, G>(left: Link<K, V, A, G>, mut right: Link<K, V, A, G>, alloc: &A,
+                          counters: &Counters) -> Link<K, V, A, G>
+    where A: Allocator + Clone, G: Augment<K, V> {
+
+    match Min::extreme(&mut right, PathBuilder::default()).take(counters) {
+        None => left,
+        Some((key, value)) => Some(join(left, Node::boxed(key, value, alloc), right, counters)),
+    }
+}
+
+pub mod build {
+    use alloc::vec::Vec;
+    use allocator::Allocator;
+    use core::marker::PhantomData;
+    use super::{Augment, Link, NodeBox, Path};
+
+    pub struct Closed<'a, K: 'a, V: 'a, A: 'a + Allocator, G: 'a> {
+        link: *const Link<K, V, A, G>,
+        _marker: PhantomData<&'a Link<K, V, A, G>>,
     }
 
     pub trait Build<'a>: Sized + Default {
         type Key: 'a;
         type Value: 'a;
-        type Node: ::std::ops::Deref<Target = Box<Node<Self::Key, Self::Value>>>;
+        type Alloc: 'a + Allocator;
+        type Aug: 'a;
+        type Node: ::core::ops::Deref<
+            Target = NodeBox<Self::Key, Self::Value, Self::Alloc, Self::Aug>>;
         type Link;
         type Output;
 
-        fn closed(link: &Self::Link) -> Closed<'a, Self::Key, Self::Value>;
+        fn closed(link: &Self::Link) -> Closed<'a, Self::Key, Self::Value, Self::Alloc, Self::Aug>;
 
         fn into_option(link: Self::Link) -> Option<Self::Node>;
 
@@ -129,23 +363,27 @@ pub mod build {
 
         fn build_open(self, link: Self::Link) -> Self::Output;
 
-        fn build_closed(self, link: Closed<'a, Self::Key, Self::Value>) -> Self::Output;
+        fn build_closed(self, link: Closed<'a, Self::Key, Self::Value, Self::Alloc, Self::Aug>)
+            -> Self::Output;
     }
 
-    pub struct Get<'a, K: 'a, V: 'a>(PhantomData<fn(&'a Link<K, V>)>);
+    pub struct Get<'a, K: 'a, V: 'a, A: 'a + Allocator, G: 'a = ()>(
+        PhantomData<fn(&'a Link<K, V, A, G>)>);
 
-    impl<'a, K, V> Default for Get<'a, K, V> {
+    impl<'a, K, V, A, G> Default for Get<'a, K, V, A, G> where A: Allocator {
         fn default() -> Self { Get(PhantomData) }
     }
 
-    impl<'a, K: 'a, V: 'a> Build<'a> for Get<'a, K, V> {
+    impl<'a, K: 'a, V: 'a, A: 'a, G: 'a> Build<'a> for Get<'a, K, V, A, G> where A: Allocator {
         type Key = K;
         type Value = V;
-        type Node = &'a Box<Node<K, V>>;
-        type Link = &'a Link<K, V>;
+        type Alloc = A;
+        type Aug = G;
+        type Node = &'a NodeBox<K, V, A, G>;
+        type Link = &'a Link<K, V, A, G>;
         type Output = Option<(&'a K, &'a V)>;
 
-        fn closed(link: &Self::Link) -> Closed<'a, K, V> {
+        fn closed(link: &Self::Link) -> Closed<'a, K, V, A, G> {
             Closed { link: *link, _marker: PhantomData }
         }
 
@@ -159,30 +397,37 @@ pub mod build {
             link.as_ref().map(|node| (&node.key, &node.value))
         }
 
-        fn build_closed(self, link: Closed<'a, Self::Key, Self::Value>) -> Self::Output {
+        fn build_closed(self, link: Closed<'a, K, V, A, G>) -> Self::Output {
             self.build_open(unsafe { &*link.link })
         }
     }
 
-    pub struct GetMut<'a, K: 'a, V: 'a>(PhantomData<&'a mut Link<K, V>>);
+    // Builds a mutable reference to a value, pushing deferred work down the path so that the
+    // value is up to date.
+    pub struct GetMut<'a, K: 'a, V: 'a, A: 'a + Allocator, G: 'a = ()>(
+        PhantomData<&'a mut Link<K, V, A, G>>);
 
-    impl<'a, K, V> Default for GetMut<'a, K, V> {
+    impl<'a, K, V, A, G> Default for GetMut<'a, K, V, A, G> where A: Allocator {
         fn default() -> Self { GetMut(PhantomData) }
     }
 
-    impl<'a, K: 'a, V: 'a> Build<'a> for GetMut<'a, K, V> {
+    impl<'a, K: 'a, V: 'a, A: 'a, G: 'a> Build<'a> for GetMut<'a, K, V, A, G>
+        where A: Allocator, G: Augment<K, V> {
+
         type Key = K;
         type Value = V;
-        type Node = &'a mut Box<Node<K, V>>;
-        type Link = &'a mut Link<K, V>;
+        type Alloc = A;
+        type Aug = G;
+        type Node = &'a mut NodeBox<K, V, A, G>;
+        type Link = &'a mut Link<K, V, A, G>;
         type Output = Option<(&'a K, &'a mut V)>;
 
-        fn closed(link: &Self::Link) -> Closed<'a, K, V> {
+        fn closed(link: &Self::Link) -> Closed<'a, K, V, A, G> {
             Closed { link: *link, _marker: PhantomData }
         }
 
         fn into_option(link: Self::Link) -> Option<Self::Node> {
-            link.as_mut()
+            link.as_mut().map(|node| { node.push(); node })
         }
 
         fn left(&mut self, node: Self::Node) -> Self::Link { &mut node.left }
@@ -193,33 +438,39 @@ pub mod build {
             link.as_mut().map(|node| { let node = &mut **node; (&node.key, &mut node.value) })
         }
 
-        fn build_closed(self, link: Closed<'a, Self::Key, Self::Value>) -> Self::Output {
+        fn build_closed(self, link: Closed<'a, K, V, A, G>) -> Self::Output {
             self.build_open(unsafe { &mut *(link.link as *mut _) })
         }
     }
 
-    pub struct PathBuilder<'a, K: 'a, V: 'a> {
-        path: Vec<*mut Box<Node<K, V>>>,
-        _marker: PhantomData<&'a mut Box<Node<K, V>>>,
+    // Builds the path to a link, pushing deferred work down the path so that the nodes on it can
+    // be changed.
+    pub struct PathBuilder<'a, K: 'a, V: 'a, A: 'a + Allocator, G: 'a = ()> {
+        path: Vec<*mut NodeBox<K, V, A, G>>,
+        _marker: PhantomData<&'a mut NodeBox<K, V, A, G>>,
     }
 
-    impl<'a, K, V> Default for PathBuilder<'a, K, V> {
-        fn default() -> Self { PathBuilder { path: vec![], _marker: PhantomData } }
+    impl<'a, K, V, A, G> Default for PathBuilder<'a, K, V, A, G> where A: Allocator {
+        fn default() -> Self { PathBuilder { path: Vec::new(), _marker: PhantomData } }
     }
 
-    impl<'a, K: 'a, V: 'a> Build<'a> for PathBuilder<'a, K, V> {
+    impl<'a, K: 'a, V: 'a, A: 'a, G: 'a> Build<'a> for PathBuilder<'a, K, V, A, G>
+        where A: Allocator, G: Augment<K, V> {
+
         type Key = K;
         type Value = V;
-        type Node = &'a mut Box<Node<K, V>>;
-        type Link = &'a mut Link<K, V>;
-        type Output = Path<'a, K, V>;
+        type Alloc = A;
+        type Aug = G;
+        type Node = &'a mut NodeBox<K, V, A, G>;
+        type Link = &'a mut Link<K, V, A, G>;
+        type Output = Path<'a, K, V, A, G>;
 
-        fn closed(link: &Self::Link) -> Closed<'a, K, V> {
+        fn closed(link: &Self::Link) -> Closed<'a, K, V, A, G> {
             Closed { link: *link, _marker: PhantomData }
         }
 
         fn into_option(link: Self::Link) -> Option<Self::Node> {
-            link.as_mut()
+            link.as_mut().map(|node| { node.push(); node })
         }
 
         fn left(&mut self, node: Self::Node) -> Self::Link {
@@ -236,7 +487,7 @@ pub mod build {
             Path { path: self.path, link: link }
         }
 
-        fn build_closed(self, link: Closed<'a, K, V>) -> Self::Output {
+        fn build_closed(self, link: Closed<'a, K, V, A, G>) -> Self::Output {
             Path {
                 path: self.path.into_iter().take_while(|l| *l as *const _ != link.link).collect(),
                 link: unsafe { &mut *(link.link as *mut _) },
@@ -245,15 +496,23 @@ pub mod build {
     }
 }
 
-pub fn find<'a, B, C: ?Sized, Q: ?Sized>(mut link: B::Link, mut build: B, cmp: &C, key: &Q)
+pub fn find<'a, B, C: ?Sized, Q: ?Sized>(link: B::Link, build: B, cmp: &C, key: &Q)
     -> B::Output where B: Build<'a>, C: Compare<Q, B::Key> {
 
+    seek(link, build, |node| cmp.compare(key, &node.key))
+}
+
+// Follows the path given by `dir`, which is called on each node on the path and returns the
+// order of the sought position relative to the node's entry.
+pub fn seek<'a, B, F>(mut link: B::Link, mut build: B, mut dir: F) -> B::Output
+    where B: Build<'a>, F: FnMut(&Node<B::Key, B::Value, B::Alloc, B::Aug>) -> Ordering {
+
     loop {
         let closed = B::closed(&link);
 
         link = match B::into_option(link) {
             None => return build.build_closed(closed),
-            Some(node) => match cmp.compare(key, &node.key) {
+            Some(node) => match dir(&node) {
                 Less => build.left(node),
                 Equal => return build.build_closed(closed),
                 Greater => build.right(node),
@@ -262,11 +521,97 @@ pub fn find<'a, B, C: ?Sized, Q: ?Sized>(mut link: B::Link, mut build: B, cmp: &
     }
 }
 
+// Checks if the key lies before the lower bound.
+pub fn below<K, C: ?Sized, Q: ?Sized>(cmp: &C, key: &K, min: Bound<&Q>) -> bool
+    where C: Compare<Q, K> {
+
+    match min {
+        Bound::Included(min) => cmp.compares_gt(min, key),
+        Bound::Excluded(min) => cmp.compares_ge(min, key),
+        Bound::Unbounded => false,
+    }
+}
+
+// Checks if the key lies after the upper bound.
+pub fn above<K, C: ?Sized, Q: ?Sized>(cmp: &C, key: &K, max: Bound<&Q>) -> bool
+    where C: Compare<Q, K> {
+
+    match max {
+        Bound::Included(max) => cmp.compares_lt(max, key),
+        Bound::Excluded(max) => cmp.compares_le(max, key),
+        Bound::Unbounded => false,
+    }
+}
+
+// A violation of the comparator's order, found by `check_order` or `check_path`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Inconsistency {
+    Asymmetric,
+    OutOfOrder,
+}
+
+// Checks that the comparator orders `l` strictly before `r`, consistently in both directions.
+pub fn check_order<K: ?Sized, C: ?Sized>(cmp: &C, l: &K, r: &K) -> Result<(), Inconsistency>
+    where C: Compare<K> {
+
+    let order = cmp.compare(l, r);
+
+    if cmp.compare(r, l) != order.reverse() {
+        Err(Inconsistency::Asymmetric)
+    } else if order != Less {
+        Err(Inconsistency::OutOfOrder)
+    } else {
+        Ok(())
+    }
+}
+
+// Follows the path that `find` would take for the given key, checking that each node's key lies
+// strictly between the nearest keys above it on the path, and that comparing the given key with
+// each node's key is antisymmetric.
+pub fn check_path<K, V, A, G, C: ?Sized>(mut link: &Link<K, V, A, G>, cmp: &C, key: &K)
+    -> Result<(), Inconsistency> where A: Allocator, C: Compare<K> {
+
+    let (mut lower, mut upper) = (None, None);
+
+    while let Some(ref node) = *link {
+        for &(l, r) in &[(lower, Some(&node.key)), (Some(&node.key), upper)] {
+            if let (Some(l), Some(r)) = (l, r) { check_order(cmp, l, r)?; }
+        }
+
+        let order = cmp.compare(key, &node.key);
+        if cmp.compare(&node.key, key) != order.reverse() {
+            return Err(Inconsistency::Asymmetric);
+        }
+
+        link = match order {
+            Less => { upper = Some(&node.key); &node.left }
+            Equal => break,
+            Greater => { lower = Some(&node.key); &node.right }
+        };
+    }
+
+    Ok(())
+}
+
+// Counts the nodes at each depth of the tree, adding them to `levels` from index `depth`.
+#[cfg(feature = "stats")]
+pub fn count_levels<K, V, A, G>(link: &Link<K, V, A, G>, levels: &mut Vec<usize>,
+                                depth: usize)
+    where A: Allocator {
+
+    if let Some(ref node) = *link {
+        if levels.len() == depth { levels.push(0); }
+        levels[depth] += 1;
+        count_levels(&node.left, levels, depth + 1);
+        count_levels(&node.right, levels, depth + 1);
+    }
+}
+
 pub trait Extreme: Sized {
     type Opposite: Extreme<Opposite = Self>;
 
     fn min() -> bool;
-    fn has_forward<K, V>(node: &Node<K, V>) -> bool;
+    fn has_forward<K, V, A, G>(node: &Node<K, V, A, G>) -> bool where A: Allocator;
     fn forward<'a, B>(node: B::Node, build: &mut B) -> B::Link where B: Build<'a>;
 
     fn extreme<'a, B>(mut link: B::Link, mut build: B) -> B::Output where B: Build<'a> {
@@ -331,7 +676,9 @@ pub enum Max {}
 impl Extreme for Max {
     type Opposite = Min;
     fn min() -> bool { false }
-    fn has_forward<K, V>(node: &Node<K, V>) -> bool { node.right.is_some() }
+    fn has_forward<K, V, A, G>(node: &Node<K, V, A, G>) -> bool where A: Allocator {
+        node.right.is_some()
+    }
     fn forward<'a, B>(node: B::Node, build: &mut B) -> B::Link where B: Build<'a> {
         build.right(node)
     }
@@ -343,78 +690,110 @@ pub enum Min {}
 impl Extreme for Min {
     type Opposite = Max;
     fn min() -> bool { true }
-    fn has_forward<K, V>(node: &Node<K, V>) -> bool { node.left.is_some() }
+    fn has_forward<K, V, A, G>(node: &Node<K, V, A, G>) -> bool where A: Allocator {
+        node.left.is_some()
+    }
     fn forward<'a, B>(node: B::Node, build: &mut B) -> B::Link where B: Build<'a> {
         build.left(node)
     }
 }
 
-pub struct Path<'a, K: 'a, V: 'a> {
-    path: Vec<*mut Box<Node<K, V>>>,
-    link: &'a mut Link<K, V>,
+pub struct Path<'a, K: 'a, V: 'a, A: 'a + Allocator, G: 'a = ()> {
+    path: Vec<*mut NodeBox<K, V, A, G>>,
+    link: &'a mut Link<K, V, A, G>,
 }
 
-impl<'a, K, V> Path<'a, K, V> {
-    pub fn into_entry(self, len: &'a mut usize, key: K) -> Entry<'a, K, V> {
+impl<'a, K, V, A> Path<'a, K, V, A> where A: Allocator {
+    pub fn into_entry(self, len: &'a mut usize, alloc: &'a A, counters: &'a Counters, key: K)
+        -> Entry<'a, K, V, A> {
+
         if self.link.is_some() {
-            Entry::Occupied(OccupiedEntry { path: self, len: len })
+            Entry::Occupied(OccupiedEntry { path: self, len: len, counters: counters })
         } else {
-            Entry::Vacant(VacantEntry { path: self, len: len, key: key })
+            Entry::Vacant(VacantEntry {
+                path: self, len: len, alloc: alloc, counters: counters, key: key
+            })
         }
     }
 
-    pub fn into_occupied_entry(self, len: &'a mut usize) -> Option<OccupiedEntry<'a, K, V>> {
+    pub fn into_occupied_entry(self, len: &'a mut usize, counters: &'a Counters)
+        -> Option<OccupiedEntry<'a, K, V, A>> {
+
         if self.link.is_some() {
-            Some(OccupiedEntry { path: self, len: len })
+            Some(OccupiedEntry { path: self, len: len, counters: counters })
         } else {
             None
         }
     }
 
-    fn remove_(self) -> Option<(K, V)> {
-        let key_value = match *self.link {
+}
+
+impl<'a, K, V, A, G> Path<'a, K, V, A, G> where A: Allocator, G: Augment<K, V> {
+    // Removes the entry at the end of the path along with its node's data.
+    //
+    // A node whose entry is replaced by its predecessor or successor takes that entry's data too,
+    // since the data may hold work deferred to the entry rather than to the subtree.
+    fn remove_(self, counters: &Counters) -> Option<(K, V, G)> {
+        let removed = match *self.link {
             None => return None,
             Some(ref mut node) => {
+                node.push();
+
                 let replacement = if node.left.is_some() {
-                    Max::extreme(&mut node.left, PathBuilder::default()).remove_()
+                    Max::extreme(&mut node.left, PathBuilder::default()).remove_(counters)
                 } else if node.right.is_some() {
-                    Min::extreme(&mut node.right, PathBuilder::default()).remove_()
+                    Min::extreme(&mut node.right, PathBuilder::default()).remove_(counters)
                 } else {
                     None
                 };
 
                 replacement.map(|replacement| {
-                    let key_value = (replace(&mut node.key, replacement.0),
-                                     replace(&mut node.value, replacement.1));
-                    Node::rebalance(node);
-                    key_value
+                    let removed = (replace(&mut node.key, replacement.0),
+                                   replace(&mut node.value, replacement.1),
+                                   replace(&mut node.aug, replacement.2));
+                    Node::rebalance(node, counters);
+                    removed
                 })
             }
-        }.or_else(|| self.link.take().map(|node| { let node = *node; (node.key, node.value) }));
+        }.or_else(|| self.link.take().map(|node| {
+            let node = *node;
+            (node.key, node.value, node.aug)
+        }));
 
-        for node in self.path.into_iter().rev() { Node::rebalance(unsafe { &mut *node }); }
-        key_value
+        for node in self.path.into_iter().rev() {
+            Node::rebalance(unsafe { &mut *node }, counters);
+        }
+
+        removed
     }
 
-    pub fn remove(self, len: &mut usize) -> Option<(K, V)> {
-        let key_value = self.remove_();
+    // Removes the entry at the end of the path, if any.
+    pub fn take(self, counters: &Counters) -> Option<(K, V)> {
+        self.remove_(counters).map(|(key, value, _)| (key, value))
+    }
+
+    pub fn remove(self, len: &mut usize, counters: &Counters) -> Option<(K, V)> {
+        let key_value = self.take(counters);
         if key_value.is_some() { *len -= 1; }
         key_value
     }
 }
 
-unsafe impl<'a, K, V> Send for Path<'a, K, V> where K: Send, V: Send {}
-unsafe impl<'a, K, V> Sync for Path<'a, K, V> where K: Sync, V: Sync {}
+unsafe impl<'a, K, V, A, G> Send for Path<'a, K, V, A, G>
+    where K: Send, V: Send, A: Allocator + Send, G: Send {}
+unsafe impl<'a, K, V, A, G> Sync for Path<'a, K, V, A, G>
+    where K: Sync, V: Sync, A: Allocator + Sync, G: Sync {}
 
 /// An occupied entry.
 ///
 /// See [`Map::entry`](struct.Map.html#method.entry) for an example.
-pub struct OccupiedEntry<'a, K: 'a, V: 'a> {
-    path: Path<'a, K, V>,
+pub struct OccupiedEntry<'a, K: 'a, V: 'a, A: 'a + Allocator = Global> {
+    path: Path<'a, K, V, A>,
     len: &'a mut usize,
+    counters: &'a Counters,
 }
 
-impl<'a, K, V> OccupiedEntry<'a, K, V> {
+impl<'a, K, V, A> OccupiedEntry<'a, K, V, A> where A: Allocator {
     /// Returns a reference to the entry's key.
     pub fn key(&self) -> &K { &self.path.link.as_ref().unwrap().key }
 
@@ -432,35 +811,65 @@ impl<'a, K, V> OccupiedEntry<'a, K, V> {
 
     /// Removes the entry from the map and returns its key and value.
     pub fn remove(self) -> (K, V) {
-        self.path.remove(self.len).unwrap()
+        self.path.remove(self.len, self.counters).unwrap()
+    }
+
+    // Replaces the entry's value with the result of calling `f` with the value, or removes the
+    // entry if `f` returns `None`.
+    pub(crate) fn update<F>(self, f: F) where F: FnOnce(V) -> Option<V> {
+        // Removes the entry without dropping its value, which has been moved out of it, if `f`
+        // panics.
+        struct Hole<'a, K: 'a, V: 'a, A: 'a + Allocator>(Option<OccupiedEntry<'a, K, V, A>>);
+
+        impl<'a, K, V, A> Drop for Hole<'a, K, V, A> where A: Allocator {
+            fn drop(&mut self) {
+                if let Some(entry) = self.0.take() { forget(entry.remove().1); }
+            }
+        }
+
+        let mut hole = Hole(Some(self));
+        let value = unsafe { ptr::read(hole.0.as_ref().unwrap().get()) };
+
+        if let Some(value) = f(value) {
+            let entry = hole.0.take().unwrap();
+            unsafe { ptr::write(entry.into_mut(), value); }
+        }
     }
 }
 
 /// A vacant entry.
 ///
 /// See [`Map::entry`](struct.Map.html#method.entry) for an example.
-pub struct VacantEntry<'a, K: 'a, V: 'a> {
-    path: Path<'a, K, V>,
+pub struct VacantEntry<'a, K: 'a, V: 'a, A: 'a + Allocator = Global> {
+    path: Path<'a, K, V, A>,
     len: &'a mut usize,
+    alloc: &'a A,
+    counters: &'a Counters,
     key: K,
 }
 
-impl<'a, K, V> VacantEntry<'a, K, V> {
+impl<'a, K, V, A> VacantEntry<'a, K, V, A> where A: Allocator + Clone {
+    /// Returns a reference to the entry's key.
+    pub fn key(&self) -> &K { &self.key }
+
     /// Inserts the entry into the map with its key and the given value, returning a mutable
     /// reference to the value with the same lifetime as the map.
-    pub fn insert(self, value: V) -> &'a mut V {
+    pub fn insert(self, value: V) -> &'a mut V { self.insert_entry(value).1 }
+
+    // Inserts the entry as `insert` does, returning references to its key and value.
+    pub(crate) fn insert_entry(self, value: V) -> (&'a K, &'a mut V) {
         *self.len += 1;
 
-        *self.path.link = Some(Box::new(Node::new(self.key, value)));
-        let value = &mut self.path.link.as_mut().unwrap().value;
+        *self.path.link = Some(Node::boxed(self.key, value, self.alloc));
+        let node = &mut **self.path.link.as_mut().unwrap();
 
         for node in self.path.path.into_iter().rev() {
             unsafe {
-                Node::skew(&mut *node);
-                Node::split(&mut *node);
+                Node::skew(&mut *node, self.counters);
+                Node::split(&mut *node, self.counters);
             }
         }
 
-        value
+        (&node.key, &mut node.value)
     }
 }
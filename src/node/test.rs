@@ -1,7 +1,12 @@
 extern crate quickcheck;
 
+use allocator::Global;
+use compare::Natural;
 use self::quickcheck::{Arbitrary, Gen, Rng, TestResult, quickcheck};
-use super::{Link, Node};
+use std::boxed::Box;
+use std::string::String;
+use std::vec::Vec;
+use super::{Augment, Link, Node};
 use Map;
 
 /// An operation on a `Map`.
@@ -71,8 +76,10 @@ impl<K> Op<K> where K: Clone + Ord {
 }
 
 // Adapted from https://github.com/Gankro/collect-rs/tree/map.rs
-fn assert_andersson_tree<K, V>(map: &Map<K, V>) where K: Ord {
-    fn check_left<K, V>(link: &Link<K, V>, parent: &Node<K, V>) where K: Ord {
+fn assert_andersson_tree<K, V, G>(root: &Link<K, V, Global, G>) where K: Ord {
+    fn check_left<K, V, G>(link: &Link<K, V, Global, G>, parent: &Node<K, V, Global, G>)
+        where K: Ord {
+
         match *link {
             None => assert_eq!(parent.level, 1),
             Some(ref node) => {
@@ -84,7 +91,8 @@ fn assert_andersson_tree<K, V>(map: &Map<K, V>) where K: Ord {
         }
     }
 
-    fn check_right<K, V>(link: &Link<K, V>, parent: &Node<K, V>, parent_red: bool) where K: Ord {
+    fn check_right<K, V, G>(link: &Link<K, V, Global, G>, parent: &Node<K, V, Global, G>,
+                            parent_red: bool) where K: Ord {
         match *link {
             None => assert_eq!(parent.level, 1),
             Some(ref node) => {
@@ -98,7 +106,7 @@ fn assert_andersson_tree<K, V>(map: &Map<K, V>) where K: Ord {
         }
     }
 
-    if let Some(ref node) = *map.root() {
+    if let Some(ref node) = *root {
         check_left(&node.left, node);
         check_right(&node.right, node, false);
     }
@@ -110,9 +118,173 @@ fn test_andersson() {
     fn check(ops: Vec<Op<u32>>) -> TestResult {
         let mut map = Map::new();
         for op in ops { op.exec(&mut map); }
-        assert_andersson_tree(&map);
+        assert_andersson_tree(map.root());
         TestResult::passed()
     }
 
     quickcheck(check as fn(Vec<Op<u32>>) -> TestResult);
 }
+
+#[test]
+fn test_from_sorted() {
+    fn check(len: usize) -> TestResult {
+        let map: Map<_, _> =
+            Map::from_sorted((0..len).map(|i| (i, ())), Default::default(), Global);
+        assert_andersson_tree(map.root());
+        TestResult::from_bool(map.iter().map(|e| *e.0).eq(0..len))
+    }
+
+    quickcheck(check as fn(usize) -> TestResult);
+}
+
+#[cfg(feature = "quickcheck")]
+#[test]
+fn test_retain() {
+    fn check(mut map: Map<u32, u32>, modulus: u32) -> TestResult {
+        let modulus = modulus % 8 + 1;
+        let expected: Vec<_> = map.iter().filter(|e| e.0 % modulus == 0)
+                                  .map(|(k, v)| (*k, v.wrapping_add(1))).collect();
+
+        map.retain(|k, v| { *v = v.wrapping_add(1); k % modulus == 0 });
+        assert_andersson_tree(map.root());
+        TestResult::from_bool(map.len() == expected.len() &&
+                              map.iter().map(|(k, v)| (*k, *v)).eq(expected))
+    }
+
+    quickcheck(check as fn(Map<u32, u32>, u32) -> TestResult);
+}
+
+#[cfg(feature = "quickcheck")]
+#[test]
+fn test_borrowed() {
+    fn check(mut map: Map<String, u32>, key: String) -> TestResult {
+        let expected = map.get(&key).cloned();
+
+        if map.get_borrowed(&key[..]) != expected.as_ref() ||
+           map.contains_key_borrowed(&key[..]) != expected.is_some() {
+            return TestResult::failed();
+        }
+
+        let len = map.len();
+        let removed = map.remove_borrowed(&key[..]);
+        assert_andersson_tree(map.root());
+        TestResult::from_bool(removed.map(|e| e.1) == expected &&
+                              map.len() + expected.iter().count() == len &&
+                              map.get_borrowed(&key[..]).is_none())
+    }
+
+    quickcheck(check as fn(Map<String, u32>, String) -> TestResult);
+}
+
+#[cfg(feature = "quickcheck")]
+#[test]
+fn test_resort_with() {
+    fn check(map: Map<u32, u32>, modulus: u32) -> TestResult {
+        let modulus = modulus % 16 + 1;
+
+        // Keep the first entry of each residue class, in the map's current order.
+        let mut expected: Vec<_> = map.iter().map(|(k, v)| (*k, *v)).collect();
+        expected.sort_by_key(|e| e.0 % modulus);
+        expected.dedup_by_key(|e| e.0 % modulus);
+
+        let map = map.resort_with(move |l: &u32, r: &u32| (l % modulus).cmp(&(r % modulus)));
+        let ok = map.len() == expected.len() && map.iter().map(|(k, v)| (*k, *v)).eq(expected);
+
+        let map = map.resort_with(Natural::default());
+        assert_andersson_tree(map.root());
+        TestResult::from_bool(ok && map.iter().zip(map.iter().skip(1)).all(|(l, r)| l.0 < r.0))
+    }
+
+    quickcheck(check as fn(Map<u32, u32>, u32) -> TestResult);
+}
+
+// Counts the entries in each subtree.
+#[derive(Clone)]
+struct Size(usize);
+
+impl Augment<u32, u32> for Size {
+    fn new(_key: &u32, _value: &u32) -> Self { Size(1) }
+
+    fn update(&mut self, _key: &u32, _value: &u32, left: Option<&Self>, right: Option<&Self>) {
+        self.0 = 1 + left.map_or(0, |size| size.0) + right.map_or(0, |size| size.0);
+    }
+}
+
+// Checks the subtree's sizes and returns its size.
+fn check_sizes(link: &Link<u32, u32, Global, Size>) -> usize {
+    match *link {
+        None => 0,
+        Some(ref node) => {
+            let len = 1 + check_sizes(&node.left) + check_sizes(&node.right);
+            assert_eq!(node.aug.0, len);
+            len
+        }
+    }
+}
+
+#[test]
+#[allow(trivial_casts)]
+fn test_augmented_andersson() {
+    fn check(ops: Vec<(u8, u32, u32)>) -> TestResult {
+        let mut augmented: Map<u32, u32, Natural<u32>, Global, Size> = Map::default();
+        let mut map = Map::new();
+
+        for (op, key, value) in ops {
+            let key = key % 64;
+
+            match op % 4 {
+                0 | 1 => assert_eq!(augmented.insert(key, value), map.insert(key, value)),
+                2 => assert_eq!(augmented.remove(&key), map.remove(&key)),
+                _ => assert_eq!(augmented.remove_succ(&key, false), map.remove_succ(&key, false)),
+            }
+
+            assert_andersson_tree(augmented.root());
+            assert_eq!(check_sizes(augmented.root()), map.len());
+        }
+
+        TestResult::from_bool(augmented.iter().eq(map.iter()))
+    }
+
+    quickcheck(check as fn(Vec<(u8, u32, u32)>) -> TestResult);
+}
+
+// Returns the keys of the subtree in order.
+fn keys(link: &Link<u32, u32, Global, Size>) -> Vec<u32> {
+    match *link {
+        None => Vec::new(),
+        Some(ref node) => {
+            let mut left = keys(&node.left);
+            left.push(node.key);
+            left.extend(keys(&node.right));
+            left
+        }
+    }
+}
+
+#[test]
+#[allow(trivial_casts)]
+fn test_split_concat() {
+    fn check(keys: Vec<u32>, at: u32) -> TestResult {
+        let mut link = None;
+        for &key in &keys {
+            super::insert(&mut link, &Natural::default(), key, key, &Global, &Default::default());
+        }
+
+        let all = self::keys(&link);
+        let (left, right) = super::split(link, &mut |node| node.key >= at, &Default::default());
+
+        assert_andersson_tree(&left);
+        assert_andersson_tree(&right);
+        assert_eq!(check_sizes(&left) + check_sizes(&right), all.len());
+        assert!(self::keys(&left).iter().all(|&key| key < at));
+        assert!(self::keys(&right).iter().all(|&key| key >= at));
+
+        let link = super::concat(left, right, &Global, &Default::default());
+
+        assert_andersson_tree(&link);
+        assert_eq!(check_sizes(&link), all.len());
+        TestResult::from_bool(self::keys(&link) == all)
+    }
+
+    quickcheck(check as fn(Vec<u32>, u32) -> TestResult);
+}
@@ -0,0 +1,295 @@
+//! An ordered map that reports its changes to a listener.
+//!
+//! An `ObservedMap` wraps a `Map` and calls its `Listener` once for every change to the map's
+//! contents, including those made through its entries. It dereferences to the wrapped `Map` for
+//! lookups and iteration, but offers no mutable access to values that could bypass the listener.
+//! A plain `Map` is unaffected and pays nothing for this.
+//!
+//! # Examples
+//!
+//! ```
+//! use tree::observed::{Change, ObservedMap};
+//!
+//! let mut log = Vec::new();
+//!
+//! {
+//!     let mut map = ObservedMap::new(|change: Change<_, _>| log.push(format!("{:?}", change)));
+//!
+//!     map.insert(1, "a");
+//!     map.insert(1, "b");
+//!     map.remove_min();
+//!     assert!(map.is_empty());
+//! }
+//!
+//! assert_eq!(log, ["Inserted(1, \"a\")", "Updated(1, \"a\", \"b\")", "Removed(1, \"b\")"]);
+//! ```
+
+use compare::{Compare, Natural};
+use core::fmt::{self, Debug};
+use core::ops::Deref;
+use map::{self, Map};
+
+/// A change to an `ObservedMap`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Change<'a, K: 'a, V: 'a> {
+    /// An entry was inserted with a key that was not in the map.
+    Inserted(&'a K, &'a V),
+    /// The value associated with a key was replaced, with the old and new values.
+    Updated(&'a K, &'a V, &'a V),
+    /// An entry was removed.
+    Removed(&'a K, &'a V),
+}
+
+/// A listener for changes to an `ObservedMap`.
+///
+/// This is implemented for closures accepting a `Change`.
+pub trait Listener<K, V> {
+    /// Called after the map is changed.
+    fn changed(&mut self, change: Change<K, V>);
+}
+
+impl<K, V, F> Listener<K, V> for F where F: for<'a> FnMut(Change<'a, K, V>) {
+    fn changed(&mut self, change: Change<K, V>) { self(change) }
+}
+
+/// An ordered map that reports its changes to a listener.
+///
+/// See the [module documentation](index.html) for details.
+pub struct ObservedMap<K, V, L, C = Natural<K>> where C: Compare<K>, L: Listener<K, V> {
+    map: Map<K, V, C>,
+    listener: L,
+}
+
+impl<K, V, L> ObservedMap<K, V, L> where K: Ord, L: Listener<K, V> {
+    /// Creates an empty map ordered according to the natural order of its keys and reporting its
+    /// changes to the given listener.
+    pub fn new(listener: L) -> Self { ObservedMap::with_cmp(Natural::default(), listener) }
+}
+
+impl<K, V, L, C> ObservedMap<K, V, L, C> where C: Compare<K>, L: Listener<K, V> {
+    /// Creates an empty map ordered according to the given comparator and reporting its changes
+    /// to the given listener.
+    pub fn with_cmp(cmp: C, listener: L) -> Self {
+        ObservedMap::from_map(Map::with_cmp(cmp), listener)
+    }
+
+    /// Wraps an existing map, reporting subsequent changes to the given listener.
+    pub fn from_map(map: Map<K, V, C>, listener: L) -> Self {
+        ObservedMap { map: map, listener: listener }
+    }
+
+    /// Unwraps the map and its listener.
+    pub fn into_inner(self) -> (Map<K, V, C>, L) { (self.map, self.listener) }
+
+    /// Returns a reference to the map's listener.
+    pub fn listener(&self) -> &L { &self.listener }
+
+    /// Returns a mutable reference to the map's listener.
+    pub fn listener_mut(&mut self) -> &mut L { &mut self.listener }
+
+    /// Removes all entries from the map, reporting `Change::Removed` for each of them in
+    /// ascending order.
+    pub fn clear(&mut self) {
+        let listener = &mut self.listener;
+
+        self.map.retain(|key, value| {
+            listener.changed(Change::Removed(key, value));
+            false
+        });
+    }
+
+    /// Inserts an entry into the map, returning the previous value, if any, associated with the
+    /// key.
+    ///
+    /// Reports `Change::Updated` if the map contained the key and `Change::Inserted` otherwise.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        match self.entry(key) {
+            Entry::Occupied(mut e) => Some(e.insert(value)),
+            Entry::Vacant(e) => { e.insert(value); None }
+        }
+    }
+
+    /// Removes and returns the entry whose key is equal to the given key, returning `None` if
+    /// the map does not contain the key.
+    pub fn remove<Q: ?Sized>(&mut self, key: &Q) -> Option<(K, V)> where C: Compare<Q, K> {
+        let removed = self.map.remove(key);
+        self.removed(removed)
+    }
+
+    /// Removes and returns the entry whose key is the maximum in the map, returning `None` if the
+    /// map is empty.
+    pub fn remove_max(&mut self) -> Option<(K, V)> {
+        let removed = self.map.remove_max();
+        self.removed(removed)
+    }
+
+    /// Removes and returns the entry whose key is the minimum in the map, returning `None` if the
+    /// map is empty.
+    pub fn remove_min(&mut self) -> Option<(K, V)> {
+        let removed = self.map.remove_min();
+        self.removed(removed)
+    }
+
+    /// Removes and returns the entry whose key is the predecessor of the given key, as
+    /// `Map::remove_pred` does.
+    pub fn remove_pred<Q: ?Sized>(&mut self, key: &Q, inclusive: bool) -> Option<(K, V)>
+        where C: Compare<Q, K> {
+
+        let removed = self.map.remove_pred(key, inclusive);
+        self.removed(removed)
+    }
+
+    /// Removes and returns the entry whose key is the successor of the given key, as
+    /// `Map::remove_succ` does.
+    pub fn remove_succ<Q: ?Sized>(&mut self, key: &Q, inclusive: bool) -> Option<(K, V)>
+        where C: Compare<Q, K> {
+
+        let removed = self.map.remove_succ(key, inclusive);
+        self.removed(removed)
+    }
+
+    fn removed(&mut self, removed: Option<(K, V)>) -> Option<(K, V)> {
+        if let Some((ref key, ref value)) = removed {
+            self.listener.changed(Change::Removed(key, value));
+        }
+
+        removed
+    }
+
+    /// Returns the map's entry corresponding to the given key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tree::observed::{Change, Entry, ObservedMap};
+    ///
+    /// let mut removed = 0;
+    ///
+    /// {
+    ///     let mut map = ObservedMap::new(|change: Change<_, _>| {
+    ///         if let Change::Removed(..) = change { removed += 1; }
+    ///     });
+    ///
+    ///     map.insert("a", 1);
+    ///
+    ///     if let Entry::Occupied(e) = map.entry("a") { e.remove(); }
+    ///     assert_eq!(*map.entry("b").or_insert(2), 2);
+    /// }
+    ///
+    /// assert_eq!(removed, 1);
+    /// ```
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, L> {
+        let listener = &mut self.listener;
+
+        match self.map.entry(key) {
+            map::Entry::Occupied(e) =>
+                Entry::Occupied(OccupiedEntry { entry: e, listener: listener }),
+            map::Entry::Vacant(e) =>
+                Entry::Vacant(VacantEntry { entry: e, listener: listener }),
+        }
+    }
+}
+
+impl<K, V, L, C> Debug for ObservedMap<K, V, L, C>
+    where K: Debug, V: Debug, C: Compare<K>, L: Listener<K, V> {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { self.map.fmt(f) }
+}
+
+impl<K, V, L, C> Deref for ObservedMap<K, V, L, C> where C: Compare<K>, L: Listener<K, V> {
+    type Target = Map<K, V, C>;
+    fn deref(&self) -> &Map<K, V, C> { &self.map }
+}
+
+impl<K, V, L, C> Extend<(K, V)> for ObservedMap<K, V, L, C>
+    where C: Compare<K>, L: Listener<K, V> {
+
+    fn extend<I: IntoIterator<Item=(K, V)>>(&mut self, it: I) {
+        for (k, v) in it { self.insert(k, v); }
+    }
+}
+
+/// An entry in an `ObservedMap`.
+///
+/// See [`ObservedMap::entry`](struct.ObservedMap.html#method.entry) for an example.
+pub enum Entry<'a, K: 'a, V: 'a, L: 'a> where L: Listener<K, V> {
+    /// An occupied entry.
+    Occupied(OccupiedEntry<'a, K, V, L>),
+    /// A vacant entry.
+    Vacant(VacantEntry<'a, K, V, L>),
+}
+
+impl<'a, K, V, L> Entry<'a, K, V, L> where L: Listener<K, V> {
+    /// Returns the entry's value, inserting the given default if the entry is vacant.
+    pub fn or_insert(self, default: V) -> &'a V {
+        match self {
+            Entry::Occupied(e) => e.into_ref(),
+            Entry::Vacant(e) => e.insert(default),
+        }
+    }
+
+    /// Returns the entry's value, inserting the given function's result if the entry is vacant.
+    pub fn or_insert_with<F>(self, default: F) -> &'a V where F: FnOnce() -> V {
+        match self {
+            Entry::Occupied(e) => e.into_ref(),
+            Entry::Vacant(e) => e.insert(default()),
+        }
+    }
+}
+
+/// An occupied entry in an `ObservedMap`.
+///
+/// See [`ObservedMap::entry`](struct.ObservedMap.html#method.entry) for an example.
+pub struct OccupiedEntry<'a, K: 'a, V: 'a, L: 'a> where L: Listener<K, V> {
+    entry: map::OccupiedEntry<'a, K, V>,
+    listener: &'a mut L,
+}
+
+impl<'a, K, V, L> OccupiedEntry<'a, K, V, L> where L: Listener<K, V> {
+    /// Returns a reference to the entry's key.
+    pub fn key(&self) -> &K { self.entry.key() }
+
+    /// Returns a reference to the entry's value.
+    pub fn get(&self) -> &V { self.entry.get() }
+
+    /// Returns a reference to the entry's value with the same lifetime as the map.
+    pub fn into_ref(self) -> &'a V { self.entry.into_mut() }
+
+    /// Replaces the entry's value with the given value, returning the old one and reporting
+    /// `Change::Updated`.
+    pub fn insert(&mut self, value: V) -> V {
+        let old_value = self.entry.insert(value);
+        self.listener.changed(Change::Updated(self.entry.key(), &old_value, self.entry.get()));
+        old_value
+    }
+
+    /// Removes the entry from the map and returns its key and value, reporting
+    /// `Change::Removed`.
+    pub fn remove(self) -> (K, V) {
+        let (key, value) = self.entry.remove();
+        self.listener.changed(Change::Removed(&key, &value));
+        (key, value)
+    }
+}
+
+/// A vacant entry in an `ObservedMap`.
+///
+/// See [`ObservedMap::entry`](struct.ObservedMap.html#method.entry) for an example.
+pub struct VacantEntry<'a, K: 'a, V: 'a, L: 'a> where L: Listener<K, V> {
+    entry: map::VacantEntry<'a, K, V>,
+    listener: &'a mut L,
+}
+
+impl<'a, K, V, L> VacantEntry<'a, K, V, L> where L: Listener<K, V> {
+    /// Returns a reference to the entry's key.
+    pub fn key(&self) -> &K { self.entry.key() }
+
+    /// Inserts the entry into the map with its key and the given value, reporting
+    /// `Change::Inserted`, and returns a reference to the value with the same lifetime as the
+    /// map.
+    pub fn insert(self, value: V) -> &'a V {
+        let (key, value) = self.entry.insert_entry(value);
+        self.listener.changed(Change::Inserted(key, value));
+        value
+    }
+}
@@ -1,47 +1,48 @@
 extern crate ordered_iter;
 
 use self::ordered_iter::{OrderedMapIterator, OrderedSetIterator};
+use super::allocator::Allocator;
 use super::{map, set};
 
-impl<K, V> OrderedMapIterator for map::IntoIter<K, V> where K: Ord {
+impl<K, V, A> OrderedMapIterator for map::IntoIter<K, V, A> where K: Ord, A: Allocator {
     type Key = K;
     type Val = V;
 }
 
-impl<'a, K, V> OrderedMapIterator for map::Iter<'a, K, V> where K: Ord {
+impl<'a, K, V, A> OrderedMapIterator for map::Iter<'a, K, V, A> where K: Ord, A: Allocator {
     type Key = &'a K;
     type Val = &'a V;
 }
 
-impl<'a, K, V> OrderedMapIterator for map::IterMut<'a, K, V> where K: Ord {
+impl<'a, K, V, A> OrderedMapIterator for map::IterMut<'a, K, V, A> where K: Ord, A: Allocator {
     type Key = &'a K;
     type Val = &'a mut V;
 }
 
 #[cfg(feature = "range")]
-impl<K, V> OrderedMapIterator for map::IntoRange<K, V> where K: Ord {
+impl<K, V, A> OrderedMapIterator for map::IntoRange<K, V, A> where K: Ord, A: Allocator {
     type Key = K;
     type Val = V;
 }
 
 #[cfg(feature = "range")]
-impl<'a, K, V> OrderedMapIterator for map::Range<'a, K, V> where K: Ord {
+impl<'a, K, V, A> OrderedMapIterator for map::Range<'a, K, V, A> where K: Ord, A: Allocator {
     type Key = &'a K;
     type Val = &'a V;
 }
 
 #[cfg(feature = "range")]
-impl<'a, K, V> OrderedMapIterator for map::RangeMut<'a, K, V> where K: Ord {
+impl<'a, K, V, A> OrderedMapIterator for map::RangeMut<'a, K, V, A> where K: Ord, A: Allocator {
     type Key = &'a K;
     type Val = &'a mut V;
 }
 
-impl<T> OrderedSetIterator for set::IntoIter<T> where T: Ord {}
+impl<T, A> OrderedSetIterator for set::IntoIter<T, A> where T: Ord, A: Allocator {}
 
-impl<'a, T> OrderedSetIterator for set::Iter<'a, T> where T: Ord {}
+impl<'a, T, A> OrderedSetIterator for set::Iter<'a, T, A> where T: Ord, A: Allocator {}
 
 #[cfg(feature = "range")]
-impl<T> OrderedSetIterator for set::IntoRange<T> where T: Ord {}
+impl<T, A> OrderedSetIterator for set::IntoRange<T, A> where T: Ord, A: Allocator {}
 
 #[cfg(feature = "range")]
-impl<'a, T> OrderedSetIterator for set::Range<'a, T> where T: Ord {}
+impl<'a, T, A> OrderedSetIterator for set::Range<'a, T, A> where T: Ord, A: Allocator {}
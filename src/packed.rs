@@ -0,0 +1,731 @@
+//! A versioned binary layout for immutable sorted maps.
+//!
+//! A packed map is a byte buffer that can be written to a file and later read, for example through
+//! a memory map, without deserialization: `PackedMap` performs lookups directly on the bytes,
+//! decoding only the entries it visits. `write` and `to_vec` produce the layout from the entries of
+//! a `Map`, in a fixed number of passes over its iterator and without buffering its entries.
+//!
+//! # Layout
+//!
+//! All integers in the header and index are little-endian. The layout begins with a 64-byte
+//! header:
+//!
+//! | Offset | Size | Field                                                          |
+//! |--------|------|----------------------------------------------------------------|
+//! | 0      | 8    | `MAGIC`                                                        |
+//! | 8      | 4    | `VERSION`                                                      |
+//! | 12     | 4    | The width of every key, or `0xffff_ffff` if keys vary in width |
+//! | 16     | 4    | The width of every value, likewise                             |
+//! | 20     | 4    | Reserved, zero                                                 |
+//! | 24     | 8    | The number of entries                                          |
+//! | 32     | 8    | The length of the data region                                  |
+//! | 40     | 8    | The checksum of the index region                               |
+//! | 48     | 8    | The checksum of the data region                                |
+//! | 56     | 8    | The checksum of the preceding 56 bytes                         |
+//!
+//! Checksums are 64-bit FNV-1a hashes. The header is followed by the index region and then the
+//! data region.
+//!
+//! The data region holds the entries in strictly ascending order of their encoded keys, compared
+//! as byte strings. Each entry is its encoded key followed by its encoded value; a key or value
+//! that varies in width is preceded by its width as a four-byte integer.
+//!
+//! If both keys and values have fixed widths, the index region is empty and entries are located
+//! by their position. Otherwise, it holds the offset of each entry from the start of the data
+//! region, as an eight-byte integer.
+//!
+//! # Examples
+//!
+//! ```
+//! use tree::packed::{self, PackedMap};
+//!
+//! let mut map = tree::Map::new();
+//! map.insert(3u32, "c");
+//! map.insert(1, "a");
+//! map.insert(2, "b");
+//!
+//! let bytes = packed::to_vec(map.iter()).unwrap();
+//! let packed = PackedMap::<u32, &str>::new(&bytes).unwrap();
+//!
+//! assert_eq!(packed.get(&2u32), Some("b"));
+//! assert_eq!(packed.pred(&2u32, false), Some((1, "a")));
+//! assert_eq!(packed.iter().collect::<Vec<_>>(), [(1, "a"), (2, "b"), (3, "c")]);
+//! ```
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cmp::Ordering::*;
+use core::convert::Infallible;
+use core::fmt::{self, Debug, Display};
+use core::marker::PhantomData;
+#[cfg(feature = "range")] use core::ops::Bound;
+use core::str;
+
+/// The magic number at the start of every packed map.
+pub const MAGIC: [u8; 8] = *b"TREEMAP\0";
+
+/// The version of the layout written by this module.
+pub const VERSION: u32 = 1;
+
+const HEADER_LEN: usize = 64;
+const VARIABLE: u32 = 0xffff_ffff;
+
+/// A type that can be encoded in a packed map.
+///
+/// The encoding must preserve order: the encodings of two keys, compared as byte strings, must be
+/// ordered as the keys themselves are. The implementations for integers are big-endian, with the
+/// sign bit of signed integers flipped, so that they have this property.
+pub trait Encode {
+    /// The width of every encoding of this type, or `None` if it varies.
+    const WIDTH: Option<usize>;
+
+    /// Appends the encoding of this value to the given buffer.
+    fn encode(&self, out: &mut Vec<u8>);
+}
+
+/// A type that can be decoded from a packed map, possibly borrowing from it.
+pub trait Decode<'a>: Sized {
+    /// Decodes a value from the given bytes, returning `None` if they are not a valid encoding.
+    fn decode(bytes: &'a [u8]) -> Option<Self>;
+}
+
+macro_rules! int_encoding {
+    ($($T:ty, $U:ty, $flip:expr;)*) => {$(
+        impl Encode for $T {
+            const WIDTH: Option<usize> = Some(::core::mem::size_of::<$T>());
+
+            fn encode(&self, out: &mut Vec<u8>) {
+                out.extend_from_slice(&((*self as $U) ^ $flip).to_be_bytes());
+            }
+        }
+
+        impl<'a> Decode<'a> for $T {
+            fn decode(bytes: &'a [u8]) -> Option<$T> {
+                let mut array = [0; ::core::mem::size_of::<$T>()];
+                if bytes.len() != array.len() { return None; }
+                array.copy_from_slice(bytes);
+                Some((<$U>::from_be_bytes(array) ^ $flip) as $T)
+            }
+        }
+    )*}
+}
+
+int_encoding! {
+    u8, u8, 0;
+    u16, u16, 0;
+    u32, u32, 0;
+    u64, u64, 0;
+    u128, u128, 0;
+    i8, u8, 1 << 7;
+    i16, u16, 1 << 15;
+    i32, u32, 1 << 31;
+    i64, u64, 1 << 63;
+    i128, u128, 1 << 127;
+}
+
+impl Encode for () {
+    const WIDTH: Option<usize> = Some(0);
+    fn encode(&self, _out: &mut Vec<u8>) {}
+}
+
+impl<'a> Decode<'a> for () {
+    fn decode(bytes: &'a [u8]) -> Option<()> { if bytes.is_empty() { Some(()) } else { None } }
+}
+
+impl Encode for [u8] {
+    const WIDTH: Option<usize> = None;
+    fn encode(&self, out: &mut Vec<u8>) { out.extend_from_slice(self); }
+}
+
+impl Encode for str {
+    const WIDTH: Option<usize> = None;
+    fn encode(&self, out: &mut Vec<u8>) { out.extend_from_slice(self.as_bytes()); }
+}
+
+impl Encode for Vec<u8> {
+    const WIDTH: Option<usize> = None;
+    fn encode(&self, out: &mut Vec<u8>) { out.extend_from_slice(self); }
+}
+
+impl Encode for String {
+    const WIDTH: Option<usize> = None;
+    fn encode(&self, out: &mut Vec<u8>) { out.extend_from_slice(self.as_bytes()); }
+}
+
+impl<'b, T: ?Sized> Encode for &'b T where T: Encode {
+    const WIDTH: Option<usize> = T::WIDTH;
+    fn encode(&self, out: &mut Vec<u8>) { (**self).encode(out) }
+}
+
+impl<'a> Decode<'a> for &'a [u8] {
+    fn decode(bytes: &'a [u8]) -> Option<&'a [u8]> { Some(bytes) }
+}
+
+impl<'a> Decode<'a> for &'a str {
+    fn decode(bytes: &'a [u8]) -> Option<&'a str> { str::from_utf8(bytes).ok() }
+}
+
+impl<'a> Decode<'a> for Vec<u8> {
+    fn decode(bytes: &'a [u8]) -> Option<Vec<u8>> { Some(bytes.to_vec()) }
+}
+
+impl<'a> Decode<'a> for String {
+    fn decode(bytes: &'a [u8]) -> Option<String> { str::from_utf8(bytes).ok().map(Into::into) }
+}
+
+/// An error reading or writing a packed map.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Error {
+    /// The bytes are shorter or longer than their header requires.
+    Truncated,
+    /// The bytes do not begin with `MAGIC`.
+    BadMagic,
+    /// The layout's version is not supported by this module.
+    UnsupportedVersion(u32),
+    /// The widths recorded in the header do not match those of the key and value types.
+    WidthMismatch,
+    /// A checksum does not match the bytes it covers.
+    ChecksumMismatch,
+    /// An entry is out of bounds or cannot be decoded.
+    Malformed,
+    /// The encoded keys are not in strictly ascending order.
+    Unsorted,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Truncated => f.write_str("packed map has the wrong length"),
+            Error::BadMagic => f.write_str("packed map has the wrong magic number"),
+            Error::UnsupportedVersion(v) => write!(f, "packed map has unsupported version {}", v),
+            Error::WidthMismatch => f.write_str("packed map has the wrong key or value width"),
+            Error::ChecksumMismatch => f.write_str("packed map has the wrong checksum"),
+            Error::Malformed => f.write_str("packed map has a malformed entry"),
+            Error::Unsorted => f.write_str("packed map keys are not in ascending order"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl ::std::error::Error for Error {}
+
+/// An immutable sorted map read directly from the bytes of a packed map.
+///
+/// See the [module documentation](index.html) for details.
+pub struct PackedMap<'a, K, V> {
+    bytes: &'a [u8],
+    index: &'a [u8],
+    data: &'a [u8],
+    len: usize,
+    marker: PhantomData<fn() -> (K, V)>,
+}
+
+impl<'a, K, V> Clone for PackedMap<'a, K, V> {
+    fn clone(&self) -> Self { *self }
+}
+
+impl<'a, K, V> Copy for PackedMap<'a, K, V> {}
+
+impl<'a, K, V> PackedMap<'a, K, V> where K: Encode + Decode<'a>, V: Encode + Decode<'a> {
+    /// Reads a packed map from the given bytes.
+    ///
+    /// This verifies the header, the checksums and every entry, so that later lookups cannot fail.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tree::packed::{self, Error, PackedMap};
+    ///
+    /// let map: tree::Map<_, _> = (0..10u8).map(|i| (i, i as u64 * 10)).collect();
+    /// let mut bytes = packed::to_vec(&map).unwrap();
+    /// assert_eq!(PackedMap::<u8, u64>::new(&bytes).unwrap().len(), 10);
+    ///
+    /// assert_eq!(PackedMap::<u8, u32>::new(&bytes).err(), Some(Error::WidthMismatch));
+    /// assert_eq!(PackedMap::<u8, u64>::new(&bytes[..100]).err(), Some(Error::Truncated));
+    ///
+    /// *bytes.last_mut().unwrap() ^= 1;
+    /// assert_eq!(PackedMap::<u8, u64>::new(&bytes).err(), Some(Error::ChecksumMismatch));
+    /// ```
+    pub fn new(bytes: &'a [u8]) -> Result<Self, Error> {
+        if bytes.len() < HEADER_LEN { return Err(Error::Truncated); }
+        if bytes[..8] != MAGIC { return Err(Error::BadMagic); }
+
+        let version = read_u32(bytes, 8);
+        if version != VERSION { return Err(Error::UnsupportedVersion(version)); }
+        if fnv(FNV_OFFSET, &bytes[..56]) != read_u64(bytes, 56) {
+            return Err(Error::ChecksumMismatch);
+        }
+
+        if read_u32(bytes, 12) != width::<K>() || read_u32(bytes, 16) != width::<V>() {
+            return Err(Error::WidthMismatch);
+        }
+
+        let len = read_u64(bytes, 24);
+        let index_len = if is_fixed::<K, V>() { Some(0) } else { len.checked_mul(8) };
+        let total = index_len.and_then(|index_len| index_len.checked_add(read_u64(bytes, 32)));
+
+        if total != Some((bytes.len() - HEADER_LEN) as u64) { return Err(Error::Truncated); }
+
+        let (index, data) = bytes[HEADER_LEN..].split_at(index_len.unwrap_or(0) as usize);
+
+        if fnv(FNV_OFFSET, index) != read_u64(bytes, 40) ||
+           fnv(FNV_OFFSET, data) != read_u64(bytes, 48) {
+            return Err(Error::ChecksumMismatch);
+        }
+
+        let map = PackedMap { bytes: bytes, index: index, data: data, len: len as usize,
+                              marker: PhantomData };
+
+        if is_fixed::<K, V>() {
+            let stride = K::WIDTH.unwrap_or(0) + V::WIDTH.unwrap_or(0);
+            if stride.checked_mul(map.len) != Some(data.len()) { return Err(Error::Malformed); }
+        }
+
+        let mut prev = None;
+
+        for i in 0..map.len {
+            let (key, value) = match map.parse(i) {
+                Some(entry) => entry,
+                None => return Err(Error::Malformed),
+            };
+
+            if K::decode(key).is_none() || V::decode(value).is_none() {
+                return Err(Error::Malformed);
+            }
+
+            if let Some(prev) = prev {
+                if prev >= key { return Err(Error::Unsorted); }
+            }
+
+            prev = Some(key);
+        }
+
+        Ok(map)
+    }
+
+    /// Returns the bytes of the packed map.
+    pub fn as_bytes(&self) -> &'a [u8] { self.bytes }
+
+    /// Checks if the map is empty.
+    pub fn is_empty(&self) -> bool { self.len == 0 }
+
+    /// Returns the number of entries in the map.
+    pub fn len(&self) -> usize { self.len }
+
+    /// Returns the value associated with the given key, or `None` if the map does not contain the
+    /// key.
+    ///
+    /// The key is compared by its encoding, which must agree with that of the map's keys.
+    pub fn get<Q: ?Sized>(&self, key: &Q) -> Option<V> where Q: Encode {
+        let key = encode(key);
+        let i = self.partition(&key, true);
+        if i < self.len && self.key(i) == &key[..] { Some(self.entry(i).1) } else { None }
+    }
+
+    /// Checks if the map contains the given key.
+    pub fn contains_key<Q: ?Sized>(&self, key: &Q) -> bool where Q: Encode {
+        let key = encode(key);
+        let i = self.partition(&key, true);
+        i < self.len && self.key(i) == &key[..]
+    }
+
+    /// Returns the predecessor of the given key and its associated value, as `Map::pred` does.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tree::packed::{self, PackedMap};
+    ///
+    /// let map: tree::Map<_, _> = vec![("a", 1u8), ("c", 3)].into_iter().collect();
+    /// let bytes = packed::to_vec(&map).unwrap();
+    /// let map = PackedMap::<&str, u8>::new(&bytes).unwrap();
+    ///
+    /// assert_eq!(map.pred("a", false), None);
+    /// assert_eq!(map.pred("b", false), Some(("a", 1)));
+    /// assert_eq!(map.pred("c", true), Some(("c", 3)));
+    /// ```
+    pub fn pred<Q: ?Sized>(&self, key: &Q, inclusive: bool) -> Option<(K, V)> where Q: Encode {
+        let i = self.partition(&encode(key), !inclusive);
+        if i == 0 { None } else { Some(self.entry(i - 1)) }
+    }
+
+    /// Returns the successor of the given key and its associated value, as `Map::succ` does.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tree::packed::{self, PackedMap};
+    ///
+    /// let map: tree::Map<_, _> = vec![("a", 1u8), ("c", 3)].into_iter().collect();
+    /// let bytes = packed::to_vec(&map).unwrap();
+    /// let map = PackedMap::<&str, u8>::new(&bytes).unwrap();
+    ///
+    /// assert_eq!(map.succ("a", false), Some(("c", 3)));
+    /// assert_eq!(map.succ("a", true), Some(("a", 1)));
+    /// assert_eq!(map.succ("c", false), None);
+    /// ```
+    pub fn succ<Q: ?Sized>(&self, key: &Q, inclusive: bool) -> Option<(K, V)> where Q: Encode {
+        let i = self.partition(&encode(key), inclusive);
+        if i < self.len { Some(self.entry(i)) } else { None }
+    }
+
+    /// Returns an iterator over the map's entries.
+    ///
+    /// The iterator yields the entries in ascending order of their encoded keys.
+    pub fn iter(&self) -> Iter<'a, K, V> { Iter { map: *self, front: 0, back: self.len } }
+
+    /// Returns an iterator over the map's entries whose keys lie in the given range, as
+    /// `Map::range` does.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::ops::Bound::{Excluded, Included};
+    /// use tree::packed::{self, PackedMap};
+    ///
+    /// let map: tree::Map<_, _> = (0..10i32).map(|i| (i - 5, ())).collect();
+    /// let bytes = packed::to_vec(&map).unwrap();
+    /// let map = PackedMap::<i32, ()>::new(&bytes).unwrap();
+    ///
+    /// assert_eq!(map.range(Excluded(&-2), Included(&1)).map(|e| e.0).collect::<Vec<_>>(),
+    ///            [-1, 0, 1]);
+    /// ```
+    #[cfg(feature = "range")]
+    pub fn range<Min: ?Sized, Max: ?Sized>(&self, min: Bound<&Min>, max: Bound<&Max>)
+        -> Iter<'a, K, V> where Min: Encode, Max: Encode {
+
+        let front = match min {
+            Bound::Included(key) => self.partition(&encode(key), true),
+            Bound::Excluded(key) => self.partition(&encode(key), false),
+            Bound::Unbounded => 0,
+        };
+
+        let back = match max {
+            Bound::Included(key) => self.partition(&encode(key), false),
+            Bound::Excluded(key) => self.partition(&encode(key), true),
+            Bound::Unbounded => self.len,
+        };
+
+        Iter { map: *self, front: front, back: if back < front { front } else { back } }
+    }
+
+    // Returns the index of the first entry whose key is greater than (or, if `inclusive`, equal
+    // to) the given encoded key.
+    fn partition(&self, key: &[u8], inclusive: bool) -> usize {
+        let (mut lo, mut hi) = (0, self.len);
+
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+
+            match self.key(mid).cmp(key) {
+                Less => lo = mid + 1,
+                Equal if !inclusive => lo = mid + 1,
+                _ => hi = mid,
+            }
+        }
+
+        lo
+    }
+
+    fn key(&self, i: usize) -> &'a [u8] { self.parse(i).expect("entry was verified").0 }
+
+    fn entry(&self, i: usize) -> (K, V) {
+        let (key, value) = self.parse(i).expect("entry was verified");
+        (K::decode(key).expect("key was verified"), V::decode(value).expect("value was verified"))
+    }
+
+    // Returns the encoded key and value at the given index, or `None` if they are out of bounds.
+    fn parse(&self, i: usize) -> Option<(&'a [u8], &'a [u8])> {
+        let offset = if is_fixed::<K, V>() {
+            i * (K::WIDTH.unwrap_or(0) + V::WIDTH.unwrap_or(0))
+        } else {
+            read_u64(self.index, i * 8) as usize
+        };
+
+        let (key, rest) = match split::<K>(self.data, offset) { Some(s) => s, None => return None };
+        let (value, _) = match split::<V>(self.data, rest) { Some(s) => s, None => return None };
+        Some((key, value))
+    }
+}
+
+impl<'a, K, V> Debug for PackedMap<'a, K, V>
+    where K: Encode + Decode<'a> + Debug, V: Encode + Decode<'a> + Debug {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<'a, K, V> IntoIterator for PackedMap<'a, K, V>
+    where K: Encode + Decode<'a>, V: Encode + Decode<'a> {
+
+    type Item = (K, V);
+    type IntoIter = Iter<'a, K, V>;
+    fn into_iter(self) -> Iter<'a, K, V> { self.iter() }
+}
+
+/// An iterator over a packed map's entries.
+///
+/// The iterator yields the entries in ascending order of their encoded keys.
+///
+/// Acquire through [`PackedMap::iter`](struct.PackedMap.html#method.iter),
+/// [`PackedMap::range`](struct.PackedMap.html#method.range) or the `IntoIterator` implementation
+/// for `PackedMap`.
+pub struct Iter<'a, K, V> {
+    map: PackedMap<'a, K, V>,
+    front: usize,
+    back: usize,
+}
+
+impl<'a, K, V> Clone for Iter<'a, K, V> {
+    fn clone(&self) -> Self { Iter { map: self.map, front: self.front, back: self.back } }
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> where K: Encode + Decode<'a>, V: Encode + Decode<'a> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<(K, V)> {
+        if self.front == self.back { return None; }
+        self.front += 1;
+        Some(self.map.entry(self.front - 1))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.back - self.front;
+        (len, Some(len))
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for Iter<'a, K, V>
+    where K: Encode + Decode<'a>, V: Encode + Decode<'a> {
+
+    fn next_back(&mut self) -> Option<(K, V)> {
+        if self.front == self.back { return None; }
+        self.back -= 1;
+        Some(self.map.entry(self.back))
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for Iter<'a, K, V>
+    where K: Encode + Decode<'a>, V: Encode + Decode<'a> {}
+
+/// Encodes the given entries as a packed map.
+///
+/// The entries must be in strictly ascending order of their encoded keys, as they are when
+/// iterating over a `Map` whose comparator agrees with the encoding. Otherwise, this returns
+/// `Error::Unsorted`.
+///
+/// # Examples
+///
+/// ```
+/// use tree::packed::{self, Error, PackedMap};
+///
+/// let map: tree::Map<_, _> = vec![("b", "x"), ("a", "y")].into_iter().collect();
+/// let bytes = packed::to_vec(&map).unwrap();
+/// let packed = PackedMap::<&str, &str>::new(&bytes).unwrap();
+/// assert_eq!(packed.get("a"), Some("y"));
+///
+/// let map: tree::Map<_, _> = (0..3u8).map(|i| (vec![i], i)).collect();
+/// assert!(packed::to_vec(map.iter().rev()).is_err());
+/// ```
+pub fn to_vec<'b, K: 'b, V: 'b, I>(entries: I) -> Result<Vec<u8>, Error>
+    where K: Encode, V: Encode, I: IntoIterator<Item=(&'b K, &'b V)>, I::IntoIter: Clone {
+
+    let mut out = Vec::new();
+
+    let result = emit(entries.into_iter(), |bytes| -> Result<(), Infallible> {
+        out.extend_from_slice(bytes);
+        Ok(())
+    });
+
+    match result {
+        Ok(()) => Ok(out),
+        Err(_) => Err(Error::Unsorted),
+    }
+}
+
+/// Writes the given entries to the given writer as a packed map, as `to_vec` does.
+///
+/// The entries are encoded three times, in passes over clones of their iterator, so that they
+/// need not be buffered. If the entries are not in strictly ascending order of their encoded
+/// keys, this returns an error of kind `InvalidInput` wrapping `Error::Unsorted`, before writing
+/// anything.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::BufWriter;
+/// use tree::packed::{self, PackedMap};
+///
+/// let map: tree::Map<_, _> = (0..100u32).map(|i| (i, i * i)).collect();
+///
+/// let mut out = BufWriter::new(Vec::new());
+/// packed::write(&map, &mut out).unwrap();
+///
+/// let bytes = out.into_inner().unwrap();
+/// let packed = PackedMap::<u32, u32>::new(&bytes).unwrap();
+/// assert_eq!(packed.get(&9u32), Some(81));
+/// ```
+#[cfg(feature = "std")]
+pub fn write<'b, K: 'b, V: 'b, I, W: ?Sized>(entries: I, out: &mut W) -> ::std::io::Result<()>
+    where K: Encode, V: Encode, I: IntoIterator<Item=(&'b K, &'b V)>, I::IntoIter: Clone,
+          W: ::std::io::Write {
+
+    use std::io::{Error as IoError, ErrorKind};
+
+    match emit(entries.into_iter(), |bytes| out.write_all(bytes)) {
+        Ok(()) => Ok(()),
+        Err(Emit::Unsorted) => Err(IoError::new(ErrorKind::InvalidInput, Error::Unsorted)),
+        Err(Emit::Sink(e)) => Err(e),
+    }
+}
+
+enum Emit<E> {
+    Unsorted,
+    Sink(E),
+}
+
+// Writes the layout to the given sink in three passes over the entries: the first checks their
+// order and computes the checksums, the second writes the index and the third the data.
+fn emit<'b, K: 'b, V: 'b, I, F, E>(entries: I, mut sink: F) -> Result<(), Emit<E>>
+    where K: Encode, V: Encode, I: Iterator<Item=(&'b K, &'b V)> + Clone,
+          F: FnMut(&[u8]) -> Result<(), E> {
+
+    let mut encoder = Encoder { key: Vec::new(), value: Vec::new(), entry: Vec::new() };
+    let mut prev = Vec::new();
+    let (mut len, mut data_len) = (0u64, 0u64);
+    let (mut index_hash, mut data_hash) = (FNV_OFFSET, FNV_OFFSET);
+
+    for (i, (key, value)) in entries.clone().enumerate() {
+        encoder.encode(key, value);
+        if i > 0 && prev >= encoder.key { return Err(Emit::Unsorted); }
+
+        if !is_fixed::<K, V>() { index_hash = fnv(index_hash, &data_len.to_le_bytes()); }
+        data_hash = fnv(data_hash, &encoder.entry);
+        data_len += encoder.entry.len() as u64;
+        len += 1;
+        ::core::mem::swap(&mut prev, &mut encoder.key);
+    }
+
+    let mut header = [0; HEADER_LEN];
+    header[..8].copy_from_slice(&MAGIC);
+    header[8..12].copy_from_slice(&VERSION.to_le_bytes());
+    header[12..16].copy_from_slice(&width::<K>().to_le_bytes());
+    header[16..20].copy_from_slice(&width::<V>().to_le_bytes());
+    header[24..32].copy_from_slice(&len.to_le_bytes());
+    header[32..40].copy_from_slice(&data_len.to_le_bytes());
+    header[40..48].copy_from_slice(&index_hash.to_le_bytes());
+    header[48..56].copy_from_slice(&data_hash.to_le_bytes());
+    let header_hash = fnv(FNV_OFFSET, &header[..56]);
+    header[56..].copy_from_slice(&header_hash.to_le_bytes());
+    if let Err(e) = sink(&header) { return Err(Emit::Sink(e)); }
+
+    if !is_fixed::<K, V>() {
+        let mut offset = 0u64;
+
+        for (key, value) in entries.clone() {
+            if let Err(e) = sink(&offset.to_le_bytes()) { return Err(Emit::Sink(e)); }
+            offset += encoder.encode(key, value).len() as u64;
+        }
+    }
+
+    for (key, value) in entries {
+        if let Err(e) = sink(encoder.encode(key, value)) { return Err(Emit::Sink(e)); }
+    }
+
+    Ok(())
+}
+
+struct Encoder {
+    key: Vec<u8>,
+    value: Vec<u8>,
+    entry: Vec<u8>,
+}
+
+impl Encoder {
+    // Encodes the entry, returning its bytes in the data region.
+    fn encode<K: ?Sized, V: ?Sized>(&mut self, key: &K, value: &V) -> &[u8]
+        where K: Encode, V: Encode {
+
+        self.key.clear();
+        self.value.clear();
+        self.entry.clear();
+        key.encode(&mut self.key);
+        value.encode(&mut self.value);
+        push_field::<K>(&mut self.entry, &self.key);
+        push_field::<V>(&mut self.entry, &self.value);
+        &self.entry
+    }
+}
+
+fn push_field<T: ?Sized + Encode>(out: &mut Vec<u8>, bytes: &[u8]) {
+    match T::WIDTH {
+        Some(width) => assert_eq!(bytes.len(), width, "encoding has the wrong width"),
+        None => {
+            assert!(bytes.len() < VARIABLE as usize, "encoding is too long");
+            out.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+        }
+    }
+
+    out.extend_from_slice(bytes);
+}
+
+// Splits the field of type `T` at the given offset from `data`, returning it and the offset of
+// the following field.
+fn split<T: ?Sized + Encode>(data: &[u8], offset: usize) -> Option<(&[u8], usize)> {
+    let (start, width) = match T::WIDTH {
+        Some(width) => (offset, width),
+        None => {
+            if data.len() < 4 || offset > data.len() - 4 { return None; }
+            (offset + 4, read_u32(data, offset) as usize)
+        }
+    };
+
+    match start.checked_add(width) {
+        Some(end) if end <= data.len() => Some((&data[start..end], end)),
+        _ => None,
+    }
+}
+
+fn encode<T: ?Sized + Encode>(value: &T) -> Vec<u8> {
+    let mut out = Vec::with_capacity(T::WIDTH.unwrap_or(16));
+    value.encode(&mut out);
+    out
+}
+
+fn width<T: ?Sized + Encode>() -> u32 {
+    match T::WIDTH {
+        Some(width) => {
+            assert!(width < VARIABLE as usize, "encoding is too wide");
+            width as u32
+        }
+        None => VARIABLE,
+    }
+}
+
+fn is_fixed<K: ?Sized + Encode, V: ?Sized + Encode>() -> bool {
+    K::WIDTH.is_some() && V::WIDTH.is_some()
+}
+
+pub(crate) fn read_u32(bytes: &[u8], at: usize) -> u32 {
+    let mut array = [0; 4];
+    array.copy_from_slice(&bytes[at..at + 4]);
+    u32::from_le_bytes(array)
+}
+
+pub(crate) fn read_u64(bytes: &[u8], at: usize) -> u64 {
+    let mut array = [0; 8];
+    array.copy_from_slice(&bytes[at..at + 8]);
+    u64::from_le_bytes(array)
+}
+
+pub(crate) const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+
+pub(crate) fn fnv(mut hash: u64, bytes: &[u8]) -> u64 {
+    for &byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+
+    hash
+}
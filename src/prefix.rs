@@ -0,0 +1,155 @@
+//! Keys that can be matched by prefix.
+//!
+//! The `Prefix` trait supports `Map::longest_prefix_of` and, with the `range` feature,
+//! `Map::prefix_range`. It is implemented for strings, slices and vectors, whose prefixes are
+//! measured in characters and elements, and for `BitPrefix`, a prefix of up to 128 bits such as
+//! an IP network in CIDR notation.
+
+use alloc::borrow::{Cow, ToOwned};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::{self, Debug};
+
+/// A key that can be matched by prefix.
+///
+/// The prefixes of a key are ordered lexicographically: a map whose keys implement `Prefix` must
+/// order every key after its proper prefixes, and every key extending a prefix between that prefix
+/// and any key that does not extend it. The natural order of strings, slices and vectors has this
+/// property.
+pub trait Prefix: ToOwned {
+    /// Returns the key's length.
+    fn prefix_len(&self) -> usize;
+
+    /// Returns the length of the longest common prefix of the two keys.
+    fn common_prefix_len(&self, other: &Self) -> usize;
+
+    /// Returns the key's prefix of the given length, which must not exceed the key's length.
+    fn truncate(&self, len: usize) -> Cow<'_, Self>;
+
+    /// Checks if the key is a prefix of the other key.
+    fn is_prefix_of(&self, other: &Self) -> bool {
+        self.common_prefix_len(other) == self.prefix_len()
+    }
+}
+
+impl Prefix for str {
+    fn prefix_len(&self) -> usize { self.len() }
+
+    fn common_prefix_len(&self, other: &Self) -> usize {
+        let mut len = self.as_bytes().common_prefix_len(other.as_bytes());
+        while !self.is_char_boundary(len) { len -= 1; }
+        len
+    }
+
+    fn truncate(&self, len: usize) -> Cow<'_, Self> { Cow::Borrowed(&self[..len]) }
+}
+
+impl<T> Prefix for [T] where T: Eq + Clone {
+    fn prefix_len(&self) -> usize { self.len() }
+
+    fn common_prefix_len(&self, other: &Self) -> usize {
+        self.iter().zip(other).take_while(|&(l, r)| l == r).count()
+    }
+
+    fn truncate(&self, len: usize) -> Cow<'_, Self> { Cow::Borrowed(&self[..len]) }
+}
+
+impl<'a> Prefix for &'a str {
+    fn prefix_len(&self) -> usize { self.len() }
+    fn common_prefix_len(&self, other: &Self) -> usize { (**self).common_prefix_len(other) }
+    fn truncate(&self, len: usize) -> Cow<'_, Self> { Cow::Owned(&self[..len]) }
+}
+
+impl<'a, T> Prefix for &'a [T] where T: Eq + Clone {
+    fn prefix_len(&self) -> usize { self.len() }
+    fn common_prefix_len(&self, other: &Self) -> usize { (**self).common_prefix_len(other) }
+    fn truncate(&self, len: usize) -> Cow<'_, Self> { Cow::Owned(&self[..len]) }
+}
+
+impl Prefix for String {
+    fn prefix_len(&self) -> usize { self.len() }
+    fn common_prefix_len(&self, other: &Self) -> usize { (**self).common_prefix_len(other) }
+    fn truncate(&self, len: usize) -> Cow<'_, Self> { Cow::Owned(self[..len].to_owned()) }
+}
+
+impl<T> Prefix for Vec<T> where T: Eq + Clone {
+    fn prefix_len(&self) -> usize { self.len() }
+    fn common_prefix_len(&self, other: &Self) -> usize { (**self).common_prefix_len(other) }
+    fn truncate(&self, len: usize) -> Cow<'_, Self> { Cow::Owned(self[..len].to_owned()) }
+}
+
+/// A prefix of up to 128 bits.
+///
+/// The bits are stored left-aligned, so that the natural order of `BitPrefix` is lexicographic.
+///
+/// # Examples
+///
+/// ```
+/// use tree::prefix::{BitPrefix, Prefix};
+///
+/// // 10.0.0.0/8 and 10.1.2.3/32
+/// let net = BitPrefix::from_u32(0x0a00_0000, 8);
+/// let host = BitPrefix::from_u32(0x0a01_0203, 32);
+///
+/// assert!(net.is_prefix_of(&host));
+/// assert!(net < host);
+/// assert_eq!(host.truncate(8).into_owned(), net);
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct BitPrefix {
+    bits: u128,
+    len: u8,
+}
+
+impl BitPrefix {
+    /// Creates a prefix consisting of the `len` most significant bits of `bits`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `len > 128`.
+    pub fn new(bits: u128, len: u32) -> Self {
+        assert!(len <= 128, "prefix is longer than 128 bits");
+        BitPrefix { bits: bits & BitPrefix::mask(len), len: len as u8 }
+    }
+
+    /// Creates a prefix consisting of the `len` most significant bits of `bits`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `len > 32`.
+    pub fn from_u32(bits: u32, len: u32) -> Self {
+        assert!(len <= 32, "prefix is longer than 32 bits");
+        BitPrefix::new((bits as u128) << 96, len)
+    }
+
+    /// Returns the prefix's bits, left-aligned and followed by zeros.
+    pub fn bits(&self) -> u128 { self.bits }
+
+    /// Returns the number of bits in the prefix.
+    pub fn len(&self) -> u32 { self.len as u32 }
+
+    /// Checks if the prefix is empty.
+    pub fn is_empty(&self) -> bool { self.len == 0 }
+
+    fn mask(len: u32) -> u128 { if len == 0 { 0 } else { !0 << (128 - len) } }
+}
+
+impl Debug for BitPrefix {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:#034x}/{}", self.bits, self.len)
+    }
+}
+
+impl Prefix for BitPrefix {
+    fn prefix_len(&self) -> usize { self.len as usize }
+
+    fn common_prefix_len(&self, other: &Self) -> usize {
+        let len = (self.bits ^ other.bits).leading_zeros();
+        len.min(self.len()).min(other.len()) as usize
+    }
+
+    fn truncate(&self, len: usize) -> Cow<'_, Self> {
+        assert!(len <= self.len as usize, "truncated prefix is longer than the prefix");
+        Cow::Owned(BitPrefix::new(self.bits, len as u32))
+    }
+}
@@ -0,0 +1,407 @@
+//! A double-ended priority queue with stable handles.
+//!
+//! A `PriorityQueue` stores items ordered by priority. Unlike a `Set` used as a min-max heap, it
+//! allows several items with the same priority, and every pushed item is identified by a `Handle`
+//! through which its priority can later be changed or the item removed.
+//!
+//! Items with equal priorities are ordered by the time they were pushed: `pop_min` yields the
+//! oldest of them first and `pop_max` the newest. Changing an item's priority preserves its place
+//! among items of equal priority.
+//!
+//! # Examples
+//!
+//! ```
+//! use tree::queue::PriorityQueue;
+//!
+//! let mut tasks = PriorityQueue::new();
+//! let a = tasks.push("a", 3);
+//! tasks.push("b", 1);
+//! tasks.push("c", 2);
+//!
+//! tasks.change_priority(a, 0);
+//!
+//! assert_eq!(tasks.pop_min(), Some(("a", 0)));
+//! assert_eq!(tasks.pop_max(), Some(("c", 2)));
+//! assert_eq!(tasks.pop_min(), Some(("b", 1)));
+//! assert_eq!(tasks.pop_min(), None);
+//! ```
+
+use compare::{Compare, Natural};
+use core::cmp::Ordering::{self, Equal};
+use core::fmt::{self, Debug};
+use core::iter;
+use core::mem::replace;
+use map::{self, Map};
+
+/// A handle to an item in a `PriorityQueue`.
+///
+/// A handle remains valid until its item is popped or removed from the queue, and is never reused
+/// by the same queue afterwards.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Handle(u64);
+
+// Orders items by priority, breaking ties by the order in which they were pushed.
+#[derive(Clone, Default)]
+struct ByPriority<C>(C);
+
+impl<P, C> Compare<(P, u64)> for ByPriority<C> where C: Compare<P> {
+    fn compare(&self, l: &(P, u64), r: &(P, u64)) -> Ordering {
+        match self.0.compare(&l.0, &r.0) {
+            Equal => l.1.cmp(&r.1),
+            order => order,
+        }
+    }
+}
+
+/// A double-ended priority queue with stable handles.
+///
+/// See the [module documentation](index.html) for details.
+pub struct PriorityQueue<T, P, C = Natural<P>> where C: Compare<P> {
+    items: Map<(P, u64), T, ByPriority<C>>,
+    priorities: Map<u64, P>,
+    next_id: u64,
+}
+
+impl<T, P> PriorityQueue<T, P> where P: Ord {
+    /// Creates an empty queue ordered according to the natural order of its priorities.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tree::queue::PriorityQueue;
+    ///
+    /// let mut queue = PriorityQueue::new();
+    /// queue.push("a", 2);
+    /// queue.push("b", 1);
+    /// assert_eq!(queue.peek_min(), Some((&"b", &1)));
+    /// ```
+    pub fn new() -> Self { PriorityQueue::with_cmp(Natural::default()) }
+}
+
+impl<T, P, C> PriorityQueue<T, P, C> where C: Compare<P> {
+    /// Creates an empty queue ordered according to the given comparator.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate compare;
+    /// # extern crate tree;
+    /// use compare::{Compare, natural};
+    /// use tree::queue::PriorityQueue;
+    ///
+    /// # fn main() {
+    /// let mut queue = PriorityQueue::with_cmp(natural().rev());
+    /// queue.push("a", 2);
+    /// queue.push("b", 1);
+    /// assert_eq!(queue.peek_min(), Some((&"a", &2)));
+    /// # }
+    /// ```
+    pub fn with_cmp(cmp: C) -> Self {
+        PriorityQueue { items: Map::with_cmp(ByPriority(cmp)), priorities: Map::new(), next_id: 0 }
+    }
+
+    /// Checks if the queue is empty.
+    pub fn is_empty(&self) -> bool { self.items.is_empty() }
+
+    /// Returns the number of items in the queue.
+    pub fn len(&self) -> usize { self.items.len() }
+
+    /// Returns a reference to the queue's comparator.
+    pub fn cmp(&self) -> &C { &self.items.cmp().0 }
+
+    /// Removes all items from the queue, invalidating their handles.
+    pub fn clear(&mut self) {
+        self.items.clear();
+        self.priorities.clear();
+    }
+
+    /// Pushes an item with the given priority into the queue, returning its handle.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tree::queue::PriorityQueue;
+    ///
+    /// let mut queue = PriorityQueue::new();
+    /// let a = queue.push("a", 1);
+    /// let b = queue.push("b", 1);
+    ///
+    /// assert!(a != b);
+    /// assert_eq!(queue.len(), 2);
+    /// assert_eq!(queue.get(a), Some((&"a", &1)));
+    /// ```
+    pub fn push(&mut self, item: T, priority: P) -> Handle where P: Clone {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.priorities.insert(id, priority.clone());
+        self.items.insert((priority, id), item);
+        Handle(id)
+    }
+
+    /// Returns references to the item with the minimum priority and its priority, or `None`
+    /// if the queue is empty.
+    pub fn peek_min(&self) -> Option<(&T, &P)> {
+        self.items.min().map(|(key, item)| (item, &key.0))
+    }
+
+    /// Returns references to the item with the maximum priority and its priority, or `None`
+    /// if the queue is empty.
+    pub fn peek_max(&self) -> Option<(&T, &P)> {
+        self.items.max().map(|(key, item)| (item, &key.0))
+    }
+
+    /// Returns the handle of the item with the minimum priority, or `None` if the queue is empty.
+    pub fn min_handle(&self) -> Option<Handle> { self.items.min().map(|(key, _)| Handle(key.1)) }
+
+    /// Returns the handle of the item with the maximum priority, or `None` if the queue is empty.
+    pub fn max_handle(&self) -> Option<Handle> { self.items.max().map(|(key, _)| Handle(key.1)) }
+
+    /// Removes and returns the item with the minimum priority along with its priority, or
+    /// `None` if the queue is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tree::queue::PriorityQueue;
+    ///
+    /// let mut queue = PriorityQueue::new();
+    /// queue.push("a", 1);
+    /// queue.push("b", 0);
+    /// queue.push("c", 0);
+    ///
+    /// assert_eq!(queue.pop_min(), Some(("b", 0)));
+    /// assert_eq!(queue.pop_min(), Some(("c", 0)));
+    /// assert_eq!(queue.pop_min(), Some(("a", 1)));
+    /// assert_eq!(queue.pop_min(), None);
+    /// ```
+    pub fn pop_min(&mut self) -> Option<(T, P)> {
+        self.items.remove_min().map(|((priority, id), item)| {
+            self.priorities.remove(&id);
+            (item, priority)
+        })
+    }
+
+    /// Removes and returns the item with the maximum priority along with its priority, or
+    /// `None` if the queue is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tree::queue::PriorityQueue;
+    ///
+    /// let mut queue = PriorityQueue::new();
+    /// queue.push("a", 1);
+    /// queue.push("b", 0);
+    /// queue.push("c", 1);
+    ///
+    /// assert_eq!(queue.pop_max(), Some(("c", 1)));
+    /// assert_eq!(queue.pop_max(), Some(("a", 1)));
+    /// assert_eq!(queue.pop_max(), Some(("b", 0)));
+    /// assert_eq!(queue.pop_max(), None);
+    /// ```
+    pub fn pop_max(&mut self) -> Option<(T, P)> {
+        self.items.remove_max().map(|((priority, id), item)| {
+            self.priorities.remove(&id);
+            (item, priority)
+        })
+    }
+
+    /// Checks if the handle refers to an item in the queue.
+    pub fn contains(&self, handle: Handle) -> bool { self.priorities.contains_key(&handle.0) }
+
+    /// Returns references to the item with the given handle and its priority, or `None` if the
+    /// handle is no longer valid.
+    pub fn get(&self, handle: Handle) -> Option<(&T, &P)> where P: Clone {
+        let priority = match self.priorities.get(&handle.0) {
+            None => return None,
+            Some(priority) => priority,
+        };
+
+        self.items.get(&(priority.clone(), handle.0)).map(|item| (item, priority))
+    }
+
+    /// Returns a mutable reference to the item with the given handle, or `None` if the handle is
+    /// no longer valid.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tree::queue::PriorityQueue;
+    ///
+    /// let mut queue = PriorityQueue::new();
+    /// let a = queue.push(1, "low");
+    ///
+    /// *queue.get_mut(a).unwrap() += 1;
+    /// assert_eq!(queue.pop_min(), Some((2, "low")));
+    /// assert_eq!(queue.get_mut(a), None);
+    /// ```
+    pub fn get_mut(&mut self, handle: Handle) -> Option<&mut T> where P: Clone {
+        let priority = match self.priorities.get(&handle.0) {
+            None => return None,
+            Some(priority) => priority.clone(),
+        };
+
+        self.items.get_mut(&(priority, handle.0))
+    }
+
+    /// Changes the priority of the item with the given handle, returning its previous priority,
+    /// or `None` if the handle is no longer valid.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tree::queue::PriorityQueue;
+    ///
+    /// let mut queue = PriorityQueue::new();
+    /// let a = queue.push("a", 1);
+    /// queue.push("b", 2);
+    ///
+    /// assert_eq!(queue.change_priority(a, 3), Some(1));
+    /// assert_eq!(queue.peek_max(), Some((&"a", &3)));
+    /// ```
+    pub fn change_priority(&mut self, handle: Handle, priority: P) -> Option<P> where P: Clone {
+        let old_priority = match self.priorities.get_mut(&handle.0) {
+            None => return None,
+            Some(old_priority) => replace(old_priority, priority.clone()),
+        };
+
+        let ((old_priority, _), item) = self.items.remove(&(old_priority, handle.0))
+            .expect("queue's indices are inconsistent");
+        self.items.insert((priority, handle.0), item);
+        Some(old_priority)
+    }
+
+    /// Removes and returns the item with the given handle along with its priority, or `None`
+    /// if the handle is no longer valid.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tree::queue::PriorityQueue;
+    ///
+    /// let mut queue = PriorityQueue::new();
+    /// let a = queue.push("a", 1);
+    /// queue.push("b", 2);
+    ///
+    /// assert_eq!(queue.remove(a), Some(("a", 1)));
+    /// assert_eq!(queue.remove(a), None);
+    /// assert_eq!(queue.len(), 1);
+    /// ```
+    pub fn remove(&mut self, handle: Handle) -> Option<(T, P)> {
+        self.priorities.remove(&handle.0).map(|(id, priority)| {
+            let ((priority, _), item) = self.items.remove(&(priority, id))
+                .expect("queue's indices are inconsistent");
+            (item, priority)
+        })
+    }
+
+    /// Returns an iterator over the queue's items and their priorities.
+    ///
+    /// The iterator yields the items in ascending order of priority, as `pop_min` would.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tree::queue::PriorityQueue;
+    ///
+    /// let queue: PriorityQueue<_, _> = vec![("a", 2), ("b", 1), ("c", 3)].into_iter().collect();
+    /// assert_eq!(queue.iter().map(|e| *e.0).collect::<Vec<_>>(), ["b", "a", "c"]);
+    /// ```
+    pub fn iter(&self) -> Iter<'_, T, P> { Iter(self.items.iter()) }
+}
+
+impl<T, P, C> Clone for PriorityQueue<T, P, C>
+    where T: Clone, P: Clone, C: Compare<P> + Clone {
+
+    fn clone(&self) -> Self {
+        PriorityQueue {
+            items: self.items.clone(),
+            priorities: self.priorities.clone(),
+            next_id: self.next_id,
+        }
+    }
+}
+
+impl<T, P, C> Debug for PriorityQueue<T, P, C> where T: Debug, P: Debug, C: Compare<P> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T, P, C> Default for PriorityQueue<T, P, C> where C: Compare<P> + Default {
+    fn default() -> Self { PriorityQueue::with_cmp(C::default()) }
+}
+
+impl<T, P, C> Extend<(T, P)> for PriorityQueue<T, P, C> where P: Clone, C: Compare<P> {
+    fn extend<I: IntoIterator<Item=(T, P)>>(&mut self, it: I) {
+        for (item, priority) in it { self.push(item, priority); }
+    }
+}
+
+impl<T, P, C> iter::FromIterator<(T, P)> for PriorityQueue<T, P, C>
+    where P: Clone, C: Compare<P> + Default {
+
+    fn from_iter<I: IntoIterator<Item=(T, P)>>(it: I) -> Self {
+        let mut queue = PriorityQueue::default();
+        queue.extend(it);
+        queue
+    }
+}
+
+impl<'a, T, P, C> IntoIterator for &'a PriorityQueue<T, P, C> where C: Compare<P> {
+    type Item = (&'a T, &'a P);
+    type IntoIter = Iter<'a, T, P>;
+    fn into_iter(self) -> Iter<'a, T, P> { self.iter() }
+}
+
+impl<T, P, C> IntoIterator for PriorityQueue<T, P, C> where C: Compare<P> {
+    type Item = (T, P);
+    type IntoIter = IntoIter<T, P>;
+    fn into_iter(self) -> IntoIter<T, P> { IntoIter(self.items.into_iter()) }
+}
+
+/// An iterator that consumes the queue.
+///
+/// The iterator yields the items in ascending order of priority.
+///
+/// Acquire through the `IntoIterator` trait.
+pub struct IntoIter<T, P>(map::IntoIter<(P, u64), T>);
+
+impl<T, P> Iterator for IntoIter<T, P> {
+    type Item = (T, P);
+    fn next(&mut self) -> Option<Self::Item> { self.0.next().map(|((p, _), t)| (t, p)) }
+    fn size_hint(&self) -> (usize, Option<usize>) { self.0.size_hint() }
+}
+
+impl<T, P> DoubleEndedIterator for IntoIter<T, P> {
+    fn next_back(&mut self) -> Option<Self::Item> { self.0.next_back().map(|((p, _), t)| (t, p)) }
+}
+
+impl<T, P> ExactSizeIterator for IntoIter<T, P> {
+    fn len(&self) -> usize { self.0.len() }
+}
+
+/// An iterator over the queue's items and their priorities.
+///
+/// The iterator yields the items in ascending order of priority.
+///
+/// Acquire through [`PriorityQueue::iter`](struct.PriorityQueue.html#method.iter).
+pub struct Iter<'a, T: 'a, P: 'a>(map::Iter<'a, (P, u64), T>);
+
+impl<'a, T, P> Clone for Iter<'a, T, P> {
+    fn clone(&self) -> Self { Iter(self.0.clone()) }
+}
+
+impl<'a, T, P> Iterator for Iter<'a, T, P> {
+    type Item = (&'a T, &'a P);
+    fn next(&mut self) -> Option<Self::Item> { self.0.next().map(|(k, t)| (t, &k.0)) }
+    fn size_hint(&self) -> (usize, Option<usize>) { self.0.size_hint() }
+}
+
+impl<'a, T, P> DoubleEndedIterator for Iter<'a, T, P> {
+    fn next_back(&mut self) -> Option<Self::Item> { self.0.next_back().map(|(k, t)| (t, &k.0)) }
+}
+
+impl<'a, T, P> ExactSizeIterator for Iter<'a, T, P> {
+    fn len(&self) -> usize { self.0.len() }
+}
@@ -1,6 +1,8 @@
 extern crate quickcheck;
 
 use compare::Compare;
+use std::boxed::Box;
+use std::vec::Vec;
 use self::quickcheck::{Arbitrary, Gen};
 use super::{Map, Set};
 
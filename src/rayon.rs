@@ -0,0 +1,270 @@
+//! Parallel iteration and collection with `rayon`.
+//!
+//! `Map` and `Set` implement `IntoParallelIterator`, so `rayon`'s `par_iter`, `par_iter_mut` and
+//! `into_par_iter` are available on them. The iterators split their work along the boundaries of
+//! the tree's subtrees.
+//!
+//! `Map` and `Set` also implement `FromParallelIterator`. Collecting sorts the items in parallel
+//! and then builds the tree bottom-up in linear time. As with `FromIterator`, a later entry whose
+//! key is equal to that of an earlier one replaces the earlier entry's value.
+//!
+//! # Examples
+//!
+//! ```
+//! # extern crate rayon;
+//! # extern crate tree;
+//! # fn main() {
+//! use rayon::prelude::*;
+//!
+//! let map: tree::Map<u32, u32> = (0..1000).into_par_iter().map(|i| (i, i * 2)).collect();
+//!
+//! assert_eq!(map.len(), 1000);
+//! assert_eq!(map.par_iter().map(|e| *e.1 as u64).sum::<u64>(), 999_000);
+//! # }
+//! ```
+
+extern crate rayon;
+
+use alloc::vec::Vec;
+use compare::Compare;
+use node::NodeRef;
+use self::rayon::iter::{FromParallelIterator, IntoParallelIterator, ParallelIterator};
+use self::rayon::iter::plumbing::{Folder, UnindexedProducer};
+use self::rayon::slice::ParallelSliceMut;
+use super::{Map, Set};
+
+// Produces an optional entry followed by the entries of a subtree.
+//
+// Splitting a subtree yields its left subtree and its root followed by its right subtree, so both
+// halves have the same shape as the original.
+struct Producer<N> where N: NodeRef {
+    head: Option<N::Item>,
+    tree: Option<N>,
+}
+
+impl<N> Producer<N> where N: NodeRef {
+    fn new(root: Option<N>) -> Self { Producer { head: None, tree: root } }
+}
+
+impl<N> UnindexedProducer for Producer<N> where N: NodeRef + Send, N::Item: Send {
+    type Item = N::Item;
+
+    fn split(self) -> (Self, Option<Self>) {
+        if self.tree.is_none() { return (self, None); }
+
+        let mut node = self.tree.unwrap();
+        let left = node.left();
+        let right = node.right();
+
+        let head = Some(node.item());
+        (Producer { head: self.head, tree: left }, Some(Producer { head: head, tree: right }))
+    }
+
+    fn fold_with<F>(self, folder: F) -> F where F: Folder<Self::Item> {
+        fn fold<N, F>(link: Option<N>, folder: F) -> F where N: NodeRef, F: Folder<N::Item> {
+            match link {
+                None => folder,
+                Some(mut node) => {
+                    let right = node.right();
+                    let folder = fold(node.left(), folder);
+                    if folder.full() { return folder; }
+                    fold(right, folder.consume(node.item()))
+                }
+            }
+        }
+
+        fold(self.tree, folder.consume_iter(self.head))
+    }
+}
+
+/// Parallel iterators over maps.
+pub mod map {
+    use allocator::{Allocator, Global};
+    use compare::Compare;
+    use node::{MarkedNode, MutMarkedNode, NodeBox};
+    use super::Producer;
+    use super::rayon::iter::{IntoParallelIterator, ParallelIterator};
+    use super::rayon::iter::plumbing::{UnindexedConsumer, bridge_unindexed};
+    use super::super::Map;
+
+    /// A parallel iterator that consumes the map.
+    ///
+    /// Acquire through the `IntoParallelIterator` trait.
+    pub struct IntoIter<K, V, A = Global> where A: Allocator {
+        root: Option<NodeBox<K, V, A>>,
+    }
+
+    impl<K, V, A> ParallelIterator for IntoIter<K, V, A>
+        where K: Send, V: Send, A: Allocator + Send {
+
+        type Item = (K, V);
+
+        fn drive_unindexed<C>(self, consumer: C) -> C::Result
+            where C: UnindexedConsumer<Self::Item> {
+
+            bridge_unindexed(Producer::new(self.root), consumer)
+        }
+    }
+
+    /// A parallel iterator over the map's entries with immutable references to the values.
+    ///
+    /// Acquire through the `IntoParallelRefIterator` trait's `par_iter` method.
+    pub struct Iter<'a, K: 'a, V: 'a, A: 'a + Allocator = Global> {
+        root: Option<MarkedNode<'a, K, V, A>>,
+    }
+
+    impl<'a, K, V, A> ParallelIterator for Iter<'a, K, V, A>
+        where K: Sync, V: Sync, A: Allocator + Sync {
+
+        type Item = (&'a K, &'a V);
+
+        fn drive_unindexed<C>(self, consumer: C) -> C::Result
+            where C: UnindexedConsumer<Self::Item> {
+
+            bridge_unindexed(Producer::new(self.root), consumer)
+        }
+    }
+
+    /// A parallel iterator over the map's entries with mutable references to the values.
+    ///
+    /// Acquire through the `IntoParallelRefMutIterator` trait's `par_iter_mut` method.
+    pub struct IterMut<'a, K: 'a, V: 'a, A: 'a + Allocator = Global> {
+        root: Option<MutMarkedNode<'a, K, V, A>>,
+    }
+
+    impl<'a, K, V, A> ParallelIterator for IterMut<'a, K, V, A>
+        where K: Send + Sync, V: Send, A: Allocator + Send + Sync {
+
+        type Item = (&'a K, &'a mut V);
+
+        fn drive_unindexed<C>(self, consumer: C) -> C::Result
+            where C: UnindexedConsumer<Self::Item> {
+
+            bridge_unindexed(Producer::new(self.root), consumer)
+        }
+    }
+
+    impl<K, V, C, A> IntoParallelIterator for Map<K, V, C, A>
+        where K: Send, V: Send, C: Compare<K>, A: Allocator + Clone + Send {
+
+        type Item = (K, V);
+        type Iter = IntoIter<K, V, A>;
+
+        fn into_par_iter(mut self) -> Self::Iter {
+            IntoIter { root: self.root_mut().take() }
+        }
+    }
+
+    impl<'a, K, V, C, A> IntoParallelIterator for &'a Map<K, V, C, A>
+        where K: Sync, V: Sync, C: Compare<K>, A: Allocator + Clone + Sync {
+
+        type Item = (&'a K, &'a V);
+        type Iter = Iter<'a, K, V, A>;
+
+        fn into_par_iter(self) -> Self::Iter {
+            Iter { root: self.root().as_ref().map(MarkedNode::new) }
+        }
+    }
+
+    impl<'a, K, V, C, A> IntoParallelIterator for &'a mut Map<K, V, C, A>
+        where K: Send + Sync, V: Send, C: Compare<K>, A: Allocator + Clone + Send + Sync {
+
+        type Item = (&'a K, &'a mut V);
+        type Iter = IterMut<'a, K, V, A>;
+
+        fn into_par_iter(self) -> Self::Iter {
+            IterMut { root: self.root_mut().as_mut().map(MutMarkedNode::new) }
+        }
+    }
+}
+
+/// Parallel iterators over sets.
+pub mod set {
+    use allocator::{Allocator, Global};
+    use compare::Compare;
+    use super::map;
+    use super::rayon::iter::{IntoParallelIterator, ParallelIterator};
+    use super::rayon::iter::plumbing::UnindexedConsumer;
+    use super::super::Set;
+
+    /// A parallel iterator that consumes the set.
+    ///
+    /// Acquire through the `IntoParallelIterator` trait.
+    pub struct IntoIter<T, A = Global>(map::IntoIter<T, (), A>) where A: Allocator;
+
+    impl<T, A> ParallelIterator for IntoIter<T, A> where T: Send, A: Allocator + Send {
+        type Item = T;
+
+        fn drive_unindexed<C>(self, consumer: C) -> C::Result
+            where C: UnindexedConsumer<Self::Item> {
+
+            self.0.map(|e| e.0).drive_unindexed(consumer)
+        }
+    }
+
+    /// A parallel iterator over the set.
+    ///
+    /// Acquire through the `IntoParallelRefIterator` trait's `par_iter` method.
+    pub struct Iter<'a, T: 'a, A: 'a + Allocator = Global>(map::Iter<'a, T, (), A>);
+
+    impl<'a, T, A> ParallelIterator for Iter<'a, T, A> where T: Sync, A: Allocator + Sync {
+        type Item = &'a T;
+
+        fn drive_unindexed<C>(self, consumer: C) -> C::Result
+            where C: UnindexedConsumer<Self::Item> {
+
+            self.0.map(|e| e.0).drive_unindexed(consumer)
+        }
+    }
+
+    impl<T, C, A> IntoParallelIterator for Set<T, C, A>
+        where T: Send, C: Compare<T>, A: Allocator + Clone + Send {
+
+        type Item = T;
+        type Iter = IntoIter<T, A>;
+
+        fn into_par_iter(self) -> Self::Iter { IntoIter(self.into_map().into_par_iter()) }
+    }
+
+    impl<'a, T, C, A> IntoParallelIterator for &'a Set<T, C, A>
+        where T: Sync, C: Compare<T>, A: Allocator + Clone + Sync {
+
+        type Item = &'a T;
+        type Iter = Iter<'a, T, A>;
+
+        fn into_par_iter(self) -> Self::Iter { Iter(self.map().into_par_iter()) }
+    }
+}
+
+impl<K, V, C> FromParallelIterator<(K, V)> for Map<K, V, C>
+    where K: Send, V: Send, C: Compare<K> + Default + Sync {
+
+    fn from_par_iter<I>(it: I) -> Self where I: IntoParallelIterator<Item=(K, V)> {
+        let cmp = C::default();
+
+        let mut entries: Vec<(K, V)> = it.into_par_iter().collect();
+        entries.par_sort_by(|l, r| cmp.compare(&l.0, &r.0));
+
+        // The sort is stable, so the last of a run of equal keys is the most recent entry.
+        let mut deduped: Vec<(K, V)> = Vec::with_capacity(entries.len());
+
+        for e in entries {
+            match deduped.last_mut() {
+                Some(last) if cmp.compares_eq(&last.0, &e.0) => { last.1 = e.1; continue; }
+                _ => {}
+            }
+
+            deduped.push(e);
+        }
+
+        Map::from_sorted(deduped, cmp, Default::default())
+    }
+}
+
+impl<T, C> FromParallelIterator<T> for Set<T, C>
+    where T: Send, C: Compare<T> + Default + Sync {
+
+    fn from_par_iter<I>(it: I) -> Self where I: IntoParallelIterator<Item=T> {
+        Set::from_map(it.into_par_iter().map(|item| (item, ())).collect())
+    }
+}
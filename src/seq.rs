@@ -0,0 +1,423 @@
+//! A sequence based on a binary search tree.
+
+use alloc::vec::Vec;
+use allocator::Global;
+use core::cmp::Ordering::{self, Greater, Less};
+use core::fmt::{self, Debug};
+use core::hash::{self, Hash};
+use core::iter;
+use core::ops;
+use super::node::{self, Augment, Counters, MarkedNode, MutMarkedNode, NodeBox};
+use super::node::build::{Get, GetMut, PathBuilder};
+
+// Counts the entries in each subtree.
+#[derive(Clone)]
+struct Size(usize);
+
+impl<T> Augment<(), T> for Size {
+    fn new(_key: &(), _value: &T) -> Self { Size(1) }
+
+    fn update(&mut self, _key: &(), _value: &T, left: Option<&Self>, right: Option<&Self>) {
+        self.0 = 1 + left.map_or(0, |size| size.0) + right.map_or(0, |size| size.0);
+    }
+}
+
+type Node<T> = node::Node<(), T, Global, Size>;
+
+type Link<T> = node::Link<(), T, Global, Size>;
+
+fn len<T>(node: Option<&Node<T>>) -> usize { node.map_or(0, |node| node.aug().0) }
+
+// Builds a tree from the next `len` items of `it`.
+fn build<T, I>(it: &mut I, len: usize) -> Link<T> where I: Iterator<Item=T> {
+    node::from_sorted(&mut it.map(|item| ((), item)), len, &Global)
+}
+
+// Returns a function that directs a search to the item at the given index.
+fn at<T>(mut index: usize) -> impl FnMut(&Node<T>) -> Ordering {
+    move |node| {
+        let left = len(node.left_child());
+
+        match index.cmp(&left) {
+            Greater => { index -= left + 1; Greater }
+            order => order,
+        }
+    }
+}
+
+/// A sequence based on a binary search tree.
+///
+/// Items are ordered by position rather than by key. Each node records the size of its subtree,
+/// so inserting, removing and accessing an item at any position takes logarithmic time.
+///
+/// # Examples
+///
+/// ```
+/// let mut seq = tree::seq::Seq::new();
+///
+/// seq.push_back(2);
+/// seq.push_front(0);
+/// seq.insert(1, 1);
+///
+/// assert_eq!(seq[1], 1);
+/// assert_eq!(seq.iter().collect::<Vec<_>>(), [&0, &1, &2]);
+/// ```
+pub struct Seq<T> {
+    root: Link<T>,
+}
+
+impl<T> Seq<T> {
+    /// Creates an empty sequence.
+    pub fn new() -> Self { Seq { root: None } }
+
+    /// Checks if the sequence is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut seq = tree::seq::Seq::new();
+    /// assert!(seq.is_empty());
+    ///
+    /// seq.push_back(1);
+    /// assert!(!seq.is_empty());
+    /// ```
+    pub fn is_empty(&self) -> bool { self.root.is_none() }
+
+    /// Returns the number of items in the sequence.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut seq = tree::seq::Seq::new();
+    /// assert_eq!(seq.len(), 0);
+    ///
+    /// seq.push_back(1);
+    /// assert_eq!(seq.len(), 1);
+    /// ```
+    pub fn len(&self) -> usize { len(self.root.as_ref().map(|node| &**node)) }
+
+    /// Removes all items from the sequence.
+    pub fn clear(&mut self) { self.root = None; }
+
+    /// Inserts an item at the given index, shifting all items after it towards the back.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is greater than the sequence's length.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut seq: tree::seq::Seq<_> = vec![1, 3].into_iter().collect();
+    /// seq.insert(1, 2);
+    /// assert_eq!(seq.iter().collect::<Vec<_>>(), [&1, &2, &3]);
+    /// ```
+    pub fn insert(&mut self, index: usize, value: T) {
+        assert!(index <= self.len(), "index out of bounds");
+
+        let mut index = index;
+
+        node::insert_by(&mut self.root, &mut |_, node| {
+            let left = len(node.left_child());
+            if index <= left { Less } else { index -= left + 1; Greater }
+        }, (), value, &Global, &Counters::default());
+    }
+
+    /// Removes and returns the item at the given index, shifting all items after it towards the
+    /// front, or returns `None` if the index is out of bounds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut seq: tree::seq::Seq<_> = vec![1, 2, 3].into_iter().collect();
+    /// assert_eq!(seq.remove(1), Some(2));
+    /// assert_eq!(seq.remove(2), None);
+    /// assert_eq!(seq.iter().collect::<Vec<_>>(), [&1, &3]);
+    /// ```
+    pub fn remove(&mut self, index: usize) -> Option<T> {
+        if index >= self.len() { return None; }
+
+        node::seek(&mut self.root, PathBuilder::default(), at(index))
+            .take(&Counters::default()).map(|e| e.1)
+    }
+
+    /// Appends an item to the back of the sequence.
+    pub fn push_back(&mut self, value: T) {
+        let len = self.len();
+        self.insert(len, value);
+    }
+
+    /// Prepends an item to the front of the sequence.
+    pub fn push_front(&mut self, value: T) { self.insert(0, value); }
+
+    /// Removes and returns the item at the back of the sequence, or returns `None` if the
+    /// sequence is empty.
+    pub fn pop_back(&mut self) -> Option<T> {
+        match self.len() {
+            0 => None,
+            len => self.remove(len - 1),
+        }
+    }
+
+    /// Removes and returns the item at the front of the sequence, or returns `None` if the
+    /// sequence is empty.
+    pub fn pop_front(&mut self) -> Option<T> { self.remove(0) }
+
+    /// Returns a reference to the item at the given index, or `None` if the index is out of
+    /// bounds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let seq: tree::seq::Seq<_> = vec!["a", "b"].into_iter().collect();
+    /// assert_eq!(seq.get(1), Some(&"b"));
+    /// assert_eq!(seq.get(2), None);
+    /// ```
+    pub fn get(&self, index: usize) -> Option<&T> {
+        node::seek(&self.root, Get::default(), at(index)).map(|e| e.1)
+    }
+
+    /// Returns a mutable reference to the item at the given index, or `None` if the index is out
+    /// of bounds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut seq: tree::seq::Seq<_> = vec![1, 2].into_iter().collect();
+    /// *seq.get_mut(1).unwrap() = 3;
+    /// assert_eq!(seq[1], 3);
+    /// ```
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        node::seek(&mut self.root, GetMut::default(), at(index)).map(|e| e.1)
+    }
+
+    /// Returns a reference to the item at the front of the sequence, or `None` if the sequence
+    /// is empty.
+    pub fn front(&self) -> Option<&T> { self.get(0) }
+
+    /// Returns a reference to the item at the back of the sequence, or `None` if the sequence
+    /// is empty.
+    pub fn back(&self) -> Option<&T> { self.len().checked_sub(1).and_then(|i| self.get(i)) }
+
+    /// Splits the sequence in two at the given index, returning the items at and after the index.
+    ///
+    /// This takes logarithmic time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `at` is greater than the sequence's length.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut seq: tree::seq::Seq<_> = (0..5).collect();
+    /// let back = seq.split_off(2);
+    /// assert_eq!(seq.iter().collect::<Vec<_>>(), [&0, &1]);
+    /// assert_eq!(back.iter().collect::<Vec<_>>(), [&2, &3, &4]);
+    /// ```
+    pub fn split_off(&mut self, at: usize) -> Self {
+        assert!(at <= self.len(), "index out of bounds");
+
+        let mut index = at;
+
+        let (front, back) = node::split(self.root.take(), &mut |node| {
+            let left = len(node.left_child());
+            if index <= left { true } else { index -= left + 1; false }
+        }, &Counters::default());
+
+        self.root = front;
+        Seq { root: back }
+    }
+
+    /// Moves all items of the given sequence to the back of this one, leaving the given sequence
+    /// empty.
+    ///
+    /// This takes logarithmic time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut front: tree::seq::Seq<_> = (0..2).collect();
+    /// let mut back: tree::seq::Seq<_> = (2..4).collect();
+    ///
+    /// front.append(&mut back);
+    /// assert!(back.is_empty());
+    /// assert_eq!(front.iter().collect::<Vec<_>>(), [&0, &1, &2, &3]);
+    /// ```
+    pub fn append(&mut self, other: &mut Self) {
+        self.root = node::concat(self.root.take(), other.root.take(), &Global,
+                                 &Counters::default());
+    }
+
+    /// Returns an iterator over the sequence's items.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let seq: tree::seq::Seq<_> = (0..3).collect();
+    ///
+    /// let mut it = seq.iter();
+    /// assert_eq!(it.next(), Some(&0));
+    /// assert_eq!(it.next_back(), Some(&2));
+    /// assert_eq!(it.next(), Some(&1));
+    /// assert_eq!(it.next(), None);
+    /// ```
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter(node::Iter::new(self.root.as_ref().map(MarkedNode::new), self.len()))
+    }
+
+    /// Returns an iterator over the sequence's items with mutable references.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut seq: tree::seq::Seq<_> = (0..3).collect();
+    /// for item in seq.iter_mut() { *item *= 2; }
+    /// assert_eq!(seq.iter().collect::<Vec<_>>(), [&0, &2, &4]);
+    /// ```
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+        let len = self.len();
+        IterMut(node::Iter::new(self.root.as_mut().map(MutMarkedNode::new), len))
+    }
+}
+
+impl<T> Clone for Seq<T> where T: Clone {
+    fn clone(&self) -> Self { Seq { root: self.root.clone() } }
+}
+
+impl<T> Debug for Seq<T> where T: Debug {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { f.debug_list().entries(self).finish() }
+}
+
+impl<T> Default for Seq<T> {
+    fn default() -> Self { Seq::new() }
+}
+
+impl<T> Extend<T> for Seq<T> {
+    fn extend<I: IntoIterator<Item=T>>(&mut self, it: I) {
+        for item in it { self.push_back(item); }
+    }
+}
+
+impl<T> iter::FromIterator<T> for Seq<T> {
+    fn from_iter<I: IntoIterator<Item=T>>(it: I) -> Self {
+        let items: Vec<T> = it.into_iter().collect();
+        let len = items.len();
+        Seq { root: build(&mut items.into_iter(), len) }
+    }
+}
+
+impl<T> Hash for Seq<T> where T: Hash {
+    fn hash<H: hash::Hasher>(&self, h: &mut H) {
+        for item in self.iter() { item.hash(h); }
+    }
+}
+
+impl<T> ops::Index<usize> for Seq<T> {
+    type Output = T;
+    fn index(&self, index: usize) -> &T { self.get(index).expect("index out of bounds") }
+}
+
+impl<T> ops::IndexMut<usize> for Seq<T> {
+    fn index_mut(&mut self, index: usize) -> &mut T {
+        self.get_mut(index).expect("index out of bounds")
+    }
+}
+
+impl<'a, T> IntoIterator for &'a Seq<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+    fn into_iter(self) -> Iter<'a, T> { self.iter() }
+}
+
+impl<'a, T> IntoIterator for &'a mut Seq<T> {
+    type Item = &'a mut T;
+    type IntoIter = IterMut<'a, T>;
+    fn into_iter(self) -> IterMut<'a, T> { self.iter_mut() }
+}
+
+impl<T> IntoIterator for Seq<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(mut self) -> IntoIter<T> {
+        let len = self.len();
+        IntoIter(node::Iter::new(self.root.take(), len))
+    }
+}
+
+impl<T> PartialEq for Seq<T> where T: PartialEq {
+    fn eq(&self, other: &Self) -> bool { self.len() == other.len() && self.iter().eq(other) }
+}
+
+impl<T> Eq for Seq<T> where T: Eq {}
+
+impl<T> PartialOrd for Seq<T> where T: PartialOrd {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> { self.iter().partial_cmp(other) }
+}
+
+impl<T> Ord for Seq<T> where T: Ord {
+    fn cmp(&self, other: &Self) -> Ordering { self.iter().cmp(other) }
+}
+
+/// An iterator that consumes the sequence.
+///
+/// Acquire through the `IntoIterator` trait.
+pub struct IntoIter<T>(node::Iter<NodeBox<(), T, Global, Size>>);
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+    fn next(&mut self) -> Option<T> { self.0.next().map(|e| e.1) }
+    fn size_hint(&self) -> (usize, Option<usize>) { self.0.size_hint() }
+}
+
+impl<T> DoubleEndedIterator for IntoIter<T> {
+    fn next_back(&mut self) -> Option<T> { self.0.next_back().map(|e| e.1) }
+}
+
+impl<T> ExactSizeIterator for IntoIter<T> {
+    fn len(&self) -> usize { self.0.len() }
+}
+
+/// An iterator over the sequence's items.
+///
+/// Acquire through [`Seq::iter`](struct.Seq.html#method.iter) or the `IntoIterator` trait.
+pub struct Iter<'a, T: 'a>(node::Iter<MarkedNode<'a, (), T, Global, Size>>);
+
+impl<'a, T> Clone for Iter<'a, T> {
+    fn clone(&self) -> Self { Iter(self.0.clone()) }
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<&'a T> { self.0.next().map(|e| e.1) }
+    fn size_hint(&self) -> (usize, Option<usize>) { self.0.size_hint() }
+}
+
+impl<'a, T> DoubleEndedIterator for Iter<'a, T> {
+    fn next_back(&mut self) -> Option<&'a T> { self.0.next_back().map(|e| e.1) }
+}
+
+impl<'a, T> ExactSizeIterator for Iter<'a, T> {
+    fn len(&self) -> usize { self.0.len() }
+}
+
+/// An iterator over the sequence's items with mutable references.
+///
+/// Acquire through [`Seq::iter_mut`](struct.Seq.html#method.iter_mut) or the `IntoIterator`
+/// trait.
+pub struct IterMut<'a, T: 'a>(node::Iter<MutMarkedNode<'a, (), T, Global, Size>>);
+
+impl<'a, T> Iterator for IterMut<'a, T> {
+    type Item = &'a mut T;
+    fn next(&mut self) -> Option<&'a mut T> { self.0.next().map(|e| e.1) }
+    fn size_hint(&self) -> (usize, Option<usize>) { self.0.size_hint() }
+}
+
+impl<'a, T> DoubleEndedIterator for IterMut<'a, T> {
+    fn next_back(&mut self) -> Option<&'a mut T> { self.0.next_back().map(|e| e.1) }
+}
+
+impl<'a, T> ExactSizeIterator for IterMut<'a, T> {
+    fn len(&self) -> usize { self.0.len() }
+}
@@ -1,11 +1,12 @@
 //! An ordered set based on a binary search tree.
 
+use allocator::{Allocator, Global};
 use compare::{Compare, Natural};
-use std::cmp::Ordering;
-#[cfg(feature = "range")] use std::collections::Bound;
-use std::fmt::{self, Debug};
-use std::hash::{self, Hash};
-use std::iter;
+use core::cmp::Ordering;
+use core::fmt::{self, Debug};
+use core::hash::{self, Hash};
+use core::iter;
+#[cfg(feature = "range")] use core::ops::Bound;
 use super::map::{self, Map};
 
 /// An ordered set based on a binary search tree.
@@ -14,8 +15,8 @@ use super::map::{self, Map};
 /// while the item is in the set. This is normally only possible through `Cell`, `RefCell`, or
 /// unsafe code.
 #[derive(Clone)]
-pub struct Set<T, C = Natural<T>> where C: Compare<T> {
-    map: Map<T, (), C>,
+pub struct Set<T, C = Natural<T>, A = Global> where C: Compare<T>, A: Allocator {
+    map: Map<T, (), C, A>,
 }
 
 impl<T> Set<T> where T: Ord {
@@ -64,6 +65,59 @@ impl<T, C> Set<T, C> where C: Compare<T> {
     /// # }
     /// ```
     pub fn with_cmp(cmp: C) -> Self { Set { map: Map::with_cmp(cmp) } }
+}
+
+impl<T, A> Set<T, Natural<T>, A> where T: Ord, A: Allocator + Clone {
+    /// Creates an empty set ordered according to the natural order of its items, whose nodes are
+    /// allocated in the given allocator.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #![cfg_attr(feature = "allocator_api", feature(allocator_api))]
+    /// let mut set = tree::Set::new_in(tree::allocator::Global);
+    ///
+    /// set.insert(2);
+    /// set.insert(1);
+    /// set.insert(3);
+    ///
+    /// let mut it = set.iter();
+    /// assert_eq!(it.next(), Some(&1));
+    /// assert_eq!(it.next(), Some(&2));
+    /// assert_eq!(it.next(), Some(&3));
+    /// assert_eq!(it.next(), None);
+    /// ```
+    pub fn new_in(alloc: A) -> Self { Set { map: Map::new_in(alloc) } }
+}
+
+impl<T, C, A> Set<T, C, A> where C: Compare<T>, A: Allocator + Clone {
+    /// Creates an empty set ordered according to the given comparator, whose nodes are allocated
+    /// in the given allocator.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #![cfg_attr(feature = "allocator_api", feature(allocator_api))]
+    /// # extern crate compare;
+    /// # extern crate tree;
+    /// # fn main() {
+    /// use compare::{Compare, natural};
+    /// use tree::allocator::Global;
+    ///
+    /// let mut set = tree::Set::with_cmp_in(natural().rev(), Global);
+    ///
+    /// set.insert(2);
+    /// set.insert(1);
+    /// set.insert(3);
+    ///
+    /// let mut it = set.iter();
+    /// assert_eq!(it.next(), Some(&3));
+    /// assert_eq!(it.next(), Some(&2));
+    /// assert_eq!(it.next(), Some(&1));
+    /// assert_eq!(it.next(), None);
+    /// # }
+    /// ```
+    pub fn with_cmp_in(cmp: C, alloc: A) -> Self { Set { map: Map::with_cmp_in(cmp, alloc) } }
 
     /// Checks if the set is empty.
     ///
@@ -110,6 +164,9 @@ impl<T, C> Set<T, C> where C: Compare<T> {
     /// ```
     pub fn cmp(&self) -> &C { self.map.cmp() }
 
+    /// Returns a reference to the set's allocator.
+    pub fn allocator(&self) -> &A { self.map.allocator() }
+
     /// Removes all items from the set.
     ///
     /// # Examples
@@ -197,7 +254,7 @@ impl<T, C> Set<T, C> where C: Compare<T> {
     ///
     /// assert!(set.contains(&4));
     /// ```
-    pub fn entry(&mut self, item: T) -> Entry<T> {
+    pub fn entry(&mut self, item: T) -> Entry<'_, T, A> {
         match self.map.entry(item) {
             map::Entry::Occupied(e) => Entry::Occupied(OccupiedEntry(e)),
             map::Entry::Vacant(e) => Entry::Vacant(VacantEntry(e)),
@@ -270,7 +327,7 @@ impl<T, C> Set<T, C> where C: Compare<T> {
     ///
     /// assert!(!set.contains(&3));
     /// ```
-    pub fn max_entry(&mut self) -> Option<OccupiedEntry<T>> {
+    pub fn max_entry(&mut self) -> Option<OccupiedEntry<'_, T, A>> {
         self.map.max_entry().map(OccupiedEntry)
     }
 
@@ -326,7 +383,7 @@ impl<T, C> Set<T, C> where C: Compare<T> {
     ///
     /// assert!(!set.contains(&1));
     /// ```
-    pub fn min_entry(&mut self) -> Option<OccupiedEntry<T>> {
+    pub fn min_entry(&mut self) -> Option<OccupiedEntry<'_, T, A>> {
         self.map.min_entry().map(OccupiedEntry)
     }
 
@@ -429,7 +486,7 @@ impl<T, C> Set<T, C> where C: Compare<T> {
     /// assert!(!set.contains(&2));
     /// ```
     pub fn pred_entry<Q: ?Sized>(&mut self, item: &Q, inclusive: bool)
-        -> Option<OccupiedEntry<T>> where C: Compare<Q, T> {
+        -> Option<OccupiedEntry<'_, T, A>> where C: Compare<Q, T> {
 
         self.map.pred_entry(item, inclusive).map(OccupiedEntry)
     }
@@ -534,7 +591,7 @@ impl<T, C> Set<T, C> where C: Compare<T> {
     /// assert!(!set.contains(&2));
     /// ```
     pub fn succ_entry<Q: ?Sized>(&mut self, item: &Q, inclusive: bool)
-        -> Option<OccupiedEntry<T>> where C: Compare<Q, T> {
+        -> Option<OccupiedEntry<'_, T, A>> where C: Compare<Q, T> {
 
         self.map.succ_entry(item, inclusive).map(OccupiedEntry)
     }
@@ -558,11 +615,20 @@ impl<T, C> Set<T, C> where C: Compare<T> {
     /// assert_eq!(it.next(), Some(&3));
     /// assert_eq!(it.next(), None);
     /// ```
-    pub fn iter(&self) -> Iter<T> { Iter(self.map.iter()) }
+    pub fn iter(&self) -> Iter<'_, T, A> { Iter(self.map.iter()) }
+
+    #[cfg(feature = "rayon")]
+    pub(crate) fn from_map(map: Map<T, (), C, A>) -> Self { Set { map: map } }
+
+    #[cfg(feature = "rayon")]
+    pub(crate) fn map(&self) -> &Map<T, (), C, A> { &self.map }
+
+    #[cfg(feature = "rayon")]
+    pub(crate) fn into_map(self) -> Map<T, (), C, A> { self.map }
 }
 
 #[cfg(feature = "range")]
-impl<T, C> Set<T, C> where C: Compare<T> {
+impl<T, C, A> Set<T, C, A> where C: Compare<T>, A: Allocator + Clone {
     /// Returns an iterator that consumes the set, yielding only those items that lie in the given
     /// range.
     ///
@@ -571,10 +637,7 @@ impl<T, C> Set<T, C> where C: Compare<T> {
     /// # Examples
     ///
     /// ```
-    /// # #![feature(collections_bound)]
-    /// # extern crate tree;
-    /// # fn main() {
-    /// use std::collections::Bound::{Excluded, Unbounded};
+    /// use std::ops::Bound::{Excluded, Unbounded};
     ///
     /// let mut set = tree::Set::new();
     ///
@@ -583,10 +646,9 @@ impl<T, C> Set<T, C> where C: Compare<T> {
     /// set.insert(3);
     ///
     /// assert_eq!(set.into_range(Excluded(&1), Unbounded).collect::<Vec<_>>(), [2, 3]);
-    /// # }
     /// ```
     pub fn into_range<Min: ?Sized, Max: ?Sized>(self, min: Bound<&Min>, max: Bound<&Max>)
-        -> IntoRange<T> where C: Compare<Min, T> + Compare<Max, T> {
+        -> IntoRange<T, A> where C: Compare<Min, T> + Compare<Max, T> {
 
         IntoRange(self.map.into_range(min, max))
     }
@@ -598,10 +660,7 @@ impl<T, C> Set<T, C> where C: Compare<T> {
     /// # Examples
     ///
     /// ```
-    /// # #![feature(collections_bound)]
-    /// # extern crate tree;
-    /// # fn main() {
-    /// use std::collections::Bound::{Included, Excluded, Unbounded};
+    /// use std::ops::Bound::{Included, Excluded, Unbounded};
     ///
     /// let mut set = tree::Set::new();
     ///
@@ -612,16 +671,15 @@ impl<T, C> Set<T, C> where C: Compare<T> {
     /// assert_eq!(set.range(Unbounded, Unbounded).collect::<Vec<_>>(), [&1, &2, &3]);
     /// assert_eq!(set.range(Excluded(&1), Included(&5)).collect::<Vec<_>>(), [&2, &3]);
     /// assert_eq!(set.range(Included(&1), Excluded(&2)).collect::<Vec<_>>(), [&1]);
-    /// # }
     /// ```
     pub fn range<Min: ?Sized, Max: ?Sized>(&self, min: Bound<&Min>, max: Bound<&Max>)
-        -> Range<T> where C: Compare<Min, T> + Compare<Max, T> {
+        -> Range<'_, T, A> where C: Compare<Min, T> + Compare<Max, T> {
 
         Range(self.map.range(min, max))
     }
 }
 
-impl<T, C> Debug for Set<T, C> where T: Debug, C: Compare<T> {
+impl<T, C, A> Debug for Set<T, C, A> where T: Debug, C: Compare<T>, A: Allocator + Clone {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_set().entries(self).finish()
     }
@@ -631,7 +689,7 @@ impl<T, C> Default for Set<T, C> where C: Compare<T> + Default {
     fn default() -> Self { Set::with_cmp(C::default()) }
 }
 
-impl<T, C> Extend<T> for Set<T, C> where C: Compare<T> {
+impl<T, C, A> Extend<T> for Set<T, C, A> where C: Compare<T>, A: Allocator + Clone {
     fn extend<I: IntoIterator<Item=T>>(&mut self, it: I) {
         for item in it { self.insert(item); }
     }
@@ -645,19 +703,19 @@ impl<T, C> iter::FromIterator<T> for Set<T, C> where C: Compare<T> + Default {
     }
 }
 
-impl<T, C> Hash for Set<T, C> where T: Hash, C: Compare<T> {
+impl<T, C, A> Hash for Set<T, C, A> where T: Hash, C: Compare<T>, A: Allocator + Clone {
     fn hash<H: hash::Hasher>(&self, h: &mut H) { self.map.hash(h); }
 }
 
-impl<'a, T, C> IntoIterator for &'a Set<T, C> where C: Compare<T> {
+impl<'a, T, C, A> IntoIterator for &'a Set<T, C, A> where C: Compare<T>, A: Allocator + Clone {
     type Item = &'a T;
-    type IntoIter = Iter<'a, T>;
-    fn into_iter(self) -> Iter<'a, T> { self.iter() }
+    type IntoIter = Iter<'a, T, A>;
+    fn into_iter(self) -> Iter<'a, T, A> { self.iter() }
 }
 
-impl<T, C> IntoIterator for Set<T, C> where C: Compare<T> {
+impl<T, C, A> IntoIterator for Set<T, C, A> where C: Compare<T>, A: Allocator + Clone {
     type Item = T;
-    type IntoIter = IntoIter<T>;
+    type IntoIter = IntoIter<T, A>;
 
     /// Returns an iterator that consumes the set.
     ///
@@ -678,22 +736,22 @@ impl<T, C> IntoIterator for Set<T, C> where C: Compare<T> {
     /// assert_eq!(it.next(), Some(3));
     /// assert_eq!(it.next(), None);
     /// ```
-    fn into_iter(self) -> IntoIter<T> { IntoIter(self.map.into_iter()) }
+    fn into_iter(self) -> IntoIter<T, A> { IntoIter(self.map.into_iter()) }
 }
 
-impl<T, C> PartialEq for Set<T, C> where C: Compare<T> {
+impl<T, C, A> PartialEq for Set<T, C, A> where C: Compare<T>, A: Allocator + Clone {
     fn eq(&self, other: &Self) -> bool { self.map == other.map }
 }
 
-impl<T, C> Eq for Set<T, C> where C: Compare<T> {}
+impl<T, C, A> Eq for Set<T, C, A> where C: Compare<T>, A: Allocator + Clone {}
 
-impl<T, C> PartialOrd for Set<T, C> where C: Compare<T> {
+impl<T, C, A> PartialOrd for Set<T, C, A> where C: Compare<T>, A: Allocator + Clone {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         self.map.partial_cmp(&other.map)
     }
 }
 
-impl<T, C> Ord for Set<T, C> where C: Compare<T> {
+impl<T, C, A> Ord for Set<T, C, A> where C: Compare<T>, A: Allocator + Clone {
     fn cmp(&self, other: &Self) -> Ordering { Ord::cmp(&self.map, &other.map) }
 }
 
@@ -717,9 +775,9 @@ impl<T, C> Ord for Set<T, C> where C: Compare<T> {
 /// }
 /// ```
 #[derive(Clone)]
-pub struct IntoIter<T>(map::IntoIter<T, ()>);
+pub struct IntoIter<T, A = Global>(map::IntoIter<T, (), A>) where A: Allocator;
 
-impl<T> Iterator for IntoIter<T> {
+impl<T, A> Iterator for IntoIter<T, A> where A: Allocator {
     type Item = T;
     fn next(&mut self) -> Option<Self::Item> { self.0.next().map(|e| e.0) }
     fn size_hint(&self) -> (usize, Option<usize>) { self.0.size_hint() }
@@ -727,11 +785,11 @@ impl<T> Iterator for IntoIter<T> {
     fn last(self) -> Option<Self::Item> { self.0.last().map(|e| e.0) }
 }
 
-impl<T> DoubleEndedIterator for IntoIter<T> {
+impl<T, A> DoubleEndedIterator for IntoIter<T, A> where A: Allocator {
     fn next_back(&mut self) -> Option<Self::Item> { self.0.next_back().map(|e| e.0) }
 }
 
-impl<T> ExactSizeIterator for IntoIter<T> {
+impl<T, A> ExactSizeIterator for IntoIter<T, A> where A: Allocator {
     fn len(&self) -> usize { self.0.len() }
 }
 
@@ -754,13 +812,13 @@ impl<T> ExactSizeIterator for IntoIter<T> {
 ///     println!("{:?}", item);
 /// }
 /// ```
-pub struct Iter<'a, T: 'a>(map::Iter<'a, T, ()>);
+pub struct Iter<'a, T: 'a, A: 'a + Allocator = Global>(map::Iter<'a, T, (), A>);
 
-impl<'a, T> Clone for Iter<'a, T> {
+impl<'a, T, A> Clone for Iter<'a, T, A> where A: Allocator {
     fn clone(&self) -> Self { Iter(self.0.clone()) }
 }
 
-impl<'a, T> Iterator for Iter<'a, T> {
+impl<'a, T, A> Iterator for Iter<'a, T, A> where A: Allocator {
     type Item = &'a T;
     fn next(&mut self) -> Option<Self::Item> { self.0.next().map(|e| e.0) }
     fn size_hint(&self) -> (usize, Option<usize>) { self.0.size_hint() }
@@ -768,11 +826,11 @@ impl<'a, T> Iterator for Iter<'a, T> {
     fn last(self) -> Option<Self::Item> { self.0.last().map(|e| e.0) }
 }
 
-impl<'a, T> DoubleEndedIterator for Iter<'a, T> {
+impl<'a, T, A> DoubleEndedIterator for Iter<'a, T, A> where A: Allocator {
     fn next_back(&mut self) -> Option<Self::Item> { self.0.next_back().map(|e| e.0) }
 }
 
-impl<'a, T> ExactSizeIterator for Iter<'a, T> {
+impl<'a, T, A> ExactSizeIterator for Iter<'a, T, A> where A: Allocator {
     fn len(&self) -> usize { self.0.len() }
 }
 
@@ -783,10 +841,10 @@ impl<'a, T> ExactSizeIterator for Iter<'a, T> {
 /// Acquire through [`Set::into_range`](struct.Set.html#method.into_range).
 #[cfg(feature = "range")]
 #[derive(Clone)]
-pub struct IntoRange<T>(map::IntoRange<T, ()>);
+pub struct IntoRange<T, A = Global>(map::IntoRange<T, (), A>) where A: Allocator;
 
 #[cfg(feature = "range")]
-impl<T> Iterator for IntoRange<T> {
+impl<T, A> Iterator for IntoRange<T, A> where A: Allocator {
     type Item = T;
     fn next(&mut self) -> Option<Self::Item> { self.0.next().map(|e| e.0) }
     fn size_hint(&self) -> (usize, Option<usize>) { self.0.size_hint() }
@@ -794,7 +852,7 @@ impl<T> Iterator for IntoRange<T> {
 }
 
 #[cfg(feature = "range")]
-impl<T> DoubleEndedIterator for IntoRange<T> {
+impl<T, A> DoubleEndedIterator for IntoRange<T, A> where A: Allocator {
     fn next_back(&mut self) -> Option<Self::Item> { self.0.next_back().map(|e| e.0) }
 }
 
@@ -804,15 +862,15 @@ impl<T> DoubleEndedIterator for IntoRange<T> {
 ///
 /// Acquire through [`Set::range`](struct.Set.html#method.range).
 #[cfg(feature = "range")]
-pub struct Range<'a, T: 'a>(map::Range<'a, T, ()>);
+pub struct Range<'a, T: 'a, A: 'a + Allocator = Global>(map::Range<'a, T, (), A>);
 
 #[cfg(feature = "range")]
-impl<'a, T> Clone for Range<'a, T> {
+impl<'a, T, A> Clone for Range<'a, T, A> where A: Allocator {
     fn clone(&self) -> Self { Range(self.0.clone()) }
 }
 
 #[cfg(feature = "range")]
-impl<'a, T> Iterator for Range<'a, T> {
+impl<'a, T, A> Iterator for Range<'a, T, A> where A: Allocator {
     type Item = &'a T;
     fn next(&mut self) -> Option<Self::Item> { self.0.next().map(|e| e.0) }
     fn size_hint(&self) -> (usize, Option<usize>) { self.0.size_hint() }
@@ -820,22 +878,22 @@ impl<'a, T> Iterator for Range<'a, T> {
 }
 
 #[cfg(feature = "range")]
-impl<'a, T> DoubleEndedIterator for Range<'a, T> {
+impl<'a, T, A> DoubleEndedIterator for Range<'a, T, A> where A: Allocator {
     fn next_back(&mut self) -> Option<Self::Item> { self.0.next_back().map(|e| e.0) }
 }
 
 /// An entry in the set.
-pub enum Entry<'a, T: 'a> {
+pub enum Entry<'a, T: 'a, A: 'a + Allocator = Global> {
     /// An occupied entry.
-    Occupied(OccupiedEntry<'a, T>),
+    Occupied(OccupiedEntry<'a, T, A>),
     /// A vacant entry.
-    Vacant(VacantEntry<'a, T>),
+    Vacant(VacantEntry<'a, T, A>),
 }
 
 /// An occupied entry.
-pub struct OccupiedEntry<'a, T: 'a>(map::OccupiedEntry<'a, T, ()>);
+pub struct OccupiedEntry<'a, T: 'a, A: 'a + Allocator = Global>(map::OccupiedEntry<'a, T, (), A>);
 
-impl<'a, T> OccupiedEntry<'a, T> {
+impl<'a, T, A> OccupiedEntry<'a, T, A> where A: Allocator {
     /// Returns a reference to the entry's item.
     pub fn get(&self) -> &T { self.0.key() }
 
@@ -844,9 +902,9 @@ impl<'a, T> OccupiedEntry<'a, T> {
 }
 
 /// A vacant entry.
-pub struct VacantEntry<'a, T: 'a>(map::VacantEntry<'a, T, ()>);
+pub struct VacantEntry<'a, T: 'a, A: 'a + Allocator = Global>(map::VacantEntry<'a, T, (), A>);
 
-impl<'a, T> VacantEntry<'a, T> {
+impl<'a, T, A> VacantEntry<'a, T, A> where A: Allocator + Clone {
     /// Inserts the entry into the set with its item.
     pub fn insert(self) { self.0.insert(()); }
 }
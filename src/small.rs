@@ -0,0 +1,1052 @@
+//! An ordered map that stores few entries inline.
+//!
+//! A `SmallMap` stores up to `N` entries in a sorted array inside the map itself, so that a small
+//! map performs no allocation and its lookups are a binary search over contiguous memory. When an
+//! insertion would exceed `N` entries, the map is promoted to a `Map` in linear time, and remains
+//! one afterwards.
+//!
+//! Either way, it offers the same API for lookups, insertions, removals, entries, neighbors,
+//! combining insertions, iteration and ranges as `Map`. It does not offer `Map`'s methods that
+//! take an allocator, borrowed lookups, `nearest` and `k_nearest`, `longest_prefix_of` and
+//! `prefix_range`, `diff` and `apply_patch`, `transaction`, `freeze`, `augment`, `resort_with`,
+//! `into_range` or statistics.
+//!
+//! # Examples
+//!
+//! ```
+//! use tree::small::SmallMap;
+//!
+//! let mut map: SmallMap<_, _, _, 2> = SmallMap::new();
+//!
+//! map.insert(2, "b");
+//! map.insert(1, "a");
+//! assert!(map.is_inline());
+//!
+//! map.insert(3, "c");
+//! assert!(!map.is_inline());
+//!
+//! assert_eq!(map.pred(&3, false), Some((&2, &"b")));
+//! assert_eq!(map.iter().collect::<Vec<_>>(), [(&1, &"a"), (&2, &"b"), (&3, &"c")]);
+//! ```
+
+use alloc::vec::Vec;
+use allocator::Global;
+use compare::{Compare, Natural};
+use core::array;
+use core::cmp::Ordering::*;
+use core::fmt::{self, Debug};
+use core::hash::{self, Hash};
+use core::iter;
+use core::mem;
+#[cfg(feature = "range")] use core::ops::Bound;
+use core::ops;
+use core::slice;
+use map::{self, Map};
+
+// A sorted array of at most `N` entries, whose first `len` slots are occupied.
+#[derive(Clone)]
+struct Inline<K, V, C, const N: usize> {
+    entries: [Option<(K, V)>; N],
+    len: usize,
+    // Only taken while the map is promoted.
+    cmp: Option<C>,
+}
+
+impl<K, V, C, const N: usize> Inline<K, V, C, N> where C: Compare<K> {
+    fn new(cmp: C) -> Self {
+        Inline { entries: array::from_fn(|_| None), len: 0, cmp: Some(cmp) }
+    }
+
+    fn cmp(&self) -> &C { self.cmp.as_ref().expect("inline map has no comparator") }
+
+    fn entry(&self, i: usize) -> (&K, &V) {
+        let entry = self.entries[i].as_ref().expect("inline entry is vacant");
+        (&entry.0, &entry.1)
+    }
+
+    fn entry_mut(&mut self, i: usize) -> (&K, &mut V) {
+        let entry = self.entries[i].as_mut().expect("inline entry is vacant");
+        (&entry.0, &mut entry.1)
+    }
+
+    // Returns the number of keys less than the given key, or, if `after_equal`, less than or equal
+    // to it.
+    fn rank<Q: ?Sized>(&self, key: &Q, after_equal: bool) -> usize where C: Compare<Q, K> {
+        let (mut lo, mut hi) = (0, self.len);
+
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+
+            match self.cmp().compare(key, self.entry(mid).0) {
+                Greater => lo = mid + 1,
+                Equal if after_equal => lo = mid + 1,
+                _ => hi = mid,
+            }
+        }
+
+        lo
+    }
+
+    // Returns the index of the given key, or the index at which it would be inserted.
+    fn find<Q: ?Sized>(&self, key: &Q) -> Result<usize, usize> where C: Compare<Q, K> {
+        let i = self.rank(key, false);
+
+        if i < self.len && self.cmp().compares_eq(key, self.entry(i).0) { Ok(i) } else { Err(i) }
+    }
+
+    fn pred<Q: ?Sized>(&self, key: &Q, inclusive: bool) -> Option<usize> where C: Compare<Q, K> {
+        match self.rank(key, inclusive) { 0 => None, i => Some(i - 1) }
+    }
+
+    fn succ<Q: ?Sized>(&self, key: &Q, inclusive: bool) -> Option<usize> where C: Compare<Q, K> {
+        let i = self.rank(key, !inclusive);
+        if i < self.len { Some(i) } else { None }
+    }
+
+    // Inserts the entry at the given index, which must not exceed `len`, shifting later entries.
+    fn insert(&mut self, i: usize, key: K, value: V) {
+        self.entries[self.len] = Some((key, value));
+        self.entries[i..self.len + 1].rotate_right(1);
+        self.len += 1;
+    }
+
+    fn remove(&mut self, i: usize) -> (K, V) {
+        let entry = self.entries[i].take().expect("inline entry is vacant");
+        self.entries[i..self.len].rotate_left(1);
+        self.len -= 1;
+        entry
+    }
+
+    fn slice(&self) -> &[Option<(K, V)>] { &self.entries[..self.len] }
+
+    // Returns the indices of the first entry in the range and of the entry after the last one.
+    #[cfg(feature = "range")]
+    fn range<Min: ?Sized, Max: ?Sized>(&self, min: Bound<&Min>, max: Bound<&Max>)
+        -> (usize, usize) where C: Compare<Min, K> + Compare<Max, K> {
+
+        let front = match min {
+            Bound::Included(key) => self.rank(key, false),
+            Bound::Excluded(key) => self.rank(key, true),
+            Bound::Unbounded => 0,
+        };
+
+        let back = match max {
+            Bound::Included(key) => self.rank(key, true),
+            Bound::Excluded(key) => self.rank(key, false),
+            Bound::Unbounded => self.len,
+        };
+
+        (front, if back < front { front } else { back })
+    }
+}
+
+#[derive(Clone)]
+enum Repr<K, V, C, const N: usize> where C: Compare<K> {
+    Inline(Inline<K, V, C, N>),
+    Tree(Map<K, V, C>),
+}
+
+impl<K, V, C, const N: usize> Repr<K, V, C, N> where C: Compare<K> {
+    // Converts an inline map to a tree, returning the tree.
+    fn promote(&mut self) -> &mut Map<K, V, C> {
+        let tree = match *self {
+            Repr::Inline(ref mut inline) => {
+                let len = mem::replace(&mut inline.len, 0);
+                let entries: Vec<_> = inline.entries[..len].iter_mut()
+                    .map(|e| e.take().expect("inline entry is vacant")).collect();
+                let cmp = inline.cmp.take().expect("inline map has no comparator");
+                Map::from_sorted(entries, cmp, Global)
+            }
+            Repr::Tree(ref mut tree) => return tree,
+        };
+
+        *self = Repr::Tree(tree);
+        match *self { Repr::Tree(ref mut tree) => tree, Repr::Inline(_) => unreachable!() }
+    }
+}
+
+/// An ordered map that stores up to `N` entries inline.
+///
+/// See the [module documentation](index.html) for details.
+#[derive(Clone)]
+pub struct SmallMap<K, V, C = Natural<K>, const N: usize = 8> where C: Compare<K> {
+    repr: Repr<K, V, C, N>,
+}
+
+impl<K, V, const N: usize> SmallMap<K, V, Natural<K>, N> where K: Ord {
+    /// Creates an empty map ordered according to the natural order of its keys.
+    pub fn new() -> Self { SmallMap::with_cmp(Natural::default()) }
+}
+
+impl<K, V, C, const N: usize> SmallMap<K, V, C, N> where C: Compare<K> {
+    /// Creates an empty map ordered according to the given comparator.
+    pub fn with_cmp(cmp: C) -> Self { SmallMap { repr: Repr::Inline(Inline::new(cmp)) } }
+
+    /// Checks if the map stores its entries inline, rather than in a `Map`.
+    pub fn is_inline(&self) -> bool {
+        match self.repr { Repr::Inline(_) => true, Repr::Tree(_) => false }
+    }
+
+    /// Checks if the map is empty.
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+
+    /// Returns the number of entries in the map.
+    pub fn len(&self) -> usize {
+        match self.repr {
+            Repr::Inline(ref inline) => inline.len,
+            Repr::Tree(ref tree) => tree.len(),
+        }
+    }
+
+    /// Returns a reference to the map's comparator.
+    pub fn cmp(&self) -> &C {
+        match self.repr {
+            Repr::Inline(ref inline) => inline.cmp(),
+            Repr::Tree(ref tree) => tree.cmp(),
+        }
+    }
+
+    /// Removes all entries from the map.
+    ///
+    /// A promoted map remains promoted.
+    pub fn clear(&mut self) {
+        match self.repr {
+            Repr::Inline(ref mut inline) => {
+                for entry in &mut inline.entries[..inline.len] { *entry = None; }
+                inline.len = 0;
+            }
+            Repr::Tree(ref mut tree) => tree.clear(),
+        }
+    }
+
+    /// Inserts an entry into the map, returning the previous value, if any, associated with the
+    /// key.
+    ///
+    /// As with `Map::insert`, the key is not updated if the map already contains it.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        match self.entry(key) {
+            Entry::Occupied(mut e) => Some(e.insert(value)),
+            Entry::Vacant(e) => { e.insert(value); None }
+        }
+    }
+
+    /// Removes and returns the entry whose key is equal to the given key, returning `None` if the
+    /// map does not contain the key.
+    pub fn remove<Q: ?Sized>(&mut self, key: &Q) -> Option<(K, V)> where C: Compare<Q, K> {
+        match self.repr {
+            Repr::Inline(ref mut inline) => inline.find(key).ok().map(|i| inline.remove(i)),
+            Repr::Tree(ref mut tree) => tree.remove(key),
+        }
+    }
+
+    /// Returns the map's entry corresponding to the given key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tree::small::{Entry, SmallMap};
+    ///
+    /// let mut counts: SmallMap<_, _> = SmallMap::new();
+    ///
+    /// for s in vec!["a", "b", "a", "c", "a", "b"] {
+    ///     match counts.entry(s) {
+    ///         Entry::Occupied(mut e) => *e.get_mut() += 1,
+    ///         Entry::Vacant(e) => { e.insert(1); }
+    ///     }
+    /// }
+    ///
+    /// assert_eq!(counts.iter().collect::<Vec<_>>(), [(&"a", &3), (&"b", &2), (&"c", &1)]);
+    /// ```
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, C, N> {
+        let index = match self.repr {
+            Repr::Inline(ref inline) => inline.find(&key),
+            Repr::Tree(_) => Err(0),
+        };
+
+        match (index, &mut self.repr) {
+            (Ok(i), &mut Repr::Inline(ref mut inline)) =>
+                Entry::Occupied(OccupiedEntry(OccupiedRepr::Inline(inline, i))),
+            (Err(i), repr @ &mut Repr::Inline(_)) =>
+                Entry::Vacant(VacantEntry(VacantRepr::Inline(repr, i, key))),
+            (_, &mut Repr::Tree(ref mut tree)) => match tree.entry(key) {
+                map::Entry::Occupied(e) => Entry::Occupied(OccupiedEntry(OccupiedRepr::Tree(e))),
+                map::Entry::Vacant(e) => Entry::Vacant(VacantEntry(VacantRepr::Tree(e))),
+            },
+        }
+    }
+
+    /// Inserts an entry into the map, or combines the given value with the value already
+    /// associated with the key, as `Map::insert_with` does.
+    pub fn insert_with<F>(&mut self, key: K, value: V, f: F) -> &mut V
+        where F: FnOnce(&mut V, V) {
+
+        match self.entry(key) {
+            Entry::Occupied(e) => {
+                let old_value = e.into_mut();
+                f(old_value, value);
+                old_value
+            }
+            Entry::Vacant(e) => e.insert(value),
+        }
+    }
+
+    /// Updates the value associated with the given key, or removes the key, as `Map::update`
+    /// does.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut map: tree::small::SmallMap<_, _> = vec![("a", 1)].into_iter().collect();
+    ///
+    /// map.update("a", |count| count.map(|c| c + 1));
+    /// map.update("b", |count| count.map(|c| c + 1));
+    /// assert_eq!(map.iter().collect::<Vec<_>>(), [(&"a", &2)]);
+    /// ```
+    pub fn update<F>(&mut self, key: K, f: F) where F: FnOnce(Option<V>) -> Option<V> {
+        match self.entry(key) {
+            Entry::Occupied(OccupiedEntry(OccupiedRepr::Inline(inline, i))) => {
+                let (key, value) = inline.remove(i);
+                if let Some(value) = f(Some(value)) { inline.insert(i, key, value); }
+            }
+            Entry::Occupied(OccupiedEntry(OccupiedRepr::Tree(e))) =>
+                e.update(|value| f(Some(value))),
+            Entry::Vacant(e) => if let Some(value) = f(None) { e.insert(value); },
+        }
+    }
+
+    /// Inserts the entries of the given iterator into the map, combining the values of
+    /// duplicate keys as `Map::extend_with` does.
+    pub fn extend_with<I, F>(&mut self, it: I, mut f: F)
+        where I: IntoIterator<Item=(K, V)>, F: FnMut(&mut V, V) {
+
+        for (key, value) in it { self.insert_with(key, value, &mut f); }
+    }
+
+    /// Moves the entries of the other map into this map, combining the values of keys present in
+    /// both maps as `Map::merge` does.
+    pub fn merge<F, const M: usize>(&mut self, other: SmallMap<K, V, C, M>, f: F)
+        where F: FnMut(&mut V, V) {
+
+        self.extend_with(other, f);
+    }
+
+    /// Checks if the map contains the given key.
+    pub fn contains_key<Q: ?Sized>(&self, key: &Q) -> bool where C: Compare<Q, K> {
+        self.get(key).is_some()
+    }
+
+    /// Returns a reference to the value associated with the given key, or `None` if the map does
+    /// not contain the key.
+    pub fn get<Q: ?Sized>(&self, key: &Q) -> Option<&V> where C: Compare<Q, K> {
+        match self.repr {
+            Repr::Inline(ref inline) => inline.find(key).ok().map(|i| inline.entry(i).1),
+            Repr::Tree(ref tree) => tree.get(key),
+        }
+    }
+
+    /// Returns a mutable reference to the value associated with the given key, or `None` if the
+    /// map does not contain the key.
+    pub fn get_mut<Q: ?Sized>(&mut self, key: &Q) -> Option<&mut V> where C: Compare<Q, K> {
+        match self.repr {
+            Repr::Inline(ref mut inline) => match inline.find(key) {
+                Ok(i) => Some(inline.entry_mut(i).1),
+                Err(_) => None,
+            },
+            Repr::Tree(ref mut tree) => tree.get_mut(key),
+        }
+    }
+
+    /// Returns a reference to the maximum key in the map and its associated value, or `None` if
+    /// the map is empty.
+    pub fn max(&self) -> Option<(&K, &V)> { self.iter().next_back() }
+
+    /// Returns a reference to the maximum key in the map and a mutable reference to its
+    /// associated value, or `None` if the map is empty.
+    pub fn max_mut(&mut self) -> Option<(&K, &mut V)> { self.iter_mut().next_back() }
+
+    /// Returns the map's entry corresponding to the maximum key, or `None` if the map is empty.
+    pub fn max_entry(&mut self) -> Option<OccupiedEntry<'_, K, V, C, N>> {
+        match self.repr {
+            Repr::Inline(ref mut inline) => match inline.len {
+                0 => None,
+                len => Some(OccupiedEntry(OccupiedRepr::Inline(inline, len - 1))),
+            },
+            Repr::Tree(ref mut tree) =>
+                tree.max_entry().map(|e| OccupiedEntry(OccupiedRepr::Tree(e))),
+        }
+    }
+
+    /// Removes the maximum key from the map and returns it and its associated value, or `None` if
+    /// the map is empty.
+    pub fn remove_max(&mut self) -> Option<(K, V)> {
+        match self.repr {
+            Repr::Inline(ref mut inline) => match inline.len {
+                0 => None,
+                len => Some(inline.remove(len - 1)),
+            },
+            Repr::Tree(ref mut tree) => tree.remove_max(),
+        }
+    }
+
+    /// Returns a reference to the minimum key in the map and its associated value, or `None` if
+    /// the map is empty.
+    pub fn min(&self) -> Option<(&K, &V)> { self.iter().next() }
+
+    /// Returns a reference to the minimum key in the map and a mutable reference to its
+    /// associated value, or `None` if the map is empty.
+    pub fn min_mut(&mut self) -> Option<(&K, &mut V)> { self.iter_mut().next() }
+
+    /// Returns the map's entry corresponding to the minimum key, or `None` if the map is empty.
+    pub fn min_entry(&mut self) -> Option<OccupiedEntry<'_, K, V, C, N>> {
+        match self.repr {
+            Repr::Inline(ref mut inline) => match inline.len {
+                0 => None,
+                _ => Some(OccupiedEntry(OccupiedRepr::Inline(inline, 0))),
+            },
+            Repr::Tree(ref mut tree) =>
+                tree.min_entry().map(|e| OccupiedEntry(OccupiedRepr::Tree(e))),
+        }
+    }
+
+    /// Removes the minimum key from the map and returns it and its associated value, or `None` if
+    /// the map is empty.
+    pub fn remove_min(&mut self) -> Option<(K, V)> {
+        match self.repr {
+            Repr::Inline(ref mut inline) => match inline.len {
+                0 => None,
+                _ => Some(inline.remove(0)),
+            },
+            Repr::Tree(ref mut tree) => tree.remove_min(),
+        }
+    }
+
+    /// Returns a reference to the predecessor of the given key and its associated value, as
+    /// `Map::pred` does.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let map: tree::small::SmallMap<_, _> = vec![(1, "a"), (3, "c")].into_iter().collect();
+    ///
+    /// assert_eq!(map.pred(&1, false), None);
+    /// assert_eq!(map.pred(&1, true), Some((&1, &"a")));
+    /// assert_eq!(map.pred(&2, false), Some((&1, &"a")));
+    /// ```
+    pub fn pred<Q: ?Sized>(&self, key: &Q, inclusive: bool) -> Option<(&K, &V)>
+        where C: Compare<Q, K> {
+
+        match self.repr {
+            Repr::Inline(ref inline) => inline.pred(key, inclusive).map(|i| inline.entry(i)),
+            Repr::Tree(ref tree) => tree.pred(key, inclusive),
+        }
+    }
+
+    /// Returns a reference to the predecessor of the given key and a mutable reference to its
+    /// associated value, as `Map::pred_mut` does.
+    pub fn pred_mut<Q: ?Sized>(&mut self, key: &Q, inclusive: bool) -> Option<(&K, &mut V)>
+        where C: Compare<Q, K> {
+
+        match self.repr {
+            Repr::Inline(ref mut inline) =>
+                inline.pred(key, inclusive).map(move |i| inline.entry_mut(i)),
+            Repr::Tree(ref mut tree) => tree.pred_mut(key, inclusive),
+        }
+    }
+
+    /// Returns the map's entry corresponding to the predecessor of the given key, as
+    /// `Map::pred_entry` does.
+    pub fn pred_entry<Q: ?Sized>(&mut self, key: &Q, inclusive: bool)
+        -> Option<OccupiedEntry<'_, K, V, C, N>> where C: Compare<Q, K> {
+
+        match self.repr {
+            Repr::Inline(ref mut inline) => inline.pred(key, inclusive)
+                .map(move |i| OccupiedEntry(OccupiedRepr::Inline(inline, i))),
+            Repr::Tree(ref mut tree) => tree.pred_entry(key, inclusive)
+                .map(|e| OccupiedEntry(OccupiedRepr::Tree(e))),
+        }
+    }
+
+    /// Removes the predecessor of the given key from the map and returns it and its associated
+    /// value, as `Map::remove_pred` does.
+    pub fn remove_pred<Q: ?Sized>(&mut self, key: &Q, inclusive: bool) -> Option<(K, V)>
+        where C: Compare<Q, K> {
+
+        match self.repr {
+            Repr::Inline(ref mut inline) => inline.pred(key, inclusive).map(|i| inline.remove(i)),
+            Repr::Tree(ref mut tree) => tree.remove_pred(key, inclusive),
+        }
+    }
+
+    /// Returns a reference to the successor of the given key and its associated value, as
+    /// `Map::succ` does.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let map: tree::small::SmallMap<_, _> = vec![(1, "a"), (3, "c")].into_iter().collect();
+    ///
+    /// assert_eq!(map.succ(&1, false), Some((&3, &"c")));
+    /// assert_eq!(map.succ(&1, true), Some((&1, &"a")));
+    /// assert_eq!(map.succ(&3, false), None);
+    /// ```
+    pub fn succ<Q: ?Sized>(&self, key: &Q, inclusive: bool) -> Option<(&K, &V)>
+        where C: Compare<Q, K> {
+
+        match self.repr {
+            Repr::Inline(ref inline) => inline.succ(key, inclusive).map(|i| inline.entry(i)),
+            Repr::Tree(ref tree) => tree.succ(key, inclusive),
+        }
+    }
+
+    /// Returns a reference to the successor of the given key and a mutable reference to its
+    /// associated value, as `Map::succ_mut` does.
+    pub fn succ_mut<Q: ?Sized>(&mut self, key: &Q, inclusive: bool) -> Option<(&K, &mut V)>
+        where C: Compare<Q, K> {
+
+        match self.repr {
+            Repr::Inline(ref mut inline) =>
+                inline.succ(key, inclusive).map(move |i| inline.entry_mut(i)),
+            Repr::Tree(ref mut tree) => tree.succ_mut(key, inclusive),
+        }
+    }
+
+    /// Returns the map's entry corresponding to the successor of the given key, as
+    /// `Map::succ_entry` does.
+    pub fn succ_entry<Q: ?Sized>(&mut self, key: &Q, inclusive: bool)
+        -> Option<OccupiedEntry<'_, K, V, C, N>> where C: Compare<Q, K> {
+
+        match self.repr {
+            Repr::Inline(ref mut inline) => inline.succ(key, inclusive)
+                .map(move |i| OccupiedEntry(OccupiedRepr::Inline(inline, i))),
+            Repr::Tree(ref mut tree) => tree.succ_entry(key, inclusive)
+                .map(|e| OccupiedEntry(OccupiedRepr::Tree(e))),
+        }
+    }
+
+    /// Removes the successor of the given key from the map and returns it and its associated
+    /// value, as `Map::remove_succ` does.
+    pub fn remove_succ<Q: ?Sized>(&mut self, key: &Q, inclusive: bool) -> Option<(K, V)>
+        where C: Compare<Q, K> {
+
+        match self.repr {
+            Repr::Inline(ref mut inline) => inline.succ(key, inclusive).map(|i| inline.remove(i)),
+            Repr::Tree(ref mut tree) => tree.remove_succ(key, inclusive),
+        }
+    }
+
+    /// Returns an iterator over the map's entries with immutable references to the values.
+    ///
+    /// The iterator yields the entries in ascending order according to the map's comparator.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        match self.repr {
+            Repr::Inline(ref inline) => Iter(IterRepr::Inline(inline.slice().iter())),
+            Repr::Tree(ref tree) => Iter(IterRepr::Tree(tree.iter())),
+        }
+    }
+
+    /// Returns an iterator over the map's entries with mutable references to the values.
+    ///
+    /// The iterator yields the entries in ascending order according to the map's comparator.
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        match self.repr {
+            Repr::Inline(ref mut inline) =>
+                IterMut(IterMutRepr::Inline(inline.entries[..inline.len].iter_mut())),
+            Repr::Tree(ref mut tree) => IterMut(IterMutRepr::Tree(tree.iter_mut())),
+        }
+    }
+
+    /// Returns an iterator over the map's entries whose keys lie in the given range with immutable
+    /// references to the values, as `Map::range` does.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::ops::Bound::{Excluded, Included};
+    ///
+    /// let map: tree::small::SmallMap<_, _> = (0..5).map(|i| (i, i * 10)).collect();
+    ///
+    /// assert_eq!(map.range(Excluded(&1), Included(&3)).collect::<Vec<_>>(),
+    ///            [(&2, &20), (&3, &30)]);
+    /// ```
+    #[cfg(feature = "range")]
+    pub fn range<Min: ?Sized, Max: ?Sized>(&self, min: Bound<&Min>, max: Bound<&Max>)
+        -> Range<'_, K, V> where C: Compare<Min, K> + Compare<Max, K> {
+
+        match self.repr {
+            Repr::Inline(ref inline) => {
+                let (front, back) = inline.range(min, max);
+                Range(RangeRepr::Inline(inline.entries[front..back].iter()))
+            }
+            Repr::Tree(ref tree) => Range(RangeRepr::Tree(tree.range(min, max))),
+        }
+    }
+
+    /// Returns an iterator over the map's entries whose keys lie in the given range with mutable
+    /// references to the values, as `Map::range_mut` does.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::ops::Bound::{Excluded, Unbounded};
+    ///
+    /// let mut map: tree::small::SmallMap<_, _> = (0..5).map(|i| (i, i)).collect();
+    /// for (_, value) in map.range_mut(Unbounded, Excluded(&2)) { *value += 10; }
+    ///
+    /// assert_eq!(map.iter().map(|e| *e.1).collect::<Vec<_>>(), [10, 11, 2, 3, 4]);
+    /// ```
+    #[cfg(feature = "range")]
+    pub fn range_mut<Min: ?Sized, Max: ?Sized>(&mut self, min: Bound<&Min>, max: Bound<&Max>)
+        -> RangeMut<'_, K, V> where C: Compare<Min, K> + Compare<Max, K> {
+
+        match self.repr {
+            Repr::Inline(ref mut inline) => {
+                let (front, back) = inline.range(min, max);
+                RangeMut(RangeMutRepr::Inline(inline.entries[front..back].iter_mut()))
+            }
+            Repr::Tree(ref mut tree) => RangeMut(RangeMutRepr::Tree(tree.range_mut(min, max))),
+        }
+    }
+}
+
+impl<K, V, C, const N: usize> Debug for SmallMap<K, V, C, N>
+    where K: Debug, V: Debug, C: Compare<K> {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self).finish()
+    }
+}
+
+impl<K, V, C, const N: usize> Default for SmallMap<K, V, C, N> where C: Compare<K> + Default {
+    fn default() -> Self { SmallMap::with_cmp(C::default()) }
+}
+
+impl<K, V, C, const N: usize> Extend<(K, V)> for SmallMap<K, V, C, N> where C: Compare<K> {
+    fn extend<I: IntoIterator<Item=(K, V)>>(&mut self, it: I) {
+        for (k, v) in it { self.insert(k, v); }
+    }
+}
+
+impl<K, V, C, const N: usize> iter::FromIterator<(K, V)> for SmallMap<K, V, C, N>
+    where C: Compare<K> + Default {
+
+    fn from_iter<I: IntoIterator<Item=(K, V)>>(it: I) -> Self {
+        let mut map = SmallMap::default();
+        map.extend(it);
+        map
+    }
+}
+
+impl<K, V, C, const N: usize> Hash for SmallMap<K, V, C, N>
+    where K: Hash, V: Hash, C: Compare<K> {
+
+    fn hash<H: hash::Hasher>(&self, h: &mut H) {
+        for e in self.iter() { e.hash(h); }
+    }
+}
+
+impl<'a, K, V, C, Q: ?Sized, const N: usize> ops::Index<&'a Q> for SmallMap<K, V, C, N>
+    where C: Compare<K> + Compare<Q, K> {
+
+    type Output = V;
+    fn index(&self, key: &Q) -> &V { self.get(key).expect("key not found") }
+}
+
+impl<K, V, C, const N: usize> PartialEq for SmallMap<K, V, C, N>
+    where V: PartialEq, C: Compare<K> {
+
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().zip(other.iter()).all(|(l, r)| {
+            self.cmp().compares_eq(l.0, r.0) && l.1 == r.1
+        })
+    }
+}
+
+impl<K, V, C, const N: usize> Eq for SmallMap<K, V, C, N> where V: Eq, C: Compare<K> {}
+
+impl<'a, K, V, C, const N: usize> IntoIterator for &'a SmallMap<K, V, C, N>
+    where C: Compare<K> {
+
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+    fn into_iter(self) -> Iter<'a, K, V> { self.iter() }
+}
+
+impl<'a, K, V, C, const N: usize> IntoIterator for &'a mut SmallMap<K, V, C, N>
+    where C: Compare<K> {
+
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = IterMut<'a, K, V>;
+    fn into_iter(self) -> IterMut<'a, K, V> { self.iter_mut() }
+}
+
+impl<K, V, C, const N: usize> IntoIterator for SmallMap<K, V, C, N> where C: Compare<K> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V, N>;
+
+    /// Returns an iterator that consumes the map.
+    ///
+    /// The iterator yields the entries in ascending order according to the map's comparator.
+    fn into_iter(self) -> IntoIter<K, V, N> {
+        match self.repr {
+            Repr::Inline(inline) => {
+                let mut it = IntoIterator::into_iter(inline.entries);
+                for _ in 0..N - inline.len { it.next_back(); }
+                IntoIter(IntoIterRepr::Inline(it))
+            }
+            Repr::Tree(tree) => IntoIter(IntoIterRepr::Tree(tree.into_iter())),
+        }
+    }
+}
+
+/// An entry in a `SmallMap`.
+///
+/// See [`SmallMap::entry`](struct.SmallMap.html#method.entry) for an example.
+pub enum Entry<'a, K: 'a, V: 'a, C: 'a, const N: usize> where C: Compare<K> {
+    /// An occupied entry.
+    Occupied(OccupiedEntry<'a, K, V, C, N>),
+    /// A vacant entry.
+    Vacant(VacantEntry<'a, K, V, C, N>),
+}
+
+impl<'a, K, V, C, const N: usize> Entry<'a, K, V, C, N> where C: Compare<K> {
+    /// Returns a mutable reference to the entry's value, inserting the given default if the entry
+    /// is vacant.
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        match self {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => e.insert(default),
+        }
+    }
+
+    /// Returns a mutable reference to the entry's value, inserting the given function's result if
+    /// the entry is vacant.
+    pub fn or_insert_with<F>(self, default: F) -> &'a mut V where F: FnOnce() -> V {
+        match self {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => e.insert(default()),
+        }
+    }
+}
+
+/// An occupied entry in a `SmallMap`.
+///
+/// See [`SmallMap::entry`](struct.SmallMap.html#method.entry) for an example.
+pub struct OccupiedEntry<'a, K: 'a, V: 'a, C: 'a, const N: usize>(OccupiedRepr<'a, K, V, C, N>)
+    where C: Compare<K>;
+
+enum OccupiedRepr<'a, K: 'a, V: 'a, C: 'a, const N: usize> where C: Compare<K> {
+    Inline(&'a mut Inline<K, V, C, N>, usize),
+    Tree(map::OccupiedEntry<'a, K, V>),
+}
+
+impl<'a, K, V, C, const N: usize> OccupiedEntry<'a, K, V, C, N> where C: Compare<K> {
+    /// Returns a reference to the entry's key.
+    pub fn key(&self) -> &K {
+        match self.0 {
+            OccupiedRepr::Inline(ref inline, i) => inline.entry(i).0,
+            OccupiedRepr::Tree(ref e) => e.key(),
+        }
+    }
+
+    /// Returns a reference to the entry's value.
+    pub fn get(&self) -> &V {
+        match self.0 {
+            OccupiedRepr::Inline(ref inline, i) => inline.entry(i).1,
+            OccupiedRepr::Tree(ref e) => e.get(),
+        }
+    }
+
+    /// Returns a mutable reference to the entry's value.
+    pub fn get_mut(&mut self) -> &mut V {
+        match self.0 {
+            OccupiedRepr::Inline(ref mut inline, i) => inline.entry_mut(i).1,
+            OccupiedRepr::Tree(ref mut e) => e.get_mut(),
+        }
+    }
+
+    /// Returns a mutable reference to the entry's value with the same lifetime as the map.
+    pub fn into_mut(self) -> &'a mut V {
+        match self.0 {
+            OccupiedRepr::Inline(inline, i) => inline.entry_mut(i).1,
+            OccupiedRepr::Tree(e) => e.into_mut(),
+        }
+    }
+
+    /// Replaces the entry's value with the given value, returning the old one.
+    pub fn insert(&mut self, value: V) -> V { mem::replace(self.get_mut(), value) }
+
+    /// Removes the entry from the map and returns its key and value.
+    pub fn remove(self) -> (K, V) {
+        match self.0 {
+            OccupiedRepr::Inline(inline, i) => inline.remove(i),
+            OccupiedRepr::Tree(e) => e.remove(),
+        }
+    }
+}
+
+/// A vacant entry in a `SmallMap`.
+///
+/// See [`SmallMap::entry`](struct.SmallMap.html#method.entry) for an example.
+pub struct VacantEntry<'a, K: 'a, V: 'a, C: 'a, const N: usize>(VacantRepr<'a, K, V, C, N>)
+    where C: Compare<K>;
+
+enum VacantRepr<'a, K: 'a, V: 'a, C: 'a, const N: usize> where C: Compare<K> {
+    Inline(&'a mut Repr<K, V, C, N>, usize, K),
+    Tree(map::VacantEntry<'a, K, V>),
+}
+
+impl<'a, K, V, C, const N: usize> VacantEntry<'a, K, V, C, N> where C: Compare<K> {
+    /// Returns a reference to the entry's key.
+    pub fn key(&self) -> &K {
+        match self.0 {
+            VacantRepr::Inline(_, _, ref key) => key,
+            VacantRepr::Tree(ref e) => e.key(),
+        }
+    }
+
+    /// Inserts the entry into the map with its key and the given value, returning a mutable
+    /// reference to the value with the same lifetime as the map.
+    ///
+    /// This promotes the map if it already holds `N` entries inline.
+    pub fn insert(self, value: V) -> &'a mut V {
+        let (repr, i, key) = match self.0 {
+            VacantRepr::Inline(repr, i, key) => (repr, i, key),
+            VacantRepr::Tree(e) => return e.insert(value),
+        };
+
+        let full = match *repr {
+            Repr::Inline(ref inline) => inline.len == N,
+            Repr::Tree(_) => true,
+        };
+
+        if full {
+            match repr.promote().entry(key) {
+                map::Entry::Vacant(e) => e.insert(value),
+                map::Entry::Occupied(_) => unreachable!(),
+            }
+        } else {
+            match *repr {
+                Repr::Inline(ref mut inline) => {
+                    inline.insert(i, key, value);
+                    inline.entry_mut(i).1
+                }
+                Repr::Tree(_) => unreachable!(),
+            }
+        }
+    }
+}
+
+/// An iterator over a small map's entries with immutable references to the values.
+///
+/// The iterator yields the entries in ascending order according to the map's comparator.
+///
+/// Acquire through [`SmallMap::iter`](struct.SmallMap.html#method.iter) or the `IntoIterator`
+/// implementation for `&SmallMap`.
+#[derive(Clone)]
+pub struct Iter<'a, K: 'a, V: 'a>(IterRepr<'a, K, V>);
+
+#[derive(Clone)]
+enum IterRepr<'a, K: 'a, V: 'a> {
+    Inline(slice::Iter<'a, Option<(K, V)>>),
+    Tree(map::Iter<'a, K, V>),
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.0 {
+            IterRepr::Inline(ref mut it) => it.next().map(inline_ref),
+            IterRepr::Tree(ref mut it) => it.next(),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self.0 {
+            IterRepr::Inline(ref it) => it.size_hint(),
+            IterRepr::Tree(ref it) => it.size_hint(),
+        }
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for Iter<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        match self.0 {
+            IterRepr::Inline(ref mut it) => it.next_back().map(inline_ref),
+            IterRepr::Tree(ref mut it) => it.next_back(),
+        }
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for Iter<'a, K, V> {}
+
+/// An iterator over a small map's entries with mutable references to the values.
+///
+/// The iterator yields the entries in ascending order according to the map's comparator.
+///
+/// Acquire through [`SmallMap::iter_mut`](struct.SmallMap.html#method.iter_mut) or the
+/// `IntoIterator` implementation for `&mut SmallMap`.
+pub struct IterMut<'a, K: 'a, V: 'a>(IterMutRepr<'a, K, V>);
+
+enum IterMutRepr<'a, K: 'a, V: 'a> {
+    Inline(slice::IterMut<'a, Option<(K, V)>>),
+    Tree(map::IterMut<'a, K, V>),
+}
+
+impl<'a, K, V> Iterator for IterMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.0 {
+            IterMutRepr::Inline(ref mut it) => it.next().map(inline_mut),
+            IterMutRepr::Tree(ref mut it) => it.next(),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self.0 {
+            IterMutRepr::Inline(ref it) => it.size_hint(),
+            IterMutRepr::Tree(ref it) => it.size_hint(),
+        }
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for IterMut<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        match self.0 {
+            IterMutRepr::Inline(ref mut it) => it.next_back().map(inline_mut),
+            IterMutRepr::Tree(ref mut it) => it.next_back(),
+        }
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for IterMut<'a, K, V> {}
+
+/// An iterator that consumes a small map.
+///
+/// The iterator yields the entries in ascending order according to the map's comparator.
+///
+/// Acquire through the `IntoIterator` implementation for `SmallMap`.
+pub struct IntoIter<K, V, const N: usize>(IntoIterRepr<K, V, N>);
+
+enum IntoIterRepr<K, V, const N: usize> {
+    Inline(array::IntoIter<Option<(K, V)>, N>),
+    Tree(map::IntoIter<K, V>),
+}
+
+impl<K, V, const N: usize> Iterator for IntoIter<K, V, N> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.0 {
+            IntoIterRepr::Inline(ref mut it) => it.next().map(inline_owned),
+            IntoIterRepr::Tree(ref mut it) => it.next(),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self.0 {
+            IntoIterRepr::Inline(ref it) => it.size_hint(),
+            IntoIterRepr::Tree(ref it) => it.size_hint(),
+        }
+    }
+}
+
+impl<K, V, const N: usize> DoubleEndedIterator for IntoIter<K, V, N> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        match self.0 {
+            IntoIterRepr::Inline(ref mut it) => it.next_back().map(inline_owned),
+            IntoIterRepr::Tree(ref mut it) => it.next_back(),
+        }
+    }
+}
+
+impl<K, V, const N: usize> ExactSizeIterator for IntoIter<K, V, N> {}
+
+/// An iterator over a small map's entries whose keys lie in a given range with immutable
+/// references to the values.
+///
+/// The iterator yields the entries in ascending order according to the map's comparator.
+///
+/// Acquire through [`SmallMap::range`](struct.SmallMap.html#method.range).
+#[cfg(feature = "range")]
+#[derive(Clone)]
+pub struct Range<'a, K: 'a, V: 'a>(RangeRepr<'a, K, V>);
+
+#[cfg(feature = "range")]
+#[derive(Clone)]
+enum RangeRepr<'a, K: 'a, V: 'a> {
+    Inline(slice::Iter<'a, Option<(K, V)>>),
+    Tree(map::Range<'a, K, V>),
+}
+
+#[cfg(feature = "range")]
+impl<'a, K, V> Iterator for Range<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.0 {
+            RangeRepr::Inline(ref mut it) => it.next().map(inline_ref),
+            RangeRepr::Tree(ref mut it) => it.next(),
+        }
+    }
+}
+
+#[cfg(feature = "range")]
+impl<'a, K, V> DoubleEndedIterator for Range<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        match self.0 {
+            RangeRepr::Inline(ref mut it) => it.next_back().map(inline_ref),
+            RangeRepr::Tree(ref mut it) => it.next_back(),
+        }
+    }
+}
+
+/// An iterator over a small map's entries whose keys lie in a given range with mutable
+/// references to the values.
+///
+/// The iterator yields the entries in ascending order according to the map's comparator.
+///
+/// Acquire through [`SmallMap::range_mut`](struct.SmallMap.html#method.range_mut).
+#[cfg(feature = "range")]
+pub struct RangeMut<'a, K: 'a, V: 'a>(RangeMutRepr<'a, K, V>);
+
+#[cfg(feature = "range")]
+enum RangeMutRepr<'a, K: 'a, V: 'a> {
+    Inline(slice::IterMut<'a, Option<(K, V)>>),
+    Tree(map::RangeMut<'a, K, V>),
+}
+
+#[cfg(feature = "range")]
+impl<'a, K, V> Iterator for RangeMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.0 {
+            RangeMutRepr::Inline(ref mut it) => it.next().map(inline_mut),
+            RangeMutRepr::Tree(ref mut it) => it.next(),
+        }
+    }
+}
+
+#[cfg(feature = "range")]
+impl<'a, K, V> DoubleEndedIterator for RangeMut<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        match self.0 {
+            RangeMutRepr::Inline(ref mut it) => it.next_back().map(inline_mut),
+            RangeMutRepr::Tree(ref mut it) => it.next_back(),
+        }
+    }
+}
+
+fn inline_ref<K, V>(entry: &Option<(K, V)>) -> (&K, &V) {
+    let entry = entry.as_ref().expect("inline entry is vacant");
+    (&entry.0, &entry.1)
+}
+
+fn inline_mut<K, V>(entry: &mut Option<(K, V)>) -> (&K, &mut V) {
+    let entry = entry.as_mut().expect("inline entry is vacant");
+    (&entry.0, &mut entry.1)
+}
+
+fn inline_owned<K, V>(entry: Option<(K, V)>) -> (K, V) { entry.expect("inline entry is vacant") }
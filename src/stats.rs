@@ -0,0 +1,57 @@
+//! Operation counters and shape reports for maps.
+//!
+//! With the `stats` feature enabled, every `Map` counts the work its operations do: the
+//! comparisons made while searching the tree, and the skews, splits and rebalances performed to
+//! keep it balanced. [`Map::stats`](../map/struct.Map.html#method.stats) returns the counts since
+//! the map was created or cloned, or since the last call to
+//! [`Map::reset_stats`](../map/struct.Map.html#method.reset_stats).
+//! [`Map::shape`](../map/struct.Map.html#method.shape) describes the tree's current shape.
+//!
+//! Without the feature, maps keep no counters and pay nothing for them.
+//!
+//! # Examples
+//!
+//! ```
+//! let mut map = tree::Map::new();
+//! for i in 0..1000 { map.insert(i, ()); }
+//!
+//! map.reset_stats();
+//! map.get(&500);
+//!
+//! let stats = map.stats();
+//! assert!(stats.compares > 0 && stats.compares <= map.shape().height);
+//! assert_eq!(stats.skews + stats.splits + stats.rebalances, 0);
+//! ```
+
+use alloc::vec::Vec;
+
+/// Counts of the work done by a map's operations.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Stats {
+    /// The number of comparisons made while searching the map's tree.
+    ///
+    /// This counts each call to one of the comparator's methods, but not comparisons made to
+    /// compare whole maps, such as by `PartialEq` or `Map::diff`.
+    pub compares: usize,
+    /// The number of skews, which are right rotations that remove a left horizontal link.
+    pub skews: usize,
+    /// The number of splits, which are left rotations that remove two consecutive right
+    /// horizontal links and raise the new parent's level.
+    pub splits: usize,
+    /// The number of rebalances, which lower a node's level after a removal below it.
+    pub rebalances: usize,
+}
+
+/// The shape of a map's tree.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Shape {
+    /// The number of nodes in the tree, which equals the map's length.
+    pub nodes: usize,
+    /// The number of nodes on the longest path from the root to a leaf, which bounds the number
+    /// of comparisons made by a search.
+    pub height: usize,
+    /// The number of nodes at each depth, with the root at index 0.
+    ///
+    /// The vector's length equals the height.
+    pub levels: Vec<usize>,
+}
@@ -0,0 +1,371 @@
+//! An ordered map that answers reads as of past versions.
+//!
+//! A `VersionedMap` stores, for each key, a chain of the values written to it, each tagged with
+//! the version at which it was written, in a `Map` from keys to chains. A removal writes a
+//! tombstone. A read at a given version sees, for each key, the latest value written at or before
+//! that version, unless a tombstone was written after it.
+//!
+//! Versions are `u64`s chosen by the caller, such as transaction or log sequence numbers. They need
+//! not be written in order, but reads are cheapest when each chain is short: `gc` discards the
+//! versions that can no longer be read once no reader needs versions older than a watermark.
+//!
+//! # Examples
+//!
+//! ```
+//! use tree::versioned::VersionedMap;
+//!
+//! let mut balances = VersionedMap::new();
+//!
+//! balances.insert_at("alice", 10, 1);
+//! balances.insert_at("bob", 5, 1);
+//! balances.insert_at("alice", 7, 3);
+//! balances.remove_at("bob", 4);
+//!
+//! assert_eq!(balances.get_at(&"alice", 2), Some(&10));
+//! assert_eq!(balances.get_at(&"alice", 3), Some(&7));
+//! assert_eq!(balances.get_at(&"bob", 3), Some(&5));
+//! assert_eq!(balances.get_at(&"bob", 4), None);
+//! assert_eq!(balances.iter_at(2).collect::<Vec<_>>(), [(&"alice", &10), (&"bob", &5)]);
+//!
+//! // Versions 3 and later are still readable after garbage collection.
+//! assert_eq!(balances.gc(3), 1);
+//! assert_eq!(balances.get_at(&"alice", 3), Some(&7));
+//! ```
+
+use alloc::vec::Vec;
+use compare::{Compare, Natural};
+use core::fmt::{self, Debug};
+#[cfg(feature = "range")] use core::ops::Bound;
+use core::slice;
+use map::{self, Map};
+
+// The values written to a key, in ascending order of version, with `None` for a removal.
+#[derive(Clone)]
+struct Chain<V>(Vec<(u64, Option<V>)>);
+
+impl<V> Chain<V> {
+    // Returns the value visible at the given version.
+    fn at(&self, version: u64) -> Option<&V> {
+        let i = match self.0.binary_search_by_key(&version, |e| e.0) {
+            Ok(i) => i,
+            Err(0) => return None,
+            Err(i) => i - 1,
+        };
+
+        self.0[i].1.as_ref()
+    }
+
+    // Writes the given value or tombstone at the given version, returning the one it replaces.
+    fn write(&mut self, version: u64, value: Option<V>) -> Option<V> {
+        match self.0.binary_search_by_key(&version, |e| e.0) {
+            Ok(i) => ::core::mem::replace(&mut self.0[i].1, value),
+            Err(i) => { self.0.insert(i, (version, value)); None }
+        }
+    }
+
+    // Discards the versions that cannot be read at or after the watermark, returning their number.
+    fn gc(&mut self, watermark: u64) -> usize {
+        // The last version before the watermark remains visible at the watermark, unless it is a
+        // tombstone.
+        let mut keep = match self.0.binary_search_by_key(&watermark, |e| e.0) {
+            Ok(i) => i,
+            Err(i) => i.saturating_sub(1),
+        };
+
+        if keep < self.0.len() && self.0[keep].0 < watermark && self.0[keep].1.is_none() {
+            keep += 1;
+        }
+
+        self.0.drain(..keep);
+        keep
+    }
+}
+
+/// An ordered map that answers reads as of past versions.
+///
+/// See the [module documentation](index.html) for details.
+#[derive(Clone)]
+pub struct VersionedMap<K, V, C = Natural<K>> where C: Compare<K> {
+    chains: Map<K, Chain<V>, C>,
+    watermark: u64,
+}
+
+impl<K, V> VersionedMap<K, V> where K: Ord {
+    /// Creates an empty map ordered according to the natural order of its keys.
+    pub fn new() -> Self { VersionedMap::with_cmp(Natural::default()) }
+}
+
+impl<K, V, C> VersionedMap<K, V, C> where C: Compare<K> {
+    /// Creates an empty map ordered according to the given comparator.
+    pub fn with_cmp(cmp: C) -> Self { VersionedMap { chains: Map::with_cmp(cmp), watermark: 0 } }
+
+    /// Checks if the map contains no keys with retained versions.
+    pub fn is_empty(&self) -> bool { self.chains.is_empty() }
+
+    /// Returns the number of keys in the map with retained versions, including keys that are
+    /// removed at the latest version.
+    pub fn len(&self) -> usize { self.chains.len() }
+
+    /// Returns a reference to the map's comparator.
+    pub fn cmp(&self) -> &C { self.chains.cmp() }
+
+    /// Returns the watermark of the last garbage collection, or zero if there has been none.
+    ///
+    /// Reads at versions older than the watermark may not reflect the versions that were
+    /// discarded.
+    pub fn watermark(&self) -> u64 { self.watermark }
+
+    /// Removes all keys and their versions from the map.
+    pub fn clear(&mut self) { self.chains.clear(); }
+
+    /// Writes a value for the given key at the given version, returning the value previously
+    /// written for the key at exactly that version, if any.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the version is older than the watermark.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut map = tree::versioned::VersionedMap::new();
+    ///
+    /// assert_eq!(map.insert_at("a", 1, 5), None);
+    /// assert_eq!(map.insert_at("a", 2, 5), Some(1));
+    /// assert_eq!(map.insert_at("a", 3, 2), None);
+    ///
+    /// assert_eq!(map.get_at(&"a", 4), Some(&3));
+    /// assert_eq!(map.get_at(&"a", 5), Some(&2));
+    /// ```
+    pub fn insert_at(&mut self, key: K, value: V, version: u64) -> Option<V> {
+        assert!(version >= self.watermark, "version is older than the watermark");
+        self.chains.entry(key).or_insert_with(|| Chain(Vec::new())).write(version, Some(value))
+    }
+
+    /// Removes the given key at the given version, returning `true` if the key had a value at
+    /// that version.
+    ///
+    /// This writes a removal even if the map has no versions of the key, so that it applies to
+    /// values written later at older versions.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the version is older than the watermark.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut map = tree::versioned::VersionedMap::new();
+    /// map.insert_at("a", 1, 1);
+    ///
+    /// assert!(map.remove_at("a", 3));
+    /// assert!(!map.remove_at("a", 4));
+    ///
+    /// assert_eq!(map.get_at(&"a", 2), Some(&1));
+    /// assert_eq!(map.get_at(&"a", 3), None);
+    /// assert_eq!(map.get(&"a"), None);
+    /// ```
+    pub fn remove_at(&mut self, key: K, version: u64) -> bool {
+        assert!(version >= self.watermark, "version is older than the watermark");
+
+        let chain = self.chains.entry(key).or_insert_with(|| Chain(Vec::new()));
+        let present = chain.at(version).is_some();
+        chain.write(version, None);
+        present
+    }
+
+    /// Returns a reference to the value of the given key at the given version, or `None` if the
+    /// key had no value at that version.
+    pub fn get_at<Q: ?Sized>(&self, key: &Q, version: u64) -> Option<&V> where C: Compare<Q, K> {
+        self.chains.get(key).and_then(|chain| chain.at(version))
+    }
+
+    /// Returns a reference to the value of the given key at the latest version, or `None` if the
+    /// key has no value at that version.
+    pub fn get<Q: ?Sized>(&self, key: &Q) -> Option<&V> where C: Compare<Q, K> {
+        self.get_at(key, u64::MAX)
+    }
+
+    /// Returns an iterator over the retained versions of the given key, in ascending order, with
+    /// `None` for the versions at which the key was removed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut map = tree::versioned::VersionedMap::new();
+    ///
+    /// map.insert_at("a", 1, 1);
+    /// map.remove_at("a", 2);
+    /// map.insert_at("a", 3, 3);
+    ///
+    /// assert_eq!(map.versions(&"a").collect::<Vec<_>>(),
+    ///            [(1, Some(&1)), (2, None), (3, Some(&3))]);
+    /// assert_eq!(map.versions(&"b").next(), None);
+    /// ```
+    pub fn versions<Q: ?Sized>(&self, key: &Q) -> Versions<'_, V> where C: Compare<Q, K> {
+        Versions(self.chains.get(key).map_or(&[][..], |chain| &chain.0[..]).iter())
+    }
+
+    /// Returns an iterator over the map's entries at the given version.
+    ///
+    /// The iterator yields the entries in ascending order according to the map's comparator.
+    pub fn iter_at(&self, version: u64) -> IterAt<'_, K, V> {
+        IterAt { chains: self.chains.iter(), version: version }
+    }
+
+    /// Returns an iterator over the map's entries at the given version whose keys lie in the given
+    /// range.
+    ///
+    /// The iterator yields the entries in ascending order according to the map's comparator.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::ops::Bound::{Included, Unbounded};
+    ///
+    /// let mut map = tree::versioned::VersionedMap::new();
+    ///
+    /// for i in 0..5 { map.insert_at(i, i * 10, i as u64); }
+    ///
+    /// assert_eq!(map.range_at(Included(&1), Unbounded, 2).collect::<Vec<_>>(),
+    ///            [(&1, &10), (&2, &20)]);
+    /// ```
+    #[cfg(feature = "range")]
+    pub fn range_at<Min: ?Sized, Max: ?Sized>(&self, min: Bound<&Min>, max: Bound<&Max>,
+                                             version: u64)
+        -> RangeAt<'_, K, V> where C: Compare<Min, K> + Compare<Max, K> {
+
+        RangeAt { chains: self.chains.range(min, max), version: version }
+    }
+
+    /// Discards the versions that cannot be read at or after the given watermark, returning their
+    /// number.
+    ///
+    /// For each key, this keeps the versions at or after the watermark and the latest one before
+    /// it, unless that one is a removal. Keys left with no versions are removed from the map.
+    /// Afterwards, versions older than the watermark can no longer be written, and reads at them
+    /// may not reflect the discarded versions.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut map = tree::versioned::VersionedMap::new();
+    ///
+    /// map.insert_at("a", 1, 1);
+    /// map.insert_at("a", 2, 2);
+    /// map.insert_at("b", 1, 1);
+    /// map.remove_at("b", 2);
+    ///
+    /// assert_eq!(map.gc(3), 3);
+    /// assert_eq!(map.len(), 1);
+    /// assert_eq!(map.versions(&"a").collect::<Vec<_>>(), [(2, Some(&2))]);
+    /// ```
+    pub fn gc(&mut self, watermark: u64) -> usize {
+        if watermark > self.watermark { self.watermark = watermark; }
+
+        let mut removed = 0;
+
+        self.chains.retain(|_, chain| {
+            removed += chain.gc(watermark);
+            !chain.0.is_empty()
+        });
+
+        removed
+    }
+}
+
+impl<K, V, C> Debug for VersionedMap<K, V, C> where K: Debug, V: Debug, C: Compare<K> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.chains.iter().map(|(k, chain)| (k, &chain.0))).finish()
+    }
+}
+
+impl<K, V, C> Default for VersionedMap<K, V, C> where C: Compare<K> + Default {
+    fn default() -> Self { VersionedMap::with_cmp(C::default()) }
+}
+
+/// An iterator over the retained versions of a key.
+///
+/// Acquire through [`VersionedMap::versions`](struct.VersionedMap.html#method.versions).
+#[derive(Clone)]
+pub struct Versions<'a, V: 'a>(slice::Iter<'a, (u64, Option<V>)>);
+
+impl<'a, V> Iterator for Versions<'a, V> {
+    type Item = (u64, Option<&'a V>);
+    fn next(&mut self) -> Option<Self::Item> { self.0.next().map(|e| (e.0, e.1.as_ref())) }
+    fn size_hint(&self) -> (usize, Option<usize>) { self.0.size_hint() }
+}
+
+impl<'a, V> DoubleEndedIterator for Versions<'a, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.next_back().map(|e| (e.0, e.1.as_ref()))
+    }
+}
+
+impl<'a, V> ExactSizeIterator for Versions<'a, V> {}
+
+/// An iterator over a versioned map's entries at a given version.
+///
+/// Acquire through [`VersionedMap::iter_at`](struct.VersionedMap.html#method.iter_at).
+pub struct IterAt<'a, K: 'a, V: 'a> {
+    chains: map::Iter<'a, K, Chain<V>>,
+    version: u64,
+}
+
+impl<'a, K, V> Clone for IterAt<'a, K, V> {
+    fn clone(&self) -> Self { IterAt { chains: self.chains.clone(), version: self.version } }
+}
+
+impl<'a, K, V> Iterator for IterAt<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let version = self.version;
+        self.chains.by_ref().filter_map(|(k, chain)| chain.at(version).map(|v| (k, v))).next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) { (0, self.chains.size_hint().1) }
+}
+
+impl<'a, K, V> DoubleEndedIterator for IterAt<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let version = self.version;
+        self.chains.by_ref().rev().filter_map(|(k, chain)| chain.at(version).map(|v| (k, v)))
+            .next()
+    }
+}
+
+/// An iterator over a versioned map's entries at a given version whose keys lie in a range.
+///
+/// Acquire through [`VersionedMap::range_at`](struct.VersionedMap.html#method.range_at).
+#[cfg(feature = "range")]
+pub struct RangeAt<'a, K: 'a, V: 'a> {
+    chains: map::Range<'a, K, Chain<V>>,
+    version: u64,
+}
+
+#[cfg(feature = "range")]
+impl<'a, K, V> Clone for RangeAt<'a, K, V> {
+    fn clone(&self) -> Self { RangeAt { chains: self.chains.clone(), version: self.version } }
+}
+
+#[cfg(feature = "range")]
+impl<'a, K, V> Iterator for RangeAt<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let version = self.version;
+        self.chains.by_ref().filter_map(|(k, chain)| chain.at(version).map(|v| (k, v))).next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) { (0, self.chains.size_hint().1) }
+}
+
+#[cfg(feature = "range")]
+impl<'a, K, V> DoubleEndedIterator for RangeAt<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let version = self.version;
+        self.chains.by_ref().rev().filter_map(|(k, chain)| chain.at(version).map(|v| (k, v)))
+            .next()
+    }
+}
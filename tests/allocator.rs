@@ -0,0 +1,79 @@
+#![cfg(feature = "allocator_api")]
+#![feature(allocator_api)]
+
+extern crate tree;
+
+use std::alloc::{AllocError, Allocator, Global, Layout};
+use std::cell::Cell;
+use std::ptr::NonNull;
+use tree::{Map, Set};
+
+/// An allocator that counts its live allocations.
+#[derive(Clone, Copy)]
+struct Counting<'a>(&'a Cell<usize>);
+
+unsafe impl<'a> Allocator for Counting<'a> {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        self.0.set(self.0.get() + 1);
+        Global.allocate(layout)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        self.0.set(self.0.get() - 1);
+        Global.deallocate(ptr, layout)
+    }
+}
+
+#[test]
+fn insert_and_remove() {
+    let live = Cell::new(0);
+    let mut map = Map::new_in(Counting(&live));
+
+    for i in 0..100 { map.insert(i, i); }
+    assert_eq!(live.get(), 100);
+
+    for i in 0..50 { map.remove(&i); }
+    assert_eq!(live.get(), 50);
+
+    drop(map);
+    assert_eq!(live.get(), 0);
+}
+
+#[test]
+fn entry() {
+    let live = Cell::new(0);
+    let mut map = Map::new_in(Counting(&live));
+
+    for s in vec!["a", "b", "a", "c", "a", "b"] { *map.entry(s).or_insert(0) += 1; }
+    assert_eq!(live.get(), 3);
+    assert_eq!(map[&"a"], 3);
+}
+
+#[test]
+fn clone() {
+    let live = Cell::new(0);
+    let mut map = Map::new_in(Counting(&live));
+    for i in 0..10 { map.insert(i, i); }
+
+    let copy = map.clone();
+    assert_eq!(live.get(), 20);
+    assert!(map == copy);
+
+    drop(map);
+    assert_eq!(live.get(), 10);
+}
+
+#[test]
+fn into_iter() {
+    let live = Cell::new(0);
+    let mut set = Set::new_in(Counting(&live));
+    for i in 0..10 { set.insert(i); }
+
+    let mut it = set.into_iter();
+    assert_eq!(it.next(), Some(0));
+    assert_eq!(it.next_back(), Some(9));
+    assert_eq!(live.get(), 8);
+
+    drop(it);
+    assert_eq!(live.get(), 0);
+}
@@ -0,0 +1,95 @@
+extern crate quickcheck;
+extern crate tree;
+
+use quickcheck::{Arbitrary, Gen, quickcheck};
+use tree::bimap::{BiMap, Overwritten};
+
+/// An operation on a `BiMap`, with values in `0..16`.
+#[derive(Clone, Debug)]
+enum Op {
+    Insert(u8, u8),
+    InsertNoOverwrite(u8, u8),
+    RemoveByLeft(u8),
+    RemoveByRight(u8),
+}
+
+impl Arbitrary for Op {
+    fn arbitrary<G: Gen>(gen: &mut G) -> Self {
+        let (l, r) = (gen.gen_range(0, 16), gen.gen_range(0, 16));
+
+        match gen.gen_range(0, 4) {
+            0 => Op::Insert(l, r),
+            1 => Op::InsertNoOverwrite(l, r),
+            2 => Op::RemoveByLeft(l),
+            _ => Op::RemoveByRight(r),
+        }
+    }
+}
+
+#[test]
+fn agrees_with_pairs() {
+    fn test(ops: Vec<Op>) -> bool {
+        let mut map = BiMap::new();
+        let mut pairs: Vec<(u8, u8)> = Vec::new();
+
+        for op in ops {
+            let ok = match op {
+                Op::Insert(l, r) => {
+                    let by_l = pairs.iter().position(|p| p.0 == l).map(|i| pairs.remove(i));
+                    let by_r = pairs.iter().position(|p| p.1 == r).map(|i| pairs.remove(i));
+                    pairs.push((l, r));
+
+                    let expected = match (by_l, by_r) {
+                        (None, None) => Overwritten::Neither,
+                        (Some(p), None) if p.1 == r => Overwritten::Pair(p.0, p.1),
+                        (Some(p), None) => Overwritten::Left(p.0, p.1),
+                        (None, Some(p)) => Overwritten::Right(p.0, p.1),
+                        (Some(p), Some(q)) => Overwritten::Both(p, q),
+                    };
+
+                    map.insert(l, r) == expected
+                }
+                Op::InsertNoOverwrite(l, r) => {
+                    let conflict = pairs.iter().any(|p| p.0 == l || p.1 == r);
+                    if !conflict { pairs.push((l, r)); }
+                    map.insert_no_overwrite(l, r) == if conflict { Err((l, r)) } else { Ok(()) }
+                }
+                Op::RemoveByLeft(l) => {
+                    let expected = pairs.iter().position(|p| p.0 == l).map(|i| pairs.remove(i));
+                    map.remove_by_left(&l) == expected
+                }
+                Op::RemoveByRight(r) => {
+                    let expected = pairs.iter().position(|p| p.1 == r).map(|i| pairs.remove(i));
+                    map.remove_by_right(&r) == expected
+                }
+            };
+
+            if !ok || map.len() != pairs.len() { return false; }
+        }
+
+        let mut by_left = pairs.clone();
+        by_left.sort();
+        let mut by_right: Vec<_> = pairs.iter().map(|p| (p.1, p.0)).collect();
+        by_right.sort();
+
+        map.iter_by_left().map(|e| (*e.0, *e.1)).eq(by_left.iter().cloned()) &&
+            map.iter_by_right().map(|e| (*e.0, *e.1)).eq(by_right.iter().cloned()) &&
+            pairs.iter().all(|p| map.get_by_left(&p.0) == Some(&p.1) &&
+                                 map.get_by_right(&p.1) == Some(&p.0))
+    }
+
+    quickcheck(test as fn(Vec<Op>) -> bool);
+}
+
+#[cfg(feature = "range")]
+#[test]
+fn ranges_on_both_sides() {
+    use std::ops::Bound::{Excluded, Included};
+
+    let map: BiMap<_, _> = (0..10).map(|i| (i, 9 - i)).collect();
+
+    assert_eq!(map.range_by_left(Included(&2), Excluded(&5)).collect::<Vec<_>>(),
+               [(&2, &7), (&3, &6), (&4, &5)]);
+    assert_eq!(map.range_by_right(Included(&2), Excluded(&5)).rev().collect::<Vec<_>>(),
+               [(&4, &5), (&3, &6), (&2, &7)]);
+}
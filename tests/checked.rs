@@ -0,0 +1,112 @@
+extern crate quickcheck;
+extern crate tree;
+
+use quickcheck::{Arbitrary, Gen, quickcheck};
+use std::cell::Cell;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use tree::checked::{CheckedMap, Error};
+
+/// An operation on a `CheckedMap`, with keys in `0..32`.
+#[derive(Clone, Debug)]
+enum Op {
+    Insert(u8, u8),
+    Remove(u8),
+    Get(u8),
+    EntryInsert(u8, u8),
+}
+
+impl Arbitrary for Op {
+    fn arbitrary<G: Gen>(gen: &mut G) -> Self {
+        let (key, value) = (gen.gen_range(0, 32), u8::arbitrary(gen));
+
+        match gen.gen_range(0, 4) {
+            0 => Op::Insert(key, value),
+            1 => Op::Remove(key),
+            2 => Op::Get(key),
+            _ => Op::EntryInsert(key, value),
+        }
+    }
+}
+
+#[test]
+fn consistent_comparator_is_never_reported() {
+    fn test(ops: Vec<Op>) -> bool {
+        let mut map = CheckedMap::new();
+        let mut model = BTreeMap::new();
+
+        for op in ops {
+            let agrees = match op {
+                Op::Insert(k, v) => map.insert(k, v) == Ok(model.insert(k, v)),
+                Op::Remove(k) => map.remove(&k) == Ok(model.remove(&k).map(|v| (k, v))),
+                Op::Get(k) => map.get(&k) == Ok(model.get(&k)),
+                Op::EntryInsert(k, v) => {
+                    *map.entry(k).unwrap().or_insert(0) = v;
+                    model.insert(k, v);
+                    true
+                }
+            };
+
+            if !agrees { return false; }
+        }
+
+        map.validate() == Ok(()) && map.iter().eq(model.iter().map(Ok))
+    }
+
+    quickcheck(test as fn(Vec<Op>) -> bool);
+}
+
+#[test]
+fn asymmetric_comparator_is_reported() {
+    let cmp = |l: &u32, r: &u32| if l == r { Ordering::Equal } else { Ordering::Less };
+    let mut map = CheckedMap::with_cmp(cmp);
+
+    assert_eq!(map.insert(0, "a"), Ok(None));
+    assert_eq!(map.insert(1, "b"), Err(Error::Asymmetric));
+    assert_eq!(map.get(&1), Err(Error::Asymmetric));
+    assert_eq!(map.get(&0), Ok(Some(&"a")));
+    assert_eq!(map.len(), 1);
+}
+
+#[test]
+fn changed_order_is_reported() {
+    // Orders the keys naturally, except that `moved` is treated as if it were greater than all
+    // other keys.
+    let moved = Cell::new(None);
+    let key = |k: &u32| if moved.get() == Some(*k) { u32::MAX } else { *k };
+    let cmp = |l: &u32, r: &u32| key(l).cmp(&key(r));
+
+    let mut map = CheckedMap::with_cmp(&cmp);
+    for i in 0..100 { map.insert(i * 2, i).unwrap(); }
+    assert_eq!(map.validate(), Ok(()));
+
+    moved.set(Some(100));
+    assert_eq!(map.validate(), Err(Error::OutOfOrder));
+
+    let results: Vec<_> = map.iter().collect();
+    assert_eq!(results.len(), 52);
+    assert_eq!(results[50], Ok((&100, &50)));
+    assert_eq!(results[51], Err(Error::OutOfOrder));
+
+    // Searches for keys next to the moved key pass it and detect it, leaving the map unchanged.
+    let len = map.len();
+    assert_eq!(map.insert(101, 0), Err(Error::OutOfOrder));
+    assert_eq!(map.remove(&101), Err(Error::OutOfOrder));
+    assert_eq!(map.len(), len);
+
+    moved.set(None);
+    assert_eq!(map.validate(), Ok(()));
+    assert_eq!(map.get(&102), Ok(Some(&51)));
+}
+
+#[test]
+fn from_map_validates() {
+    let reversed = Cell::new(false);
+    let cmp = |l: &u32, r: &u32| if reversed.get() { r.cmp(l) } else { l.cmp(r) };
+
+    let mut map = tree::Map::with_cmp(&cmp);
+    for i in 0..10 { map.insert(i, ()); }
+
+    reversed.set(true);
+    assert_eq!(CheckedMap::from_map(map).err(), Some(Error::OutOfOrder));
+}
@@ -0,0 +1,127 @@
+#![cfg(feature = "std")]
+
+extern crate tree;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use tree::concurrent::ConcurrentMap;
+
+const WRITERS: u32 = 4;
+const KEYS: u32 = 2000;
+
+fn map() -> Arc<ConcurrentMap<u32, u32>> {
+    Arc::new(ConcurrentMap::with_bounds((1..16).map(|i| i * WRITERS * KEYS / 16).collect()))
+}
+
+// Each writer owns the keys congruent to its index modulo `WRITERS` and inserts them in ascending
+// order, so a consistent view contains a prefix of each writer's keys.
+fn spawn_writers(map: &Arc<ConcurrentMap<u32, u32>>) -> Vec<thread::JoinHandle<()>> {
+    (0..WRITERS).map(|w| {
+        let map = map.clone();
+        thread::spawn(move || for i in 0..KEYS { map.insert(i * WRITERS + w, w); })
+    }).collect()
+}
+
+fn assert_prefixes<I>(keys: I) where I: IntoIterator<Item=u32> {
+    let mut next = vec![0; WRITERS as usize];
+
+    for key in keys {
+        let w = (key % WRITERS) as usize;
+        assert_eq!(key / WRITERS, next[w], "missing key {}", next[w] * WRITERS + w as u32);
+        next[w] += 1;
+    }
+}
+
+#[test]
+fn insert_get_remove() {
+    let map = map();
+    for thread in spawn_writers(&map) { thread.join().unwrap(); }
+    assert_eq!(map.len(), (WRITERS * KEYS) as usize);
+
+    let threads: Vec<_> = (0..WRITERS).map(|w| {
+        let map = map.clone();
+        thread::spawn(move || for i in 0..KEYS {
+            let key = i * WRITERS + w;
+            assert_eq!(map.get(&key), Some(w));
+            if i % 2 == 0 { assert_eq!(map.remove(&key), Some((key, w))); }
+        })
+    }).collect();
+
+    for thread in threads { thread.join().unwrap(); }
+
+    assert_eq!(map.len(), (WRITERS * KEYS / 2) as usize);
+    assert!(map.snapshot().iter().all(|(k, _)| (k / WRITERS) % 2 == 1));
+}
+
+#[test]
+fn concurrent_updates() {
+    let map = Arc::new(ConcurrentMap::with_bounds(vec![10, 20, 30]));
+    for key in 0..40 { map.insert(key, 0); }
+
+    let threads: Vec<_> = (0..8).map(|_| {
+        let map = map.clone();
+        thread::spawn(move || for _ in 0..1000 {
+            for key in 0..40 { map.with_mut(&key, |v| *v.unwrap() += 1); }
+        })
+    }).collect();
+
+    for thread in threads { thread.join().unwrap(); }
+
+    assert!(map.snapshot().iter().all(|(_, &v)| v == 8000));
+}
+
+#[test]
+fn panic_does_not_poison() {
+    let map = Arc::new(ConcurrentMap::with_bounds(vec![10]));
+    map.insert(5, 0);
+
+    let panicker = map.clone();
+    let result = thread::spawn(move || panicker.with_mut(&5, |_| panic!("in with_mut"))).join();
+    assert!(result.is_err());
+
+    map.insert(15, 1);
+    assert_eq!(map.get(&5), Some(0));
+    assert_eq!(map.snapshot().len(), 2);
+}
+
+#[test]
+fn consistent_snapshots() {
+    let map = map();
+    let writers = spawn_writers(&map);
+
+    while map.len() < (WRITERS * KEYS) as usize {
+        assert_prefixes(map.snapshot().iter().map(|e| *e.0));
+    }
+
+    for thread in writers { thread.join().unwrap(); }
+}
+
+#[cfg(feature = "range")]
+#[test]
+fn consistent_ranges() {
+    use std::ops::Bound::{Excluded, Unbounded};
+
+    let map = map();
+    let done = Arc::new(AtomicBool::new(false));
+
+    let readers: Vec<_> = (0..2).map(|_| {
+        let (map, done) = (map.clone(), done.clone());
+        thread::spawn(move || while !done.load(Ordering::SeqCst) {
+            let entries = map.range(Unbounded, Excluded(&(WRITERS * KEYS / 2)));
+            assert_prefixes(entries.into_iter().map(|e| e.0));
+
+            let mut prev = None;
+            map.scan(Excluded(&100), Unbounded, |&key, _| {
+                assert!(key > 100 && prev.map_or(true, |p| p < key));
+                prev = Some(key);
+            });
+        })
+    }).collect();
+
+    for thread in spawn_writers(&map) { thread.join().unwrap(); }
+    done.store(true, Ordering::SeqCst);
+    for thread in readers { thread.join().unwrap(); }
+
+    assert_eq!(map.range(Unbounded::<&u32>, Unbounded::<&u32>).len(), (WRITERS * KEYS) as usize);
+}
@@ -0,0 +1,237 @@
+#![cfg(feature = "std")]
+
+extern crate quickcheck;
+extern crate tree;
+
+use quickcheck::{Arbitrary, Gen, quickcheck};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tree::durable::DurableMap;
+
+/// An operation on a `DurableMap`, with keys in `0..16`.
+#[derive(Clone, Debug)]
+enum Op {
+    Insert(u8, String),
+    Remove(u8),
+    Clear,
+    Compact,
+    Reopen,
+}
+
+impl Arbitrary for Op {
+    fn arbitrary<G: Gen>(gen: &mut G) -> Self {
+        match gen.gen_range(0, 20) {
+            0..=9 => Op::Insert(gen.gen_range(0, 16), String::arbitrary(gen)),
+            10..=15 => Op::Remove(gen.gen_range(0, 16)),
+            16 => Op::Clear,
+            17 => Op::Compact,
+            _ => Op::Reopen,
+        }
+    }
+}
+
+/// A fresh directory that is removed when dropped.
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new() -> Self {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let name = format!("tree-durable-{}-{}", std::process::id(),
+                           NEXT.fetch_add(1, Ordering::SeqCst));
+        let dir = std::env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&dir);
+        TempDir(dir)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) { let _ = fs::remove_dir_all(&self.0); }
+}
+
+fn open(dir: &TempDir) -> DurableMap<u8, String> { DurableMap::open(&dir.0).unwrap() }
+
+fn apply(map: &mut DurableMap<u8, String>, expected: &mut BTreeMap<u8, String>, op: Op) {
+    match op {
+        Op::Insert(k, v) => assert_eq!(map.insert(k, v.clone()).unwrap(), expected.insert(k, v)),
+        Op::Remove(k) => assert_eq!(map.remove(&k).unwrap(), expected.remove(&k)),
+        Op::Clear => { map.clear().unwrap(); expected.clear(); }
+        Op::Compact => map.compact().unwrap(),
+        Op::Reopen => unreachable!(),
+    }
+}
+
+fn agrees(map: &DurableMap<u8, String>, expected: &BTreeMap<u8, String>) -> bool {
+    map.len() == expected.len() && map.iter().eq(expected.iter())
+}
+
+#[test]
+fn agrees_with_btree_map_across_reopens() {
+    fn test(ops: Vec<Op>) -> bool {
+        let dir = TempDir::new();
+        let mut map = open(&dir);
+        let mut expected = BTreeMap::new();
+
+        for op in ops {
+            if let Op::Reopen = op {
+                drop(map);
+                map = open(&dir);
+                if !agrees(&map, &expected) { return false; }
+            } else {
+                apply(&mut map, &mut expected, op);
+            }
+        }
+
+        drop(map);
+        agrees(&open(&dir), &expected)
+    }
+
+    quickcheck(test as fn(Vec<Op>) -> bool);
+}
+
+#[test]
+fn recovers_from_truncated_log() {
+    fn test(mut ops: Vec<Op>) -> bool {
+        // Every cut reopens the map twice, so keep the log short.
+        ops.truncate(8);
+
+        let dir = TempDir::new();
+        let mut map = open(&dir);
+        let mut expected = BTreeMap::new();
+        let mut states = vec![(map.log_len(), expected.clone())];
+
+        for op in ops {
+            match op {
+                Op::Compact | Op::Reopen => continue,
+                op => apply(&mut map, &mut expected, op),
+            }
+
+            states.push((map.log_len(), expected.clone()));
+        }
+
+        drop(map);
+        let log = fs::read(dir.0.join("log")).unwrap();
+
+        // Cut the log at every length, as a crash in the middle of any write could.
+        for cut in 0..log.len() + 1 {
+            let crashed = TempDir::new();
+            fs::create_dir_all(&crashed.0).unwrap();
+            fs::write(crashed.0.join("log"), &log[..cut]).unwrap();
+
+            let recovered = states.iter().rev().find(|s| s.0 <= cut as u64).unwrap_or(&states[0]);
+            let mut map = open(&crashed);
+            if !agrees(&map, &recovered.1) || map.log_len() != recovered.0 { return false; }
+
+            // A record appended after recovery must survive the next recovery.
+            let mut expected = recovered.1.clone();
+            apply(&mut map, &mut expected, Op::Insert(100, "after".to_string()));
+            drop(map);
+            if !agrees(&open(&crashed), &expected) { return false; }
+        }
+
+        true
+    }
+
+    quickcheck(test as fn(Vec<Op>) -> bool);
+}
+
+#[test]
+fn corrupt_record_ends_log() {
+    let dir = TempDir::new();
+    let mut map = open(&dir);
+    map.insert(1, "a".to_string()).unwrap();
+    let len = map.log_len();
+    map.insert(2, "b".to_string()).unwrap();
+    map.insert(3, "c".to_string()).unwrap();
+    drop(map);
+
+    let path = dir.0.join("log");
+    let mut log = fs::read(&path).unwrap();
+    log[len as usize + 3] ^= 1;
+    fs::write(&path, &log).unwrap();
+
+    let map = open(&dir);
+    assert_eq!(map.iter().collect::<Vec<_>>(), [(&1, &"a".to_string())]);
+    assert_eq!(map.log_len(), len);
+}
+
+#[test]
+fn interrupted_compaction_loses_nothing() {
+    let dir = TempDir::new();
+    let mut map = open(&dir);
+
+    for i in 0..10 { map.insert(i, i.to_string()).unwrap(); }
+    map.remove(&3).unwrap();
+    let log = fs::read(dir.0.join("log")).unwrap();
+
+    // A crash after the snapshot is renamed into place but before the log is truncated.
+    map.compact().unwrap();
+    drop(map);
+    fs::write(dir.0.join("log"), &log).unwrap();
+    fs::write(dir.0.join("snapshot.tmp"), b"partial").unwrap();
+
+    let map = open(&dir);
+    let expected: Vec<_> = (0..10).filter(|&i| i != 3).map(|i| (i, i.to_string())).collect();
+    assert_eq!(map.iter().map(|(k, v)| (*k, v.clone())).collect::<Vec<_>>(), expected);
+}
+
+#[test]
+fn compaction_threshold_bounds_log() {
+    let dir = TempDir::new();
+    let mut map = open(&dir);
+    map.set_compaction_threshold(Some(256));
+
+    for i in 0..1000u32 {
+        map.insert((i % 7) as u8, i.to_string()).unwrap();
+        assert!(map.log_len() < 256);
+    }
+
+    drop(map);
+    let map = open(&dir);
+    assert_eq!(map.len(), 7);
+    assert_eq!(map.get(&5), Some(&"999".to_string()));
+}
+
+#[test]
+fn failed_compaction_is_reported_separately() {
+    let dir = TempDir::new();
+    let mut map = open(&dir);
+    map.set_compaction_threshold(Some(64));
+
+    // A directory in the way of the temporary snapshot makes compaction fail.
+    fs::create_dir_all(dir.0.join("snapshot.tmp")).unwrap();
+
+    for i in 0..10 { assert_eq!(map.insert(i, i.to_string()).unwrap(), None); }
+    assert_eq!(map.remove(&0).unwrap(), Some("0".to_string()));
+    map.clear().unwrap();
+    assert!(map.is_empty() && map.log_len() >= 64);
+    assert!(map.take_compaction_error().is_some());
+    assert!(map.take_compaction_error().is_none());
+
+    fs::remove_dir(dir.0.join("snapshot.tmp")).unwrap();
+    map.insert(1, "a".to_string()).unwrap();
+    assert!(map.take_compaction_error().is_none());
+    assert!(map.log_len() < 64);
+
+    drop(map);
+    let map = open(&dir);
+    assert_eq!(map.iter().collect::<Vec<_>>(), [(&1, &"a".to_string())]);
+}
+
+#[test]
+fn corrupt_snapshot_is_an_error() {
+    let dir = TempDir::new();
+    let mut map = open(&dir);
+    map.insert(1, "a".to_string()).unwrap();
+    map.compact().unwrap();
+    drop(map);
+
+    let path = dir.0.join("snapshot");
+    let mut snapshot = fs::read(&path).unwrap();
+    *snapshot.last_mut().unwrap() ^= 1;
+    fs::write(&path, &snapshot).unwrap();
+
+    let e = DurableMap::<u8, String>::open(&dir.0).unwrap_err();
+    assert_eq!(e.kind(), std::io::ErrorKind::InvalidData);
+}
@@ -0,0 +1,129 @@
+#![cfg(feature = "std")]
+
+extern crate quickcheck;
+extern crate tree;
+
+use quickcheck::{Arbitrary, Gen, quickcheck};
+use std::collections::BTreeMap;
+use std::time::Duration;
+use tree::expiring::{Clock, ExpiringMap, ManualClock};
+
+/// An operation on an `ExpiringMap`, with keys in `0..16` and times in seconds.
+#[derive(Clone, Debug)]
+enum Op {
+    Insert(u8, u8),
+    InsertWithTtl(u8, u8, u8),
+    Remove(u8),
+    Advance(u8),
+    Evict,
+}
+
+impl Arbitrary for Op {
+    fn arbitrary<G: Gen>(gen: &mut G) -> Self {
+        match gen.gen_range(0, 5) {
+            0 => Op::Insert(gen.gen_range(0, 16), u8::arbitrary(gen)),
+            1 => Op::InsertWithTtl(gen.gen_range(0, 16), u8::arbitrary(gen), gen.gen_range(0, 8)),
+            2 => Op::Remove(gen.gen_range(0, 16)),
+            3 => Op::Advance(gen.gen_range(0, 4)),
+            _ => Op::Evict,
+        }
+    }
+}
+
+#[test]
+fn agrees_with_model() {
+    fn test(ops: Vec<Op>) -> bool {
+        let clock = ManualClock::new();
+        let mut map = ExpiringMap::with_clock(&clock);
+        // Maps each key to its value and deadline in seconds.
+        let mut model: BTreeMap<u8, (u8, Option<u64>)> = BTreeMap::new();
+
+        for op in ops {
+            let now = clock.now().as_secs();
+            let live = |e: &(u8, Option<u64>)| e.1.map_or(true, |deadline| deadline > now);
+
+            let ok = match op {
+                Op::Insert(key, value) => {
+                    let old = model.insert(key, (value, None))
+                        .and_then(|e| Some(e.0).filter(|_| live(&e)));
+                    map.insert(key, value) == old
+                }
+                Op::InsertWithTtl(key, value, ttl) => {
+                    let old = model.insert(key, (value, Some(now + ttl as u64)))
+                        .and_then(|e| Some(e.0).filter(|_| live(&e)));
+                    map.insert_with_ttl(key, value, Duration::from_secs(ttl as u64)) == old
+                }
+                Op::Remove(key) => {
+                    let old = model.remove(&key)
+                        .and_then(|e| Some((key, e.0)).filter(|_| live(&e)));
+                    map.remove(&key) == old
+                }
+                Op::Advance(secs) => {
+                    clock.advance(Duration::from_secs(secs as u64));
+                    true
+                }
+                Op::Evict => {
+                    let before = model.len();
+                    model.retain(|_, e| live(e));
+                    map.evict_expired(&clock.now()) == before - model.len()
+                }
+            };
+
+            if !ok || map.len() != model.len() { return false; }
+
+            let now = clock.now().as_secs();
+            let live = |e: &(u8, Option<u64>)| e.1.map_or(true, |deadline| deadline > now);
+            let expected: Vec<_> = model.iter().filter(|e| live(e.1)).map(|e| (*e.0, (e.1).0))
+                .collect();
+
+            if !map.iter().map(|e| (*e.0, *e.1)).eq(expected.iter().cloned()) { return false; }
+            if !map.iter().rev().map(|e| (*e.0, *e.1)).eq(expected.iter().rev().cloned()) {
+                return false;
+            }
+            if !(0..16).all(|k| map.get(&k) == model.get(&k).filter(|e| live(e)).map(|e| &e.0)) {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    quickcheck(test as fn(Vec<Op>) -> bool);
+}
+
+#[test]
+fn evicts_in_deadline_order() {
+    let clock = ManualClock::new();
+    let mut map = ExpiringMap::with_clock(&clock);
+
+    for i in 0..100 { map.insert_with_ttl(i, i, Duration::from_secs(100 - i)); }
+    map.insert_with_ttl(50, 50, Duration::from_secs(1_000));
+    map.insert(99, 99);
+
+    clock.set(Duration::from_secs(50));
+    assert_eq!(map.iter().count(), 52);
+    assert_eq!(map.len(), 100);
+
+    assert_eq!(map.evict_expired(&clock.now()), 48);
+    assert_eq!(map.len(), 52);
+    assert_eq!(map.deadline(&50), Some(&Duration::from_secs(1_000)));
+    assert_eq!(map.deadline(&99), None);
+    assert_eq!(map.iter().rev().take(2).collect::<Vec<_>>(), [(&99, &99), (&50, &50)]);
+}
+
+#[cfg(feature = "range")]
+#[test]
+fn range_skips_expired_entries() {
+    use std::ops::Bound::{Excluded, Included};
+
+    let clock = ManualClock::new();
+    let mut map = ExpiringMap::with_clock(&clock);
+
+    for i in 0..10 { map.insert_with_ttl(i, i, Duration::from_secs(1 + i % 2)); }
+    clock.advance(Duration::from_secs(1));
+
+    assert_eq!(map.range(Included(&2), Excluded(&8)).map(|e| *e.0).collect::<Vec<_>>(),
+               [3, 5, 7]);
+    assert_eq!(map.range(Included(&2), Excluded(&8)).rev().map(|e| *e.0).collect::<Vec<_>>(),
+               [7, 5, 3]);
+}
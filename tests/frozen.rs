@@ -0,0 +1,75 @@
+#![cfg(feature = "quickcheck")]
+
+extern crate quickcheck;
+extern crate tree;
+
+use quickcheck::quickcheck;
+use tree::Map;
+
+#[test]
+fn agrees_with_map() {
+    fn test(map: Map<u8, u16>, keys: Vec<u8>) -> bool {
+        let frozen = map.clone().freeze();
+
+        frozen.len() == map.len() &&
+            frozen.iter().eq(map.iter()) &&
+            frozen.iter().rev().eq(map.iter().rev()) &&
+            frozen.max() == map.iter().next_back() &&
+            frozen.min() == map.iter().next() &&
+            keys.iter().all(|k| frozen.get(k) == map.get(k) &&
+                                frozen.pred(k, false) == map.pred(k, false) &&
+                                frozen.pred(k, true) == map.pred(k, true) &&
+                                frozen.succ(k, false) == map.succ(k, false) &&
+                                frozen.succ(k, true) == map.succ(k, true))
+    }
+
+    quickcheck(test as fn(Map<u8, u16>, Vec<u8>) -> bool);
+}
+
+#[test]
+fn iter_from_both_ends() {
+    fn test(map: Map<u8, u16>, fronts: Vec<bool>) -> bool {
+        let frozen = map.clone().freeze();
+        let (mut expected, mut actual) = (map.iter(), frozen.iter());
+
+        for front in fronts {
+            let ok = if front { actual.next() == expected.next() }
+                     else { actual.next_back() == expected.next_back() };
+            if !ok || actual.len() != expected.len() { return false; }
+        }
+
+        actual.eq(expected)
+    }
+
+    quickcheck(test as fn(Map<u8, u16>, Vec<bool>) -> bool);
+}
+
+#[test]
+fn thaw_round_trips() {
+    fn test(map: Map<u8, u16>) -> bool {
+        let thawed = map.clone().freeze().thaw();
+        thawed.len() == map.len() && thawed.iter().eq(map.iter())
+    }
+
+    quickcheck(test as fn(Map<u8, u16>) -> bool);
+}
+
+#[cfg(feature = "range")]
+#[test]
+fn range_agrees_with_map() {
+    use std::ops::Bound::{self, Excluded, Included, Unbounded};
+
+    fn bound(b: u8, k: &u8) -> Bound<&u8> {
+        match b % 3 { 0 => Included(k), 1 => Excluded(k), _ => Unbounded }
+    }
+
+    fn test(map: Map<u8, u16>, min: (u8, u8), max: (u8, u8)) -> bool {
+        let frozen = map.clone().freeze();
+        let (min, max) = (bound(min.0, &min.1), bound(max.0, &max.1));
+
+        frozen.range(min, max).eq(map.range(min, max)) &&
+            frozen.range(min, max).rev().eq(map.range(min, max).rev())
+    }
+
+    quickcheck(test as fn(Map<u8, u16>, (u8, u8), (u8, u8)) -> bool);
+}
@@ -0,0 +1,210 @@
+extern crate quickcheck;
+extern crate tree;
+
+use quickcheck::{Arbitrary, Gen, quickcheck};
+use std::collections::BTreeMap;
+use std::ops::Bound::{self, Excluded, Included, Unbounded};
+use tree::lazy::{AddMax, AddMin, AddSum, LazyMap};
+
+/// A bound on a key.
+#[derive(Clone, Copy, Debug)]
+enum B {
+    Included(u8),
+    Excluded(u8),
+    Unbounded,
+}
+
+impl Arbitrary for B {
+    fn arbitrary<G: Gen>(gen: &mut G) -> Self {
+        match gen.gen_range(0, 3) {
+            0 => B::Included(gen.gen_range(0, 32)),
+            1 => B::Excluded(gen.gen_range(0, 32)),
+            _ => B::Unbounded,
+        }
+    }
+}
+
+impl B {
+    fn as_bound(&self) -> Bound<&u8> {
+        match *self {
+            B::Included(ref key) => Included(key),
+            B::Excluded(ref key) => Excluded(key),
+            B::Unbounded => Unbounded,
+        }
+    }
+
+    fn below(&self, key: u8) -> bool {
+        match *self { B::Included(min) => key < min, B::Excluded(min) => key <= min, _ => false }
+    }
+
+    fn above(&self, key: u8) -> bool {
+        match *self { B::Included(max) => key > max, B::Excluded(max) => key >= max, _ => false }
+    }
+}
+
+/// An operation on a `LazyMap`, with keys in `0..32`.
+#[derive(Clone, Debug)]
+enum Op {
+    Insert(u8, i8),
+    Remove(u8),
+    Update(B, B, i8),
+    Aggregate(B, B),
+    Get(u8),
+}
+
+impl Arbitrary for Op {
+    fn arbitrary<G: Gen>(gen: &mut G) -> Self {
+        match gen.gen_range(0, 5) {
+            0 => Op::Insert(gen.gen_range(0, 32), i8::arbitrary(gen)),
+            1 => Op::Remove(gen.gen_range(0, 32)),
+            2 => Op::Update(B::arbitrary(gen), B::arbitrary(gen), i8::arbitrary(gen)),
+            3 => Op::Aggregate(B::arbitrary(gen), B::arbitrary(gen)),
+            _ => Op::Get(gen.gen_range(0, 32)),
+        }
+    }
+}
+
+struct Maps {
+    sum: LazyMap<u8, i64, AddSum>,
+    max: LazyMap<u8, i64, AddMax>,
+    min: LazyMap<u8, i64, AddMin>,
+    model: BTreeMap<u8, i64>,
+}
+
+impl Maps {
+    fn exec(&mut self, op: Op) -> bool {
+        match op {
+            Op::Insert(key, value) => {
+                let value = value as i64;
+                let old = self.model.insert(key, value);
+                self.sum.insert(key, value) == old && self.max.insert(key, value) == old &&
+                    self.min.insert(key, value) == old
+            }
+            Op::Remove(key) => {
+                let old = self.model.remove(&key).map(|value| (key, value));
+                self.sum.remove(&key) == old && self.max.remove(&key) == old &&
+                    self.min.remove(&key) == old
+            }
+            Op::Update(min, max, delta) => {
+                let delta = delta as i64;
+
+                for (key, value) in &mut self.model {
+                    if !min.below(*key) && !max.above(*key) { *value += delta; }
+                }
+
+                self.sum.update_range(min.as_bound(), max.as_bound(), delta);
+                self.max.update_range(min.as_bound(), max.as_bound(), delta);
+                self.min.update_range(min.as_bound(), max.as_bound(), delta);
+                true
+            }
+            Op::Aggregate(min, max) => {
+                let values: Vec<i64> = self.model.iter()
+                    .filter(|e| !min.below(*e.0) && !max.above(*e.0))
+                    .map(|e| *e.1)
+                    .collect();
+
+                let sum = if values.is_empty() { None } else { Some(values.iter().sum()) };
+
+                self.sum.aggregate(min.as_bound(), max.as_bound()) == sum &&
+                    self.max.aggregate(min.as_bound(), max.as_bound()) ==
+                        values.iter().cloned().max() &&
+                    self.min.aggregate(min.as_bound(), max.as_bound()) ==
+                        values.iter().cloned().min()
+            }
+            Op::Get(key) => {
+                let expected = self.model.get(&key);
+                self.sum.get(&key) == expected && self.max.get(&key) == expected &&
+                    self.min.get(&key) == expected
+            }
+        }
+    }
+}
+
+#[test]
+fn agrees_with_btree_map() {
+    fn test(ops: Vec<Op>) -> bool {
+        let mut maps = Maps {
+            sum: LazyMap::new(),
+            max: LazyMap::new(),
+            min: LazyMap::new(),
+            model: BTreeMap::new(),
+        };
+
+        for op in ops {
+            if !maps.exec(op) { return false; }
+            if maps.sum.len() != maps.model.len() { return false; }
+            let total = if maps.model.is_empty() { None } else { Some(maps.model.values().sum()) };
+            if maps.sum.aggregate_all().cloned() != total { return false; }
+        }
+
+        maps.sum.iter().map(|e| (*e.0, *e.1)).eq(maps.model.iter().map(|e| (*e.0, *e.1))) &&
+            maps.max.into_iter().rev().eq(maps.model.into_iter().rev())
+    }
+
+    quickcheck(test as fn(Vec<Op>) -> bool);
+}
+
+#[test]
+fn updates_large_ranges() {
+    let mut map: LazyMap<u32, i64, AddSum> = (0..10_000).map(|i| (i, 0)).collect();
+
+    for i in 0..5_000 { map.update_range(Included(&i), Excluded(&(10_000 - i)), 1); }
+
+    let total = (0..5_000).map(|i| 10_000 - 2 * i).sum();
+    assert_eq!(map.aggregate(Unbounded, Unbounded), Some(total));
+    assert_eq!(map.get(&0), Some(&1));
+    assert_eq!(map.get(&4_999), Some(&5_000));
+    assert_eq!(map.aggregate(Included(&4_999), Included(&5_000)), Some(10_000));
+    assert!(map.iter().all(|(&i, &v)| v == 1 + i.min(9_999 - i) as i64));
+}
+
+#[test]
+fn sums_narrow_types() {
+    let mut map: LazyMap<u32, i8, AddSum> =
+        (0..200).map(|i| (i, if i < 100 { -1 } else { 0 })).collect();
+    assert_eq!(map.aggregate_all(), Some(&-100));
+
+    map.update_range(Unbounded, Unbounded, 1);
+    assert_eq!(map.aggregate_all(), Some(&100));
+    assert_eq!(map.aggregate(Included(&100), Unbounded), Some(100));
+
+    map.update_range(Unbounded, Excluded(&100), 100);
+    map.update_range(Unbounded, Excluded(&100), -100);
+    assert_eq!(map.aggregate(Unbounded, Excluded(&100)), Some(0));
+}
+
+#[test]
+fn extremes_of_narrow_types_wrap() {
+    let mut max: LazyMap<u32, i8, AddMax> = (0..10).map(|i| (i, 100 + i as i8)).collect();
+    let mut min: LazyMap<u32, i8, AddMin> = (0..10).map(|i| (i, -100 - i as i8)).collect();
+
+    max.update_range(Unbounded, Unbounded, 100);
+    min.update_range(Unbounded, Unbounded, -100);
+    max.update_range(Unbounded, Unbounded, -100);
+    min.update_range(Unbounded, Unbounded, 100);
+
+    assert_eq!(max.aggregate_all(), Some(&109));
+    assert_eq!(min.aggregate_all(), Some(&-109));
+    assert_eq!(max.get(&0), Some(&100));
+    assert_eq!(min.get(&0), Some(&-100));
+}
+
+#[test]
+fn debug_applies_pending_updates() {
+    fn test(ops: Vec<Op>) -> bool {
+        let mut maps = Maps {
+            sum: LazyMap::new(),
+            max: LazyMap::new(),
+            min: LazyMap::new(),
+            model: BTreeMap::new(),
+        };
+
+        for op in ops {
+            if !maps.exec(op) { return false; }
+        }
+
+        format!("{:?}", maps.sum) == format!("{:?}", maps.model)
+    }
+
+    quickcheck(test as fn(Vec<Op>) -> bool);
+}
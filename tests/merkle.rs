@@ -0,0 +1,137 @@
+#![cfg(feature = "quickcheck")]
+
+extern crate quickcheck;
+extern crate tree;
+
+use quickcheck::quickcheck;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::ops::Bound::{self, Excluded, Included, Unbounded};
+use tree::Map;
+use tree::merkle::{Difference, Digest, DigestProvider, MerkleMap};
+
+fn merkle(map: &Map<u8, u8>) -> MerkleMap<u8, u8> {
+    map.iter().map(|(k, v)| (*k, *v)).collect()
+}
+
+fn contains(min: Bound<&u8>, max: Bound<&u8>, key: &u8) -> bool {
+    (match min { Included(min) => min <= key, Excluded(min) => min < key, Unbounded => true }) &&
+    (match max { Included(max) => key <= max, Excluded(max) => key < max, Unbounded => true })
+}
+
+#[test]
+fn root_hash_ignores_insertion_order() {
+    fn test(map: Map<u8, u8>) -> bool {
+        let forward = merkle(&map);
+        let backward: MerkleMap<_, _> = map.iter().rev().map(|(k, v)| (*k, *v)).collect();
+        let linear: MerkleMap<_, _> = map.clone().augment();
+
+        forward.root_hash() == backward.root_hash() && forward.root_hash() == linear.root_hash() &&
+            forward.iter().eq(map.iter()) && linear.iter().eq(map.iter())
+    }
+
+    quickcheck(test as fn(Map<u8, u8>) -> bool);
+}
+
+#[test]
+fn eq_and_hash_agree_with_map() {
+    fn hash<T: Hash>(t: &T) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        t.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn test(left: Map<u8, u8>, right: Map<u8, u8>) -> bool {
+        (merkle(&left) == merkle(&right)) == (left == right) &&
+            hash(&merkle(&left)) == hash(&left)
+    }
+
+    quickcheck(test as fn(Map<u8, u8>, Map<u8, u8>) -> bool);
+}
+
+#[test]
+fn root_hash_after_removal() {
+    fn test(map: Map<u8, u8>, removed: Vec<u8>) -> bool {
+        let mut merkle = merkle(&map);
+        let mut map = map;
+
+        for key in &removed {
+            if merkle.remove(key) != map.remove(key) { return false; }
+        }
+
+        merkle.root_hash() == self::merkle(&map).root_hash() && merkle.len() == map.len()
+    }
+
+    quickcheck(test as fn(Map<u8, u8>, Vec<u8>) -> bool);
+}
+
+#[test]
+fn digest_agrees_with_submap() {
+    fn test(map: Map<u8, u8>, min: u8, max: u8, min_inc: bool, max_inc: bool) -> bool {
+        let min = if min_inc { Included(&min) } else { Excluded(&min) };
+        let max = if max_inc { Included(&max) } else { Excluded(&max) };
+
+        let sub: Map<_, _> =
+            map.iter().filter(|e| contains(min, max, e.0)).map(|(k, v)| (*k, *v)).collect();
+        let digest = merkle(&map).digest(min, max);
+
+        digest == merkle(&sub).digest(Unbounded, Unbounded) && digest.len() == sub.len()
+    }
+
+    quickcheck(test as fn(Map<u8, u8>, u8, u8, bool, bool) -> bool);
+}
+
+#[test]
+fn diff_against_finds_differences() {
+    fn test(local: Map<u8, u8>, remote: Map<u8, u8>) -> bool {
+        let (local_merkle, remote_merkle) = (merkle(&local), merkle(&remote));
+        let diffs = local_merkle.diff_against(&remote_merkle);
+
+        let entries: Vec<_> = diffs.iter().filter_map(|d| match *d {
+            Difference::Entry(k, v) => Some((k, v)),
+            Difference::Missing(..) => None,
+        }).collect();
+
+        let expected: Vec<_> = local.iter().filter(|e| remote.get(e.0) != Some(e.1)).collect();
+
+        // Every remote entry the local map lacks lies in exactly one missing range, and every
+        // missing range contains such an entry.
+        let missing = remote.iter().filter(|e| local.get(e.0) != Some(e.1));
+
+        let covered = missing.filter(|e| !local.contains_key(e.0)).all(|(k, _)|
+            diffs.iter().filter(|d| match **d {
+                Difference::Missing(min, max) => contains(min, max, k),
+                Difference::Entry(..) => false,
+            }).count() == 1);
+
+        let nonempty = diffs.iter().all(|d| match *d {
+            Difference::Missing(min, max) => remote.iter().any(|e| contains(min, max, e.0)),
+            Difference::Entry(..) => true,
+        });
+
+        entries == expected && covered && nonempty &&
+            (diffs.is_empty() == (local_merkle.root_hash() == remote_merkle.root_hash()))
+    }
+
+    quickcheck(test as fn(Map<u8, u8>, Map<u8, u8>) -> bool);
+}
+
+#[test]
+fn diff_against_visits_only_differing_subtrees() {
+    struct Counting<'a>(&'a MerkleMap<u32, u32>, usize);
+
+    impl<'a> DigestProvider<u32> for Counting<'a> {
+        fn digest(&mut self, min: Bound<&u32>, max: Bound<&u32>) -> Digest {
+            self.1 += 1;
+            self.0.digest(min, max)
+        }
+    }
+
+    let local: MerkleMap<_, _> = (0..100_000).map(|i| (i, i)).collect();
+    let mut remote = local.clone();
+    remote.insert(12_345, 0);
+
+    let mut provider = Counting(&remote, 0);
+    assert_eq!(local.diff_against(&mut provider), [Difference::Entry(&12_345, &12_345)]);
+    assert!(provider.1 < 100, "{} digests requested", provider.1);
+}
@@ -0,0 +1,138 @@
+extern crate quickcheck;
+extern crate tree;
+
+use quickcheck::{Arbitrary, Gen, quickcheck};
+use std::collections::BTreeMap;
+use tree::observed::{Change, Entry, ObservedMap};
+
+/// A mutation of an `ObservedMap`, with keys in `0..16`.
+#[derive(Clone, Debug)]
+enum Op {
+    Insert(u8, u8),
+    Remove(u8),
+    RemoveMax,
+    RemoveMin,
+    RemovePred(u8, bool),
+    RemoveSucc(u8, bool),
+    EntryInsert(u8, u8),
+    EntryRemove(u8),
+    Clear,
+}
+
+impl Arbitrary for Op {
+    fn arbitrary<G: Gen>(gen: &mut G) -> Self {
+        let (key, value) = (gen.gen_range(0, 16), u8::arbitrary(gen));
+
+        match gen.gen_range(0, 17) {
+            0 | 1 | 2 => Op::Insert(key, value),
+            3 | 4 => Op::Remove(key),
+            5 => Op::RemoveMax,
+            6 => Op::RemoveMin,
+            7 | 8 => Op::RemovePred(key, bool::arbitrary(gen)),
+            9 | 10 => Op::RemoveSucc(key, bool::arbitrary(gen)),
+            11 | 12 | 13 => Op::EntryInsert(key, value),
+            14 | 15 => Op::EntryRemove(key),
+            _ => Op::Clear,
+        }
+    }
+}
+
+// Applies a change to a mirror of the map, checking that it is consistent with the mirror.
+fn apply(mirror: &mut BTreeMap<u8, u8>, change: Change<u8, u8>) {
+    match change {
+        Change::Inserted(k, v) => assert_eq!(mirror.insert(*k, *v), None),
+        Change::Updated(k, old, new) => assert_eq!(mirror.insert(*k, *new), Some(*old)),
+        Change::Removed(k, v) => assert_eq!(mirror.remove(k), Some(*v)),
+    }
+}
+
+#[test]
+fn listener_mirrors_map() {
+    fn test(ops: Vec<Op>) -> bool {
+        let mut mirror = BTreeMap::new();
+        let map = {
+            let mut map = ObservedMap::new(|change: Change<_, _>| apply(&mut mirror, change));
+
+            for op in ops {
+                match op {
+                    Op::Insert(k, v) => { map.insert(k, v); }
+                    Op::Remove(k) => { map.remove(&k); }
+                    Op::RemoveMax => { map.remove_max(); }
+                    Op::RemoveMin => { map.remove_min(); }
+                    Op::RemovePred(k, inclusive) => { map.remove_pred(&k, inclusive); }
+                    Op::RemoveSucc(k, inclusive) => { map.remove_succ(&k, inclusive); }
+                    Op::EntryInsert(k, v) => match map.entry(k) {
+                        Entry::Occupied(mut e) => { e.insert(v); }
+                        Entry::Vacant(e) => { e.insert(v); }
+                    },
+                    Op::EntryRemove(k) => if let Entry::Occupied(e) = map.entry(k) { e.remove(); },
+                    Op::Clear => map.clear(),
+                }
+            }
+
+            map.into_inner().0
+        };
+
+        map.into_iter().eq(mirror)
+    }
+
+    quickcheck(test as fn(Vec<Op>) -> bool);
+}
+
+type Listener<'a> = &'a mut dyn FnMut(Change<u8, u8>);
+
+// Runs the function on a map reporting to a log of its changes, and returns the log.
+fn log<F>(f: F) -> Vec<String> where F: FnOnce(&mut ObservedMap<u8, u8, Listener>) {
+    let mut log = Vec::new();
+
+    {
+        let mut listener = |change: Change<_, _>| log.push(format!("{:?}", change));
+        let mut map = ObservedMap::new(&mut listener as Listener);
+        f(&mut map);
+    }
+
+    log
+}
+
+#[test]
+fn reports_removals_by_neighbor() {
+    let log = log(|map| {
+        map.extend(vec![(1, 10), (3, 30), (5, 50)]);
+        assert_eq!(map.remove_pred(&3, false), Some((1, 10)));
+        assert_eq!(map.remove_pred(&3, true), Some((3, 30)));
+        assert_eq!(map.remove_pred(&3, true), None);
+        assert_eq!(map.remove_succ(&5, false), None);
+        assert_eq!(map.remove_succ(&4, false), Some((5, 50)));
+    });
+
+    assert_eq!(log[3..], ["Removed(1, 10)", "Removed(3, 30)", "Removed(5, 50)"]);
+}
+
+#[test]
+fn reports_entry_changes() {
+    let log = log(|map| {
+        if let Entry::Vacant(e) = map.entry(1) { assert_eq!(*e.insert(10), 10); }
+        if let Entry::Occupied(mut e) = map.entry(1) { assert_eq!(e.insert(11), 10); }
+        if let Entry::Occupied(e) = map.entry(1) { assert_eq!(e.remove(), (1, 11)); }
+        assert!(map.is_empty());
+    });
+
+    assert_eq!(log, ["Inserted(1, 10)", "Updated(1, 10, 11)", "Removed(1, 11)"]);
+}
+
+#[test]
+fn reports_changes_in_order() {
+    let log = log(|map| {
+        map.insert(2, 20);
+        map.insert(1, 10);
+        map.insert(3, 30);
+        map.insert(2, 21);
+        map.remove(&1);
+        map.clear();
+        assert!(map.is_empty());
+    });
+
+    assert_eq!(log, ["Inserted(2, 20)", "Inserted(1, 10)", "Inserted(3, 30)",
+                     "Updated(2, 20, 21)", "Removed(1, 10)", "Removed(2, 21)",
+                     "Removed(3, 30)"]);
+}
@@ -0,0 +1,112 @@
+#![cfg(feature = "quickcheck")]
+
+extern crate quickcheck;
+extern crate tree;
+
+use quickcheck::{TestResult, quickcheck};
+use tree::Map;
+use tree::packed::{self, Error, PackedMap};
+
+fn copied<K: Copy, V: Copy>(entry: (&K, &V)) -> (K, V) { (*entry.0, *entry.1) }
+
+#[test]
+fn fixed_width_agrees_with_map() {
+    fn test(map: Map<i16, u32>, keys: Vec<i16>) -> bool {
+        let bytes = packed::to_vec(&map).unwrap();
+        let packed = PackedMap::<i16, u32>::new(&bytes).unwrap();
+
+        packed.len() == map.len() &&
+            packed.iter().eq(map.iter().map(copied)) &&
+            packed.iter().rev().eq(map.iter().rev().map(copied)) &&
+            keys.iter().all(|k| packed.get(k) == map.get(k).cloned() &&
+                                [false, true].iter().all(|&inc| {
+                                    packed.pred(k, inc) == map.pred(k, inc).map(copied) &&
+                                    packed.succ(k, inc) == map.succ(k, inc).map(copied)
+                                }))
+    }
+
+    quickcheck(test as fn(Map<i16, u32>, Vec<i16>) -> bool);
+}
+
+#[test]
+fn variable_width_agrees_with_map() {
+    fn test(map: Map<String, Vec<u8>>, keys: Vec<String>) -> bool {
+        let bytes = packed::to_vec(&map).unwrap();
+        let packed = PackedMap::<&str, &[u8]>::new(&bytes).unwrap();
+
+        packed.iter().eq(map.iter().map(|(k, v)| (&k[..], &v[..]))) &&
+            keys.iter().chain(map.iter().map(|e| e.0)).all(|k| {
+                packed.get(k) == map.get(k).map(|v| &v[..]) &&
+                    packed.pred(k, false).map(|e| e.0) == map.pred(k, false).map(|e| &e.0[..]) &&
+                    packed.succ(k, true).map(|e| e.0) == map.succ(k, true).map(|e| &e.0[..])
+            })
+    }
+
+    quickcheck(test as fn(Map<String, Vec<u8>>, Vec<String>) -> bool);
+}
+
+#[test]
+fn corruption_is_detected() {
+    fn test(map: Map<String, u8>, at: usize, bit: u8) -> TestResult {
+        let mut bytes = packed::to_vec(&map).unwrap();
+        let at = at % bytes.len();
+        bytes[at] ^= 1 << (bit % 8);
+        TestResult::from_bool(PackedMap::<String, u8>::new(&bytes).is_err())
+    }
+
+    quickcheck(test as fn(Map<String, u8>, usize, u8) -> TestResult);
+}
+
+#[test]
+fn truncation_is_detected() {
+    fn test(map: Map<u8, String>, len: usize) -> TestResult {
+        let bytes = packed::to_vec(&map).unwrap();
+        let len = len % bytes.len();
+        TestResult::from_bool(PackedMap::<u8, String>::new(&bytes[..len]).is_err())
+    }
+
+    quickcheck(test as fn(Map<u8, String>, usize) -> TestResult);
+}
+
+#[test]
+fn write_agrees_with_to_vec() {
+    fn test(map: Map<String, u64>) -> bool {
+        let mut out = Vec::new();
+        packed::write(&map, &mut out).unwrap();
+        out == packed::to_vec(&map).unwrap()
+    }
+
+    quickcheck(test as fn(Map<String, u64>) -> bool);
+}
+
+#[test]
+fn unsorted_entries_are_rejected() {
+    let map: Map<u32, ()> = (0..10).map(|i| (i, ())).collect();
+    assert_eq!(packed::to_vec(map.iter().rev()), Err(Error::Unsorted));
+
+    let mut out = Vec::new();
+    let e = packed::write(map.iter().rev(), &mut out).unwrap_err();
+    assert_eq!(e.kind(), std::io::ErrorKind::InvalidInput);
+    assert!(out.is_empty());
+}
+
+#[cfg(feature = "range")]
+#[test]
+fn range_agrees_with_map() {
+    use std::ops::Bound::{self, Excluded, Included, Unbounded};
+
+    fn bound(b: u8, k: &u8) -> Bound<&u8> {
+        match b % 3 { 0 => Included(k), 1 => Excluded(k), _ => Unbounded }
+    }
+
+    fn test(map: Map<u8, u16>, min: (u8, u8), max: (u8, u8)) -> bool {
+        let bytes = packed::to_vec(&map).unwrap();
+        let packed = PackedMap::<u8, u16>::new(&bytes).unwrap();
+        let (min, max) = (bound(min.0, &min.1), bound(max.0, &max.1));
+
+        packed.range(min, max).eq(map.range(min, max).map(copied)) &&
+            packed.range(min, max).len() == map.range(min, max).count()
+    }
+
+    quickcheck(test as fn(Map<u8, u16>, (u8, u8), (u8, u8)) -> bool);
+}
@@ -0,0 +1,120 @@
+extern crate quickcheck;
+extern crate tree;
+
+use quickcheck::{Arbitrary, Gen, quickcheck};
+use tree::queue::{Handle, PriorityQueue};
+
+/// An operation on a `PriorityQueue`, with handles chosen by index modulo the number of pushes.
+#[derive(Clone, Debug)]
+enum Op {
+    Push(u8, u8),
+    PopMin,
+    PopMax,
+    ChangePriority(usize, u8),
+    Remove(usize),
+}
+
+impl Arbitrary for Op {
+    fn arbitrary<G: Gen>(gen: &mut G) -> Self {
+        match gen.gen_range(0, 5) {
+            0 => Op::Push(u8::arbitrary(gen), gen.gen_range(0, 8)),
+            1 => Op::PopMin,
+            2 => Op::PopMax,
+            3 => Op::ChangePriority(usize::arbitrary(gen), gen.gen_range(0, 8)),
+            _ => Op::Remove(usize::arbitrary(gen)),
+        }
+    }
+}
+
+/// A naive model of a `PriorityQueue`, holding `(handle, item, priority)` in push order.
+struct Model {
+    handles: Vec<Handle>,
+    items: Vec<(Handle, u8, u8)>,
+}
+
+impl Model {
+    fn position(&self, min: bool) -> Option<usize> {
+        let key = |i: &usize| (self.items[*i].2, self.items[*i].0);
+        let indices = 0..self.items.len();
+        if min { indices.min_by_key(key) } else { indices.max_by_key(key) }
+    }
+
+    fn pop(&mut self, min: bool) -> Option<(u8, u8)> {
+        self.position(min).map(|i| { let (_, t, p) = self.items.remove(i); (t, p) })
+    }
+
+    fn find(&self, handle: Handle) -> Option<usize> {
+        self.items.iter().position(|e| e.0 == handle)
+    }
+}
+
+#[test]
+fn agrees_with_model() {
+    fn test(ops: Vec<Op>) -> bool {
+        let mut queue = PriorityQueue::new();
+        let mut model = Model { handles: Vec::new(), items: Vec::new() };
+
+        for op in ops {
+            let ok = match op {
+                Op::Push(item, priority) => {
+                    let handle = queue.push(item, priority);
+                    model.handles.push(handle);
+                    model.items.push((handle, item, priority));
+                    true
+                }
+                Op::PopMin => queue.pop_min() == model.pop(true),
+                Op::PopMax => queue.pop_max() == model.pop(false),
+                Op::ChangePriority(i, priority) => model.handles.is_empty() || {
+                    let handle = model.handles[i % model.handles.len()];
+                    let expected = model.find(handle).map(|i| {
+                        let old = model.items[i].2;
+                        model.items[i].2 = priority;
+                        old
+                    });
+                    queue.change_priority(handle, priority) == expected
+                },
+                Op::Remove(i) => model.handles.is_empty() || {
+                    let handle = model.handles[i % model.handles.len()];
+                    let expected = model.find(handle)
+                        .map(|i| { let (_, t, p) = model.items.remove(i); (t, p) });
+                    queue.remove(handle) == expected && !queue.contains(handle)
+                },
+            };
+
+            if !ok || queue.len() != model.items.len() { return false; }
+
+            let min = model.position(true).map(|i| (&model.items[i].1, &model.items[i].2));
+            let max = model.position(false).map(|i| (&model.items[i].1, &model.items[i].2));
+            if queue.peek_min() != min || queue.peek_max() != max { return false; }
+        }
+
+        model.items.iter().all(|e| queue.get(e.0) == Some((&e.1, &e.2)))
+    }
+
+    quickcheck(test as fn(Vec<Op>) -> bool);
+}
+
+#[test]
+fn handles_are_not_reused() {
+    let mut queue = PriorityQueue::new();
+    let a = queue.push('a', 1);
+    assert_eq!(queue.pop_min(), Some(('a', 1)));
+
+    let b = queue.push('b', 1);
+    assert!(a != b);
+    assert!(!queue.contains(a));
+    assert_eq!(queue.get(a), None);
+    assert_eq!(queue.change_priority(a, 2), None);
+    assert_eq!(queue.remove(a), None);
+    assert_eq!(queue.min_handle(), Some(b));
+    assert_eq!(queue.max_handle(), Some(b));
+}
+
+#[test]
+fn iterates_in_priority_order() {
+    let queue: PriorityQueue<_, _> = vec![('a', 3), ('b', 1), ('c', 3), ('d', 2)].into_iter()
+        .collect();
+
+    assert_eq!(queue.iter().map(|e| *e.0).collect::<String>(), "bdac");
+    assert_eq!(queue.into_iter().rev().map(|e| e.0).collect::<String>(), "cadb");
+}
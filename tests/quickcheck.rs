@@ -1,4 +1,4 @@
-#![cfg_attr(feature = "range", feature(collections_bound))]
+#![cfg(feature = "quickcheck")]
 
 extern crate compare;
 extern crate quickcheck;
@@ -447,11 +447,11 @@ mod iter {
 #[cfg(feature = "range")]
 mod range {
     use quickcheck::{Arbitrary, Gen, quickcheck};
-    use std::collections::Bound::*;
+    use std::ops::Bound::*;
     use tree::Map;
 
     #[derive(Clone, Debug)]
-    struct Bound<T>(::std::collections::Bound<T>);
+    struct Bound<T>(::std::ops::Bound<T>);
 
     impl<T> Bound<T> {
         fn as_ref(&self) -> Bound<&T> {
@@ -527,3 +527,325 @@ mod range {
         quickcheck(test as fn(Map<u32, u16>, Bound<u32>, Bound<u32>) -> bool);
     }
 }
+
+mod diff {
+    use quickcheck::quickcheck;
+    use std::panic::{AssertUnwindSafe, catch_unwind};
+    use tree::Map;
+    use tree::map::DiffItem;
+
+    #[test]
+    fn agrees_with_lookups() {
+        fn test(old: Map<u8, u8>, new: Map<u8, u8>) -> bool {
+            let diff: Vec<_> = old.diff(&new).collect();
+
+            let mut keys: Vec<_> = old.iter().chain(&new).map(|e| e.0).collect();
+            keys.sort();
+            keys.dedup();
+
+            let expected: Vec<_> = keys.into_iter().filter_map(|k| match (old.get(k), new.get(k)) {
+                (Some(o), None) => Some(DiffItem::Removed(k, o)),
+                (None, Some(n)) => Some(DiffItem::Added(k, n)),
+                (Some(o), Some(n)) if o != n => Some(DiffItem::Changed(k, o, n)),
+                _ => None,
+            }).collect();
+
+            diff == expected
+        }
+
+        quickcheck(test as fn(Map<u8, u8>, Map<u8, u8>) -> bool);
+    }
+
+    #[test]
+    fn apply_patch_makes_equal() {
+        fn test(mut old: Map<u8, u8>, new: Map<u8, u8>) -> bool {
+            let patch: Vec<_> = old.diff(&new).map(DiffItem::cloned).collect();
+            old.apply_patch(patch);
+            old == new && old.diff(&new).next().is_none()
+        }
+
+        quickcheck(test as fn(Map<u8, u8>, Map<u8, u8>) -> bool);
+    }
+
+    #[test]
+    fn apply_partial_patch_agrees_with_inserts_and_removes() {
+        fn test(old: Map<u8, u8>, new: Map<u8, u8>, keep: Vec<bool>) -> bool {
+            let patch: Vec<_> = old.diff(&new).map(DiffItem::cloned)
+                .zip(keep.into_iter().cycle()).filter(|e| e.1).map(|e| e.0).collect();
+
+            let mut expected = old.clone();
+
+            for item in patch.clone() {
+                match item {
+                    DiffItem::Added(k, v) | DiffItem::Changed(k, _, v) => { expected.insert(k, v); }
+                    DiffItem::Removed(k, _) => { expected.remove(&k); }
+                }
+            }
+
+            let mut old = old;
+            old.apply_patch(patch);
+            old == expected
+        }
+
+        quickcheck(test as fn(Map<u8, u8>, Map<u8, u8>, Vec<bool>) -> bool);
+    }
+
+    #[test]
+    fn apply_small_patch_to_large_map() {
+        let mut old: Map<u32, u32> = (0..1000).map(|i| (i, i)).collect();
+        let mut new = old.clone();
+        new.remove(&10);
+        new.insert(500, 0);
+        new.insert(1000, 1000);
+
+        let patch: Vec<_> = old.diff(&new).map(DiffItem::cloned).collect();
+        old.apply_patch(patch);
+        assert!(old == new);
+    }
+
+    #[test]
+    fn misordered_patch_leaves_map_unchanged() {
+        // one patch is small enough to be applied by searching, and the other is merged
+        for &len in &[1000, 10] {
+            let map: Map<u32, u32> = (0..len).map(|i| (i, i)).collect();
+            let patch = vec![DiffItem::Added(len + 1, 0), DiffItem::Removed(0, 0)];
+
+            let mut patched = map.clone();
+            let result = catch_unwind(AssertUnwindSafe(|| patched.apply_patch(patch)));
+            assert!(result.is_err());
+            assert!(patched == map);
+        }
+    }
+}
+
+mod prefix {
+    use quickcheck::quickcheck;
+    use tree::Map;
+    use tree::prefix::{BitPrefix, Prefix};
+
+    // Maps arbitrary bytes to short strings over a small alphabet, so that prefixes are common.
+    fn key(bytes: Vec<u8>) -> Vec<u8> {
+        bytes.into_iter().take(4).map(|b| b % 3).collect()
+    }
+
+    #[test]
+    fn longest_prefix_of_agrees_with_search() {
+        fn test(keys: Vec<Vec<u8>>, query: Vec<u8>) -> bool {
+            let map: Map<_, _> = keys.into_iter().map(key).map(|k| (k, ())).collect();
+            let query = key(query);
+            let expected = map.iter().filter(|e| e.0.is_prefix_of(&query)).map(|e| e.0).last();
+
+            map.longest_prefix_of(&query).map(|e| e.0) == expected
+        }
+
+        quickcheck(test as fn(Vec<Vec<u8>>, Vec<u8>) -> bool);
+    }
+
+    #[test]
+    fn longest_prefix_of_bit_prefixes() {
+        fn test(nets: Vec<(u32, u8)>, addr: u32) -> bool {
+            let map: Map<_, _> = nets.into_iter()
+                .map(|(bits, len)| (BitPrefix::from_u32(bits, len as u32 % 33), ()))
+                .collect();
+            let query = BitPrefix::from_u32(addr, 32);
+            let expected = map.iter().filter(|e| e.0.is_prefix_of(&query)).map(|e| e.0).last();
+
+            map.longest_prefix_of(&query).map(|e| e.0) == expected
+        }
+
+        quickcheck(test as fn(Vec<(u32, u8)>, u32) -> bool);
+    }
+
+    #[cfg(feature = "range")]
+    #[test]
+    fn prefix_range_agrees_with_filter() {
+        fn test(keys: Vec<Vec<u8>>, prefix: Vec<u8>) -> bool {
+            let map: Map<_, _> = keys.into_iter().map(key).map(|k| (k, ())).collect();
+            let prefix = key(prefix);
+
+            map.prefix_range(&prefix).eq(map.iter().filter(|e| prefix.is_prefix_of(e.0)))
+        }
+
+        quickcheck(test as fn(Vec<Vec<u8>>, Vec<u8>) -> bool);
+    }
+}
+
+mod nearest {
+    use quickcheck::quickcheck;
+    use tree::Map;
+    use tree::distance::Distance;
+
+    // Orders entries by distance from the key, breaking ties by key.
+    fn by_distance(map: &Map<i8, ()>, key: i8) -> Vec<(&i8, &())> {
+        let mut entries: Vec<_> = map.iter().collect();
+        entries.sort_by_key(|e| (e.0.distance(&key), *e.0));
+        entries
+    }
+
+    #[test]
+    fn nearest_agrees_with_sort() {
+        fn test(map: Map<i8, ()>, key: i8) -> bool {
+            map.nearest(&key) == by_distance(&map, key).first().cloned()
+        }
+
+        quickcheck(test as fn(Map<i8, ()>, i8) -> bool);
+    }
+
+    #[test]
+    fn k_nearest_agrees_with_sort() {
+        fn test(map: Map<i8, ()>, key: i8, k: usize) -> bool {
+            let k = k % 8;
+            let mut expected = by_distance(&map, key);
+            expected.truncate(k);
+            map.k_nearest(&key, k) == expected
+        }
+
+        quickcheck(test as fn(Map<i8, ()>, i8, usize) -> bool);
+    }
+}
+
+mod combine {
+    use quickcheck::quickcheck;
+    use std::collections::BTreeMap;
+    use std::panic::{AssertUnwindSafe, catch_unwind};
+    use std::rc::Rc;
+    use tree::Map;
+
+    #[test]
+    fn extend_with_agrees_with_btree_map() {
+        fn test(entries: Vec<(u8, u16)>) -> bool {
+            let mut map = Map::new();
+            map.extend_with(entries.iter().map(|&(k, v)| (k, v as u32)), |total, v| *total += v);
+
+            let mut expected = BTreeMap::new();
+            for (k, v) in entries { *expected.entry(k).or_insert(0) += v as u32; }
+
+            map.into_iter().eq(expected)
+        }
+
+        quickcheck(test as fn(Vec<(u8, u16)>) -> bool);
+    }
+
+    #[test]
+    fn update_agrees_with_btree_map() {
+        fn test(map: Map<u8, u8>, ops: Vec<(u8, Option<u8>)>) -> bool {
+            let mut map = map;
+            let mut expected: BTreeMap<_, _> = map.iter().map(|e| (*e.0, *e.1)).collect();
+
+            for (k, delta) in ops {
+                // Adds the delta to the value, or removes the key if there is no delta.
+                let f = |v: Option<u8>| delta.map(|d| v.unwrap_or(0).wrapping_add(d));
+
+                match f(expected.get(&k).cloned()) {
+                    None => { expected.remove(&k); }
+                    Some(v) => { expected.insert(k, v); }
+                }
+
+                map.update(k, f);
+            }
+
+            map.into_iter().eq(expected)
+        }
+
+        quickcheck(test as fn(Map<u8, u8>, Vec<(u8, Option<u8>)>) -> bool);
+    }
+
+    #[test]
+    fn update_removes_entry_if_function_panics() {
+        let token = Rc::new(());
+        let mut map: Map<_, _> = (0..10).map(|k| (k, token.clone())).collect();
+
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            map.update(5, |value| { let _value = value; panic!("update failed") });
+        }));
+
+        assert!(result.is_err());
+        assert_eq!(map.len(), 9);
+        assert!(!map.contains_key(&5));
+        assert_eq!(Rc::strong_count(&token), 10);
+    }
+
+    #[test]
+    fn merge_keeps_all_keys() {
+        fn test(a: Map<u8, u8>, b: Map<u8, u8>) -> bool {
+            let mut expected: BTreeMap<_, _> = a.iter().map(|e| (*e.0, *e.1 as u32)).collect();
+            for (k, v) in &b { *expected.entry(*k).or_insert(0) += *v as u32; }
+
+            let mut merged: Map<_, _> = a.into_iter().map(|(k, v)| (k, v as u32)).collect();
+            merged.merge(b.into_iter().map(|(k, v)| (k, v as u32)).collect::<Map<_, _>>(),
+                         |x, y| *x += y);
+
+            merged.into_iter().eq(expected)
+        }
+
+        quickcheck(test as fn(Map<u8, u8>, Map<u8, u8>) -> bool);
+    }
+}
+
+mod transaction {
+    use quickcheck::quickcheck;
+    use std::panic::{AssertUnwindSafe, catch_unwind};
+    use tree::Map;
+
+    // Applies the operations in a transaction that fails after they are applied if `fail` is set.
+    fn run(map: &mut Map<u8, u8>, ops: &[(u8, Option<u8>)], fail: bool) -> Result<(), ()> {
+        map.transaction(|tx| {
+            for &(k, v) in ops {
+                let old = tx.get(&k).cloned();
+
+                match v {
+                    Some(v) => assert_eq!(tx.insert(k, v).cloned(), old),
+                    None => assert_eq!(tx.remove(&k).map(|e| *e.1), old),
+                }
+
+                assert_eq!(tx.get(&k).cloned(), v);
+            }
+
+            if fail { Err(()) } else { Ok(()) }
+        })
+    }
+
+    #[test]
+    fn rolls_back_on_err() {
+        fn test(map: Map<u8, u8>, ops: Vec<(u8, Option<u8>)>) -> bool {
+            let mut copy = map.clone();
+            run(&mut copy, &ops, true).is_err() && copy == map
+        }
+
+        quickcheck(test as fn(Map<u8, u8>, Vec<(u8, Option<u8>)>) -> bool);
+    }
+
+    #[test]
+    fn commits_on_ok() {
+        fn test(map: Map<u8, u8>, ops: Vec<(u8, Option<u8>)>) -> bool {
+            let mut expected = map.clone();
+
+            for &(k, v) in &ops {
+                match v {
+                    Some(v) => { expected.insert(k, v); }
+                    None => { expected.remove(&k); }
+                }
+            }
+
+            let mut map = map;
+            run(&mut map, &ops, false).is_ok() && map == expected
+        }
+
+        quickcheck(test as fn(Map<u8, u8>, Vec<(u8, Option<u8>)>) -> bool);
+    }
+
+    #[test]
+    fn rolls_back_on_panic() {
+        let mut map: Map<_, _> = (0..10).map(|i| (i, i)).collect();
+        let expected = map.clone();
+
+        let result = catch_unwind(AssertUnwindSafe(|| map.transaction(|tx| -> Result<(), ()> {
+            tx.insert(20, 20);
+            tx.remove(&5);
+            panic!("validation failed");
+        })));
+
+        assert!(result.is_err());
+        assert_eq!(map, expected);
+    }
+}
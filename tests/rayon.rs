@@ -0,0 +1,69 @@
+#![cfg(feature = "rayon")]
+
+extern crate quickcheck;
+extern crate rayon;
+extern crate tree;
+
+use quickcheck::quickcheck;
+use rayon::prelude::*;
+use tree::{Map, Set};
+
+#[test]
+fn par_iter_agrees_with_iter() {
+    fn test(map: Map<u32, u16>) -> bool {
+        map.par_iter().collect::<Vec<_>>() == map.iter().collect::<Vec<_>>()
+    }
+
+    quickcheck(test as fn(Map<u32, u16>) -> bool);
+}
+
+#[test]
+fn par_iter_mut_agrees_with_iter_mut() {
+    fn test(mut map: Map<u32, u16>) -> bool {
+        let mut expected = map.clone();
+        for (_, value) in expected.iter_mut() { *value = value.wrapping_mul(3); }
+
+        map.par_iter_mut().for_each(|(_, value)| *value = value.wrapping_mul(3));
+        map == expected
+    }
+
+    quickcheck(test as fn(Map<u32, u16>) -> bool);
+}
+
+#[test]
+fn into_par_iter_agrees_with_into_iter() {
+    fn test(map: Map<u32, u16>) -> bool {
+        map.clone().into_par_iter().collect::<Vec<_>>() == map.into_iter().collect::<Vec<_>>()
+    }
+
+    quickcheck(test as fn(Map<u32, u16>) -> bool);
+}
+
+#[test]
+fn from_par_iter_agrees_with_from_iter() {
+    fn test(entries: Vec<(u8, u16)>) -> bool {
+        let map: Map<u8, u16> = entries.par_iter().cloned().collect();
+        map == entries.into_iter().collect() && map.len() == map.iter().count()
+    }
+
+    quickcheck(test as fn(Vec<(u8, u16)>) -> bool);
+}
+
+#[test]
+fn set() {
+    fn test(items: Vec<u8>) -> bool {
+        let set: Set<u8> = items.par_iter().cloned().collect();
+        set == items.iter().cloned().collect() &&
+            set.par_iter().collect::<Vec<_>>() == set.iter().collect::<Vec<_>>() &&
+            set.clone().into_par_iter().collect::<Vec<_>>() == set.into_iter().collect::<Vec<_>>()
+    }
+
+    quickcheck(test as fn(Vec<u8>) -> bool);
+}
+
+#[test]
+fn early_exit() {
+    let map: Map<u32, u32> = (0..10000).map(|i| (i, i)).collect();
+    assert_eq!(map.par_iter().find_first(|e| *e.0 >= 5000), Some((&5000, &5000)));
+    assert!(map.par_iter().any(|e| *e.1 == 9999));
+}
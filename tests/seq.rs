@@ -0,0 +1,121 @@
+extern crate quickcheck;
+extern crate tree;
+
+use quickcheck::{Arbitrary, Gen, Rng, quickcheck};
+use tree::seq::Seq;
+
+/// An operation on a `Seq`, with indices taken modulo the sequence's length.
+#[derive(Clone, Debug)]
+enum Op {
+    Insert(usize, u8),
+    Remove(usize),
+    PushBack(u8),
+    PushFront(u8),
+    PopBack,
+    PopFront,
+    Set(usize, u8),
+    SplitOffAppend(usize),
+}
+
+impl Arbitrary for Op {
+    fn arbitrary<G: Gen>(gen: &mut G) -> Self {
+        match gen.gen_range(0, 8) {
+            0 => Op::Insert(usize::arbitrary(gen), u8::arbitrary(gen)),
+            1 => Op::Remove(usize::arbitrary(gen)),
+            2 => Op::PushBack(u8::arbitrary(gen)),
+            3 => Op::PushFront(u8::arbitrary(gen)),
+            4 => Op::PopBack,
+            5 => Op::PopFront,
+            6 => Op::Set(usize::arbitrary(gen), u8::arbitrary(gen)),
+            _ => Op::SplitOffAppend(usize::arbitrary(gen)),
+        }
+    }
+}
+
+impl Op {
+    fn exec(self, seq: &mut Seq<u8>, vec: &mut Vec<u8>) {
+        match self {
+            Op::Insert(i, x) => {
+                let i = i % (vec.len() + 1);
+                seq.insert(i, x);
+                vec.insert(i, x);
+            }
+            Op::Remove(i) => {
+                let i = if vec.is_empty() { i } else { i % vec.len() };
+                let expected = if i < vec.len() { Some(vec.remove(i)) } else { None };
+                assert_eq!(seq.remove(i), expected);
+            }
+            Op::PushBack(x) => { seq.push_back(x); vec.push(x); }
+            Op::PushFront(x) => { seq.push_front(x); vec.insert(0, x); }
+            Op::PopBack => assert_eq!(seq.pop_back(), vec.pop()),
+            Op::PopFront => {
+                let expected = if vec.is_empty() { None } else { Some(vec.remove(0)) };
+                assert_eq!(seq.pop_front(), expected);
+            }
+            Op::Set(i, x) => if !vec.is_empty() {
+                let i = i % vec.len();
+                seq[i] = x;
+                vec[i] = x;
+            },
+            Op::SplitOffAppend(at) => {
+                let at = at % (vec.len() + 1);
+                let mut back = seq.split_off(at);
+                assert_eq!(seq.len(), at);
+                assert!(back.iter().eq(&vec[at..]));
+                seq.append(&mut back);
+                assert!(back.is_empty());
+            }
+        }
+    }
+}
+
+#[test]
+fn agrees_with_vec() {
+    fn test(ops: Vec<Op>) -> bool {
+        let mut seq = Seq::new();
+        let mut vec = Vec::new();
+
+        for op in ops {
+            op.exec(&mut seq, &mut vec);
+            if seq.len() != vec.len() || !seq.iter().eq(&vec) { return false; }
+        }
+
+        (0..vec.len()).all(|i| seq.get(i) == vec.get(i)) && seq.get(vec.len()).is_none()
+    }
+
+    quickcheck(test as fn(Vec<Op>) -> bool);
+}
+
+#[test]
+fn iterates_in_both_directions() {
+    fn test(vec: Vec<u8>, mut forward: Vec<bool>) -> bool {
+        let seq: Seq<_> = vec.iter().cloned().collect();
+        let mut it = seq.iter();
+        let (mut front, mut back) = (0, vec.len());
+
+        forward.truncate(vec.len());
+
+        for forward in forward {
+            let expected = if forward { front += 1; &vec[front - 1] }
+                           else { back -= 1; &vec[back] };
+            let actual = if forward { it.next() } else { it.next_back() };
+            if actual != Some(expected) || it.len() != back - front { return false; }
+        }
+
+        it.eq(&vec[front..back])
+    }
+
+    quickcheck(test as fn(Vec<u8>, Vec<bool>) -> bool);
+}
+
+#[test]
+fn into_iter_and_iter_mut() {
+    fn test(vec: Vec<u8>) -> bool {
+        let mut seq: Seq<_> = vec.iter().cloned().collect();
+        for x in &mut seq { *x = x.wrapping_add(1); }
+
+        seq.into_iter().rev().eq(vec.into_iter().rev().map(|x| x.wrapping_add(1)))
+    }
+
+    quickcheck(test as fn(Vec<u8>) -> bool);
+}
@@ -0,0 +1,245 @@
+extern crate quickcheck;
+extern crate tree;
+
+use quickcheck::{Arbitrary, Gen, quickcheck};
+use std::collections::BTreeMap;
+use std::ops::Bound::{Excluded, Included, Unbounded};
+use tree::small::{Entry, SmallMap};
+
+/// A mutation of a `SmallMap`, with keys in `0..16`.
+#[derive(Clone, Debug)]
+enum Op {
+    Insert(u8, u8),
+    Remove(u8),
+    RemoveMax,
+    RemoveMin,
+    RemovePred(u8, bool),
+    RemoveSucc(u8, bool),
+    EntryInsert(u8, u8),
+    EntryRemove(u8),
+    InsertWith(u8, u8),
+    Update(u8, Option<u8>),
+    MaxEntryInsert(u8),
+    MinEntryRemove,
+    PredEntryRemove(u8, bool),
+    SuccEntryInsert(u8, bool, u8),
+    Clear,
+}
+
+impl Arbitrary for Op {
+    fn arbitrary<G: Gen>(gen: &mut G) -> Self {
+        let (key, value) = (gen.gen_range(0, 16), u8::arbitrary(gen));
+
+        match gen.gen_range(0, 33) {
+            0..=7 => Op::Insert(key, value),
+            8 | 9 => Op::Remove(key),
+            10 => Op::RemoveMax,
+            11 => Op::RemoveMin,
+            12 | 13 => Op::RemovePred(key, bool::arbitrary(gen)),
+            14 | 15 => Op::RemoveSucc(key, bool::arbitrary(gen)),
+            16..=20 => Op::EntryInsert(key, value),
+            21..=23 => Op::EntryRemove(key),
+            24 | 25 => Op::InsertWith(key, value),
+            26 | 27 => Op::Update(key, Option::arbitrary(gen)),
+            28 => Op::MaxEntryInsert(value),
+            29 => Op::MinEntryRemove,
+            30 => Op::PredEntryRemove(key, bool::arbitrary(gen)),
+            31 => Op::SuccEntryInsert(key, bool::arbitrary(gen), value),
+            _ => Op::Clear,
+        }
+    }
+}
+
+fn pred(model: &BTreeMap<u8, u8>, key: u8, inclusive: bool) -> Option<(u8, u8)> {
+    let max = if inclusive { Included(&key) } else { Excluded(&key) };
+    model.range((Unbounded, max)).next_back().map(|(&k, &v)| (k, v))
+}
+
+fn succ(model: &BTreeMap<u8, u8>, key: u8, inclusive: bool) -> Option<(u8, u8)> {
+    let min = if inclusive { Included(&key) } else { Excluded(&key) };
+    model.range((min, Unbounded)).next().map(|(&k, &v)| (k, v))
+}
+
+// Applies the operations to a map and a model, checking that they agree after each one.
+fn check<const N: usize>(ops: Vec<Op>) -> bool {
+    let mut map: SmallMap<u8, u8, _, N> = SmallMap::new();
+    let mut model = BTreeMap::new();
+
+    for op in ops {
+        let agrees = match op {
+            Op::Insert(k, v) => map.insert(k, v) == model.insert(k, v),
+            Op::Remove(k) => map.remove(&k) == model.remove(&k).map(|v| (k, v)),
+            Op::RemoveMax => {
+                let max = model.keys().next_back().cloned();
+                map.remove_max() == max.map(|k| (k, model.remove(&k).unwrap()))
+            }
+            Op::RemoveMin => {
+                let min = model.keys().next().cloned();
+                map.remove_min() == min.map(|k| (k, model.remove(&k).unwrap()))
+            }
+            Op::RemovePred(k, inclusive) => {
+                let expected = pred(&model, k, inclusive);
+                if let Some((k, _)) = expected { model.remove(&k); }
+                map.remove_pred(&k, inclusive) == expected
+            }
+            Op::RemoveSucc(k, inclusive) => {
+                let expected = succ(&model, k, inclusive);
+                if let Some((k, _)) = expected { model.remove(&k); }
+                map.remove_succ(&k, inclusive) == expected
+            }
+            Op::EntryInsert(k, v) => {
+                let old = model.insert(k, v);
+                match map.entry(k) {
+                    Entry::Occupied(mut e) => *e.key() == k && Some(e.insert(v)) == old,
+                    Entry::Vacant(e) => *e.key() == k && *e.insert(v) == v && old.is_none(),
+                }
+            }
+            Op::EntryRemove(k) => match map.entry(k) {
+                Entry::Occupied(e) => e.remove() == (k, model.remove(&k).unwrap()),
+                Entry::Vacant(_) => !model.contains_key(&k),
+            },
+            Op::InsertWith(k, v) => {
+                let expected = *model.entry(k).and_modify(|old| *old ^= v).or_insert(v);
+                *map.insert_with(k, v, |old, v| *old ^= v) == expected
+            }
+            Op::Update(k, delta) => {
+                // Adds the delta to the value, or removes the key if there is no delta.
+                let f = |v: Option<u8>| delta.map(|d| v.unwrap_or(0).wrapping_add(d));
+
+                match f(model.get(&k).cloned()) {
+                    None => { model.remove(&k); }
+                    Some(v) => { model.insert(k, v); }
+                }
+
+                map.update(k, f);
+                true
+            }
+            Op::MaxEntryInsert(v) => match map.max_entry() {
+                Some(mut e) => Some(e.insert(v)) == model.values_mut().next_back().map(|old| {
+                    std::mem::replace(old, v)
+                }),
+                None => model.is_empty(),
+            },
+            Op::MinEntryRemove => match map.min_entry() {
+                Some(e) => {
+                    let (k, v) = e.remove();
+                    model.remove(&k) == Some(v) && model.keys().all(|&key| key > k)
+                }
+                None => model.is_empty(),
+            },
+            Op::PredEntryRemove(k, inclusive) => {
+                let expected = pred(&model, k, inclusive);
+                if let Some((k, _)) = expected { model.remove(&k); }
+                map.pred_entry(&k, inclusive).map(|e| e.remove()) == expected
+            }
+            Op::SuccEntryInsert(k, inclusive, v) => {
+                let expected = succ(&model, k, inclusive);
+                if let Some((k, _)) = expected { model.insert(k, v); }
+                map.succ_entry(&k, inclusive).map(|mut e| (*e.key(), e.insert(v))) == expected
+            }
+            Op::Clear => { map.clear(); model.clear(); true }
+        };
+
+        if !agrees || map.len() != model.len() || (model.len() > N && map.is_inline()) {
+            return false;
+        }
+
+        for k in 0..17 {
+            if map.get(&k) != model.get(&k) { return false; }
+
+            for &inclusive in &[false, true] {
+                if map.pred(&k, inclusive).map(|(&k, &v)| (k, v)) != pred(&model, k, inclusive)
+                    || map.succ(&k, inclusive).map(|(&k, &v)| (k, v))
+                        != succ(&model, k, inclusive) {
+                    return false;
+                }
+            }
+        }
+
+        if !map.iter().eq(model.iter()) || !map.iter().rev().eq(model.iter().rev()) {
+            return false;
+        }
+    }
+
+    map.into_iter().eq(model)
+}
+
+#[test]
+fn agrees_with_btree_map() {
+    fn test(ops: Vec<Op>) -> bool {
+        check::<0>(ops.clone()) && check::<1>(ops.clone()) && check::<4>(ops.clone()) &&
+            check::<8>(ops)
+    }
+
+    quickcheck(test as fn(Vec<Op>) -> bool);
+}
+
+#[test]
+fn promotes_when_full() {
+    let mut map: SmallMap<_, _, _, 2> = SmallMap::new();
+    map.insert(1, 'a');
+    map.insert(2, 'b');
+    map.insert(2, 'c');
+    assert!(map.is_inline());
+
+    map.insert(0, 'd');
+    assert!(!map.is_inline());
+    assert_eq!(map.iter().collect::<Vec<_>>(), [(&0, &'d'), (&1, &'a'), (&2, &'c')]);
+
+    map.clear();
+    assert!(map.is_empty());
+    assert!(!map.is_inline());
+}
+
+#[test]
+fn iter_mut() {
+    let mut map: SmallMap<_, _, _, 4> = SmallMap::new();
+    map.extend((0..4).map(|i| (i, i)));
+
+    for (k, v) in &mut map { *v += k * 10; }
+    *map.max_mut().unwrap().1 += 1;
+    assert_eq!(map[&3], 34);
+    assert_eq!(map.into_iter().rev().collect::<Vec<_>>(), [(3, 34), (2, 22), (1, 11), (0, 0)]);
+}
+
+#[test]
+fn merge() {
+    let mut a: SmallMap<_, _, _, 2> = SmallMap::new();
+    let mut b: SmallMap<_, _, _, 4> = SmallMap::new();
+    a.extend(vec![(1, 10), (2, 20)]);
+    b.extend(vec![(2, 2), (3, 3)]);
+
+    a.merge(b, |x, y| *x += y);
+    assert!(!a.is_inline());
+    assert_eq!(a.into_iter().collect::<Vec<_>>(), [(1, 10), (2, 22), (3, 3)]);
+}
+
+#[cfg(feature = "range")]
+#[test]
+fn range() {
+    let mut inline: SmallMap<_, _, _, 8> = SmallMap::new();
+    let mut tree: SmallMap<_, _, _, 2> = SmallMap::new();
+    inline.extend((0..8).map(|i| (i, i)));
+    tree.extend((0..8).map(|i| (i, i)));
+    assert!(inline.is_inline() && !tree.is_inline());
+
+    for min in 0..10 {
+        for max in 0..10 {
+            for &(lo, hi) in &[(Included(&min), Included(&max)), (Excluded(&min), Excluded(&max)),
+                               (Unbounded, Included(&max)), (Excluded(&min), Unbounded)] {
+                let expected: Vec<_> = (0..8).filter(|i| match lo {
+                    Included(k) => i >= k, Excluded(k) => i > k, Unbounded => true,
+                } && match hi {
+                    Included(k) => i <= k, Excluded(k) => i < k, Unbounded => true,
+                }).collect();
+
+                assert!(inline.range(lo, hi).map(|e| *e.0).eq(expected.iter().cloned()));
+                assert!(tree.range(lo, hi).map(|e| *e.0).eq(expected.iter().cloned()));
+                assert!(inline.range_mut(lo, hi).rev().map(|e| *e.0)
+                              .eq(expected.iter().rev().cloned()));
+                assert!(tree.range_mut(lo, hi).rev().map(|e| *e.0)
+                            .eq(expected.iter().rev().cloned()));
+            }
+        }
+    }
+}
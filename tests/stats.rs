@@ -0,0 +1,124 @@
+#![cfg(feature = "stats")]
+
+extern crate quickcheck;
+extern crate tree;
+
+use quickcheck::{Arbitrary, Gen, quickcheck};
+use std::cell::Cell;
+use tree::Map;
+use tree::map::Entry;
+
+/// An operation on a `Map`, with keys in `0..64`.
+#[derive(Clone, Debug)]
+enum Op {
+    Insert(u8),
+    Remove(u8),
+    Get(u8),
+    Pred(u8, bool),
+    RemoveSucc(u8, bool),
+    EntryRemove(u8),
+    RemoveMin,
+}
+
+impl Arbitrary for Op {
+    fn arbitrary<G: Gen>(gen: &mut G) -> Self {
+        let key = gen.gen_range(0, 64);
+
+        match gen.gen_range(0, 10) {
+            0..=3 => Op::Insert(key),
+            4 => Op::Remove(key),
+            5 => Op::Get(key),
+            6 => Op::Pred(key, bool::arbitrary(gen)),
+            7 => Op::RemoveSucc(key, bool::arbitrary(gen)),
+            8 => Op::EntryRemove(key),
+            _ => Op::RemoveMin,
+        }
+    }
+}
+
+#[test]
+fn compares_match_comparator_calls() {
+    fn test(ops: Vec<Op>) -> bool {
+        let calls = Cell::new(0);
+        let cmp = |l: &u8, r: &u8| { calls.set(calls.get() + 1); l.cmp(r) };
+        let mut map = Map::with_cmp(&cmp);
+
+        for op in ops {
+            match op {
+                Op::Insert(k) => { map.insert(k, ()); }
+                Op::Remove(k) => { map.remove(&k); }
+                Op::Get(k) => { map.get(&k); }
+                Op::Pred(k, inclusive) => { map.pred(&k, inclusive); }
+                Op::RemoveSucc(k, inclusive) => { map.remove_succ(&k, inclusive); }
+                Op::EntryRemove(k) => if let Entry::Occupied(e) = map.entry(k) { e.remove(); },
+                Op::RemoveMin => { map.remove_min(); }
+            }
+
+            if map.stats().compares != calls.get() { return false; }
+        }
+
+        true
+    }
+
+    quickcheck(test as fn(Vec<Op>) -> bool);
+}
+
+#[test]
+fn shape_is_balanced() {
+    fn test(ops: Vec<Op>) -> bool {
+        let mut map = Map::new();
+
+        for op in ops {
+            match op {
+                Op::Insert(k) | Op::Get(k) | Op::Pred(k, _) => { map.insert(k, ()); }
+                Op::Remove(k) | Op::EntryRemove(k) | Op::RemoveSucc(k, _) => { map.remove(&k); }
+                Op::RemoveMin => { map.remove_min(); }
+            }
+        }
+
+        let shape = map.shape();
+        let bound = 2 * (64 - (map.len() as u64).leading_zeros() as usize);
+
+        shape.nodes == map.len() && shape.levels.iter().sum::<usize>() == map.len() &&
+            shape.levels.len() == shape.height && shape.height <= bound &&
+            shape.levels.iter().enumerate().all(|(depth, &count)| count <= 1 << depth)
+    }
+
+    quickcheck(test as fn(Vec<Op>) -> bool);
+}
+
+#[test]
+fn rotations() {
+    let mut map = Map::new();
+    for i in 0..100 { map.insert(i, ()); }
+
+    let stats = map.stats();
+    assert!(stats.compares > 0);
+    assert_eq!(stats.skews, 0);
+    assert!(stats.splits > 0);
+    assert_eq!(stats.rebalances, 0);
+
+    map.reset_stats();
+    for i in 0..100 { map.remove(&i); }
+
+    let stats = map.stats();
+    assert!(map.is_empty());
+    assert!(stats.rebalances > 0);
+
+    let mut map = Map::new();
+    for i in (0..100).rev() { map.insert(i, ()); }
+    assert!(map.stats().skews > 0);
+}
+
+#[test]
+fn clone_and_reset() {
+    let mut map = Map::new();
+    for i in 0..10 { map.insert(i, ()); }
+    assert!(map.stats().compares > 0);
+
+    assert_eq!(map.clone().stats(), Default::default());
+
+    map.reset_stats();
+    assert_eq!(map.stats(), Default::default());
+    assert_eq!(map.shape().nodes, 10);
+}
@@ -0,0 +1,116 @@
+extern crate quickcheck;
+extern crate tree;
+
+use quickcheck::{Arbitrary, Gen, quickcheck};
+use tree::versioned::VersionedMap;
+
+/// A write to a `VersionedMap`, with keys and versions in `0..16`.
+#[derive(Clone, Debug)]
+struct Write {
+    key: u8,
+    value: Option<u8>,
+    version: u64,
+}
+
+impl Arbitrary for Write {
+    fn arbitrary<G: Gen>(gen: &mut G) -> Self {
+        Write {
+            key: gen.gen_range(0, 16),
+            value: if gen.gen_range(0, 4) == 0 { None } else { Some(u8::arbitrary(gen)) },
+            version: gen.gen_range(0, 16),
+        }
+    }
+}
+
+// Returns the value of the key at the version according to the writes, the last of which wins at
+// equal versions.
+fn model(writes: &[Write], key: u8, version: u64) -> Option<u8> {
+    let mut latest: Option<&Write> = None;
+
+    for w in writes.iter().filter(|w| w.key == key && w.version <= version) {
+        if latest.map_or(true, |l| w.version >= l.version) { latest = Some(w); }
+    }
+
+    latest.and_then(|w| w.value)
+}
+
+fn apply(map: &mut VersionedMap<u8, u8>, writes: &[Write]) {
+    for w in writes {
+        match w.value {
+            Some(value) => { map.insert_at(w.key, value, w.version); }
+            None => { map.remove_at(w.key, w.version); }
+        }
+    }
+}
+
+#[test]
+fn agrees_with_model() {
+    fn test(writes: Vec<Write>) -> bool {
+        let mut map = VersionedMap::new();
+        apply(&mut map, &writes);
+
+        (0..17).all(|version| {
+            let expected: Vec<_> =
+                (0..16).filter_map(|k| model(&writes, k, version).map(|v| (k, v))).collect();
+
+            let actual: Vec<_> = map.iter_at(version).map(|(k, v)| (*k, *v)).collect();
+            let reversed: Vec<_> = map.iter_at(version).rev().map(|(k, v)| (*k, *v)).collect();
+
+            actual == expected && reversed.into_iter().eq(expected.into_iter().rev()) &&
+                (0..16).all(|k| map.get_at(&k, version).cloned() == model(&writes, k, version))
+        })
+    }
+
+    quickcheck(test as fn(Vec<Write>) -> bool);
+}
+
+#[test]
+fn gc_preserves_reads_at_watermark() {
+    fn test(writes: Vec<Write>, watermark: u64, later: Vec<Write>) -> bool {
+        let watermark = watermark % 17;
+        let mut map = VersionedMap::new();
+        apply(&mut map, &writes);
+
+        let before: usize = (0..16).map(|k| map.versions(&k).len()).sum();
+        let removed = map.gc(watermark);
+        let after: usize = (0..16).map(|k| map.versions(&k).len()).sum();
+
+        let later: Vec<_> = later.into_iter().filter(|w| w.version >= watermark).collect();
+        apply(&mut map, &later);
+        let writes: Vec<_> = writes.into_iter().chain(later).collect();
+
+        before - after == removed &&
+            map.watermark() == watermark &&
+            (0..16).all(|k| map.versions(&k).len() > 0 || map.get_at(&k, 16).is_none()) &&
+            (watermark..17).all(|version| {
+                (0..16).all(|k| map.get_at(&k, version).cloned() == model(&writes, k, version))
+            })
+    }
+
+    quickcheck(test as fn(Vec<Write>, u64, Vec<Write>) -> bool);
+}
+
+#[test]
+#[should_panic(expected = "version is older than the watermark")]
+fn insert_below_watermark_panics() {
+    let mut map = VersionedMap::new();
+    map.insert_at(1, 1, 5);
+    map.gc(5);
+    map.insert_at(1, 2, 4);
+}
+
+#[cfg(feature = "range")]
+#[test]
+fn range_at_agrees_with_iter_at() {
+    use std::ops::Bound::{Excluded, Included};
+
+    fn test(writes: Vec<Write>, min: u8, max: u8, version: u64) -> bool {
+        let mut map = VersionedMap::new();
+        apply(&mut map, &writes);
+
+        let expected: Vec<_> = map.iter_at(version).filter(|e| *e.0 >= min && *e.0 < max).collect();
+        map.range_at(Included(&min), Excluded(&max), version).eq(expected.into_iter())
+    }
+
+    quickcheck(test as fn(Vec<Write>, u8, u8, u64) -> bool);
+}